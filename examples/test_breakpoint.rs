@@ -37,6 +37,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         class.type_id,
         hello_method.method_id,
         line_64.line_code_index,
+        None,
+        None,
         SuspendPolicy::All,  // Suspend all threads when hit
     ).await?;
 
@@ -29,6 +29,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         class.type_id,
         hello_method.method_id,
         line_64.line_code_index,
+        None,
+        None,
         SuspendPolicy::All,
     ).await?;
     println!("✓ Breakpoint set (request_id: {})\n", request_id);
@@ -39,7 +41,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("   (Will check every 2 seconds for 30 seconds)\n");
 
     // Poll for suspended threads (simple approach - in real impl we'd listen for events)
-    for attempt in 1..=15 {
+    for _attempt in 1..=15 {
         tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
 
         // Get all threads
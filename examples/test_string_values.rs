@@ -2,7 +2,7 @@
 //
 // This example demonstrates fetching actual string values from String objects
 
-use jdwp_client::{JdwpConnection, spawn_event_loop};
+use jdwp_client::{JdwpConnection, SuspendPolicy};
 use std::time::Duration;
 use tokio::time::sleep;
 
@@ -11,17 +11,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("🔗 Connecting to JVM at localhost:5005...");
 
     let mut connection = JdwpConnection::connect("localhost", 5005).await?;
-    let (_event_loop, mut event_rx) = spawn_event_loop(connection.event_loop_handle());
 
     println!("✅ Connected!");
 
     // Get VM version
-    let version = connection.version().await?;
+    let version = connection.get_version().await?;
     println!("📦 JVM Version: {}", version.vm_version);
 
     // Find HelloController class
     println!("\n🔍 Finding HelloController class...");
-    let classes = connection.get_classes_by_signature("Lcom/example/probedemo/HelloController;").await?;
+    let classes = connection.classes_by_signature("Lcom/example/probedemo/HelloController;").await?;
 
     if classes.is_empty() {
         eprintln!("❌ HelloController class not found. Make sure the app is running.");
@@ -53,7 +52,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Set breakpoint at line 148
     println!("\n⏸️  Setting breakpoint at line 148...");
-    let _bp_id = connection.set_breakpoint(class_id, debug_test_method.method_id, line_entry.line_code_index).await?;
+    let _bp_id = connection.set_breakpoint(class_id, debug_test_method.method_id, line_entry.line_code_index, None, None, SuspendPolicy::All).await?;
     println!("✅ Breakpoint set!");
 
     println!("\n📞 Trigger the breakpoint by running:");
@@ -61,18 +60,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("\nWaiting for breakpoint to hit...");
 
     // Wait for breakpoint event
-    let event_set = event_rx.recv().await
+    let event_set = connection.recv_event().await
         .expect("No event received");
 
     println!("\n🎯 Breakpoint hit! Event: {:?}", event_set.suspend_policy);
 
     if let Some(event) = event_set.events.first() {
-        if let jdwp_client::events::EventKind::Breakpoint { thread, .. } = event {
+        if let jdwp_client::events::EventKind::Breakpoint { thread, .. } = &event.details {
             println!("   Thread ID: {:x}", thread);
 
             // Get stack frames for this thread
             println!("\n📚 Getting stack frames...");
-            let frames = connection.get_frames(thread, 0, -1).await?;
+            let frames = connection.get_frames(*thread, 0, -1).await?;
 
             if frames.is_empty() {
                 println!("❌ No frames found");
@@ -109,7 +108,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .collect();
 
             // Get frame values
-            let values = connection.get_frame_values(thread, frame.frame_id, slots).await?;
+            let values = connection.get_frame_values(*thread, frame.frame_id, slots).await?;
 
             println!("\n🎁 Variable values:");
             for (var, value) in active_vars.iter().zip(values.iter()) {
@@ -0,0 +1,153 @@
+// ArrayReference command implementations
+//
+// Commands for reading array lengths and reading and writing elements
+
+use crate::commands::{array_reference_commands, command_sets};
+use crate::connection::JdwpConnection;
+use crate::object::{read_value_by_tag, write_untagged_value};
+use crate::protocol::{CommandPacket, JdwpResult};
+use crate::reader::{read_count, read_i32, read_u8, MAX_PREALLOCATED};
+use crate::types::{ArrayId, Value};
+use crate::vm::VmIdSizes;
+use bytes::BufMut;
+
+/// Whether array elements with this component tag are sent untagged
+///
+/// Primitive arrays return a bare run of values; arrays of references
+/// return each element with its own tag.
+fn is_primitive_tag(tag: u8) -> bool {
+    matches!(tag, b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z')
+}
+
+impl JdwpConnection {
+    /// Get the number of elements in an array (ArrayReference.Length command)
+    pub async fn get_array_length(&mut self, array_id: ArrayId) -> JdwpResult<i32> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::ARRAY_REFERENCE, array_reference_commands::LENGTH);
+
+        self.id_sizes().write_object_id(&mut packet.data, array_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        read_i32(&mut data)
+    }
+
+    /// Get a range of array elements (ArrayReference.GetValues command)
+    ///
+    /// # Arguments
+    /// * `array_id` - The ArrayId of the array
+    /// * `first_index` - Index of the first element to read
+    /// * `length` - Number of elements to read
+    pub async fn get_array_values(
+        &mut self,
+        array_id: ArrayId,
+        first_index: i32,
+        length: i32,
+    ) -> JdwpResult<Vec<Value>> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::ARRAY_REFERENCE, array_reference_commands::GET_VALUES);
+
+        let sizes = self.id_sizes();
+        sizes.write_object_id(&mut packet.data, array_id);
+        packet.data.put_i32(first_index);
+        packet.data.put_i32(length);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        read_array_region(&mut data, &sizes)
+    }
+
+    /// Overwrite a run of array elements (ArrayReference.SetValues command)
+    ///
+    /// Values are sent untagged, so each must match the array's component
+    /// type (any object reference for arrays of objects); indices past the
+    /// end fail with INVALID_INDEX or INVALID_LENGTH.
+    ///
+    /// # Arguments
+    /// * `array_id` - The ArrayId of the array
+    /// * `first_index` - Index of the first element to write
+    /// * `values` - New values for consecutive elements
+    pub async fn set_array_values(
+        &mut self,
+        array_id: ArrayId,
+        first_index: i32,
+        values: Vec<Value>,
+    ) -> JdwpResult<()> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::ARRAY_REFERENCE, array_reference_commands::SET_VALUES);
+
+        let sizes = self.id_sizes();
+        sizes.write_object_id(&mut packet.data, array_id);
+        packet.data.put_i32(first_index);
+        packet.data.put_i32(values.len() as i32);
+        for value in &values {
+            write_untagged_value(&mut packet.data, &value.data, &sizes);
+        }
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        Ok(())
+    }
+}
+
+/// Decode an arrayregion: component tag, count, then the values
+fn read_array_region(data: &mut &[u8], sizes: &VmIdSizes) -> JdwpResult<Vec<Value>> {
+    let component_tag = read_u8(data)?;
+    let count = read_count(data, 1, "array elements")?;
+    let mut values = Vec::with_capacity(count.min(MAX_PREALLOCATED));
+
+    for _ in 0..count {
+        let tag = if is_primitive_tag(component_tag) {
+            component_tag
+        } else {
+            read_u8(data)?
+        };
+
+        values.push(Value {
+            tag,
+            data: read_value_by_tag(tag, data, sizes)?,
+        });
+    }
+
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ValueData;
+    use bytes::BufMut;
+
+    #[test]
+    fn test_primitive_region_is_untagged() {
+        let mut region = Vec::new();
+        region.put_u8(b'I');
+        region.put_i32(2);
+        region.put_i32(7);
+        region.put_i32(-1);
+
+        let values = read_array_region(&mut region.as_slice(), &VmIdSizes::default()).unwrap();
+        assert_eq!(values[0].data, ValueData::Int(7));
+        assert_eq!((values[1].tag, &values[1].data), (b'I', &ValueData::Int(-1)));
+    }
+
+    #[test]
+    fn test_object_region_is_tagged_per_element() {
+        let mut region = Vec::new();
+        region.put_u8(b'L');
+        region.put_i32(2);
+        region.put_u8(b's');
+        region.put_u64(0x40);
+        region.put_u8(b'L');
+        region.put_u64(0);
+
+        let values = read_array_region(&mut region.as_slice(), &VmIdSizes::default()).unwrap();
+        assert_eq!((values[0].tag, &values[0].data), (b's', &ValueData::Object(0x40)));
+        assert_eq!((values[1].tag, &values[1].data), (b'L', &ValueData::Object(0)));
+    }
+}
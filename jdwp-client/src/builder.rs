@@ -0,0 +1,370 @@
+// JDWP connection builder
+//
+// Collects connection options so new ones can be added without new constructors
+
+use crate::connection::JdwpConnection;
+use crate::eventloop::{spawn_event_loop_with_config, EventLoopConfig, PacketDirection, PacketTracer};
+use crate::protocol::{JdwpError, JdwpResult};
+use crate::transport::Transport;
+use std::time::{Duration, Instant};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{info, warn};
+
+/// How long the peer may take to answer the handshake when no connect timeout is set
+pub const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How to retry a failed connection attempt
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first (at least 1)
+    pub max_attempts: u32,
+    /// Pause between attempts
+    pub delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Try once and give up
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// Try up to `max_attempts` times, waiting `delay` between attempts
+    pub fn fixed(max_attempts: u32, delay: Duration) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            delay,
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// Builder for a [`JdwpConnection`]
+///
+/// # Example
+/// ```no_run
+/// # use std::time::Duration;
+/// # use jdwp_client::{JdwpConnection, JdwpResult, RetryPolicy};
+/// # async fn f() -> JdwpResult<()> {
+/// let connection = JdwpConnection::builder()
+///     .host("localhost")
+///     .port(5005)
+///     .connect_timeout(Duration::from_secs(5))
+///     .retry(RetryPolicy::fixed(3, Duration::from_secs(1)))
+///     .connect()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct JdwpConnectionBuilder {
+    host: String,
+    port: u16,
+    connect_timeout: Option<Duration>,
+    retry: RetryPolicy,
+    event_loop: EventLoopConfig,
+}
+
+impl Default for JdwpConnectionBuilder {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 5005,
+            connect_timeout: None,
+            retry: RetryPolicy::none(),
+            event_loop: EventLoopConfig::default(),
+        }
+    }
+}
+
+impl JdwpConnectionBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = host.into();
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Limit how long each TCP connect plus handshake may take
+    ///
+    /// Without one, connecting has no limit and the handshake gets
+    /// [`DEFAULT_HANDSHAKE_TIMEOUT`].
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// How long a command may wait for its reply
+    ///
+    /// InvokeMethod replies only arrive once the invoked method returns, so
+    /// evaluating slow user code needs a longer timeout than the default.
+    pub fn reply_timeout(mut self, timeout: Duration) -> Self {
+        self.event_loop.reply_timeout = timeout;
+        self
+    }
+
+    /// Largest packet accepted from the VM
+    pub fn max_packet_size(mut self, bytes: usize) -> Self {
+        self.event_loop.max_packet_size = bytes;
+        self
+    }
+
+    /// Number of event sets buffered before new ones are dropped
+    pub fn event_channel_capacity(mut self, capacity: usize) -> Self {
+        self.event_loop.event_channel_capacity = capacity;
+        self
+    }
+
+    /// Number of commands buffered for the socket writer
+    pub fn command_channel_capacity(mut self, capacity: usize) -> Self {
+        self.event_loop.command_channel_capacity = capacity;
+        self
+    }
+
+    /// Call `trace` with every raw packet sent or received
+    ///
+    /// Useful for checking a mis-decoded reply against the JDWP spec.
+    /// Without a tracer, packets are not copied or inspected.
+    pub fn packet_tracer(mut self, trace: impl Fn(PacketDirection, &[u8]) + Send + Sync + 'static) -> Self {
+        self.event_loop.packet_tracer = Some(PacketTracer::new(trace));
+        self
+    }
+
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Connect, perform the handshake and start the event loop
+    pub async fn connect(self) -> JdwpResult<JdwpConnection> {
+        let mut attempt = 1;
+
+        loop {
+            match self.connect_once().await {
+                Ok(connection) => return Ok(connection),
+                // A peer that isn't speaking JDWP won't start on a retry
+                Err(e @ JdwpError::InvalidHandshake(_)) => return Err(e),
+                Err(e) if attempt >= self.retry.max_attempts => return Err(e),
+                Err(e) => {
+                    warn!(
+                        "Connection attempt {}/{} to {}:{} failed: {}",
+                        attempt, self.retry.max_attempts, self.host, self.port, e
+                    );
+                    tokio::time::sleep(self.retry.delay).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Listen on `bind_addr` and accept one connection from a VM started
+    /// with `server=n`
+    ///
+    /// The connect timeout, if set, bounds the wait for the VM plus the
+    /// handshake; the host, port and retry policy are not used.
+    pub async fn listen(self, bind_addr: &str) -> JdwpResult<JdwpConnection> {
+        let listener = TcpListener::bind(bind_addr).await?;
+        self.accept(listener).await
+    }
+
+    /// Accept one VM connection on an already bound listener
+    ///
+    /// Useful when binding to port 0 and telling the VM the chosen port.
+    pub async fn accept(self, listener: TcpListener) -> JdwpResult<JdwpConnection> {
+        let local_addr = listener.local_addr()?;
+        info!("Waiting for a JVM to connect to {}", local_addr);
+        let started = Instant::now();
+
+        let (stream, peer) = match self.connect_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, listener.accept()).await.map_err(|_| {
+                JdwpError::Timeout(format!(
+                    "no JVM connected to {} within {:?}",
+                    local_addr, timeout
+                ))
+            })??,
+            None => listener.accept().await?,
+        };
+        info!("JVM connected from {}", peer);
+
+        self.start(stream, started).await
+    }
+
+    /// Handshake over an already open transport and start the event loop
+    ///
+    /// For transports other than TCP; the connect timeout, if set, bounds
+    /// the handshake, and the host, port and retry policy are not used.
+    pub async fn connect_transport<T: Transport>(self, transport: T) -> JdwpResult<JdwpConnection> {
+        self.start(transport, Instant::now()).await
+    }
+
+    async fn connect_once(&self) -> JdwpResult<JdwpConnection> {
+        info!("Connecting to JDWP at {}:{}", self.host, self.port);
+        let started = Instant::now();
+
+        let connect = TcpStream::connect((self.host.as_str(), self.port));
+        let stream = match self.connect_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, connect).await.map_err(|_| {
+                JdwpError::Timeout(format!(
+                    "connecting to {}:{} took longer than {:?}",
+                    self.host, self.port, timeout
+                ))
+            })??,
+            None => connect.await?,
+        };
+
+        self.start(stream, started).await
+    }
+
+    /// Handshake over a freshly opened transport and start the event loop
+    ///
+    /// The debugger sends the handshake first whichever side opened the
+    /// connection, so this is the same for connect and listen.
+    async fn start<T: Transport>(&self, mut stream: T, started: Instant) -> JdwpResult<JdwpConnection> {
+        // Perform JDWP handshake with whatever is left of the connect budget,
+        // so a peer that accepts but never answers can't hang us
+        let handshake_timeout = self
+            .connect_timeout
+            .map(|timeout| timeout.saturating_sub(started.elapsed()))
+            .unwrap_or(DEFAULT_HANDSHAKE_TIMEOUT);
+        JdwpConnection::handshake(&mut stream, handshake_timeout).await?;
+
+        // Split stream and spawn event loop
+        let (reader, writer) = stream.into_split();
+        let event_loop = spawn_event_loop_with_config(reader, writer, self.event_loop.clone());
+        let mut connection = JdwpConnection::from_event_loop(event_loop);
+
+        // Learn how wide the VM's IDs are before any command encodes one
+        if let Err(e) = connection.get_id_sizes().await {
+            warn!("Failed to get ID sizes, assuming 8-byte IDs: {}", e);
+        }
+
+        // Cache the protocol version so commands can be gated on it
+        if let Err(e) = connection.get_version().await {
+            warn!("Failed to get VM version: {}", e);
+        }
+
+        Ok(connection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_vm;
+    use crate::protocol::JDWP_HANDSHAKE;
+
+    #[tokio::test]
+    async fn test_connect_performs_handshake_and_caches_version() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let jvm = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let handshake = fake_vm::answer_handshake(&mut socket, 8).await;
+            (socket, handshake)
+        });
+
+        let connection = JdwpConnectionBuilder::new()
+            .host("127.0.0.1")
+            .port(port)
+            .connect_timeout(Duration::from_secs(5))
+            .connect()
+            .await
+            .unwrap();
+
+        let (_socket, handshake) = jvm.await.unwrap();
+        assert_eq!(&handshake, JDWP_HANDSHAKE);
+
+        assert_eq!(connection.version().unwrap().vm_name, "FakeVM");
+        assert!(connection.supports_command_set_version(1, 5));
+        assert!(connection.supports_command_set_version(1, 8));
+        assert!(!connection.supports_command_set_version(1, 9));
+        assert!(!connection.supports_command_set_version(2, 0));
+    }
+
+    #[tokio::test]
+    async fn test_connect_transport_runs_over_any_stream() {
+        let (client, mut jvm) = tokio::io::duplex(4096);
+
+        let jvm = tokio::spawn(async move {
+            fake_vm::answer_handshake(&mut jvm, 4).await;
+            jvm
+        });
+
+        let connection = JdwpConnectionBuilder::new()
+            .connect_timeout(Duration::from_secs(5))
+            .connect_transport(client)
+            .await
+            .unwrap();
+        let _jvm = jvm.await.unwrap();
+
+        assert_eq!(connection.version().unwrap().vm_name, "FakeVM");
+        assert_eq!(connection.id_sizes().object_id_size, 4);
+    }
+
+    #[tokio::test]
+    async fn test_accept_handshakes_with_dialling_vm() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // A server=n VM dials out, then waits for the debugger's handshake
+        let jvm = tokio::spawn(async move {
+            let mut socket = TcpStream::connect(addr).await.unwrap();
+            let handshake = fake_vm::answer_handshake(&mut socket, 8).await;
+            (socket, handshake)
+        });
+
+        let connection = JdwpConnectionBuilder::new()
+            .connect_timeout(Duration::from_secs(5))
+            .accept(listener)
+            .await
+            .unwrap();
+
+        let (_socket, handshake) = jvm.await.unwrap();
+        assert_eq!(&handshake, JDWP_HANDSHAKE);
+        assert_eq!(connection.version().unwrap().vm_name, "FakeVM");
+    }
+
+    #[tokio::test]
+    async fn test_accept_times_out_without_vm() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let result = JdwpConnectionBuilder::new()
+            .connect_timeout(Duration::from_millis(50))
+            .accept(listener)
+            .await;
+        assert!(matches!(result, Err(JdwpError::Timeout(_))));
+    }
+
+    #[tokio::test]
+    async fn test_connect_gives_up_after_retries() {
+        // Bind and drop a listener to get a port nothing is listening on
+        let port = {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            listener.local_addr().unwrap().port()
+        };
+
+        let result = JdwpConnectionBuilder::new()
+            .host("127.0.0.1")
+            .port(port)
+            .retry(RetryPolicy::fixed(3, Duration::from_millis(10)))
+            .connect()
+            .await;
+
+        assert!(matches!(result, Err(JdwpError::Io(_))));
+    }
+}
@@ -0,0 +1,120 @@
+// ClassType command implementations
+//
+// Commands that only apply to classes (not interfaces or arrays)
+
+use crate::commands::{class_type_commands, command_sets};
+use crate::connection::JdwpConnection;
+use crate::object::{read_invoke_result, write_tagged_value};
+use crate::protocol::{CommandPacket, JdwpResult};
+use crate::reftype::MethodInfo;
+use crate::types::{ClassId, InterfaceId, MethodId, ReferenceTypeId, ThreadId, Value};
+use bytes::BufMut;
+use std::collections::HashSet;
+
+impl JdwpConnection {
+    /// Get the immediate superclass of a class (ClassType.Superclass command)
+    ///
+    /// Returns `None` for java.lang.Object, which has no superclass.
+    pub async fn get_superclass(&mut self, class_id: ClassId) -> JdwpResult<Option<ClassId>> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::CLASS_TYPE, class_type_commands::SUPERCLASS);
+
+        let sizes = self.id_sizes();
+        sizes.write_reference_type_id(&mut packet.data, class_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        let superclass = sizes.read_reference_type_id(&mut data)?;
+
+        Ok(if superclass == 0 { None } else { Some(superclass) })
+    }
+
+    /// Invoke a static method (ClassType.InvokeMethod command)
+    ///
+    /// Same rules as `invoke_object_method`: the thread must be suspended by
+    /// an event, and a thrown exception comes back as
+    /// `JdwpError::InvocationException`. `class_id` is the class declaring
+    /// the method or a subclass of it.
+    pub async fn invoke_static_method(
+        &mut self,
+        class_id: ClassId,
+        thread_id: ThreadId,
+        method_id: MethodId,
+        args: Vec<Value>,
+        options: i32,
+    ) -> JdwpResult<Value> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::CLASS_TYPE, class_type_commands::INVOKE_METHOD);
+
+        let sizes = self.id_sizes();
+        sizes.write_reference_type_id(&mut packet.data, class_id);
+        sizes.write_object_id(&mut packet.data, thread_id);
+        sizes.write_method_id(&mut packet.data, method_id);
+
+        packet.data.put_i32(args.len() as i32);
+        for arg in &args {
+            write_tagged_value(&mut packet.data, arg, &sizes);
+        }
+
+        packet.data.put_i32(options);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        read_invoke_result(reply.data(), &sizes)
+    }
+
+    /// Find a method by name and signature in a class or its superclasses
+    ///
+    /// Returns the declaring class together with the method, or `None` if no
+    /// class in the chain declares it.
+    pub async fn find_method(
+        &mut self,
+        class_id: ClassId,
+        name: &str,
+        signature: &str,
+    ) -> JdwpResult<Option<(ClassId, MethodInfo)>> {
+        let mut current = Some(class_id);
+
+        while let Some(declaring_class) = current {
+            let methods = self.get_methods(declaring_class).await?;
+            if let Some(method) = methods
+                .into_iter()
+                .find(|m| m.name == name && m.signature == signature)
+            {
+                return Ok(Some((declaring_class, method)));
+            }
+
+            current = self.get_superclass(declaring_class).await?;
+        }
+
+        Ok(None)
+    }
+
+    /// Get every interface a class implements, directly or indirectly
+    ///
+    /// Includes interfaces implemented by superclasses and all
+    /// superinterfaces of those interfaces.
+    pub async fn get_all_interfaces(&mut self, class_id: ClassId) -> JdwpResult<Vec<InterfaceId>> {
+        let mut found = Vec::new();
+        let mut seen = HashSet::new();
+        let mut pending: Vec<ReferenceTypeId> = Vec::new();
+
+        let mut current = Some(class_id);
+        while let Some(class) = current {
+            pending.extend(self.get_interfaces(class).await?);
+            current = self.get_superclass(class).await?;
+        }
+
+        while let Some(interface) = pending.pop() {
+            if seen.insert(interface) {
+                found.push(interface);
+                pending.extend(self.get_interfaces(interface).await?);
+            }
+        }
+
+        Ok(found)
+    }
+}
@@ -3,11 +3,15 @@
 // Command Sets:
 // 1 = VirtualMachine
 // 2 = ReferenceType
+// 3 = ClassType
 // 6 = Method
 // 9 = ObjectReference
 // 11 = ThreadReference
+// 12 = ThreadGroupReference
+// 13 = ArrayReference
 // 15 = EventRequest
 // 16 = StackFrame
+// 64 = Event (sent by the VM)
 
 // Command set IDs
 pub mod command_sets {
@@ -22,6 +26,7 @@ pub mod command_sets {
     pub const ARRAY_REFERENCE: u8 = 13;
     pub const EVENT_REQUEST: u8 = 15;
     pub const STACK_FRAME: u8 = 16;
+    pub const EVENT: u8 = 64;
 }
 
 // VirtualMachine commands (set 1)
@@ -42,6 +47,11 @@ pub mod vm_commands {
     pub const DISPOSE_OBJECTS: u8 = 14;
     pub const HOLD_EVENTS: u8 = 15;
     pub const RELEASE_EVENTS: u8 = 16;
+    pub const CAPABILITIES_NEW: u8 = 17;
+    pub const REDEFINE_CLASSES: u8 = 18;
+    pub const SET_DEFAULT_STRATUM: u8 = 19;
+    pub const ALL_CLASSES_WITH_GENERIC: u8 = 20;
+    pub const INSTANCE_COUNTS: u8 = 21;
 }
 
 // ReferenceType commands (set 2)
@@ -63,6 +73,14 @@ pub mod reference_type_commands {
     pub const METHODS_WITH_GENERIC: u8 = 15;
 }
 
+// ClassType commands (set 3)
+pub mod class_type_commands {
+    pub const SUPERCLASS: u8 = 1;
+    pub const SET_VALUES: u8 = 2;
+    pub const INVOKE_METHOD: u8 = 3;
+    pub const NEW_INSTANCE: u8 = 4;
+}
+
 // Method commands (set 6)
 pub mod method_commands {
     pub const LINE_TABLE: u8 = 1;
@@ -86,6 +104,19 @@ pub mod thread_commands {
     pub const STOP: u8 = 10;
     pub const INTERRUPT: u8 = 11;
     pub const SUSPEND_COUNT: u8 = 12;
+    pub const FORCE_EARLY_RETURN: u8 = 14;
+}
+
+// Event commands (set 64); the VM sends these, we never do
+pub mod vm_event_commands {
+    pub const COMPOSITE: u8 = 100;
+}
+
+// ThreadGroupReference commands (set 12)
+pub mod thread_group_commands {
+    pub const NAME: u8 = 1;
+    pub const PARENT: u8 = 2;
+    pub const CHILDREN: u8 = 3;
 }
 
 // EventRequest commands (set 15)
@@ -112,6 +143,21 @@ pub mod object_reference_commands {
     pub const IS_COLLECTED: u8 = 9;
 }
 
+// ArrayReference commands (set 13)
+pub mod array_reference_commands {
+    pub const LENGTH: u8 = 1;
+    pub const GET_VALUES: u8 = 2;
+    pub const SET_VALUES: u8 = 3;
+}
+
+// Options for ObjectReference/ClassType InvokeMethod
+pub mod invoke_options {
+    /// Only resume the invoking thread while the method runs
+    pub const INVOKE_SINGLE_THREADED: i32 = 0x01;
+    /// Invoke the exact method given, skipping virtual dispatch
+    pub const INVOKE_NONVIRTUAL: i32 = 0x02;
+}
+
 // StackFrame commands (set 16)
 pub mod stack_frame_commands {
     pub const GET_VALUES: u8 = 1;
@@ -158,3 +204,20 @@ pub mod step_depths {
     pub const OVER: i32 = 1;
     pub const OUT: i32 = 2;
 }
+
+// Error codes returned in reply packets (the ones callers act on)
+pub mod error_codes {
+    pub const INVALID_THREAD: u16 = 10;
+    pub const THREAD_NOT_SUSPENDED: u16 = 13;
+    pub const INVALID_OBJECT: u16 = 20;
+    pub const INVALID_CLASS: u16 = 21;
+    pub const INVALID_FRAMEID: u16 = 30;
+    pub const NO_MORE_FRAMES: u16 = 31;
+    pub const OPAQUE_FRAME: u16 = 32;
+    pub const TYPE_MISMATCH: u16 = 34;
+    pub const NOT_IMPLEMENTED: u16 = 99;
+    pub const ABSENT_INFORMATION: u16 = 101;
+    pub const INVALID_INDEX: u16 = 503;
+    pub const INVALID_LENGTH: u16 = 504;
+    pub const NATIVE_METHOD: u16 = 511;
+}
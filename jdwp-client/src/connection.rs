@@ -2,56 +2,105 @@
 //
 // Handles TCP connection, handshake, and event loop startup
 
-use crate::eventloop::{spawn_event_loop, EventLoopHandle};
+use crate::builder::JdwpConnectionBuilder;
+use crate::eventloop::EventLoopHandle;
 use crate::events::EventSet;
 use crate::protocol::*;
+use crate::stats::StatsSnapshot;
+use crate::vm::{VmIdSizes, VmVersion};
 use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tracing::{debug, info, warn};
 
 #[derive(Clone, Debug)]
 pub struct JdwpConnection {
     event_loop: EventLoopHandle,
     next_id: Arc<AtomicU32>,
+    /// Set by the first successful `get_version` (done while connecting)
+    pub(crate) version: Arc<OnceLock<VmVersion>>,
 }
 
 impl JdwpConnection {
-    /// Connect to a JVM via JDWP
+    /// Connect to a JVM via JDWP with default options
+    ///
+    /// Use [`JdwpConnection::builder`] to configure timeouts, retries and limits.
     pub async fn connect(host: &str, port: u16) -> JdwpResult<Self> {
-        info!("Connecting to JDWP at {}:{}", host, port);
-
-        let mut stream = TcpStream::connect((host, port)).await?;
+        Self::builder().host(host).port(port).connect().await
+    }
 
-        // Perform JDWP handshake
-        Self::handshake(&mut stream).await?;
+    /// Listen on `bind_addr` (e.g. "0.0.0.0:5005") and accept one connection
+    /// from a JVM launched with `server=n`
+    pub async fn listen(bind_addr: &str) -> JdwpResult<Self> {
+        Self::builder().listen(bind_addr).await
+    }
 
-        // Split stream and spawn event loop
-        let (reader, writer) = stream.into_split();
-        let event_loop = spawn_event_loop(reader, writer);
+    /// Start configuring a connection
+    pub fn builder() -> JdwpConnectionBuilder {
+        JdwpConnectionBuilder::new()
+    }
 
-        Ok(Self {
+    pub(crate) fn from_event_loop(event_loop: EventLoopHandle) -> Self {
+        Self {
             event_loop,
             next_id: Arc::new(AtomicU32::new(1)),
-        })
+            version: Arc::new(OnceLock::new()),
+        }
     }
 
     /// Perform JDWP handshake
-    async fn handshake(stream: &mut TcpStream) -> JdwpResult<()> {
+    ///
+    /// Fails with `InvalidHandshake` if the peer doesn't send back the full
+    /// handshake within `timeout`, closes early, or sends something else.
+    pub(crate) async fn handshake<S>(stream: &mut S, timeout: Duration) -> JdwpResult<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
         debug!("Performing JDWP handshake");
 
         // Send handshake
         stream.write_all(JDWP_HANDSHAKE).await?;
         stream.flush().await?;
 
-        // Receive handshake response
-        let mut buf = vec![0u8; JDWP_HANDSHAKE.len()];
-        stream.read_exact(&mut buf).await?;
+        // Receive handshake response, tracking progress for the error message
+        let mut buf = [0u8; JDWP_HANDSHAKE.len()];
+        let mut received = 0;
+        let read = async {
+            while received < buf.len() {
+                match stream.read(&mut buf[received..]).await? {
+                    0 => break,
+                    n => received += n,
+                }
+            }
+            Ok::<_, std::io::Error>(())
+        };
+
+        match tokio::time::timeout(timeout, read).await {
+            Ok(result) => result?,
+            Err(_) => {
+                return Err(JdwpError::InvalidHandshake(format!(
+                    "no reply within {:?} (received {} of {} bytes: {:?}); is this a JDWP port?",
+                    timeout, received, JDWP_HANDSHAKE.len(),
+                    String::from_utf8_lossy(&buf[..received])
+                )));
+            }
+        }
+
+        if received < buf.len() {
+            return Err(JdwpError::InvalidHandshake(format!(
+                "connection closed after {} of {} bytes: {:?}",
+                received, JDWP_HANDSHAKE.len(),
+                String::from_utf8_lossy(&buf[..received])
+            )));
+        }
 
         if buf != JDWP_HANDSHAKE {
             warn!("Invalid handshake response: {:?}", buf);
-            return Err(JdwpError::InvalidHandshake);
+            return Err(JdwpError::InvalidHandshake(format!(
+                "expected \"JDWP-Handshake\", got {:?}",
+                String::from_utf8_lossy(&buf)
+            )));
         }
 
         info!("JDWP handshake successful");
@@ -61,7 +110,19 @@ impl JdwpConnection {
     /// Send a command and wait for reply
     pub async fn send_command(&mut self, packet: CommandPacket) -> JdwpResult<ReplyPacket> {
         debug!("Sending command packet id={}", packet.id);
-        self.event_loop.send_command(packet).await
+
+        let stats = self.event_loop.stats();
+        stats.record_command();
+        let sent_at = Instant::now();
+
+        let reply = self.event_loop.send_command(packet).await?;
+        stats.record_reply(reply.error_code, sent_at.elapsed());
+        Ok(reply)
+    }
+
+    /// Command, reply and event counters for this connection
+    pub fn stats(&self) -> StatsSnapshot {
+        self.event_loop.stats().snapshot()
     }
 
     /// Try to receive an event without blocking.
@@ -71,9 +132,13 @@ impl JdwpConnection {
     ///
     /// # Example
     /// ```no_run
+    /// # use jdwp_client::{JdwpConnection, JdwpResult};
+    /// # async fn f(connection: &mut JdwpConnection) -> JdwpResult<()> {
     /// if let Some(event) = connection.try_recv_event().await {
     ///     // Handle event
     /// }
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn try_recv_event(&self) -> Option<EventSet> {
         self.event_loop.try_recv_event().await
@@ -88,14 +153,68 @@ impl JdwpConnection {
     ///
     /// # Example
     /// ```no_run
+    /// # use jdwp_client::{JdwpConnection, JdwpResult};
+    /// # async fn f(connection: &mut JdwpConnection) -> JdwpResult<()> {
     /// while let Some(event) = connection.recv_event().await {
     ///     // Process event
     /// }
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn recv_event(&self) -> Option<EventSet> {
         self.event_loop.recv_event().await
     }
 
+    /// Whether the connection to the VM is still up
+    ///
+    /// Becomes false once the socket is closed or fails; a closed connection
+    /// can't be revived, so connect again instead.
+    pub fn is_connected(&self) -> bool {
+        !self.event_loop.is_closed()
+    }
+
+    /// Drop the connection without telling the VM, e.g. once it has stopped
+    /// answering
+    ///
+    /// `is_connected` turns false shortly after, for every clone. Use
+    /// `dispose` to detach cleanly instead.
+    pub fn close(&self) {
+        self.event_loop.close();
+    }
+
+    /// Whether the VM announced it is exiting (a VMDeath event)
+    ///
+    /// Commands fail with `JdwpError::ConnectionClosed` from then on, even if
+    /// the socket is still open.
+    pub fn vm_exited(&self) -> bool {
+        self.event_loop.vm_exited()
+    }
+
+    /// ID sizes for encoding commands: the VM's, once `get_id_sizes` has
+    /// run (done while connecting), 8 bytes each before that
+    pub fn id_sizes(&self) -> VmIdSizes {
+        self.event_loop.id_sizes()
+    }
+
+    pub(crate) fn event_loop(&self) -> &EventLoopHandle {
+        &self.event_loop
+    }
+
+    /// The VM's version, if it has been fetched
+    pub fn version(&self) -> Option<&VmVersion> {
+        self.version.get()
+    }
+
+    /// Whether the VM speaks at least JDWP `min_major.min_minor`
+    ///
+    /// Commands added in later protocol versions (e.g. MethodsWithGeneric in
+    /// 1.5) should only be attempted when this holds. Returns false if the
+    /// version isn't known.
+    pub fn supports_command_set_version(&self, min_major: i32, min_minor: i32) -> bool {
+        self.version()
+            .is_some_and(|v| (v.jdwp_major, v.jdwp_minor) >= (min_major, min_minor))
+    }
+
     /// Generate next packet ID
     pub fn next_id(&self) -> u32 {
         self.next_id.fetch_add(1, Ordering::SeqCst)
@@ -106,6 +225,53 @@ impl JdwpConnection {
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_handshake_rejects_wrong_reply() {
+        let (mut client, mut jvm) = tokio::io::duplex(64);
+        tokio::spawn(async move {
+            let mut request = [0u8; 14];
+            jvm.read_exact(&mut request).await.unwrap();
+            jvm.write_all(b"HTTP/1.1 400 B").await.unwrap();
+        });
+
+        let err = JdwpConnection::handshake(&mut client, Duration::from_secs(5)).await.unwrap_err();
+        match err {
+            JdwpError::InvalidHandshake(context) => assert!(context.contains("HTTP/1.1"), "{}", context),
+            other => panic!("expected InvalidHandshake, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handshake_times_out_when_peer_is_silent() {
+        let (mut client, _jvm) = tokio::io::duplex(64);
+
+        let err = JdwpConnection::handshake(&mut client, Duration::from_millis(50)).await.unwrap_err();
+        match err {
+            JdwpError::InvalidHandshake(context) => assert!(context.contains("0 of 14 bytes"), "{}", context),
+            other => panic!("expected InvalidHandshake, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handshake_reports_partial_reply() {
+        let (mut client, mut jvm) = tokio::io::duplex(64);
+        tokio::spawn(async move {
+            let mut request = [0u8; 14];
+            jvm.read_exact(&mut request).await.unwrap();
+            jvm.write_all(b"JDWP-").await.unwrap();
+            // Dropping the stream closes it
+        });
+
+        let err = JdwpConnection::handshake(&mut client, Duration::from_secs(5)).await.unwrap_err();
+        match err {
+            JdwpError::InvalidHandshake(context) => {
+                assert!(context.contains("closed after 5 of 14"), "{}", context);
+                assert!(context.contains("\"JDWP-\""), "{}", context);
+            }
+            other => panic!("expected InvalidHandshake, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_next_id() {
         // Test ID counter without creating a real TcpStream
@@ -115,4 +281,73 @@ mod tests {
         assert_eq!(counter.fetch_add(1, Ordering::SeqCst), 2);
         assert_eq!(counter.fetch_add(1, Ordering::SeqCst), 3);
     }
+
+    #[tokio::test]
+    async fn test_commands_use_reported_id_sizes() {
+        use crate::commands::{command_sets, thread_commands, vm_commands};
+        use crate::fake_vm::{self, put_string, read_command, send_reply};
+        use bytes::BufMut;
+
+        let (mut connection, mut vm) = fake_vm::connect(4).await;
+        assert_eq!(connection.id_sizes().object_id_size, 4);
+
+        let jvm = tokio::spawn(async move {
+            // AllThreads replies with 4-byte thread IDs
+            let (header, _) = read_command(&mut vm, (command_sets::VIRTUAL_MACHINE, vm_commands::ALL_THREADS)).await;
+            let mut data = Vec::new();
+            data.put_i32(2);
+            data.put_u32(0x11);
+            data.put_u32(0x22);
+            send_reply(&mut vm, &header, &data).await;
+
+            // Name must be sent a 4-byte thread ID
+            let (header, body) = read_command(&mut vm, (command_sets::THREAD_REFERENCE, thread_commands::NAME)).await;
+            assert_eq!(body, [0, 0, 0, 0x22]);
+            let mut data = Vec::new();
+            put_string(&mut data, "worker");
+            send_reply(&mut vm, &header, &data).await;
+            vm
+        });
+
+        let threads = connection.get_all_threads().await.unwrap();
+        assert_eq!(threads, [0x11, 0x22]);
+        assert_eq!(connection.get_thread_name(0x22).await.unwrap(), "worker");
+
+        jvm.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_is_connected_until_vm_closes_socket() {
+        let (connection, vm) = crate::fake_vm::connect(8).await;
+        assert!(connection.is_connected());
+
+        drop(vm);
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while connection.is_connected() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("connection should notice the socket closing");
+    }
+
+    #[tokio::test]
+    async fn test_close_fails_pending_commands_and_drops_the_socket() {
+        use crate::commands::{command_sets, thread_commands};
+        use crate::fake_vm::{self, read_command};
+
+        let (connection, mut vm) = fake_vm::connect(8).await;
+
+        // Never answered
+        let mut pending = connection.clone();
+        let pending = tokio::spawn(async move { pending.get_thread_name(0x11).await });
+        read_command(&mut vm, (command_sets::THREAD_REFERENCE, thread_commands::NAME)).await;
+        connection.close();
+
+        assert!(matches!(pending.await.unwrap(), Err(JdwpError::ConnectionClosed)));
+        assert!(!connection.is_connected());
+        let mut rest = Vec::new();
+        vm.read_to_end(&mut rest).await.unwrap();
+        assert!(rest.is_empty());
+    }
 }
@@ -2,22 +2,95 @@
 //
 // Handles concurrent reading of events and replies from JDWP socket
 
-use crate::events::{parse_event_packet, EventSet};
+use crate::commands::{command_sets, vm_commands, vm_event_commands};
+use crate::events::{parse_event_packet_with_id_sizes, EventKind, EventSet};
 use crate::protocol::{CommandPacket, JdwpError, JdwpResult, ReplyPacket, HEADER_SIZE, REPLY_FLAG};
+use crate::stats::ConnectionStats;
+use crate::vm::VmIdSizes;
 use bytes::BytesMut;
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
-use tokio::sync::{mpsc, oneshot};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{mpsc, oneshot, Notify};
 use tracing::{debug, error, info, warn};
 
 /// Maximum allowed JDWP packet size (10MB)
 /// This prevents memory exhaustion from malicious or buggy JVMs
-const MAX_PACKET_SIZE: usize = 10 * 1024 * 1024;
+pub const DEFAULT_MAX_PACKET_SIZE: usize = 10 * 1024 * 1024;
 
 /// Maximum time to wait for a command reply before considering it lost
-const REPLY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+pub const DEFAULT_REPLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Initial size of the socket read buffer; it grows for larger packets
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// How often timed-out replies are swept (or sooner, for short reply timeouts)
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Tunables for the event loop
+#[derive(Debug, Clone)]
+pub struct EventLoopConfig {
+    /// How long a command may wait for its reply
+    pub reply_timeout: Duration,
+    /// Packets larger than this are rejected and close the connection
+    pub max_packet_size: usize,
+    /// Buffered events awaiting a consumer; events beyond this are dropped
+    pub event_channel_capacity: usize,
+    /// Buffered commands awaiting the socket writer
+    pub command_channel_capacity: usize,
+    /// Sees every raw packet sent or received, if set
+    pub packet_tracer: Option<PacketTracer>,
+}
+
+/// Which way a traced packet was travelling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketDirection {
+    /// A command sent to the VM
+    Outbound,
+    /// A reply or event from the VM, before it is parsed
+    Inbound,
+}
+
+/// Callback given every raw packet, header included
+///
+/// Runs on the event loop task, so it should be quick (e.g. append to a
+/// file or channel).
+#[derive(Clone)]
+pub struct PacketTracer(Arc<TraceFn>);
+
+type TraceFn = dyn Fn(PacketDirection, &[u8]) + Send + Sync;
+
+impl PacketTracer {
+    pub fn new(trace: impl Fn(PacketDirection, &[u8]) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(trace))
+    }
+
+    fn trace(&self, direction: PacketDirection, packet: &[u8]) {
+        (self.0)(direction, packet)
+    }
+}
+
+impl std::fmt::Debug for PacketTracer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PacketTracer")
+    }
+}
+
+impl Default for EventLoopConfig {
+    fn default() -> Self {
+        Self {
+            reply_timeout: DEFAULT_REPLY_TIMEOUT,
+            max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+            command_channel_capacity: 32,
+            // Use larger buffer for events to avoid loss under load
+            // Events are critical (breakpoints, exceptions) and shouldn't be dropped
+            event_channel_capacity: 256,
+            packet_tracer: None,
+        }
+    }
+}
 
 /// Request to send a command and get reply
 pub struct CommandRequest {
@@ -39,40 +112,96 @@ pub struct CommandRequest {
 ///
 /// # Example
 /// ```no_run
+/// # use jdwp_client::protocol::CommandPacket;
+/// # use jdwp_client::{EventLoopHandle, JdwpResult};
+/// # async fn f(event_loop: EventLoopHandle, cmd1: CommandPacket, cmd2: CommandPacket) -> JdwpResult<()> {
 /// // Good: Single event consumer
 /// let handle1 = event_loop.clone();
 /// let handle2 = event_loop.clone();
 ///
 /// // Both can send commands
-/// handle1.send_command(cmd1);
-/// handle2.send_command(cmd2);
+/// handle1.send_command(cmd1).await?;
+/// handle2.send_command(cmd2).await?;
 ///
 /// // Only one should consume events
 /// while let Some(event) = handle1.recv_event().await {
 ///     // Process event
 /// }
+/// # Ok(())
+/// # }
 /// ```
 #[derive(Clone, Debug)]
 pub struct EventLoopHandle {
     command_tx: mpsc::Sender<CommandRequest>,
     event_rx: Arc<tokio::sync::Mutex<mpsc::Receiver<EventSet>>>,
+    shared: Arc<SharedState>,
+}
+
+/// State shared between the event loop task and its handles
+#[derive(Debug, Default)]
+struct SharedState {
+    stats: ConnectionStats,
+    /// Set once IDSizes is known; events are parsed with the defaults until then
+    id_sizes: OnceLock<VmIdSizes>,
+    /// Set when the VM reports its own death; commands fail fast from then on
+    vm_exited: AtomicBool,
+    /// Signalled to make the event loop drop the connection
+    close: Notify,
 }
 
 impl EventLoopHandle {
     /// Send a command and wait for reply
     pub async fn send_command(&self, packet: CommandPacket) -> JdwpResult<ReplyPacket> {
+        // The socket may linger after VM death, but nothing will answer
+        if self.vm_exited() {
+            return Err(JdwpError::ConnectionClosed);
+        }
+
         let (reply_tx, reply_rx) = oneshot::channel();
 
         let request = CommandRequest { packet, reply_tx };
 
+        // Once the event loop has exited (dropping pending replies) the
+        // connection is unusable
         self.command_tx
             .send(request)
             .await
-            .map_err(|_| JdwpError::Protocol("Event loop shut down".to_string()))?;
+            .map_err(|_| JdwpError::ConnectionClosed)?;
 
         reply_rx
             .await
-            .map_err(|_| JdwpError::Protocol("Reply channel closed".to_string()))?
+            .map_err(|_| JdwpError::ConnectionClosed)?
+    }
+
+    /// Whether the event loop task has exited (socket closed or failed)
+    pub fn is_closed(&self) -> bool {
+        self.command_tx.is_closed()
+    }
+
+    /// Stop the event loop, closing the socket; pending commands fail with
+    /// `JdwpError::ConnectionClosed`
+    pub fn close(&self) {
+        // Stores a permit if the loop isn't waiting right now
+        self.shared.close.notify_one();
+    }
+
+    /// Whether the VM sent a VMDeath event saying it is exiting
+    pub fn vm_exited(&self) -> bool {
+        self.shared.vm_exited.load(Ordering::Acquire)
+    }
+
+    /// Counters shared with the event loop task
+    pub fn stats(&self) -> &ConnectionStats {
+        &self.shared.stats
+    }
+
+    /// ID sizes used to encode commands and parse events
+    pub fn id_sizes(&self) -> VmIdSizes {
+        self.shared.id_sizes.get().copied().unwrap_or_default()
+    }
+
+    pub(crate) fn set_id_sizes(&self, sizes: VmIdSizes) {
+        let _ = self.shared.id_sizes.set(sizes);
     }
 
     /// Try to receive an event (non-blocking)
@@ -88,18 +217,42 @@ impl EventLoopHandle {
     }
 }
 
-/// Start the event loop task
-pub fn spawn_event_loop(reader: OwnedReadHalf, writer: OwnedWriteHalf) -> EventLoopHandle {
-    let (command_tx, command_rx) = mpsc::channel(32);
-    // Use larger buffer for events to avoid loss under load
-    // Events are critical (breakpoints, exceptions) and shouldn't be dropped
-    let (event_tx, event_rx) = mpsc::channel(256);
+/// Start the event loop task with the default configuration
+pub fn spawn_event_loop<R, W>(reader: R, writer: W) -> EventLoopHandle
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    spawn_event_loop_with_config(reader, writer, EventLoopConfig::default())
+}
 
-    tokio::spawn(event_loop_task(reader, writer, command_rx, event_tx));
+/// Start the event loop task
+///
+/// `reader` and `writer` are the two halves of an already handshaken
+/// transport, e.g. from [`crate::transport::Transport::into_split`].
+pub fn spawn_event_loop_with_config<R, W>(reader: R, writer: W, config: EventLoopConfig) -> EventLoopHandle
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let (command_tx, command_rx) = mpsc::channel(config.command_channel_capacity);
+    let (event_tx, event_rx) = mpsc::channel(config.event_channel_capacity);
+
+    let shared = Arc::new(SharedState::default());
+
+    tokio::spawn(event_loop_task(
+        reader,
+        writer,
+        command_rx,
+        event_tx,
+        config,
+        shared.clone(),
+    ));
 
     EventLoopHandle {
         command_tx,
         event_rx: Arc::new(tokio::sync::Mutex::new(event_rx)),
+        shared,
     }
 }
 
@@ -110,25 +263,51 @@ struct PendingReply {
 }
 
 /// Main event loop task
-async fn event_loop_task(
-    mut reader: OwnedReadHalf,
-    mut writer: OwnedWriteHalf,
+async fn event_loop_task<R, W>(
+    mut reader: R,
+    mut writer: W,
     mut command_rx: mpsc::Receiver<CommandRequest>,
     event_tx: mpsc::Sender<EventSet>,
-) {
+    config: EventLoopConfig,
+    shared: Arc<SharedState>,
+) where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
     info!("Event loop started");
 
     let mut pending_replies: HashMap<u32, PendingReply> = HashMap::new();
-    let mut cleanup_interval = tokio::time::interval(tokio::time::Duration::from_secs(10));
+    // Set once we ask the VM to exit, so the socket closing isn't an error
+    let mut exit_requested = false;
+    let mut cleanup_interval = tokio::time::interval(CLEANUP_INTERVAL.min(config.reply_timeout));
+    // Bytes read but not yet handled; may end in a partial packet
+    let mut read_buf = BytesMut::with_capacity(READ_BUFFER_SIZE);
 
     loop {
         tokio::select! {
             // Handle outgoing commands
             Some(cmd) = command_rx.recv() => {
                 let packet_id = cmd.packet.id;
+                // The reply couldn't be told apart from the earlier command's
+                // (the id counter wrapped, or was reset); fail the newcomer
+                // rather than hand either caller the other's reply
+                if pending_replies.contains_key(&packet_id) {
+                    warn!("Command id={} is already awaiting a reply; not sending it", packet_id);
+                    cmd.reply_tx.send(Err(JdwpError::Protocol(format!(
+                        "packet id {} is already in use by a command awaiting its reply",
+                        packet_id
+                    )))).ok();
+                    continue;
+                }
                 debug!("Sending command id={}", packet_id);
+                if (cmd.packet.command_set, cmd.packet.command) == (command_sets::VIRTUAL_MACHINE, vm_commands::EXIT) {
+                    exit_requested = true;
+                }
 
                 let encoded = cmd.packet.encode();
+                if let Some(tracer) = &config.packet_tracer {
+                    tracer.trace(PacketDirection::Outbound, &encoded);
+                }
                 if let Err(e) = writer.write_all(&encoded).await {
                     error!("Failed to write command: {}", e);
                     cmd.reply_tx.send(Err(JdwpError::Io(e))).ok();
@@ -147,86 +326,73 @@ async fn event_loop_task(
                 });
             }
 
+            _ = shared.close.notified() => {
+                info!("Closing the connection");
+                break;
+            }
+
             // Periodic cleanup of timed-out pending replies
             _ = cleanup_interval.tick() => {
                 let now = tokio::time::Instant::now();
-                let before_count = pending_replies.len();
 
-                pending_replies.retain(|packet_id, pending| {
-                    let elapsed = now.duration_since(pending.sent_at);
-                    if elapsed > REPLY_TIMEOUT {
+                let timed_out: Vec<u32> = pending_replies
+                    .iter()
+                    .filter(|(_, pending)| now.duration_since(pending.sent_at) > config.reply_timeout)
+                    .map(|(packet_id, _)| *packet_id)
+                    .collect();
+
+                for packet_id in &timed_out {
+                    if let Some(pending) = pending_replies.remove(packet_id) {
+                        let elapsed = now.duration_since(pending.sent_at);
                         warn!("Command {} timed out after {:?}, removing from pending replies", packet_id, elapsed);
-                        // Note: sender is dropped here, which will notify the waiting command
-                        false
-                    } else {
-                        true
+                        pending.sender.send(Err(JdwpError::Timeout(format!(
+                            "no reply to command {} within {:?}",
+                            packet_id, config.reply_timeout
+                        )))).ok();
                     }
-                });
+                }
 
-                let removed = before_count - pending_replies.len();
-                if removed > 0 {
-                    warn!("Cleaned up {} timed-out pending replies", removed);
+                if !timed_out.is_empty() {
+                    warn!("Cleaned up {} timed-out pending replies", timed_out.len());
                 }
             }
 
-            // Handle incoming packets
-            result = read_packet(&mut reader) => {
+            // Handle incoming bytes. read_buf is cancel-safe, unlike
+            // read_exact: a packet split across reads survives another
+            // branch winning the select
+            result = reader.read_buf(&mut read_buf) => {
+                let exiting = exit_requested || shared.vm_exited.load(Ordering::Acquire);
                 match result {
-                    Ok((is_reply, packet_id, data)) => {
-                        if is_reply {
-                            // It's a reply - route to waiting command
-                            debug!("Received reply id={}", packet_id);
-
-                            if let Some(pending) = pending_replies.remove(&packet_id) {
-                                match ReplyPacket::decode(&data) {
-                                    Ok(reply) => {
-                                        pending.sender.send(Ok(reply)).ok();
-                                    }
-                                    Err(e) => {
-                                        warn!("Failed to decode reply: {}", e);
-                                        pending.sender.send(Err(e)).ok();
-                                    }
-                                }
-                            } else {
-                                warn!("Received reply for unknown command id={} (may have timed out)", packet_id);
-                            }
-                        } else {
-                            // It's an event - parse and broadcast
-                            debug!("Received event packet, len={}", data.len());
-
-                            // Event packets have command_set and command in header
-                            // Data starts after 11-byte header
-                            let event_data = &data[HEADER_SIZE..];
-
-                            match parse_event_packet(event_data) {
-                                Ok(event_set) => {
-                                    info!("Parsed event set: {} events, suspend_policy={}",
-                                          event_set.events.len(), event_set.suspend_policy);
-
-                                    // Send event without blocking to avoid deadlock
-                                    // If consumer is sending commands while we're reading, blocking here would deadlock
-                                    match event_tx.try_send(event_set) {
-                                        Ok(_) => {},
-                                        Err(mpsc::error::TrySendError::Full(dropped_event)) => {
-                                            // Event channel is full - this is critical
-                                            error!("Event channel full ({} buffered), dropping event with {} events. Consumer not keeping up!",
-                                                  event_tx.capacity(), dropped_event.events.len());
-                                            // TODO: Consider adding backpressure or alerting mechanism
-                                        }
-                                        Err(mpsc::error::TrySendError::Closed(_)) => {
-                                            info!("Event receiver dropped, shutting down event loop");
-                                            break;
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    warn!("Failed to parse event: {}", e);
-                                }
-                            }
+                    Ok(0) if exiting => {
+                        info!("VM closed the connection after exiting");
+                        break;
+                    }
+                    Ok(0) if read_buf.is_empty() => {
+                        info!("VM closed the connection");
+                        break;
+                    }
+                    Ok(0) => {
+                        error!("VM closed the connection mid-packet ({} bytes unread)", read_buf.len());
+                        break;
+                    }
+                    Ok(_) => match handle_buffered_packets(&mut read_buf, &mut pending_replies, &event_tx, &config, &shared) {
+                        Ok(true) => {}
+                        Ok(false) => break,
+                        // The stream can't be resynchronised after a bad header
+                        Err(e) => {
+                            error!("Failed to read packet: {}", e);
+                            break;
                         }
+                    },
+                    // Nothing was consumed, so just read again
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                    // A VM that is exiting may reset the socket
+                    Err(e) if exiting => {
+                        info!("VM closed the connection after exiting ({})", e);
+                        break;
                     }
                     Err(e) => {
-                        error!("Failed to read packet: {}", e);
+                        error!("Failed to read from the VM: {}", e);
                         break;
                     }
                 }
@@ -234,24 +400,118 @@ async fn event_loop_task(
         }
     }
 
+    // Wake every caller still waiting rather than leaving them to time out
+    for (_, pending) in pending_replies.drain() {
+        pending.sender.send(Err(JdwpError::ConnectionClosed)).ok();
+    }
+
     info!("Event loop shutting down");
 }
 
-/// Read a packet from the socket and determine if it's a reply or event
-async fn read_packet(reader: &mut OwnedReadHalf) -> JdwpResult<(bool, u32, Vec<u8>)> {
-    // Read header
-    let mut header = BytesMut::with_capacity(HEADER_SIZE);
-    header.resize(HEADER_SIZE, 0);
+/// Handle every complete packet in `buf`, leaving any partial one for the next read
+///
+/// Returns false once the event receiver is gone and the loop should stop.
+fn handle_buffered_packets(
+    buf: &mut BytesMut,
+    pending_replies: &mut HashMap<u32, PendingReply>,
+    event_tx: &mpsc::Sender<EventSet>,
+    config: &EventLoopConfig,
+    shared: &SharedState,
+) -> JdwpResult<bool> {
+    while let Some(data) = take_packet(buf, config.max_packet_size)? {
+        if let Some(tracer) = &config.packet_tracer {
+            tracer.trace(PacketDirection::Inbound, &data);
+        }
+
+        let packet_id = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+        let is_reply = data[8] == REPLY_FLAG;
+
+        if is_reply {
+            // It's a reply - route to waiting command
+            debug!("Received reply id={}", packet_id);
+
+            if let Some(pending) = pending_replies.remove(&packet_id) {
+                match ReplyPacket::decode(&data) {
+                    Ok(reply) => {
+                        pending.sender.send(Ok(reply)).ok();
+                    }
+                    Err(e) => {
+                        warn!("Failed to decode reply: {}", e);
+                        pending.sender.send(Err(e)).ok();
+                    }
+                }
+            } else {
+                warn!("Received reply for unknown command id={} (may have timed out)", packet_id);
+            }
+            continue;
+        }
+
+        // It's an event - parse and broadcast
+        debug!("Received event packet, len={}", data.len());
+
+        // The only command a VM sends is Event.Composite; anything
+        // else would be mis-parsed as one
+        let (command_set, command) = (data[9], data[10]);
+        if (command_set, command) != (command_sets::EVENT, vm_event_commands::COMPOSITE) {
+            warn!(
+                "Ignoring unexpected command packet id={} from the VM (command set {}, command {})",
+                packet_id, command_set, command
+            );
+            continue;
+        }
 
-    reader
-        .read_exact(&mut header)
-        .await
-        .map_err(JdwpError::Io)?;
+        // Data starts after 11-byte header
+        let event_data = &data[HEADER_SIZE..];
+
+        let sizes = shared.id_sizes.get().copied().unwrap_or_default();
+        match parse_event_packet_with_id_sizes(event_data, &sizes) {
+            Ok(event_set) => {
+                info!("Parsed event set: {} events, suspend_policy={}",
+                      event_set.events.len(), event_set.suspend_policy);
+                shared.stats.record_events(event_set.events.len());
+
+                // A suspending VMDeath was requested by a debugger that wants
+                // to look around first; only the VM's own notice means it's gone
+                if event_set.suspend_policy == 0
+                    && event_set.events.iter().any(|e| matches!(e.details, EventKind::VMDeath))
+                {
+                    info!("VM reported its death");
+                    shared.vm_exited.store(true, Ordering::Release);
+                }
+
+                // Send event without blocking to avoid deadlock
+                // If consumer is sending commands while we're reading, blocking here would deadlock
+                match event_tx.try_send(event_set) {
+                    Ok(_) => {},
+                    Err(mpsc::error::TrySendError::Full(dropped_event)) => {
+                        // Event channel is full - this is critical
+                        error!("Event channel full ({} buffered), dropping event with {} events. Consumer not keeping up!",
+                              event_tx.capacity(), dropped_event.events.len());
+                        // TODO: Consider adding backpressure or alerting mechanism
+                    }
+                    Err(mpsc::error::TrySendError::Closed(_)) => {
+                        info!("Event receiver dropped, shutting down event loop");
+                        return Ok(false);
+                    }
+                }
+            }
+            Err(e) => {
+                warn!("Failed to parse event: {}", e);
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+/// Split the next complete packet (header included) off the front of `buf`,
+/// or return None if it hasn't fully arrived yet
+fn take_packet(buf: &mut BytesMut, max_packet_size: usize) -> JdwpResult<Option<Vec<u8>>> {
+    if buf.len() < HEADER_SIZE {
+        return Ok(None);
+    }
 
-    // Parse header
-    let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
-    let packet_id = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
-    let flags = header[8];
+    let length = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
 
     if length < HEADER_SIZE {
         return Err(JdwpError::Protocol(format!(
@@ -260,24 +520,240 @@ async fn read_packet(reader: &mut OwnedReadHalf) -> JdwpResult<(bool, u32, Vec<u
         )));
     }
 
-    if length > MAX_PACKET_SIZE {
+    if length > max_packet_size {
         return Err(JdwpError::Protocol(format!(
             "Packet too large: {} bytes (max: {} bytes)",
-            length, MAX_PACKET_SIZE
+            length, max_packet_size
         )));
     }
 
-    // Read rest of packet
-    let data_len = length - HEADER_SIZE;
-    let mut full_packet = header.to_vec();
+    if buf.len() < length {
+        // Make room for the rest so it arrives in as few reads as possible
+        buf.reserve(length - buf.len());
+        return Ok(None);
+    }
+
+    Ok(Some(buf.split_to(length).to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::event_kinds;
+    use crate::fake_vm::send_command;
+    use bytes::BufMut;
+    use tokio::io::DuplexStream;
+
+    fn packet(id: u32, body: &[u8]) -> Vec<u8> {
+        let mut packet = Vec::new();
+        packet.put_u32((HEADER_SIZE + body.len()) as u32);
+        packet.put_u32(id);
+        packet.put_u8(REPLY_FLAG);
+        packet.put_u16(0);
+        packet.put_slice(body);
+        packet
+    }
+
+    #[test]
+    fn test_take_packet_waits_for_whole_packets() {
+        let first = packet(1, &[1, 2, 3]);
+        let second = packet(2, &[]);
+        let mut stream = first.clone();
+        stream.extend_from_slice(&second);
+
+        // Partial header, then partial body
+        let mut buf = BytesMut::from(&stream[..5]);
+        assert_eq!(take_packet(&mut buf, 1024).unwrap(), None);
+        buf.extend_from_slice(&stream[5..12]);
+        assert_eq!(take_packet(&mut buf, 1024).unwrap(), None);
+
+        // The rest arrives with the second packet in the same read
+        buf.extend_from_slice(&stream[12..]);
+        assert_eq!(take_packet(&mut buf, 1024).unwrap(), Some(first));
+        assert_eq!(take_packet(&mut buf, 1024).unwrap(), Some(second));
+        assert_eq!(take_packet(&mut buf, 1024).unwrap(), None);
+        assert!(buf.is_empty());
+    }
 
-    if data_len > 0 {
-        let mut data = vec![0u8; data_len];
-        reader.read_exact(&mut data).await.map_err(JdwpError::Io)?;
-        full_packet.extend_from_slice(&data);
+    #[test]
+    fn test_take_packet_rejects_bad_lengths() {
+        let mut buf = BytesMut::from(&packet(1, &[0; 100])[..]);
+        assert!(matches!(take_packet(&mut buf, 64), Err(JdwpError::Protocol(_))));
+
+        let mut buf = BytesMut::from(&[0, 0, 0, 5, 0, 0, 0, 1, 0, 0, 0][..]);
+        assert!(matches!(take_packet(&mut buf, 64), Err(JdwpError::Protocol(_))));
+    }
+
+    /// Start an event loop over an in-memory transport holding at most
+    /// `buffer` bytes in flight each way, returning the VM's end
+    fn spawn_fake_vm(config: EventLoopConfig, buffer: usize) -> (EventLoopHandle, DuplexStream) {
+        let (client, vm) = tokio::io::duplex(buffer);
+        let (reader, writer) = tokio::io::split(client);
+        (spawn_event_loop_with_config(reader, writer, config), vm)
     }
 
-    let is_reply = flags == REPLY_FLAG;
+    /// Read one command packet and return its id
+    async fn read_command_id(vm: &mut DuplexStream) -> u32 {
+        let mut header = [0u8; HEADER_SIZE];
+        vm.read_exact(&mut header).await.unwrap();
+        let length = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+        let mut body = vec![0u8; length - HEADER_SIZE];
+        vm.read_exact(&mut body).await.unwrap();
+        u32::from_be_bytes(header[4..8].try_into().unwrap())
+    }
 
-    Ok((is_reply, packet_id, full_packet))
+    #[tokio::test]
+    async fn test_command_reusing_a_pending_id_is_rejected() {
+        let (handle, mut vm) = spawn_fake_vm(EventLoopConfig::default(), 4096);
+
+        let version = || CommandPacket::new(7, command_sets::VIRTUAL_MACHINE, vm_commands::VERSION);
+        let first = tokio::spawn({
+            let handle = handle.clone();
+            async move { handle.send_command(version()).await }
+        });
+        assert_eq!(read_command_id(&mut vm).await, 7);
+
+        // Same id while the first is still waiting: never sent
+        let error = handle.send_command(version()).await.unwrap_err();
+        assert!(error.to_string().contains("packet id 7 is already in use"), "{}", error);
+
+        // The first command still gets its own reply
+        vm.write_all(&packet(7, b"first")).await.unwrap();
+        assert_eq!(first.await.unwrap().unwrap().data(), b"first");
+
+        // The next packet the VM sees is the next command, not the duplicate
+        let next = tokio::spawn({
+            let handle = handle.clone();
+            async move { handle.send_command(CommandPacket::new(8, command_sets::VIRTUAL_MACHINE, vm_commands::VERSION)).await }
+        });
+        assert_eq!(read_command_id(&mut vm).await, 8);
+        vm.write_all(&packet(8, b"")).await.unwrap();
+        next.await.unwrap().unwrap();
+
+        // Once answered, the id is free again
+        let again = tokio::spawn({
+            let handle = handle.clone();
+            async move { handle.send_command(version()).await }
+        });
+        assert_eq!(read_command_id(&mut vm).await, 7);
+        vm.write_all(&packet(7, b"")).await.unwrap();
+        again.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_event_loop_skips_non_event_commands() {
+        let (handle, mut vm) = spawn_fake_vm(EventLoopConfig::default(), 4096);
+
+        // A ThreadStart sent twice: first under the wrong command, then as Event.Composite
+        for (packet_id, command_set, thread) in [(1, command_sets::VIRTUAL_MACHINE, 0x11), (2, command_sets::EVENT, 0x22)] {
+            let mut data = Vec::new();
+            data.put_u8(0); // suspend none
+            data.put_i32(1);
+            data.put_u8(event_kinds::THREAD_START);
+            data.put_i32(0);
+            data.put_u64(thread);
+            send_command(&mut vm, packet_id, (command_set, vm_event_commands::COMPOSITE), &data).await;
+        }
+
+        let event_set = tokio::time::timeout(Duration::from_secs(5), handle.recv_event())
+            .await
+            .expect("the composite event should arrive")
+            .unwrap();
+        assert!(matches!(event_set.events[0].details, EventKind::ThreadStart { thread: 0x22 }));
+        assert!(handle.try_recv_event().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reply_split_across_reads_survives_concurrent_command() {
+        // A write past five bytes only completes once the event loop has
+        // read what came before
+        let (handle, mut vm) = spawn_fake_vm(EventLoopConfig::default(), 5);
+
+        let version = |id| CommandPacket::new(id, command_sets::VIRTUAL_MACHINE, vm_commands::VERSION);
+        let first = tokio::spawn({
+            let handle = handle.clone();
+            async move { handle.send_command(version(1)).await }
+        });
+        assert_eq!(read_command_id(&mut vm).await, 1);
+
+        // Stop mid-header, and only finish once the second command has
+        // been sent while the event loop was part way through reading
+        let reply = packet(1, b"first");
+        vm.write_all(&reply[..5]).await.unwrap();
+        vm.write_all(&reply[5..6]).await.unwrap();
+
+        let second = tokio::spawn({
+            let handle = handle.clone();
+            async move { handle.send_command(version(2)).await }
+        });
+        assert_eq!(read_command_id(&mut vm).await, 2);
+        vm.write_all(&reply[6..]).await.unwrap();
+        vm.write_all(&packet(2, b"second")).await.unwrap();
+
+        assert_eq!(second.await.unwrap().unwrap().data(), b"second");
+        assert_eq!(first.await.unwrap().unwrap().data(), b"first");
+    }
+
+    #[tokio::test]
+    async fn test_commands_fail_fast_after_vm_death() {
+        let (handle, mut vm) = spawn_fake_vm(EventLoopConfig::default(), 4096);
+
+        // Announce VM death but keep the transport open, as a dying VM may
+        let mut data = Vec::new();
+        data.put_u8(0); // suspend none
+        data.put_i32(1);
+        data.put_u8(event_kinds::VM_DEATH);
+        data.put_i32(0);
+        send_command(&mut vm, 1, (command_sets::EVENT, vm_event_commands::COMPOSITE), &data).await;
+
+        let event_set = tokio::time::timeout(Duration::from_secs(5), handle.recv_event())
+            .await
+            .expect("the VMDeath event should arrive")
+            .unwrap();
+        assert!(matches!(event_set.events[0].details, EventKind::VMDeath));
+        assert!(handle.vm_exited());
+
+        // Nothing answers, so this would otherwise wait for the reply timeout
+        let version = CommandPacket::new(1, command_sets::VIRTUAL_MACHINE, vm_commands::VERSION);
+        let result = tokio::time::timeout(Duration::from_secs(1), handle.send_command(version))
+            .await
+            .expect("commands should fail immediately");
+        assert!(matches!(result, Err(JdwpError::ConnectionClosed)));
+    }
+
+    #[tokio::test]
+    async fn test_packet_tracer_sees_raw_packets() {
+        use std::sync::Mutex;
+
+        let trace = Arc::new(Mutex::new(Vec::new()));
+        let recorded = trace.clone();
+        let config = EventLoopConfig {
+            packet_tracer: Some(PacketTracer::new(move |direction, packet| {
+                recorded.lock().unwrap().push((direction, packet.to_vec()))
+            })),
+            ..EventLoopConfig::default()
+        };
+        let (handle, mut vm) = spawn_fake_vm(config, 4096);
+
+        let version = CommandPacket::new(7, command_sets::VIRTUAL_MACHINE, vm_commands::VERSION);
+        let reply = tokio::spawn({
+            let handle = handle.clone();
+            async move { handle.send_command(version).await }
+        });
+        assert_eq!(read_command_id(&mut vm).await, 7);
+        vm.write_all(&packet(7, b"reply")).await.unwrap();
+        reply.await.unwrap().unwrap();
+
+        let trace = trace.lock().unwrap();
+        let directions: Vec<_> = trace.iter().map(|(direction, _)| *direction).collect();
+        assert_eq!(directions, [PacketDirection::Outbound, PacketDirection::Inbound]);
+
+        let (_, command) = &trace[0];
+        assert_eq!(command.len(), HEADER_SIZE);
+        assert_eq!(&command[9..11], [command_sets::VIRTUAL_MACHINE, vm_commands::VERSION]);
+
+        // Replies are traced whole, header included
+        let (_, reply) = &trace[1];
+        assert_eq!(reply, &packet(7, b"reply"));
+    }
 }
@@ -6,10 +6,12 @@ use crate::commands::{command_sets, event_commands, event_kinds};
 use crate::connection::JdwpConnection;
 use crate::protocol::{CommandPacket, JdwpResult};
 use crate::reader::read_i32;
-use crate::types::{Location, MethodId, ReferenceTypeId};
+use crate::types::{FieldId, MethodId, ReferenceTypeId, ThreadId};
+use crate::vm::write_string;
 use bytes::BufMut;
 
 /// Suspend policy for events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum SuspendPolicy {
     None = 0,
@@ -20,11 +22,17 @@ pub enum SuspendPolicy {
 impl JdwpConnection {
     /// Set a breakpoint at a specific location (EventRequest.Set command)
     /// Returns the request ID for this breakpoint
+    ///
+    /// With `thread` only hits on that thread are reported. With `count` the
+    /// VM ignores the first `count - 1` (matching) hits, reports the next one
+    /// and then discards the request; `Some(1)` gives a one-shot breakpoint.
     pub async fn set_breakpoint(
         &mut self,
         class_id: ReferenceTypeId,
         method_id: MethodId,
         bytecode_index: u64,
+        thread: Option<ThreadId>,
+        count: Option<i32>,
         suspend_policy: SuspendPolicy,
     ) -> JdwpResult<i32> {
         let id = self.next_id();
@@ -36,8 +44,8 @@ impl JdwpConnection {
         // Suspend policy
         packet.data.put_u8(suspend_policy as u8);
 
-        // Number of modifiers (1 - location only)
-        packet.data.put_i32(1);
+        // Number of modifiers (location, plus the thread and count if given)
+        packet.data.put_i32(1 + thread.is_some() as i32 + count.is_some() as i32);
 
         // Modifier kind: LocationOnly (7)
         packet.data.put_u8(7);
@@ -45,13 +53,135 @@ impl JdwpConnection {
         // Location:
         // - type tag (1 = class)
         packet.data.put_u8(1);
+        let sizes = self.id_sizes();
         // - class ID
-        packet.data.put_u64(class_id);
+        sizes.write_reference_type_id(&mut packet.data, class_id);
         // - method ID
-        packet.data.put_u64(method_id);
+        sizes.write_method_id(&mut packet.data, method_id);
         // - index (bytecode position)
         packet.data.put_u64(bytecode_index);
 
+        // Modifier kind: ThreadOnly (3)
+        if let Some(thread) = thread {
+            packet.data.put_u8(3);
+            sizes.write_object_id(&mut packet.data, thread);
+        }
+
+        // Modifier kind: Count (1), last so only hits the other modifiers
+        // let through count
+        if let Some(count) = count {
+            packet.data.put_u8(1);
+            packet.data.put_i32(count);
+        }
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        let request_id = read_i32(&mut data)?;
+
+        Ok(request_id)
+    }
+
+    /// Watch reads or writes of a field (EventRequest.Set command)
+    /// Returns the request ID for the watchpoint
+    ///
+    /// `event_kind` is `event_kinds::FIELD_ACCESS` or `event_kinds::FIELD_MODIFICATION`;
+    /// the VM must support `can_watch_field_access` / `can_watch_field_modification`.
+    /// Clear it with `clear_event_request` and the same kind.
+    pub async fn set_field_watchpoint(
+        &mut self,
+        event_kind: u8,
+        class_id: ReferenceTypeId,
+        field_id: FieldId,
+        suspend_policy: SuspendPolicy,
+    ) -> JdwpResult<i32> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::EVENT_REQUEST, event_commands::SET);
+
+        packet.data.put_u8(event_kind);
+        packet.data.put_u8(suspend_policy as u8);
+
+        // Number of modifiers (1 - field only)
+        packet.data.put_i32(1);
+
+        // Modifier kind: FieldOnly (9)
+        packet.data.put_u8(9);
+        let sizes = self.id_sizes();
+        sizes.write_reference_type_id(&mut packet.data, class_id);
+        sizes.write_field_id(&mut packet.data, field_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        let request_id = read_i32(&mut data)?;
+
+        Ok(request_id)
+    }
+
+    /// Suspend when an exception is thrown (EventRequest.Set command)
+    /// Returns the request ID for the exception request
+    ///
+    /// `ref_type` limits the request to that exception class and its
+    /// subclasses; None matches every exception. Clear it with
+    /// `clear_event_request` and `event_kinds::EXCEPTION`.
+    pub async fn set_exception_request(
+        &mut self,
+        ref_type: Option<ReferenceTypeId>,
+        caught: bool,
+        uncaught: bool,
+        suspend_policy: SuspendPolicy,
+    ) -> JdwpResult<i32> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::EVENT_REQUEST, event_commands::SET);
+
+        packet.data.put_u8(event_kinds::EXCEPTION);
+        packet.data.put_u8(suspend_policy as u8);
+
+        // Number of modifiers (1 - exception only)
+        packet.data.put_i32(1);
+
+        // Modifier kind: ExceptionOnly (8)
+        packet.data.put_u8(8);
+        // - exception type (0 = all exceptions)
+        self.id_sizes().write_reference_type_id(&mut packet.data, ref_type.unwrap_or(0));
+        packet.data.put_u8(caught as u8);
+        packet.data.put_u8(uncaught as u8);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        let request_id = read_i32(&mut data)?;
+
+        Ok(request_id)
+    }
+
+    /// Report classes as they are prepared (EventRequest.Set command)
+    /// Returns the request ID for the class prepare request
+    ///
+    /// `class_pattern` is a dotted class name that may start or end with '*',
+    /// e.g. "com.example.Worker" or "com.example.*". Clear it with
+    /// `clear_event_request` and `event_kinds::CLASS_PREPARE`.
+    pub async fn set_class_prepare_request(
+        &mut self,
+        class_pattern: &str,
+        suspend_policy: SuspendPolicy,
+    ) -> JdwpResult<i32> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::EVENT_REQUEST, event_commands::SET);
+
+        packet.data.put_u8(event_kinds::CLASS_PREPARE);
+        packet.data.put_u8(suspend_policy as u8);
+
+        // Number of modifiers (1 - class match only)
+        packet.data.put_i32(1);
+
+        // Modifier kind: ClassMatch (5)
+        packet.data.put_u8(5);
+        write_string(&mut packet.data, class_pattern);
+
         let reply = self.send_command(packet).await?;
         reply.check_error()?;
 
@@ -63,11 +193,18 @@ impl JdwpConnection {
 
     /// Clear a breakpoint by request ID (EventRequest.Clear command)
     pub async fn clear_breakpoint(&mut self, request_id: i32) -> JdwpResult<()> {
+        self.clear_event_request(event_kinds::BREAKPOINT, request_id).await
+    }
+
+    /// Clear any event request (EventRequest.Clear command)
+    ///
+    /// `event_kind` must be the kind the request was set with (one of
+    /// `event_kinds::*`); some VMs silently ignore a Clear with the wrong kind.
+    pub async fn clear_event_request(&mut self, event_kind: u8, request_id: i32) -> JdwpResult<()> {
         let id = self.next_id();
         let mut packet = CommandPacket::new(id, command_sets::EVENT_REQUEST, event_commands::CLEAR);
 
-        // Event kind: BREAKPOINT
-        packet.data.put_u8(event_kinds::BREAKPOINT);
+        packet.data.put_u8(event_kind);
 
         // Request ID
         packet.data.put_i32(request_id);
@@ -77,4 +214,59 @@ impl JdwpConnection {
 
         Ok(())
     }
+
+    /// Request a single step on a thread (EventRequest.Set command)
+    /// Returns the request ID for the step request
+    ///
+    /// `size` is one of `step_sizes::*` and `depth` is one of `step_depths::*`.
+    /// The step fires once the thread is resumed; clear it afterwards with
+    /// `clear_step_request`, otherwise it keeps firing on every step.
+    ///
+    /// `step_filters` are class patterns (e.g. "java.*") to step through
+    /// without stopping; a pattern may begin or end with '*'.
+    pub async fn set_step_request(
+        &mut self,
+        thread_id: ThreadId,
+        size: i32,
+        depth: i32,
+        step_filters: &[String],
+        suspend_policy: SuspendPolicy,
+    ) -> JdwpResult<i32> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::EVENT_REQUEST, event_commands::SET);
+
+        // Event kind: SINGLE_STEP (1)
+        packet.data.put_u8(event_kinds::SINGLE_STEP);
+
+        // Suspend policy
+        packet.data.put_u8(suspend_policy as u8);
+
+        // Number of modifiers (step, plus one per filter)
+        packet.data.put_i32(1 + step_filters.len() as i32);
+
+        // Modifier kind: Step (10)
+        packet.data.put_u8(10);
+        self.id_sizes().write_object_id(&mut packet.data, thread_id);
+        packet.data.put_i32(size);
+        packet.data.put_i32(depth);
+
+        // Modifier kind: ClassExclude (6)
+        for pattern in step_filters {
+            packet.data.put_u8(6);
+            write_string(&mut packet.data, pattern);
+        }
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        let request_id = read_i32(&mut data)?;
+
+        Ok(request_id)
+    }
+
+    /// Clear a step request by request ID (EventRequest.Clear command)
+    pub async fn clear_step_request(&mut self, request_id: i32) -> JdwpResult<()> {
+        self.clear_event_request(event_kinds::SINGLE_STEP, request_id).await
+    }
 }
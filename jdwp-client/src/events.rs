@@ -3,9 +3,11 @@
 // Events are sent from the JVM to notify about breakpoints, steps, etc.
 
 use crate::commands::event_kinds;
-use crate::protocol::{JdwpError, JdwpResult};
-use crate::reader::{read_i32, read_u64, read_u8};
+use crate::object::read_value_by_tag;
+use crate::protocol::JdwpResult;
+use crate::reader::{read_i32, read_string, read_u8};
 use crate::types::*;
+use crate::vm::VmIdSizes;
 use serde::{Deserialize, Serialize};
 use tracing::warn;
 
@@ -57,6 +59,27 @@ pub enum EventKind {
         exception: ObjectId,
         catch_location: Option<Location>,
     },
+    /// A watched field was read. The event carries no value; read the
+    /// field while the thread is suspended to get it
+    FieldAccess {
+        thread: ThreadId,
+        location: Location,
+        /// Type declaring the field
+        ref_type: ReferenceTypeId,
+        field_id: FieldId,
+        /// Object whose field was read (0 for a static field)
+        object: ObjectId,
+    },
+    /// A watched field is about to be written; the field still holds the
+    /// old value while the thread is suspended
+    FieldModification {
+        thread: ThreadId,
+        location: Location,
+        ref_type: ReferenceTypeId,
+        field_id: FieldId,
+        object: ObjectId,
+        new_value: Value,
+    },
     MethodEntry {
         thread: ThreadId,
         location: Location,
@@ -70,6 +93,82 @@ pub enum EventKind {
     },
 }
 
+impl EventKind {
+    /// The thread the event happened on, if it has one
+    pub fn thread(&self) -> Option<ThreadId> {
+        match self {
+            EventKind::VMStart { thread }
+            | EventKind::ThreadStart { thread }
+            | EventKind::ThreadDeath { thread }
+            | EventKind::ClassPrepare { thread, .. }
+            | EventKind::Breakpoint { thread, .. }
+            | EventKind::Step { thread, .. }
+            | EventKind::Exception { thread, .. }
+            | EventKind::FieldAccess { thread, .. }
+            | EventKind::FieldModification { thread, .. }
+            | EventKind::MethodEntry { thread, .. }
+            | EventKind::MethodExit { thread, .. } => Some(*thread),
+            EventKind::VMDeath | EventKind::Unknown { .. } => None,
+        }
+    }
+
+    /// Where the event happened, if it has a code location
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            EventKind::Breakpoint { location, .. }
+            | EventKind::Step { location, .. }
+            | EventKind::Exception { location, .. }
+            | EventKind::FieldAccess { location, .. }
+            | EventKind::FieldModification { location, .. }
+            | EventKind::MethodEntry { location, .. }
+            | EventKind::MethodExit { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+}
+
+impl EventSet {
+    /// Find the step event generated by a specific step request
+    ///
+    /// A composite event set can carry unrelated events (thread start,
+    /// breakpoints, other step requests) alongside the one we're waiting
+    /// for, so match on the request ID rather than taking the first event.
+    pub fn step_event(&self, request_id: i32) -> Option<(ThreadId, &Location)> {
+        self.events
+            .iter()
+            .filter(|event| event.request_id == request_id)
+            .find_map(|event| match &event.details {
+                EventKind::Step { thread, location } => Some((*thread, location)),
+                _ => None,
+            })
+    }
+
+    /// The first event that stops the program from the user's point of view:
+    /// a breakpoint, an exception, a watchpoint, or the VM exiting
+    pub fn stop_event(&self) -> Option<&Event> {
+        self.events.iter().find(|event| {
+            matches!(
+                event.details,
+                EventKind::Breakpoint { .. }
+                    | EventKind::Exception { .. }
+                    | EventKind::FieldAccess { .. }
+                    | EventKind::FieldModification { .. }
+                    | EventKind::VMDeath
+            )
+        })
+    }
+
+    /// The first field access or modification event
+    pub fn watchpoint_event(&self) -> Option<&Event> {
+        self.events.iter().find(|event| {
+            matches!(
+                event.details,
+                EventKind::FieldAccess { .. } | EventKind::FieldModification { .. }
+            )
+        })
+    }
+}
+
 // Event request modifiers
 #[derive(Debug, Clone)]
 pub enum EventModifier {
@@ -96,8 +195,13 @@ pub enum EventModifier {
     InstanceOnly(ObjectId),
 }
 
-/// Parse an event packet from JDWP
+/// Parse an event packet from a VM using 8-byte IDs
 pub fn parse_event_packet(data: &[u8]) -> JdwpResult<EventSet> {
+    parse_event_packet_with_id_sizes(data, &VmIdSizes::default())
+}
+
+/// Parse an event packet from JDWP, reading IDs with the VM's sizes
+pub fn parse_event_packet_with_id_sizes(data: &[u8], sizes: &VmIdSizes) -> JdwpResult<EventSet> {
     let mut buf = data;
 
     // Read suspend policy
@@ -106,7 +210,9 @@ pub fn parse_event_packet(data: &[u8]) -> JdwpResult<EventSet> {
     // Read number of events
     let event_count = read_i32(&mut buf)?;
 
-    let mut events = Vec::with_capacity(event_count as usize);
+    // Don't size the allocation from an untrusted count; a malformed packet
+    // could claim billions of events
+    let mut events = Vec::with_capacity((event_count.max(0) as usize).min(buf.len()));
 
     for _ in 0..event_count {
         let kind = read_u8(&mut buf)?;
@@ -114,28 +220,84 @@ pub fn parse_event_packet(data: &[u8]) -> JdwpResult<EventSet> {
 
         let details = match kind {
             event_kinds::BREAKPOINT => {
-                let thread = read_u64(&mut buf)?;
-                let location = read_location(&mut buf)?;
+                let thread = sizes.read_object_id(&mut buf)?;
+                let location = sizes.read_location(&mut buf)?;
                 EventKind::Breakpoint { thread, location }
             }
             event_kinds::SINGLE_STEP => {
-                let thread = read_u64(&mut buf)?;
-                let location = read_location(&mut buf)?;
+                let thread = sizes.read_object_id(&mut buf)?;
+                let location = sizes.read_location(&mut buf)?;
                 EventKind::Step { thread, location }
             }
+            event_kinds::EXCEPTION => {
+                let thread = sizes.read_object_id(&mut buf)?;
+                let location = sizes.read_location(&mut buf)?;
+                let _tag = read_u8(&mut buf)?; // Tagged object ID
+                let exception = sizes.read_object_id(&mut buf)?;
+                let catch_location = sizes.read_location(&mut buf)?;
+                EventKind::Exception {
+                    thread,
+                    location,
+                    exception,
+                    // A zero class ID means the exception isn't caught
+                    catch_location: (catch_location.class_id != 0).then_some(catch_location),
+                }
+            }
+            event_kinds::FIELD_ACCESS | event_kinds::FIELD_MODIFICATION => {
+                let thread = sizes.read_object_id(&mut buf)?;
+                let location = sizes.read_location(&mut buf)?;
+                let _ref_type_tag = read_u8(&mut buf)?;
+                let ref_type = sizes.read_reference_type_id(&mut buf)?;
+                let field_id = sizes.read_field_id(&mut buf)?;
+                let _tag = read_u8(&mut buf)?; // Tagged object ID
+                let object = sizes.read_object_id(&mut buf)?;
+
+                if kind == event_kinds::FIELD_ACCESS {
+                    EventKind::FieldAccess { thread, location, ref_type, field_id, object }
+                } else {
+                    let tag = read_u8(&mut buf)?;
+                    let data = read_value_by_tag(tag, &mut buf, sizes)?;
+                    EventKind::FieldModification {
+                        thread,
+                        location,
+                        ref_type,
+                        field_id,
+                        object,
+                        new_value: Value { tag, data },
+                    }
+                }
+            }
+            event_kinds::CLASS_PREPARE => {
+                let thread = sizes.read_object_id(&mut buf)?;
+                let _ref_type_tag = read_u8(&mut buf)?;
+                let ref_type = sizes.read_reference_type_id(&mut buf)?;
+                let signature = read_string(&mut buf)?;
+                let status = read_i32(&mut buf)?;
+                EventKind::ClassPrepare { thread, ref_type, signature, status }
+            }
+            event_kinds::METHOD_ENTRY => {
+                let thread = sizes.read_object_id(&mut buf)?;
+                let location = sizes.read_location(&mut buf)?;
+                EventKind::MethodEntry { thread, location }
+            }
+            event_kinds::METHOD_EXIT => {
+                let thread = sizes.read_object_id(&mut buf)?;
+                let location = sizes.read_location(&mut buf)?;
+                EventKind::MethodExit { thread, location }
+            }
             event_kinds::VM_START => {
-                let thread = read_u64(&mut buf)?;
+                let thread = sizes.read_object_id(&mut buf)?;
                 EventKind::VMStart { thread }
             }
             event_kinds::VM_DEATH => {
                 EventKind::VMDeath
             }
             event_kinds::THREAD_START => {
-                let thread = read_u64(&mut buf)?;
+                let thread = sizes.read_object_id(&mut buf)?;
                 EventKind::ThreadStart { thread }
             }
             event_kinds::THREAD_DEATH => {
-                let thread = read_u64(&mut buf)?;
+                let thread = sizes.read_object_id(&mut buf)?;
                 EventKind::ThreadDeath { thread }
             }
             _ => {
@@ -157,17 +319,255 @@ pub fn parse_event_packet(data: &[u8]) -> JdwpResult<EventSet> {
     })
 }
 
-/// Read a location from the buffer
-fn read_location(buf: &mut &[u8]) -> JdwpResult<Location> {
-    let type_tag = read_u8(buf)?;
-    let class_id = read_u64(buf)?;
-    let method_id = read_u64(buf)?;
-    let index = read_u64(buf)?;
-
-    Ok(Location {
-        type_tag,
-        class_id,
-        method_id,
-        index,
-    })
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BufMut;
+
+    fn put_location(buf: &mut Vec<u8>, class_id: u64, method_id: u64, index: u64) {
+        buf.put_u8(1);
+        buf.put_u64(class_id);
+        buf.put_u64(method_id);
+        buf.put_u64(index);
+    }
+
+    #[test]
+    fn test_step_event_matches_request_id() {
+        let mut packet = Vec::new();
+        packet.put_u8(2); // suspend policy: all
+        packet.put_i32(3); // event count
+
+        // Unrelated thread start
+        packet.put_u8(event_kinds::THREAD_START);
+        packet.put_i32(0);
+        packet.put_u64(0x10);
+
+        // Step for a different request
+        packet.put_u8(event_kinds::SINGLE_STEP);
+        packet.put_i32(6);
+        packet.put_u64(0x20);
+        put_location(&mut packet, 0xaa, 0xbb, 3);
+
+        // The step we're waiting for
+        packet.put_u8(event_kinds::SINGLE_STEP);
+        packet.put_i32(7);
+        packet.put_u64(0x20);
+        put_location(&mut packet, 0xaa, 0xbb, 12);
+
+        let event_set = parse_event_packet(&packet).unwrap();
+        assert_eq!(event_set.events.len(), 3);
+
+        let (thread, location) = event_set.step_event(7).unwrap();
+        assert_eq!(thread, 0x20);
+        assert_eq!(location.class_id, 0xaa);
+        assert_eq!(location.method_id, 0xbb);
+        assert_eq!(location.index, 12);
+
+        assert!(event_set.step_event(0).is_none());
+        assert!(event_set.step_event(99).is_none());
+    }
+
+    #[test]
+    fn test_stop_event_finds_exception() {
+        let mut packet = Vec::new();
+        packet.put_u8(2); // suspend policy: all
+        packet.put_i32(2); // event count
+
+        // Unrelated thread start
+        packet.put_u8(event_kinds::THREAD_START);
+        packet.put_i32(0);
+        packet.put_u64(0x10);
+
+        // Uncaught exception
+        packet.put_u8(event_kinds::EXCEPTION);
+        packet.put_i32(4);
+        packet.put_u64(0x20);
+        put_location(&mut packet, 0xaa, 0xbb, 7);
+        packet.put_u8(b'L');
+        packet.put_u64(0xe0);
+        put_location(&mut packet, 0, 0, 0);
+
+        let event_set = parse_event_packet(&packet).unwrap();
+        let event = event_set.stop_event().unwrap();
+
+        assert_eq!(event.request_id, 4);
+        match &event.details {
+            EventKind::Exception { thread, location, exception, catch_location } => {
+                assert_eq!(*thread, 0x20);
+                assert_eq!(location.index, 7);
+                assert_eq!(*exception, 0xe0);
+                assert!(catch_location.is_none());
+            }
+            other => panic!("Expected exception event, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_caught_exception() {
+        let mut packet = Vec::new();
+        packet.put_u8(1); // suspend policy: event thread
+        packet.put_i32(1);
+        packet.put_u8(event_kinds::EXCEPTION);
+        packet.put_i32(3);
+        packet.put_u64(0x20);
+        put_location(&mut packet, 0xaa, 0xbb, 7);
+        packet.put_u8(b'L');
+        packet.put_u64(0xe0);
+        put_location(&mut packet, 0xaa, 0xcc, 12);
+
+        let event_set = parse_event_packet(&packet).unwrap();
+        match &event_set.events[0].details {
+            EventKind::Exception { exception, catch_location: Some(catch_location), .. } => {
+                assert_eq!(*exception, 0xe0);
+                assert_eq!(catch_location.method_id, 0xcc);
+                assert_eq!(catch_location.index, 12);
+            }
+            other => panic!("Expected caught exception, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_class_prepare_and_method_events() {
+        let mut packet = Vec::new();
+        packet.put_u8(0);
+        packet.put_i32(3);
+
+        packet.put_u8(event_kinds::CLASS_PREPARE);
+        packet.put_i32(1);
+        packet.put_u64(0x20);
+        packet.put_u8(1); // class
+        packet.put_u64(0x90);
+        packet.put_u32(13);
+        packet.put_slice(b"Lcom/x/Hello;");
+        packet.put_i32(7); // verified | prepared | initialized
+
+        packet.put_u8(event_kinds::METHOD_ENTRY);
+        packet.put_i32(2);
+        packet.put_u64(0x20);
+        put_location(&mut packet, 0x90, 0x91, 0);
+
+        packet.put_u8(event_kinds::METHOD_EXIT);
+        packet.put_i32(3);
+        packet.put_u64(0x20);
+        put_location(&mut packet, 0x90, 0x91, 9);
+
+        let events = parse_event_packet(&packet).unwrap().events;
+        match &events[0].details {
+            EventKind::ClassPrepare { thread, ref_type, signature, status } => {
+                assert_eq!(*thread, 0x20);
+                assert_eq!(*ref_type, 0x90);
+                assert_eq!(signature, "Lcom/x/Hello;");
+                assert_eq!(*status, 7);
+            }
+            other => panic!("Expected class prepare, got {:?}", other),
+        }
+        assert!(matches!(&events[1].details, EventKind::MethodEntry { location, .. } if location.method_id == 0x91));
+        assert!(matches!(&events[2].details, EventKind::MethodExit { location, .. } if location.index == 9));
+
+        assert_eq!(events[0].details.thread(), Some(0x20));
+        assert!(events[0].details.location().is_none());
+        assert_eq!(events[2].details.location().map(|l| l.index), Some(9));
+        assert_eq!(EventKind::VMDeath.thread(), None);
+    }
+
+    #[test]
+    fn test_parse_with_four_byte_ids() {
+        let mut packet = Vec::new();
+        packet.put_u8(2);
+        packet.put_i32(1);
+        packet.put_u8(event_kinds::BREAKPOINT);
+        packet.put_i32(5);
+        packet.put_u32(0x20); // thread
+        packet.put_u8(1);
+        packet.put_u32(0xaa); // class
+        packet.put_u32(0xbb); // method
+        packet.put_u64(7); // code index is always 8 bytes
+
+        let sizes = VmIdSizes {
+            object_id_size: 4,
+            reference_type_id_size: 4,
+            method_id_size: 4,
+            ..VmIdSizes::default()
+        };
+        let event_set = parse_event_packet_with_id_sizes(&packet, &sizes).unwrap();
+
+        match &event_set.events[0].details {
+            EventKind::Breakpoint { thread, location } => {
+                assert_eq!(*thread, 0x20);
+                assert_eq!((location.class_id, location.method_id, location.index), (0xaa, 0xbb, 7));
+            }
+            other => panic!("Expected breakpoint event, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_field_modification_carries_new_value() {
+        let mut packet = Vec::new();
+        packet.put_u8(1); // suspend policy: event thread
+        packet.put_i32(2); // event count
+
+        packet.put_u8(event_kinds::FIELD_ACCESS);
+        packet.put_i32(8);
+        packet.put_u64(0x20);
+        put_location(&mut packet, 0xaa, 0xbb, 3);
+        packet.put_u8(1);
+        packet.put_u64(0xaa);
+        packet.put_u64(0xf1);
+        packet.put_u8(b'L');
+        packet.put_u64(0x300);
+
+        packet.put_u8(event_kinds::FIELD_MODIFICATION);
+        packet.put_i32(9);
+        packet.put_u64(0x20);
+        put_location(&mut packet, 0xaa, 0xbb, 5);
+        packet.put_u8(1);
+        packet.put_u64(0xaa);
+        packet.put_u64(0xf1);
+        packet.put_u8(b'L');
+        packet.put_u64(0x300);
+        packet.put_u8(b'I');
+        packet.put_i32(42);
+
+        let event_set = parse_event_packet(&packet).unwrap();
+        assert_eq!(event_set.events.len(), 2);
+        assert_eq!(event_set.watchpoint_event().unwrap().request_id, 8);
+
+        match &event_set.events[1].details {
+            EventKind::FieldModification { field_id, object, new_value, .. } => {
+                assert_eq!(*field_id, 0xf1);
+                assert_eq!(*object, 0x300);
+                assert_eq!((new_value.tag, &new_value.data), (b'I', &ValueData::Int(42)));
+            }
+            other => panic!("Expected field modification event, got {:?}", other),
+        }
+    }
+
+    mod fuzz {
+        use super::*;
+        use proptest::prelude::*;
+
+        proptest! {
+            #[test]
+            fn parse_event_packet_never_panics(bytes in proptest::collection::vec(any::<u8>(), 0..256)) {
+                let _ = parse_event_packet(&bytes);
+            }
+
+            #[test]
+            fn parse_event_packet_handles_any_event_kind(
+                suspend_policy in any::<u8>(),
+                event_count in any::<i32>(),
+                kind in any::<u8>(),
+                body in proptest::collection::vec(any::<u8>(), 0..64),
+            ) {
+                // A well-formed header followed by arbitrary event contents
+                let mut packet = Vec::new();
+                packet.put_u8(suspend_policy);
+                packet.put_i32(event_count);
+                packet.put_u8(kind);
+                packet.put_slice(&body);
+
+                let _ = parse_event_packet(&packet);
+            }
+        }
+    }
 }
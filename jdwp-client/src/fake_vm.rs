@@ -0,0 +1,240 @@
+// Fake VM for tests
+//
+// The VM end of an in-memory transport: tests read the commands the
+// connection sends and script the replies and events it gets back. Built
+// with the `test-util` feature so crates using the client can share it.
+
+use crate::builder::JdwpConnectionBuilder;
+use crate::commands::{command_sets, vm_commands};
+use crate::connection::JdwpConnection;
+use crate::protocol::{HEADER_SIZE, JDWP_HANDSHAKE, REPLY_FLAG};
+use bytes::{Buf, BufMut};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream};
+use tokio::net::TcpStream;
+
+/// Append a JDWP string (length-prefixed UTF-8)
+pub fn put_string(buf: &mut Vec<u8>, s: &str) {
+    buf.put_u32(s.len() as u32);
+    buf.put_slice(s.as_bytes());
+}
+
+/// Read the leading object ID of a command
+pub fn first_id(mut data: &[u8]) -> u64 {
+    data.get_u64()
+}
+
+/// Read one command, check it's the expected one, and return its header and data
+pub async fn read_command<S: AsyncRead + Unpin>(socket: &mut S, expected: (u8, u8)) -> ([u8; HEADER_SIZE], Vec<u8>) {
+    let mut header = [0u8; HEADER_SIZE];
+    socket.read_exact(&mut header).await.unwrap();
+    let length = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+    let mut body = vec![0u8; length - HEADER_SIZE];
+    socket.read_exact(&mut body).await.unwrap();
+    assert_eq!((header[9], header[10]), expected);
+    (header, body)
+}
+
+/// Encode a reply to the command with this header
+fn reply(header: &[u8; HEADER_SIZE], error_code: u16, data: &[u8]) -> Vec<u8> {
+    let mut reply = Vec::new();
+    reply.put_u32((HEADER_SIZE + data.len()) as u32);
+    reply.extend_from_slice(&header[4..8]);
+    reply.put_u8(REPLY_FLAG);
+    reply.put_u16(error_code);
+    reply.put_slice(data);
+    reply
+}
+
+pub async fn send_reply<S: AsyncWrite + Unpin>(socket: &mut S, header: &[u8; HEADER_SIZE], data: &[u8]) {
+    socket.write_all(&reply(header, 0, data)).await.unwrap();
+}
+
+/// Send a command packet from the VM, as events are
+pub async fn send_command<S: AsyncWrite + Unpin>(socket: &mut S, id: u32, command: (u8, u8), data: &[u8]) {
+    let mut packet = Vec::new();
+    packet.put_u32((HEADER_SIZE + data.len()) as u32);
+    packet.put_u32(id);
+    packet.put_u8(0);
+    packet.put_u8(command.0);
+    packet.put_u8(command.1);
+    packet.put_slice(data);
+    socket.write_all(&packet).await.unwrap();
+}
+
+/// Answer each command with `answer(command_set, command, data)`, until the
+/// connection goes away
+///
+/// `answer` returns the reply data, or an error code to send instead.
+pub async fn serve<S, F>(mut socket: S, answer: F)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    F: Fn(u8, u8, &[u8]) -> Result<Vec<u8>, u16>,
+{
+    loop {
+        let mut header = [0u8; HEADER_SIZE];
+        if socket.read_exact(&mut header).await.is_err() {
+            return;
+        }
+        let length = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+        let mut data = vec![0u8; length - HEADER_SIZE];
+        socket.read_exact(&mut data).await.unwrap();
+
+        let (error_code, reply_data) = match answer(header[9], header[10], &data) {
+            Ok(reply_data) => (0, reply_data),
+            Err(error_code) => (error_code, Vec::new()),
+        };
+        socket.write_all(&reply(&header, error_code, &reply_data)).await.unwrap();
+    }
+}
+
+/// Answer the commands sent while connecting: IDSizes (every ID
+/// `id_size` bytes), then Version as a JDWP 1.8 VM would
+async fn answer_connect<S: AsyncRead + AsyncWrite + Unpin>(socket: &mut S, id_size: i32) {
+    let (header, _) = read_command(socket, (command_sets::VIRTUAL_MACHINE, vm_commands::ID_SIZES)).await;
+    let mut data = Vec::new();
+    for _ in 0..5 {
+        data.put_i32(id_size);
+    }
+    send_reply(socket, &header, &data).await;
+
+    let (header, _) = read_command(socket, (command_sets::VIRTUAL_MACHINE, vm_commands::VERSION)).await;
+    send_reply(socket, &header, &version()).await;
+}
+
+/// A Version reply from a JDWP 1.8 VM
+pub fn version() -> Vec<u8> {
+    let mut reply = Vec::new();
+    put_string(&mut reply, "Fake VM");
+    reply.put_i32(1);
+    reply.put_i32(8);
+    put_string(&mut reply, "1.8.0");
+    put_string(&mut reply, "FakeVM");
+    reply
+}
+
+/// Play the VM's side of the handshake
+async fn accept_handshake<S: AsyncRead + AsyncWrite + Unpin>(socket: &mut S) -> [u8; 14] {
+    let mut handshake = [0u8; 14];
+    socket.read_exact(&mut handshake).await.unwrap();
+    socket.write_all(JDWP_HANDSHAKE).await.unwrap();
+    handshake
+}
+
+/// Play the VM's side of the handshake, then answer the connect commands
+pub async fn answer_handshake<S: AsyncRead + AsyncWrite + Unpin>(socket: &mut S, id_size: i32) -> [u8; 14] {
+    let handshake = accept_handshake(socket).await;
+    answer_connect(socket, id_size).await;
+    handshake
+}
+
+/// Connect to a fake VM whose IDs are all `id_size` bytes
+///
+/// Returns the connection and the VM's end of the transport.
+pub async fn connect(id_size: i32) -> (JdwpConnection, DuplexStream) {
+    let (client, mut vm) = tokio::io::duplex(64 * 1024);
+
+    let jvm = tokio::spawn(async move {
+        answer_handshake(&mut vm, id_size).await;
+        vm
+    });
+
+    let connection = JdwpConnectionBuilder::new()
+        .connect_timeout(Duration::from_secs(5))
+        .connect_transport(client)
+        .await
+        .unwrap();
+    (connection, jvm.await.unwrap())
+}
+
+/// Connect to a fake VM that answers every command, including IDSizes and
+/// Version while connecting, with `answer` as [`serve`] does
+///
+/// A VM that doesn't answer those gets the client's defaults: 8-byte IDs
+/// and no known version.
+pub async fn connect_with<F>(answer: F) -> JdwpConnection
+where
+    F: Fn(u8, u8, &[u8]) -> Result<Vec<u8>, u16> + Send + 'static,
+{
+    let (client, mut vm) = tokio::io::duplex(64 * 1024);
+
+    tokio::spawn(async move {
+        accept_handshake(&mut vm).await;
+        serve(vm, answer).await;
+    });
+
+    JdwpConnectionBuilder::new()
+        .connect_timeout(Duration::from_secs(5))
+        .connect_transport(client)
+        .await
+        .unwrap()
+}
+
+/// Dial a debugger listening on `addr`, as a JVM started with `server=n` does
+///
+/// Retries until the listener is up, then answers commands like [`connect_with`].
+pub async fn dial<F>(addr: String, answer: F)
+where
+    F: Fn(u8, u8, &[u8]) -> Result<Vec<u8>, u16>,
+{
+    let mut socket = loop {
+        match TcpStream::connect(addr.as_str()).await {
+            Ok(socket) => break socket,
+            Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+        }
+    };
+    accept_handshake(&mut socket).await;
+    serve(socket, answer).await;
+}
+
+/// A ClassesBySignature reply listing these classes, each initialized
+pub fn classes(class_ids: &[u64]) -> Vec<u8> {
+    let mut reply = Vec::new();
+    reply.put_i32(class_ids.len() as i32);
+    for &class_id in class_ids {
+        reply.put_u8(1);
+        reply.put_u64(class_id);
+        reply.put_i32(7);
+    }
+    reply
+}
+
+/// A Methods reply listing public methods as (method, name, signature)
+pub fn methods(methods: &[(u64, &str, &str)]) -> Vec<u8> {
+    let mut reply = Vec::new();
+    reply.put_i32(methods.len() as i32);
+    for &(method_id, name, signature) in methods {
+        reply.put_u64(method_id);
+        put_string(&mut reply, name);
+        put_string(&mut reply, signature);
+        reply.put_i32(0x1);
+    }
+    reply
+}
+
+/// A LineTable reply for code indexes 0 to 20, with lines as (index, line)
+pub fn line_table(lines: &[(u64, i32)]) -> Vec<u8> {
+    let mut reply = Vec::new();
+    reply.put_u64(0);
+    reply.put_u64(20);
+    reply.put_i32(lines.len() as i32);
+    for &(index, line) in lines {
+        reply.put_u64(index);
+        reply.put_i32(line);
+    }
+    reply
+}
+
+/// A Frames reply listing frames in classes as (frame, class, method, index)
+pub fn frames(frames: &[(u64, u64, u64, u64)]) -> Vec<u8> {
+    let mut reply = Vec::new();
+    reply.put_i32(frames.len() as i32);
+    for &(frame_id, class_id, method_id, index) in frames {
+        reply.put_u64(frame_id);
+        reply.put_u8(1);
+        reply.put_u64(class_id);
+        reply.put_u64(method_id);
+        reply.put_u64(index);
+    }
+    reply
+}
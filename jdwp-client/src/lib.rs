@@ -8,26 +8,42 @@
 // - Execution control
 
 pub mod connection;
+pub mod builder;
 pub mod protocol;
 pub mod commands;
 pub mod events;
 pub mod eventloop;
+pub mod transport;
 pub mod types;
 pub mod reader;
 pub mod vm;
 pub mod reftype;
+pub mod classtype;
 pub mod method;
 pub mod eventrequest;
 pub mod thread;
+pub mod threadgroup;
 pub mod stackframe;
 pub mod string;
 pub mod object;
+pub mod array;
+pub mod signature;
+pub mod stats;
+
+#[cfg(any(test, feature = "test-util"))]
+pub mod fake_vm;
 
 pub use connection::JdwpConnection;
-pub use eventloop::{EventLoopHandle, spawn_event_loop};
+pub use builder::{JdwpConnectionBuilder, RetryPolicy};
+pub use eventloop::{
+    EventLoopConfig, EventLoopHandle, PacketDirection, PacketTracer, spawn_event_loop,
+    spawn_event_loop_with_config,
+};
 pub use events::EventSet;
+pub use transport::Transport;
 pub use protocol::{JdwpError, JdwpResult};
 pub use eventrequest::SuspendPolicy;
+pub use stats::StatsSnapshot;
 
 #[cfg(test)]
 mod tests {
@@ -2,12 +2,11 @@
 //
 // Commands for working with methods (line tables, variable tables, etc.)
 
-use crate::commands::{command_sets, method_commands};
+use crate::commands::{command_sets, error_codes, method_commands};
 use crate::connection::JdwpConnection;
-use crate::protocol::{CommandPacket, JdwpResult};
-use crate::reader::{read_i32, read_string, read_u64};
+use crate::protocol::{CommandPacket, JdwpError, JdwpResult};
+use crate::reader::{read_count, read_i32, read_string, read_u64, read_u8, MAX_PREALLOCATED};
 use crate::types::{MethodId, ReferenceTypeId, Variable};
-use bytes::BufMut;
 use serde::{Deserialize, Serialize};
 
 /// Line table entry - maps source line to bytecode index
@@ -25,6 +24,28 @@ pub struct LineTable {
     pub lines: Vec<LineTableEntry>,
 }
 
+impl LineTable {
+    /// Source line of a code index: the line of the closest entry at or before it
+    pub fn line_at(&self, code_index: u64) -> Option<i32> {
+        self.lines
+            .iter()
+            .filter(|e| e.line_code_index <= code_index)
+            .max_by_key(|e| e.line_code_index)
+            .map(|e| e.line_number)
+    }
+
+    /// The first executable line at or after `line`, at its lowest code index
+    ///
+    /// Lines without bytecode (comments, blank lines, declarations) are
+    /// skipped. Returns None if the method has no lines from `line` on.
+    pub fn next_executable_line(&self, line: i32) -> Option<&LineTableEntry> {
+        self.lines
+            .iter()
+            .filter(|e| e.line_number >= line)
+            .min_by_key(|e| (e.line_number, e.line_code_index))
+    }
+}
+
 impl JdwpConnection {
     /// Get line table for a method (Method.LineTable command)
     /// Maps source code line numbers to bytecode positions
@@ -36,9 +57,10 @@ impl JdwpConnection {
         let id = self.next_id();
         let mut packet = CommandPacket::new(id, command_sets::METHOD, method_commands::LINE_TABLE);
 
-        // Write reference type ID and method ID (both 8 bytes)
-        packet.data.put_u64(ref_type_id);
-        packet.data.put_u64(method_id);
+        // Write reference type ID and method ID
+        let sizes = self.id_sizes();
+        sizes.write_reference_type_id(&mut packet.data, ref_type_id);
+        sizes.write_method_id(&mut packet.data, method_id);
 
         let reply = self.send_command(packet).await?;
         reply.check_error()?;
@@ -50,8 +72,8 @@ impl JdwpConnection {
         let end = read_u64(&mut data)?;
 
         // Read line table entries
-        let lines_count = read_i32(&mut data)?;
-        let mut lines = Vec::with_capacity(lines_count as usize);
+        let lines_count = read_count(&mut data, 12, "lines")?;
+        let mut lines = Vec::with_capacity(lines_count.min(MAX_PREALLOCATED));
 
         for _ in 0..lines_count {
             let line_code_index = read_u64(&mut data)?;
@@ -66,6 +88,53 @@ impl JdwpConnection {
         Ok(LineTable { start, end, lines })
     }
 
+    /// Get a method's raw bytecode (Method.Bytecodes command)
+    ///
+    /// The VM must support `can_get_bytecodes`.
+    pub async fn get_bytecodes(
+        &mut self,
+        ref_type_id: ReferenceTypeId,
+        method_id: MethodId,
+    ) -> JdwpResult<Vec<u8>> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::METHOD, method_commands::BYTECODES);
+
+        let sizes = self.id_sizes();
+        sizes.write_reference_type_id(&mut packet.data, ref_type_id);
+        sizes.write_method_id(&mut packet.data, method_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        let length = read_i32(&mut data)?;
+        match usize::try_from(length).ok().and_then(|length| data.get(..length)) {
+            Some(bytes) => Ok(bytes.to_vec()),
+            None => Err(JdwpError::Protocol(format!("Not enough data for {} bytes of bytecode", length))),
+        }
+    }
+
+    /// Whether a method was replaced by a class redefinition while frames
+    /// were still running it (Method.IsObsolete command)
+    pub async fn is_method_obsolete(
+        &mut self,
+        ref_type_id: ReferenceTypeId,
+        method_id: MethodId,
+    ) -> JdwpResult<bool> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::METHOD, method_commands::IS_OBSOLETE);
+
+        let sizes = self.id_sizes();
+        sizes.write_reference_type_id(&mut packet.data, ref_type_id);
+        sizes.write_method_id(&mut packet.data, method_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        Ok(read_u8(&mut data)? != 0)
+    }
+
     /// Get variable table for a method (Method.VariableTable command)
     /// Returns info about local variables (names, types, slots)
     pub async fn get_variable_table(
@@ -77,37 +146,163 @@ impl JdwpConnection {
         let mut packet = CommandPacket::new(id, command_sets::METHOD, method_commands::VARIABLE_TABLE);
 
         // Write reference type ID and method ID
-        packet.data.put_u64(ref_type_id);
-        packet.data.put_u64(method_id);
+        let sizes = self.id_sizes();
+        sizes.write_reference_type_id(&mut packet.data, ref_type_id);
+        sizes.write_method_id(&mut packet.data, method_id);
 
         let reply = self.send_command(packet).await?;
         reply.check_error()?;
 
-        let mut data = reply.data();
+        read_variables(&mut reply.data(), false)
+    }
 
-        // Read arg count (we don't use this)
-        let _arg_count = read_i32(&mut data)?;
-
-        // Read variables
-        let vars_count = read_i32(&mut data)?;
-        let mut variables = Vec::with_capacity(vars_count as usize);
-
-        for _ in 0..vars_count {
-            let code_index = read_u64(&mut data)?;
-            let name = read_string(&mut data)?;
-            let signature = read_string(&mut data)?;
-            let length = crate::reader::read_u32(&mut data)?;
-            let slot = crate::reader::read_u32(&mut data)?;
-
-            variables.push(Variable {
-                code_index,
-                name,
-                signature,
-                length,
-                slot,
-            });
+    /// Get variable table including generic signatures
+    /// (Method.VariableTableWithGeneric command)
+    ///
+    /// Falls back to Method.VariableTable (with no generic signatures) on VMs
+    /// older than JDWP 1.5 or that don't implement the command. Both fail
+    /// with ABSENT_INFORMATION for classes compiled without `-g`.
+    pub async fn get_variable_table_with_generic(
+        &mut self,
+        ref_type_id: ReferenceTypeId,
+        method_id: MethodId,
+    ) -> JdwpResult<Vec<Variable>> {
+        if !self.supports_command_set_version(1, 5) {
+            return self.get_variable_table(ref_type_id, method_id).await;
+        }
+
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::METHOD, method_commands::VARIABLE_TABLE_WITH_GENERIC);
+
+        let sizes = self.id_sizes();
+        sizes.write_reference_type_id(&mut packet.data, ref_type_id);
+        sizes.write_method_id(&mut packet.data, method_id);
+
+        let reply = self.send_command(packet).await?;
+        match reply.check_error() {
+            Ok(()) => {}
+            Err(e) if e.error_code() == Some(error_codes::NOT_IMPLEMENTED) => {
+                return self.get_variable_table(ref_type_id, method_id).await;
+            }
+            Err(e) => return Err(e),
         }
 
-        Ok(variables)
+        read_variables(&mut reply.data(), true)
+    }
+}
+
+/// Read a variable table reply, whose entries carry a generic signature
+/// after the plain one when `with_generic` is set
+fn read_variables(data: &mut &[u8], with_generic: bool) -> JdwpResult<Vec<Variable>> {
+    // Read arg count (we don't use this)
+    let _arg_count = read_i32(data)?;
+
+    // Read variables
+    // Each has a code index, name, signature, length and slot
+    let var_size = if with_generic { 28 } else { 24 };
+    let vars_count = read_count(data, var_size, "variables")?;
+    let mut variables = Vec::with_capacity(vars_count.min(MAX_PREALLOCATED));
+
+    for _ in 0..vars_count {
+        let code_index = read_u64(data)?;
+        let name = read_string(data)?;
+        let signature = read_string(data)?;
+        // An empty generic signature means the variable's type isn't generic
+        let generic_signature = match with_generic {
+            true => Some(read_string(data)?).filter(|s| !s.is_empty()),
+            false => None,
+        };
+        let length = crate::reader::read_u32(data)?;
+        let slot = crate::reader::read_u32(data)?;
+
+        variables.push(Variable {
+            code_index,
+            name,
+            signature,
+            generic_signature,
+            length,
+            slot,
+        });
+    }
+
+    Ok(variables)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_table(entries: &[(u64, i32)]) -> LineTable {
+        LineTable {
+            start: 0,
+            end: 100,
+            lines: entries
+                .iter()
+                .map(|&(line_code_index, line_number)| LineTableEntry { line_code_index, line_number })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_next_executable_line() {
+        // Loops can map one line to several code indices, out of order
+        let table = line_table(&[(0, 10), (4, 12), (9, 15), (20, 12), (25, 16)]);
+
+        let entry = table.next_executable_line(12).unwrap();
+        assert_eq!((entry.line_number, entry.line_code_index), (12, 4));
+
+        let entry = table.next_executable_line(13).unwrap();
+        assert_eq!((entry.line_number, entry.line_code_index), (15, 9));
+
+        assert_eq!(table.next_executable_line(1).unwrap().line_number, 10);
+        assert!(table.next_executable_line(17).is_none());
+    }
+
+    #[test]
+    fn test_line_at() {
+        let table = line_table(&[(0, 10), (4, 12), (9, 15), (20, 12)]);
+
+        assert_eq!(table.line_at(0), Some(10));
+        assert_eq!(table.line_at(7), Some(12));
+        assert_eq!(table.line_at(9), Some(15));
+        assert_eq!(table.line_at(25), Some(12));
+        assert_eq!(line_table(&[(4, 12)]).line_at(2), None);
+        // Native and synthetic methods have no lines
+        assert_eq!(line_table(&[]).line_at(0), None);
+    }
+
+    #[test]
+    fn test_read_variables_with_generic() {
+        use bytes::BufMut;
+
+        fn put_string(buf: &mut Vec<u8>, s: &str) {
+            buf.put_u32(s.len() as u32);
+            buf.put_slice(s.as_bytes());
+        }
+
+        let mut reply = Vec::new();
+        reply.put_i32(1); // arg count
+        reply.put_i32(2);
+        reply.put_u64(0);
+        put_string(&mut reply, "names");
+        put_string(&mut reply, "Ljava/util/List;");
+        put_string(&mut reply, "Ljava/util/List<Ljava/lang/String;>;");
+        reply.put_u32(12);
+        reply.put_u32(0);
+        reply.put_u64(3);
+        put_string(&mut reply, "count");
+        put_string(&mut reply, "I");
+        put_string(&mut reply, "");
+        reply.put_u32(9);
+        reply.put_u32(1);
+
+        let variables = read_variables(&mut reply.as_slice(), true).unwrap();
+        let summary: Vec<_> = variables.iter()
+            .map(|v| (v.name.as_str(), v.generic_signature.as_deref(), v.code_index, v.length, v.slot))
+            .collect();
+        assert_eq!(summary, [
+            ("names", Some("Ljava/util/List<Ljava/lang/String;>;"), 0, 12, 0),
+            ("count", None, 3, 9, 1),
+        ]);
     }
 }
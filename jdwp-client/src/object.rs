@@ -4,9 +4,10 @@
 
 use crate::commands::{command_sets, object_reference_commands};
 use crate::connection::JdwpConnection;
-use crate::protocol::{CommandPacket, JdwpResult};
-use crate::reader::{read_i32, read_u64, read_u8};
-use crate::types::{FieldId, ObjectId, ReferenceTypeId, Value, ValueData};
+use crate::protocol::{CommandPacket, JdwpError, JdwpResult};
+use crate::reader::{read_count, read_u8, MAX_PREALLOCATED};
+use crate::types::{ClassId, FieldId, MethodId, ObjectId, ReferenceTypeId, ThreadId, Value, ValueData};
+use crate::vm::VmIdSizes;
 use bytes::{Buf, BufMut};
 use serde::{Deserialize, Serialize};
 
@@ -36,7 +37,8 @@ impl JdwpConnection {
             object_reference_commands::REFERENCE_TYPE,
         );
 
-        packet.data.put_u64(object_id);
+        let sizes = self.id_sizes();
+        sizes.write_object_id(&mut packet.data, object_id);
 
         let reply = self.send_command(packet).await?;
         reply.check_error()?;
@@ -45,7 +47,7 @@ impl JdwpConnection {
 
         // Read type tag (byte) and class ID (objectID)
         let _type_tag = read_u8(&mut data)?;
-        let reference_type_id = read_u64(&mut data)?;
+        let reference_type_id = sizes.read_reference_type_id(&mut data)?;
 
         Ok(reference_type_id)
     }
@@ -61,8 +63,13 @@ impl JdwpConnection {
     ///
     /// # Example
     /// ```no_run
+    /// # use jdwp_client::types::{FieldId, ObjectId};
+    /// # use jdwp_client::{JdwpConnection, JdwpResult};
+    /// # async fn f(connection: &mut JdwpConnection, object_id: ObjectId, field_id1: FieldId, field_id2: FieldId) -> JdwpResult<()> {
     /// let fields = vec![field_id1, field_id2];
     /// let values = connection.get_object_values(object_id, fields).await?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn get_object_values(
         &mut self,
@@ -76,15 +83,17 @@ impl JdwpConnection {
             object_reference_commands::GET_VALUES,
         );
 
+        let sizes = self.id_sizes();
+
         // Write object ID
-        packet.data.put_u64(object_id);
+        sizes.write_object_id(&mut packet.data, object_id);
 
         // Write number of fields
         packet.data.put_i32(field_ids.len() as i32);
 
         // Write each field ID
         for field_id in &field_ids {
-            packet.data.put_u64(*field_id);
+            sizes.write_field_id(&mut packet.data, *field_id);
         }
 
         let reply = self.send_command(packet).await?;
@@ -93,12 +102,12 @@ impl JdwpConnection {
         let mut data = reply.data();
 
         // Read number of values (should match field_ids.len())
-        let values_count = read_i32(&mut data)?;
-        let mut values = Vec::with_capacity(values_count as usize);
+        let values_count = read_count(&mut data, 1, "values")?;
+        let mut values = Vec::with_capacity(values_count.min(MAX_PREALLOCATED));
 
         for _ in 0..values_count {
             let tag = read_u8(&mut data)?;
-            let value_data = read_value_by_tag(tag, &mut data)?;
+            let value_data = read_value_by_tag(tag, &mut data, &sizes)?;
 
             values.push(Value {
                 tag,
@@ -108,10 +117,147 @@ impl JdwpConnection {
 
         Ok(values)
     }
+
+    /// Invoke an instance method on an object (ObjectReference.InvokeMethod command)
+    ///
+    /// The thread must be suspended by an event (breakpoint, step, ...). It is
+    /// resumed while the method runs; pass `invoke_options::INVOKE_SINGLE_THREADED`
+    /// in `options` to keep all other threads suspended. `class_id` is the class
+    /// (or a superclass) declaring the method.
+    ///
+    /// If the method throws, `JdwpError::InvocationException` carries the
+    /// exception object ID.
+    pub async fn invoke_object_method(
+        &mut self,
+        object_id: ObjectId,
+        thread_id: ThreadId,
+        class_id: ClassId,
+        method_id: MethodId,
+        args: Vec<Value>,
+        options: i32,
+    ) -> JdwpResult<Value> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(
+            id,
+            command_sets::OBJECT_REFERENCE,
+            object_reference_commands::INVOKE_METHOD,
+        );
+
+        let sizes = self.id_sizes();
+        sizes.write_object_id(&mut packet.data, object_id);
+        sizes.write_object_id(&mut packet.data, thread_id);
+        sizes.write_reference_type_id(&mut packet.data, class_id);
+        sizes.write_method_id(&mut packet.data, method_id);
+
+        packet.data.put_i32(args.len() as i32);
+        for arg in &args {
+            write_tagged_value(&mut packet.data, arg, &sizes);
+        }
+
+        packet.data.put_i32(options);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        read_invoke_result(reply.data(), &sizes)
+    }
+
+    /// Keep an object from being garbage collected until
+    /// `enable_collection` is called (ObjectReference.DisableCollection)
+    ///
+    /// Calls are counted: each disable needs a matching enable.
+    pub async fn disable_collection(&mut self, object_id: ObjectId) -> JdwpResult<()> {
+        self.object_command(object_reference_commands::DISABLE_COLLECTION, object_id).await?;
+        Ok(())
+    }
+
+    /// Allow a previously pinned object to be garbage collected again
+    /// (ObjectReference.EnableCollection)
+    pub async fn enable_collection(&mut self, object_id: ObjectId) -> JdwpResult<()> {
+        self.object_command(object_reference_commands::ENABLE_COLLECTION, object_id).await?;
+        Ok(())
+    }
+
+    /// Whether an object has been garbage collected (ObjectReference.IsCollected)
+    pub async fn is_collected(&mut self, object_id: ObjectId) -> JdwpResult<bool> {
+        let reply = self.object_command(object_reference_commands::IS_COLLECTED, object_id).await?;
+        let mut data = reply.as_slice();
+        Ok(read_u8(&mut data)? != 0)
+    }
+
+    /// Send an ObjectReference command whose only argument is the object ID,
+    /// returning the reply data
+    async fn object_command(&mut self, command: u8, object_id: ObjectId) -> JdwpResult<Vec<u8>> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::OBJECT_REFERENCE, command);
+
+        self.id_sizes().write_object_id(&mut packet.data, object_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        Ok(reply.data().to_vec())
+    }
+}
+
+/// Decode an InvokeMethod reply: the tagged return value followed by the
+/// tagged exception object ID (0 when the method returned normally)
+pub(crate) fn read_invoke_result(mut data: &[u8], sizes: &VmIdSizes) -> JdwpResult<Value> {
+    let tag = read_u8(&mut data)?;
+    let return_value = read_value_by_tag(tag, &mut data, sizes)?;
+
+    let _exception_tag = read_u8(&mut data)?;
+    let exception = sizes.read_object_id(&mut data)?;
+
+    if exception != 0 {
+        return Err(JdwpError::InvocationException(exception));
+    }
+
+    Ok(Value {
+        tag,
+        data: return_value,
+    })
 }
 
-/// Read a value based on its type tag (same as in stackframe.rs)
-fn read_value_by_tag(tag: u8, buf: &mut &[u8]) -> JdwpResult<ValueData> {
+/// Write a value preceded by its type tag
+pub(crate) fn write_tagged_value(buf: &mut Vec<u8>, value: &Value, sizes: &VmIdSizes) {
+    buf.put_u8(value.tag);
+    write_untagged_value(buf, &value.data, sizes);
+}
+
+/// Write a value's bytes without a type tag
+pub(crate) fn write_untagged_value(buf: &mut Vec<u8>, data: &ValueData, sizes: &VmIdSizes) {
+    match data {
+        ValueData::Byte(v) => buf.put_i8(*v),
+        ValueData::Char(v) => buf.put_u16(*v),
+        ValueData::Float(v) => buf.put_f32(*v),
+        ValueData::Double(v) => buf.put_f64(*v),
+        ValueData::Int(v) => buf.put_i32(*v),
+        ValueData::Long(v) => buf.put_i64(*v),
+        ValueData::Short(v) => buf.put_i16(*v),
+        ValueData::Boolean(v) => buf.put_u8(*v as u8),
+        ValueData::Object(id) => sizes.write_object_id(buf, *id),
+        ValueData::Void => {}
+    }
+}
+
+/// Read a value based on its type tag
+pub(crate) fn read_value_by_tag(tag: u8, buf: &mut &[u8], sizes: &VmIdSizes) -> JdwpResult<ValueData> {
+    // Primitive sizes; object IDs are bounds-checked by read_object_id
+    let primitive_size = match tag {
+        66 | 90 => 1,
+        67 | 83 => 2,
+        70 | 73 => 4,
+        68 | 74 => 8,
+        _ => 0,
+    };
+    if buf.remaining() < primitive_size {
+        return Err(JdwpError::Protocol(format!(
+            "Not enough data for value with tag {}",
+            tag
+        )));
+    }
+
     match tag {
         // 'B' = byte
         66 => Ok(ValueData::Byte(buf.get_i8())),
@@ -134,10 +280,10 @@ fn read_value_by_tag(tag: u8, buf: &mut &[u8]) -> JdwpResult<ValueData> {
         // Object types (L, s, t, g, l, c, [)
         // L = object, s = string, t = thread, g = thread group, l = class loader, c = class object, [ = array
         76 | 115 | 116 | 103 | 108 | 99 | 91 => {
-            let object_id = read_u64(buf)?;
+            let object_id = sizes.read_object_id(buf)?;
             Ok(ValueData::Object(object_id))
         }
-        _ => Err(crate::protocol::JdwpError::Protocol(format!(
+        _ => Err(JdwpError::Protocol(format!(
             "Unknown value tag: {}",
             tag
         ))),
@@ -146,10 +292,22 @@ fn read_value_by_tag(tag: u8, buf: &mut &[u8]) -> JdwpResult<ValueData> {
 
 #[cfg(test)]
 mod tests {
-    use super::*;
-
     #[test]
     fn test_object_values_packet() {
         // Test that packet is constructed correctly
     }
+
+    #[test]
+    fn test_read_value_by_tag_rejects_truncated_values() {
+        use super::*;
+
+        let sizes = VmIdSizes::default();
+        assert!(read_value_by_tag(b'S', &mut &[0x01][..], &sizes).is_err());
+        assert!(read_value_by_tag(b'J', &mut &[0; 7][..], &sizes).is_err());
+        assert!(read_value_by_tag(b'L', &mut &[0; 4][..], &sizes).is_err());
+        assert!(matches!(
+            read_value_by_tag(b'S', &mut &[0x01, 0x02][..], &sizes),
+            Ok(ValueData::Short(0x0102))
+        ));
+    }
 }
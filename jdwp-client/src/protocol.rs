@@ -18,14 +18,30 @@ pub enum JdwpError {
     #[error("Protocol error: {0}")]
     Protocol(String),
 
-    #[error("Invalid handshake")]
-    InvalidHandshake,
+    #[error("Invalid handshake: {0}")]
+    InvalidHandshake(String),
 
     #[error("JDWP error code {0}: {1}")]
     JdwpErrorCode(u16, String),
 
     #[error("Connection closed")]
     ConnectionClosed,
+
+    #[error("Timed out: {0}")]
+    Timeout(String),
+
+    #[error("Invoked method threw exception @{0:x}")]
+    InvocationException(u64),
+}
+
+impl JdwpError {
+    /// The JDWP error code, if the VM rejected the command
+    pub fn error_code(&self) -> Option<u16> {
+        match self {
+            JdwpError::JdwpErrorCode(code, _) => Some(*code),
+            _ => None,
+        }
+    }
 }
 
 // JDWP handshake string
@@ -126,65 +142,70 @@ impl ReplyPacket {
     }
 
     pub fn error_message(&self) -> &'static str {
-        match self.error_code {
-            0 => "NONE",
-            10 => "INVALID_THREAD",
-            11 => "INVALID_THREAD_GROUP",
-            12 => "INVALID_PRIORITY",
-            13 => "THREAD_NOT_SUSPENDED",
-            14 => "THREAD_SUSPENDED",
-            20 => "INVALID_OBJECT",
-            21 => "INVALID_CLASS",
-            22 => "CLASS_NOT_PREPARED",
-            23 => "INVALID_METHODID",
-            24 => "INVALID_LOCATION",
-            25 => "INVALID_FIELDID",
-            30 => "INVALID_FRAMEID",
-            31 => "NO_MORE_FRAMES",
-            32 => "OPAQUE_FRAME",
-            33 => "NOT_CURRENT_FRAME",
-            34 => "TYPE_MISMATCH",
-            35 => "INVALID_SLOT",
-            40 => "DUPLICATE",
-            41 => "NOT_FOUND",
-            50 => "INVALID_MONITOR",
-            51 => "NOT_MONITOR_OWNER",
-            52 => "INTERRUPT",
-            60 => "INVALID_CLASS_FORMAT",
-            61 => "CIRCULAR_CLASS_DEFINITION",
-            62 => "FAILS_VERIFICATION",
-            63 => "ADD_METHOD_NOT_IMPLEMENTED",
-            64 => "SCHEMA_CHANGE_NOT_IMPLEMENTED",
-            65 => "INVALID_TYPESTATE",
-            66 => "HIERARCHY_CHANGE_NOT_IMPLEMENTED",
-            67 => "DELETE_METHOD_NOT_IMPLEMENTED",
-            68 => "UNSUPPORTED_VERSION",
-            69 => "NAMES_DONT_MATCH",
-            70 => "CLASS_MODIFIERS_CHANGE_NOT_IMPLEMENTED",
-            71 => "METHOD_MODIFIERS_CHANGE_NOT_IMPLEMENTED",
-            99 => "NOT_IMPLEMENTED",
-            100 => "NULL_POINTER",
-            101 => "ABSENT_INFORMATION",
-            102 => "INVALID_EVENT_TYPE",
-            103 => "ILLEGAL_ARGUMENT",
-            110 => "OUT_OF_MEMORY",
-            111 => "ACCESS_DENIED",
-            112 => "VM_DEAD",
-            113 => "INTERNAL",
-            115 => "UNATTACHED_THREAD",
-            500 => "INVALID_TAG",
-            502 => "ALREADY_INVOKING",
-            503 => "INVALID_INDEX",
-            504 => "INVALID_LENGTH",
-            506 => "INVALID_STRING",
-            507 => "INVALID_CLASS_LOADER",
-            508 => "INVALID_ARRAY",
-            509 => "TRANSPORT_LOAD",
-            510 => "TRANSPORT_INIT",
-            511 => "NATIVE_METHOD",
-            512 => "INVALID_COUNT",
-            _ => "UNKNOWN_ERROR",
-        }
+        error_name(self.error_code)
+    }
+}
+
+/// Spec name of a JDWP error code, e.g. 30 -> "INVALID_FRAMEID"
+pub fn error_name(error_code: u16) -> &'static str {
+    match error_code {
+        0 => "NONE",
+        10 => "INVALID_THREAD",
+        11 => "INVALID_THREAD_GROUP",
+        12 => "INVALID_PRIORITY",
+        13 => "THREAD_NOT_SUSPENDED",
+        14 => "THREAD_SUSPENDED",
+        20 => "INVALID_OBJECT",
+        21 => "INVALID_CLASS",
+        22 => "CLASS_NOT_PREPARED",
+        23 => "INVALID_METHODID",
+        24 => "INVALID_LOCATION",
+        25 => "INVALID_FIELDID",
+        30 => "INVALID_FRAMEID",
+        31 => "NO_MORE_FRAMES",
+        32 => "OPAQUE_FRAME",
+        33 => "NOT_CURRENT_FRAME",
+        34 => "TYPE_MISMATCH",
+        35 => "INVALID_SLOT",
+        40 => "DUPLICATE",
+        41 => "NOT_FOUND",
+        50 => "INVALID_MONITOR",
+        51 => "NOT_MONITOR_OWNER",
+        52 => "INTERRUPT",
+        60 => "INVALID_CLASS_FORMAT",
+        61 => "CIRCULAR_CLASS_DEFINITION",
+        62 => "FAILS_VERIFICATION",
+        63 => "ADD_METHOD_NOT_IMPLEMENTED",
+        64 => "SCHEMA_CHANGE_NOT_IMPLEMENTED",
+        65 => "INVALID_TYPESTATE",
+        66 => "HIERARCHY_CHANGE_NOT_IMPLEMENTED",
+        67 => "DELETE_METHOD_NOT_IMPLEMENTED",
+        68 => "UNSUPPORTED_VERSION",
+        69 => "NAMES_DONT_MATCH",
+        70 => "CLASS_MODIFIERS_CHANGE_NOT_IMPLEMENTED",
+        71 => "METHOD_MODIFIERS_CHANGE_NOT_IMPLEMENTED",
+        99 => "NOT_IMPLEMENTED",
+        100 => "NULL_POINTER",
+        101 => "ABSENT_INFORMATION",
+        102 => "INVALID_EVENT_TYPE",
+        103 => "ILLEGAL_ARGUMENT",
+        110 => "OUT_OF_MEMORY",
+        111 => "ACCESS_DENIED",
+        112 => "VM_DEAD",
+        113 => "INTERNAL",
+        115 => "UNATTACHED_THREAD",
+        500 => "INVALID_TAG",
+        502 => "ALREADY_INVOKING",
+        503 => "INVALID_INDEX",
+        504 => "INVALID_LENGTH",
+        506 => "INVALID_STRING",
+        507 => "INVALID_CLASS_LOADER",
+        508 => "INVALID_ARRAY",
+        509 => "TRANSPORT_LOAD",
+        510 => "TRANSPORT_INIT",
+        511 => "NATIVE_METHOD",
+        512 => "INVALID_COUNT",
+        _ => "UNKNOWN_ERROR",
     }
 }
 
@@ -222,7 +243,7 @@ mod tests {
     #[test]
     fn test_reply_packet_decode() {
         // Construct a reply packet manually with big-endian values
-        let mut reply_data = vec![
+        let reply_data = vec![
             0, 0, 0, 11,  // length = 11 (big-endian)
             0, 0, 0, 1,   // id = 1 (big-endian)
             0x80,         // reply flag
@@ -234,4 +255,61 @@ mod tests {
         assert_eq!(packet.error_code, 0);
         assert!(!packet.is_error());
     }
+
+    mod fuzz {
+        use super::*;
+        use proptest::prelude::*;
+
+        fn encode_reply(id: u32, error_code: u16, data: &[u8]) -> Vec<u8> {
+            let mut buf = Vec::new();
+            buf.put_u32((HEADER_SIZE + data.len()) as u32);
+            buf.put_u32(id);
+            buf.put_u8(REPLY_FLAG);
+            buf.put_u16(error_code);
+            buf.put_slice(data);
+            buf
+        }
+
+        proptest! {
+            #[test]
+            fn command_packet_header_is_big_endian(
+                id in any::<u32>(),
+                command_set in any::<u8>(),
+                command in any::<u8>(),
+                data in proptest::collection::vec(any::<u8>(), 0..512),
+            ) {
+                let mut packet = CommandPacket::new(id, command_set, command);
+                packet.data = data.clone();
+                let encoded = packet.encode();
+
+                let length = (HEADER_SIZE + data.len()) as u32;
+                prop_assert_eq!(encoded.len(), length as usize);
+                prop_assert_eq!(&encoded[0..4], &length.to_be_bytes()[..]);
+                prop_assert_eq!(&encoded[4..8], &id.to_be_bytes()[..]);
+                prop_assert_eq!(encoded[8], 0x00);
+                prop_assert_eq!(encoded[9], command_set);
+                prop_assert_eq!(encoded[10], command);
+                prop_assert_eq!(&encoded[HEADER_SIZE..], &data[..]);
+            }
+
+            #[test]
+            fn reply_packet_round_trip(
+                id in any::<u32>(),
+                error_code in any::<u16>(),
+                data in proptest::collection::vec(any::<u8>(), 0..512),
+            ) {
+                let reply = ReplyPacket::decode(&encode_reply(id, error_code, &data)).unwrap();
+
+                prop_assert_eq!(reply.id, id);
+                prop_assert_eq!(reply.error_code, error_code);
+                prop_assert_eq!(reply.is_error(), error_code != 0);
+                prop_assert_eq!(reply.data(), &data[..]);
+            }
+
+            #[test]
+            fn reply_packet_decode_never_panics(bytes in proptest::collection::vec(any::<u8>(), 0..64)) {
+                let _ = ReplyPacket::decode(&bytes);
+            }
+        }
+    }
 }
@@ -3,7 +3,7 @@
 use bytes::Buf;
 use crate::protocol::{JdwpError, JdwpResult};
 
-/// Read a JDWP string (4-byte length prefix + UTF-8 bytes)
+/// Read a JDWP string (4-byte length prefix + modified UTF-8 bytes)
 pub fn read_string(buf: &mut &[u8]) -> JdwpResult<String> {
     if buf.remaining() < 4 {
         return Err(JdwpError::Protocol("Not enough data for string length".to_string()));
@@ -22,8 +22,48 @@ pub fn read_string(buf: &mut &[u8]) -> JdwpResult<String> {
     let bytes = &buf[..len];
     buf.advance(len);
 
-    String::from_utf8(bytes.to_vec())
-        .map_err(|e| JdwpError::Protocol(format!("Invalid UTF-8 in string: {}", e)))
+    read_modified_utf8(bytes)
+}
+
+/// Decode Java's modified UTF-8, as used for every JDWP string
+///
+/// It differs from UTF-8 in writing U+0000 as 0xC0 0x80 and characters
+/// outside the BMP as a surrogate pair of 3-byte sequences. Unpaired
+/// surrogates, which Java strings may hold, become U+FFFD.
+pub fn read_modified_utf8(bytes: &[u8]) -> JdwpResult<String> {
+    // Most strings have neither form, which makes them plain UTF-8
+    if let Ok(s) = std::str::from_utf8(bytes) {
+        return Ok(s.to_string());
+    }
+
+    let invalid = |at: usize| JdwpError::Protocol(format!("Invalid modified UTF-8 in string at byte {}", at));
+    let continuation = |at: usize| match bytes.get(at) {
+        Some(&b) if b & 0xC0 == 0x80 => Ok(u16::from(b & 0x3F)),
+        _ => Err(invalid(at)),
+    };
+
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        match b {
+            0x00..=0x7F => {
+                units.push(u16::from(b));
+                i += 1;
+            }
+            0xC0..=0xDF => {
+                units.push(u16::from(b & 0x1F) << 6 | continuation(i + 1)?);
+                i += 2;
+            }
+            0xE0..=0xEF => {
+                units.push(u16::from(b & 0x0F) << 12 | continuation(i + 1)? << 6 | continuation(i + 2)?);
+                i += 3;
+            }
+            _ => return Err(invalid(i)),
+        }
+    }
+
+    Ok(String::from_utf16_lossy(&units))
 }
 
 /// Read a u32
@@ -57,3 +97,178 @@ pub fn read_u64(buf: &mut &[u8]) -> JdwpResult<u64> {
     }
     Ok(buf.get_u64())
 }
+
+/// Read a big-endian ID of `size` bytes (1 to 8, as reported by IDSizes)
+pub fn read_id(buf: &mut &[u8], size: i32) -> JdwpResult<u64> {
+    let size = size as usize;
+    if buf.remaining() < size {
+        return Err(JdwpError::Protocol(format!("Not enough data for {}-byte ID", size)));
+    }
+    Ok(buf.get_uint(size))
+}
+
+/// Most list entries allocated up front; longer lists grow as they're read
+pub const MAX_PREALLOCATED: usize = 4096;
+
+/// Read the count of a list whose entries take at least `min_entry_size`
+/// bytes each, checking the rest of the reply could hold that many
+///
+/// A bogus count then fails here instead of sizing an allocation; callers
+/// should still cap their capacity at [`MAX_PREALLOCATED`].
+pub fn read_count(buf: &mut &[u8], min_entry_size: usize, what: &str) -> JdwpResult<usize> {
+    let count = read_i32(buf)?;
+    let count = usize::try_from(count)
+        .map_err(|_| JdwpError::Protocol(format!("Invalid {} count {}", what, count)))?;
+
+    let needed = count.saturating_mul(min_entry_size);
+    if buf.remaining() < needed {
+        return Err(JdwpError::Protocol(format!(
+            "Truncated reply: {} {} need at least {} bytes, got {}",
+            count,
+            what,
+            needed,
+            buf.remaining()
+        )));
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn string_packet(len: u32, contents: &[u8]) -> Vec<u8> {
+        let mut packet = len.to_be_bytes().to_vec();
+        packet.extend_from_slice(contents);
+        packet
+    }
+
+    #[test]
+    fn test_read_string_empty() {
+        let packet = string_packet(0, b"trailing");
+        let mut buf = packet.as_slice();
+
+        assert_eq!(read_string(&mut buf).unwrap(), "");
+        assert_eq!(buf, b"trailing");
+    }
+
+    #[test]
+    fn test_read_string_exact_length() {
+        let packet = string_packet(5, b"hello");
+        let mut buf = packet.as_slice();
+
+        assert_eq!(read_string(&mut buf).unwrap(), "hello");
+        assert!(buf.is_empty());
+    }
+
+    /// Encode as Java's modified UTF-8 (what a JVM sends)
+    fn modified_utf8(s: &str) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for unit in s.encode_utf16() {
+            match unit {
+                0x0001..=0x007F => bytes.push(unit as u8),
+                0x0000 | 0x0080..=0x07FF => {
+                    bytes.push(0xC0 | (unit >> 6) as u8);
+                    bytes.push(0x80 | (unit & 0x3F) as u8);
+                }
+                _ => {
+                    bytes.push(0xE0 | (unit >> 12) as u8);
+                    bytes.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                    bytes.push(0x80 | (unit & 0x3F) as u8);
+                }
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_read_string_modified_utf8_null() {
+        let packet = string_packet(4, &[b'a', 0xC0, 0x80, b'b']);
+        let mut buf = packet.as_slice();
+
+        assert_eq!(read_string(&mut buf).unwrap(), "a\0b");
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_read_string_modified_utf8_supplementary() {
+        // U+1F600 as the surrogate pair D83D DE00, three bytes each
+        let contents = [0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80];
+        let packet = string_packet(6, &contents);
+        let mut buf = packet.as_slice();
+
+        assert_eq!(read_string(&mut buf).unwrap(), "\u{1F600}");
+        assert_eq!(modified_utf8("\u{1F600}"), contents);
+    }
+
+    #[test]
+    fn test_read_modified_utf8_edge_cases() {
+        // An unpaired surrogate is legal in a Java string
+        assert_eq!(read_modified_utf8(&[b'x', 0xED, 0xA0, 0xBD]).unwrap(), "x\u{FFFD}");
+
+        // Truncated and invalid sequences
+        assert!(read_modified_utf8(&[0xC0]).is_err());
+        assert!(read_modified_utf8(&[0xE2, 0x82]).is_err());
+        assert!(read_modified_utf8(&[0xC0, 0x41]).is_err());
+        assert!(read_modified_utf8(&[0xF8, 0x80, 0x80, 0x80]).is_err());
+    }
+
+    #[test]
+    fn test_read_id_honors_size() {
+        let packet = [0x00, 0x00, 0x12, 0x34, 0xff];
+        let mut buf = &packet[..];
+
+        assert_eq!(read_id(&mut buf, 4).unwrap(), 0x1234);
+        assert_eq!(buf, &[0xff]);
+        assert!(matches!(read_id(&mut buf, 8), Err(JdwpError::Protocol(_))));
+    }
+
+    #[test]
+    fn test_read_string_truncated_length_prefix() {
+        let mut buf: &[u8] = &[0, 0, 1];
+        assert!(matches!(read_string(&mut buf), Err(JdwpError::Protocol(_))));
+    }
+
+    proptest! {
+        #[test]
+        fn read_string_rejects_length_past_end(
+            contents in proptest::collection::vec(any::<u8>(), 0..64),
+            excess in 1..=u32::MAX / 2,
+        ) {
+            let len = contents.len() as u32 + excess;
+            let packet = string_packet(len, &contents);
+            let mut buf = packet.as_slice();
+
+            prop_assert!(matches!(read_string(&mut buf), Err(JdwpError::Protocol(_))));
+        }
+
+        #[test]
+        fn read_modified_utf8_round_trips(s in any::<String>()) {
+            prop_assert_eq!(read_modified_utf8(&modified_utf8(&s)).unwrap(), s);
+        }
+
+        #[test]
+        fn read_string_never_panics(bytes in proptest::collection::vec(any::<u8>(), 0..64)) {
+            let mut buf = bytes.as_slice();
+            let _ = read_string(&mut buf);
+        }
+    }
+
+    #[test]
+    fn test_read_count_rejects_counts_the_reply_cannot_hold() {
+        let mut packet = 2i32.to_be_bytes().to_vec();
+        packet.extend_from_slice(&[0; 16]);
+        assert_eq!(read_count(&mut packet.as_slice(), 8, "frames").unwrap(), 2);
+
+        let err = read_count(&mut packet.as_slice(), 9, "frames").unwrap_err();
+        assert_eq!(err.to_string(), "Protocol error: Truncated reply: 2 frames need at least 18 bytes, got 16");
+
+        let huge = i32::MAX.to_be_bytes();
+        assert!(read_count(&mut huge.as_slice(), 1, "threads").is_err());
+
+        let negative = (-1i32).to_be_bytes();
+        let err = read_count(&mut negative.as_slice(), 1, "threads").unwrap_err();
+        assert!(err.to_string().contains("Invalid threads count -1"), "{}", err);
+    }
+}
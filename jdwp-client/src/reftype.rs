@@ -2,13 +2,16 @@
 //
 // Commands for working with classes, interfaces, and arrays
 
-use crate::commands::{command_sets, reference_type_commands};
+use crate::commands::{command_sets, error_codes, reference_type_commands};
 use crate::connection::JdwpConnection;
 use crate::protocol::{CommandPacket, JdwpResult};
-use crate::reader::{read_i32, read_string, read_u64};
-use crate::types::{FieldId, MethodId, ReferenceTypeId};
+use crate::object::read_value_by_tag;
+use crate::reader::{read_count, read_i32, read_string, read_u8, MAX_PREALLOCATED};
+use crate::signature::{method_declaration, type_name};
+use crate::types::{ClassStatus, FieldId, InterfaceId, MethodId, ReferenceTypeId, Value, ACC_INTERFACE};
 use bytes::BufMut;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 
 /// Method information
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,46 +19,182 @@ pub struct MethodInfo {
     pub method_id: MethodId,
     pub name: String,
     pub signature: String,
+    /// Generic signature, if the method has one and it was requested
+    #[serde(default)]
+    pub generic_signature: Option<String>,
     pub mod_bits: i32,
 }
 
+impl MethodInfo {
+    /// Java-style declaration, using the generic signature when available
+    /// e.g. "List<String> foo(Map<K, V>)"
+    pub fn declaration(&self) -> String {
+        let signature = self.generic_signature.as_deref().unwrap_or(&self.signature);
+        method_declaration(&self.name, signature)
+    }
+}
+
 /// Field information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FieldInfo {
     pub field_id: FieldId,
     pub name: String,
     pub signature: String,
+    /// Generic signature, if the field's type is generic and it was requested
+    #[serde(default)]
+    pub generic_signature: Option<String>,
     pub mod_bits: i32,
 }
 
+impl FieldInfo {
+    /// Java-style type, using the generic signature when available
+    /// e.g. "Map<String, List<Integer>>"
+    pub fn type_name(&self) -> String {
+        type_name(self.generic_signature.as_deref().unwrap_or(&self.signature))
+    }
+}
+
+/// Field information together with the class that declares it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeclaredField {
+    pub declaring_type: ReferenceTypeId,
+    pub field: FieldInfo,
+}
+
 impl JdwpConnection {
+    /// Get the JNI signature of a reference type (ReferenceType.Signature command)
+    ///
+    /// Returns signatures like "Lcom/example/MyClass;" or "[I"
+    pub async fn get_signature(&mut self, ref_type_id: ReferenceTypeId) -> JdwpResult<String> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::REFERENCE_TYPE, reference_type_commands::SIGNATURE);
+
+        let sizes = self.id_sizes();
+        sizes.write_reference_type_id(&mut packet.data, ref_type_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        read_string(&mut data)
+    }
+
+    /// Get the source file name of a reference type (ReferenceType.SourceFile command)
+    ///
+    /// Returns just the file name, e.g. "HelloController.java". Fails with
+    /// ABSENT_INFORMATION for classes compiled without a SourceFile attribute.
+    pub async fn get_source_file(&mut self, ref_type_id: ReferenceTypeId) -> JdwpResult<String> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::REFERENCE_TYPE, reference_type_commands::SOURCE_FILE);
+
+        let sizes = self.id_sizes();
+        sizes.write_reference_type_id(&mut packet.data, ref_type_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        read_string(&mut data)
+    }
+
+    /// Get the modifiers of a reference type (ReferenceType.Modifiers command)
+    ///
+    /// Access flags as in the class file, e.g. `ACC_INTERFACE` for interfaces.
+    pub async fn get_modifiers(&mut self, ref_type_id: ReferenceTypeId) -> JdwpResult<i32> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::REFERENCE_TYPE, reference_type_commands::MODIFIERS);
+
+        let sizes = self.id_sizes();
+        sizes.write_reference_type_id(&mut packet.data, ref_type_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        read_i32(&mut data)
+    }
+
     /// Get methods for a reference type (ReferenceType.Methods command)
     pub async fn get_methods(&mut self, ref_type_id: ReferenceTypeId) -> JdwpResult<Vec<MethodInfo>> {
         let id = self.next_id();
         let mut packet = CommandPacket::new(id, command_sets::REFERENCE_TYPE, reference_type_commands::METHODS);
 
-        // Write reference type ID (8 bytes)
-        packet.data.put_u64(ref_type_id);
+        // Write reference type ID
+        let sizes = self.id_sizes();
+        sizes.write_reference_type_id(&mut packet.data, ref_type_id);
 
         let reply = self.send_command(packet).await?;
         reply.check_error()?;
 
         let mut data = reply.data();
 
-        // Read number of methods
-        let methods_count = read_i32(&mut data)?;
-        let mut methods = Vec::with_capacity(methods_count as usize);
+        // Read number of methods; each has an ID, name, signature and modifiers
+        let method_size = sizes.method_id_size as usize + 12;
+        let methods_count = read_count(&mut data, method_size, "methods")?;
+        let mut methods = Vec::with_capacity(methods_count.min(MAX_PREALLOCATED));
+
+        for _ in 0..methods_count {
+            let method_id = sizes.read_method_id(&mut data)?;
+            let name = read_string(&mut data)?;
+            let signature = read_string(&mut data)?;
+            let mod_bits = read_i32(&mut data)?;
+
+            methods.push(MethodInfo {
+                method_id,
+                name,
+                signature,
+                generic_signature: None,
+                mod_bits,
+            });
+        }
+
+        Ok(methods)
+    }
+
+    /// Get methods including generic signatures (ReferenceType.MethodsWithGeneric command)
+    ///
+    /// Falls back to ReferenceType.Methods (with no generic signatures) on VMs
+    /// older than JDWP 1.5 or if the VM reports ABSENT_INFORMATION.
+    pub async fn get_methods_with_generic(&mut self, ref_type_id: ReferenceTypeId) -> JdwpResult<Vec<MethodInfo>> {
+        if !self.supports_command_set_version(1, 5) {
+            return self.get_methods(ref_type_id).await;
+        }
+
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::REFERENCE_TYPE, reference_type_commands::METHODS_WITH_GENERIC);
+
+        let sizes = self.id_sizes();
+        sizes.write_reference_type_id(&mut packet.data, ref_type_id);
+
+        let reply = self.send_command(packet).await?;
+        match reply.check_error() {
+            Ok(()) => {}
+            Err(e) if e.error_code() == Some(error_codes::ABSENT_INFORMATION) => {
+                return self.get_methods(ref_type_id).await;
+            }
+            Err(e) => return Err(e),
+        }
+
+        let mut data = reply.data();
+
+        // As for Methods, plus a generic signature
+        let method_size = sizes.method_id_size as usize + 16;
+        let methods_count = read_count(&mut data, method_size, "methods")?;
+        let mut methods = Vec::with_capacity(methods_count.min(MAX_PREALLOCATED));
 
         for _ in 0..methods_count {
-            let method_id = read_u64(&mut data)?;
+            let method_id = sizes.read_method_id(&mut data)?;
             let name = read_string(&mut data)?;
             let signature = read_string(&mut data)?;
+            // An empty generic signature means the method isn't generic
+            let generic_signature = Some(read_string(&mut data)?).filter(|s| !s.is_empty());
             let mod_bits = read_i32(&mut data)?;
 
             methods.push(MethodInfo {
                 method_id,
                 name,
                 signature,
+                generic_signature,
                 mod_bits,
             });
         }
@@ -73,29 +212,36 @@ impl JdwpConnection {
     ///
     /// # Example
     /// ```no_run
+    /// # use jdwp_client::types::ReferenceTypeId;
+    /// # use jdwp_client::{JdwpConnection, JdwpResult};
+    /// # async fn f(connection: &mut JdwpConnection, class_id: ReferenceTypeId) -> JdwpResult<()> {
     /// let fields = connection.get_fields(class_id).await?;
     /// for field in fields {
     ///     println!("Field: {} ({})", field.name, field.signature);
     /// }
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn get_fields(&mut self, ref_type_id: ReferenceTypeId) -> JdwpResult<Vec<FieldInfo>> {
         let id = self.next_id();
         let mut packet = CommandPacket::new(id, command_sets::REFERENCE_TYPE, reference_type_commands::FIELDS);
 
-        // Write reference type ID (8 bytes)
-        packet.data.put_u64(ref_type_id);
+        // Write reference type ID
+        let sizes = self.id_sizes();
+        sizes.write_reference_type_id(&mut packet.data, ref_type_id);
 
         let reply = self.send_command(packet).await?;
         reply.check_error()?;
 
         let mut data = reply.data();
 
-        // Read number of fields
-        let fields_count = read_i32(&mut data)?;
-        let mut fields = Vec::with_capacity(fields_count as usize);
+        // Read number of fields; each has an ID, name, signature and modifiers
+        let field_size = sizes.field_id_size as usize + 12;
+        let fields_count = read_count(&mut data, field_size, "fields")?;
+        let mut fields = Vec::with_capacity(fields_count.min(MAX_PREALLOCATED));
 
         for _ in 0..fields_count {
-            let field_id = read_u64(&mut data)?;
+            let field_id = sizes.read_field_id(&mut data)?;
             let name = read_string(&mut data)?;
             let signature = read_string(&mut data)?;
             let mod_bits = read_i32(&mut data)?;
@@ -104,10 +250,220 @@ impl JdwpConnection {
                 field_id,
                 name,
                 signature,
+                generic_signature: None,
                 mod_bits,
             });
         }
 
         Ok(fields)
     }
+
+    /// Get fields including generic signatures (ReferenceType.FieldsWithGeneric command)
+    ///
+    /// Falls back to ReferenceType.Fields (with no generic signatures) on VMs
+    /// older than JDWP 1.5 or if the VM reports ABSENT_INFORMATION.
+    pub async fn get_fields_with_generic(&mut self, ref_type_id: ReferenceTypeId) -> JdwpResult<Vec<FieldInfo>> {
+        if !self.supports_command_set_version(1, 5) {
+            return self.get_fields(ref_type_id).await;
+        }
+
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::REFERENCE_TYPE, reference_type_commands::FIELDS_WITH_GENERIC);
+
+        let sizes = self.id_sizes();
+        sizes.write_reference_type_id(&mut packet.data, ref_type_id);
+
+        let reply = self.send_command(packet).await?;
+        match reply.check_error() {
+            Ok(()) => {}
+            Err(e) if e.error_code() == Some(error_codes::ABSENT_INFORMATION) => {
+                return self.get_fields(ref_type_id).await;
+            }
+            Err(e) => return Err(e),
+        }
+
+        let mut data = reply.data();
+
+        // As for Fields, plus a generic signature
+        let field_size = sizes.field_id_size as usize + 16;
+        let fields_count = read_count(&mut data, field_size, "fields")?;
+        let mut fields = Vec::with_capacity(fields_count.min(MAX_PREALLOCATED));
+
+        for _ in 0..fields_count {
+            let field_id = sizes.read_field_id(&mut data)?;
+            let name = read_string(&mut data)?;
+            let signature = read_string(&mut data)?;
+            // An empty generic signature means the field's type isn't generic
+            let generic_signature = Some(read_string(&mut data)?).filter(|s| !s.is_empty());
+            let mod_bits = read_i32(&mut data)?;
+
+            fields.push(FieldInfo {
+                field_id,
+                name,
+                signature,
+                generic_signature,
+                mod_bits,
+            });
+        }
+
+        Ok(fields)
+    }
+
+    /// Get declared and inherited fields of a class
+    ///
+    /// Walks the superclass chain starting at `class_id`, collecting the fields
+    /// declared by each class. A field shadowed by a subclass field of the same
+    /// name is omitted, so the most-derived declaration wins. Fields are returned
+    /// in order from the most-derived class up to java.lang.Object.
+    ///
+    /// Arrays and interfaces have no superclass chain to walk, so only their
+    /// declared fields are returned.
+    pub async fn get_all_fields(&mut self, class_id: ReferenceTypeId) -> JdwpResult<Vec<DeclaredField>> {
+        // ClassType.Superclass fails with INVALID_CLASS for anything but a class
+        let is_class = !self.get_signature(class_id).await?.starts_with('[')
+            && self.get_modifiers(class_id).await? & ACC_INTERFACE == 0;
+        if !is_class {
+            let fields = self.get_fields(class_id).await?;
+            return Ok(fields.into_iter().map(|field| DeclaredField { declaring_type: class_id, field }).collect());
+        }
+
+        let mut all_fields = Vec::new();
+        let mut seen_names = HashSet::new();
+        let mut current = Some(class_id);
+
+        while let Some(declaring_type) = current {
+            for field in self.get_fields(declaring_type).await? {
+                if seen_names.insert(field.name.clone()) {
+                    all_fields.push(DeclaredField { declaring_type, field });
+                }
+            }
+
+            current = self.get_superclass(declaring_type).await?;
+        }
+
+        Ok(all_fields)
+    }
+
+    /// Get the interfaces directly implemented by a type (ReferenceType.Interfaces command)
+    ///
+    /// Only direct superinterfaces are returned; interfaces inherited through
+    /// superclasses or other interfaces are not included.
+    pub async fn get_interfaces(&mut self, ref_type_id: ReferenceTypeId) -> JdwpResult<Vec<InterfaceId>> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::REFERENCE_TYPE, reference_type_commands::INTERFACES);
+
+        let sizes = self.id_sizes();
+        sizes.write_reference_type_id(&mut packet.data, ref_type_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+
+        let interfaces_count = read_count(&mut data, sizes.reference_type_id_size as usize, "interfaces")?;
+        let mut interfaces = Vec::with_capacity(interfaces_count.min(MAX_PREALLOCATED));
+
+        for _ in 0..interfaces_count {
+            interfaces.push(sizes.read_reference_type_id(&mut data)?);
+        }
+
+        Ok(interfaces)
+    }
+
+    /// Get the current status of a reference type (ReferenceType.Status command)
+    ///
+    /// Unlike the status captured when a class is looked up, this reflects
+    /// whether the class has been initialized since.
+    pub async fn get_class_status(&mut self, ref_type_id: ReferenceTypeId) -> JdwpResult<ClassStatus> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::REFERENCE_TYPE, reference_type_commands::STATUS);
+
+        let sizes = self.id_sizes();
+        sizes.write_reference_type_id(&mut packet.data, ref_type_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        Ok(ClassStatus(read_i32(&mut data)?))
+    }
+
+    /// Get the values of static fields (ReferenceType.GetValues command)
+    ///
+    /// Each field must be a static field of the type or one of its supertypes.
+    pub async fn get_reference_type_values(
+        &mut self,
+        ref_type_id: ReferenceTypeId,
+        field_ids: Vec<FieldId>,
+    ) -> JdwpResult<Vec<Value>> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::REFERENCE_TYPE, reference_type_commands::GET_VALUES);
+
+        let sizes = self.id_sizes();
+        sizes.write_reference_type_id(&mut packet.data, ref_type_id);
+        packet.data.put_i32(field_ids.len() as i32);
+        for field_id in &field_ids {
+            sizes.write_field_id(&mut packet.data, *field_id);
+        }
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+
+        let values_count = read_count(&mut data, 1, "values")?;
+        let mut values = Vec::with_capacity(values_count.min(MAX_PREALLOCATED));
+
+        for _ in 0..values_count {
+            let tag = read_u8(&mut data)?;
+            let value_data = read_value_by_tag(tag, &mut data, &sizes)?;
+            values.push(Value { tag, data: value_data });
+        }
+
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::class_type_commands;
+    use crate::fake_vm::{self, put_string};
+
+    #[tokio::test]
+    async fn test_get_all_fields_does_not_walk_arrays_or_interfaces() {
+        let (mut connection, vm) = fake_vm::connect(8).await;
+
+        // 0x10 is int[], 0x20 an interface declaring one constant; asking
+        // either for a superclass is an error, as in a real VM
+        tokio::spawn(fake_vm::serve(vm, |command_set, command, data| {
+            let type_id = u64::from_be_bytes(data[..8].try_into().unwrap());
+            let mut reply = Vec::new();
+            match (command_set, command, type_id) {
+                (command_sets::REFERENCE_TYPE, reference_type_commands::SIGNATURE, 0x10) => put_string(&mut reply, "[I"),
+                (command_sets::REFERENCE_TYPE, reference_type_commands::SIGNATURE, 0x20) => {
+                    put_string(&mut reply, "Lcom/example/Limits;")
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::MODIFIERS, 0x20) => reply.put_i32(0x0601),
+                (command_sets::REFERENCE_TYPE, reference_type_commands::FIELDS, 0x10) => reply.put_i32(0),
+                (command_sets::REFERENCE_TYPE, reference_type_commands::FIELDS, 0x20) => {
+                    reply.put_i32(1);
+                    reply.put_u64(0x21);
+                    put_string(&mut reply, "MAX");
+                    put_string(&mut reply, "I");
+                    reply.put_i32(0x0019);
+                }
+                (command_sets::CLASS_TYPE, class_type_commands::SUPERCLASS, _) => return Err(error_codes::INVALID_CLASS),
+                _ => return Err(error_codes::NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        }));
+
+        assert!(connection.get_all_fields(0x10).await.unwrap().is_empty());
+
+        let fields = connection.get_all_fields(0x20).await.unwrap();
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].declaring_type, 0x20);
+        assert_eq!(fields[0].field.name, "MAX");
+    }
 }
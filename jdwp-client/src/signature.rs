@@ -0,0 +1,236 @@
+// JNI signature formatting
+//
+// Renders field, method and generic signatures as Java source-style types,
+// using simple class names (e.g. "Ljava/util/List<Ljava/lang/String;>;" -> "List<String>")
+
+/// Render a type signature (plain or generic) as a Java type
+///
+/// Falls back to the raw signature if it can't be parsed.
+pub fn type_name(signature: &str) -> String {
+    let mut parser = Parser::new(signature);
+    match parser.parse_type() {
+        Some(name) if parser.at_end() => name,
+        _ => signature.to_string(),
+    }
+}
+
+/// Render a method as a Java-style declaration, e.g. "List<String> foo(Map<K, V>)"
+///
+/// `signature` may be a plain method signature or a generic one. Falls back
+/// to the name followed by the raw signature if it can't be parsed.
+pub fn method_declaration(name: &str, signature: &str) -> String {
+    Parser::new(signature)
+        .parse_method(name)
+        .unwrap_or_else(|| format!("{}{}", name, signature))
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { input: input.as_bytes(), pos: 0 }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos == self.input.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.pos += 1;
+        Some(byte)
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        (self.next()? == byte).then_some(())
+    }
+
+    /// Read up to (not including) the first of `terminators`
+    fn identifier(&mut self, terminators: &[u8]) -> Option<&'a str> {
+        let start = self.pos;
+        while !terminators.contains(&self.peek()?) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.input[start..self.pos]).ok()
+    }
+
+    fn parse_method(&mut self, name: &str) -> Option<String> {
+        let type_params = if self.peek() == Some(b'<') {
+            format!("{} ", self.parse_type_params()?)
+        } else {
+            String::new()
+        };
+
+        self.expect(b'(')?;
+        let mut params = Vec::new();
+        while self.peek()? != b')' {
+            params.push(self.parse_type()?);
+        }
+        self.expect(b')')?;
+
+        let return_type = self.parse_type()?;
+
+        // Generic signatures may list thrown types after '^'; they aren't shown
+        if !self.at_end() && self.peek() != Some(b'^') {
+            return None;
+        }
+
+        Some(format!("{}{} {}({})", type_params, return_type, name, params.join(", ")))
+    }
+
+    /// Parse "<T:Ljava/lang/Object;U::Ljava/lang/Comparable<TU;>;>" as "<T, U>"
+    fn parse_type_params(&mut self) -> Option<String> {
+        self.expect(b'<')?;
+        let mut names = Vec::new();
+
+        while self.peek()? != b'>' {
+            names.push(self.identifier(b":")?);
+            self.expect(b':')?;
+
+            // The class bound may be empty when only interface bounds follow
+            if !matches!(self.peek()?, b':' | b'>') && !self.starts_type_param() {
+                self.parse_type()?;
+            }
+            while self.peek()? == b':' {
+                self.next();
+                self.parse_type()?;
+            }
+        }
+        self.expect(b'>')?;
+
+        Some(format!("<{}>", names.join(", ")))
+    }
+
+    /// Whether the input continues with another "Name:" type parameter
+    fn starts_type_param(&self) -> bool {
+        let rest = &self.input[self.pos..];
+        match rest.iter().position(|&b| matches!(b, b':' | b';' | b'<' | b'>')) {
+            Some(i) => rest[i] == b':',
+            None => false,
+        }
+    }
+
+    fn parse_type(&mut self) -> Option<String> {
+        let name = match self.next()? {
+            b'B' => "byte".to_string(),
+            b'C' => "char".to_string(),
+            b'D' => "double".to_string(),
+            b'F' => "float".to_string(),
+            b'I' => "int".to_string(),
+            b'J' => "long".to_string(),
+            b'S' => "short".to_string(),
+            b'Z' => "boolean".to_string(),
+            b'V' => "void".to_string(),
+            b'[' => format!("{}[]", self.parse_type()?),
+            b'T' => {
+                let name = self.identifier(b";")?.to_string();
+                self.expect(b';')?;
+                name
+            }
+            b'L' => self.parse_class_type()?,
+            _ => return None,
+        };
+        Some(name)
+    }
+
+    /// Parse the rest of "Lpkg/Outer<...>.Inner<...>;" after the 'L'
+    fn parse_class_type(&mut self) -> Option<String> {
+        let path = self.identifier(b"<.;")?;
+        let mut name = path.rsplit('/').next().unwrap_or(path).to_string();
+
+        loop {
+            if self.peek()? == b'<' {
+                name.push_str(&self.parse_type_args()?);
+            }
+            match self.next()? {
+                b';' => return Some(name),
+                b'.' => {
+                    name.push('.');
+                    name.push_str(self.identifier(b"<.;")?);
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    fn parse_type_args(&mut self) -> Option<String> {
+        self.expect(b'<')?;
+        let mut args = Vec::new();
+
+        while self.peek()? != b'>' {
+            let arg = match self.peek()? {
+                b'*' => {
+                    self.next();
+                    "?".to_string()
+                }
+                b'+' => {
+                    self.next();
+                    format!("? extends {}", self.parse_type()?)
+                }
+                b'-' => {
+                    self.next();
+                    format!("? super {}", self.parse_type()?)
+                }
+                _ => self.parse_type()?,
+            };
+            args.push(arg);
+        }
+        self.expect(b'>')?;
+
+        Some(format!("<{}>", args.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_type_name() {
+        assert_eq!(type_name("I"), "int");
+        assert_eq!(type_name("[[J"), "long[][]");
+        assert_eq!(type_name("Ljava/lang/String;"), "String");
+        assert_eq!(type_name("Ljava/util/Map$Entry;"), "Map$Entry");
+        assert_eq!(
+            type_name("Ljava/util/Map<Ljava/lang/String;Ljava/util/List<TT;>;>;"),
+            "Map<String, List<T>>"
+        );
+        assert_eq!(
+            type_name("Ljava/util/List<+Ljava/lang/Number;>;"),
+            "List<? extends Number>"
+        );
+        assert_eq!(type_name("Ljava/lang/Class<*>;"), "Class<?>");
+        assert_eq!(type_name("Lcom/example/Outer<TT;>.Inner<-TT;>;"), "Outer<T>.Inner<? super T>");
+    }
+
+    #[test]
+    fn test_method_declaration() {
+        assert_eq!(method_declaration("main", "([Ljava/lang/String;)V"), "void main(String[])");
+        assert_eq!(
+            method_declaration("foo", "(Ljava/util/Map<TK;TV;>;I)Ljava/util/List<Ljava/lang/String;>;"),
+            "List<String> foo(Map<K, V>, int)"
+        );
+        assert_eq!(
+            method_declaration(
+                "max",
+                "<T:Ljava/lang/Object;U::Ljava/lang/Comparable<TU;>;>(TT;TU;)TU;^Ljava/io/IOException;"
+            ),
+            "<T, U> U max(T, U)"
+        );
+    }
+
+    #[test]
+    fn test_malformed_signature_falls_back_to_raw() {
+        assert_eq!(type_name("Ljava/lang/String"), "Ljava/lang/String");
+        assert_eq!(type_name("Q"), "Q");
+        assert_eq!(method_declaration("bar", "(I"), "bar(I");
+        assert_eq!(method_declaration("baz", "()VX"), "baz()VX");
+    }
+}
@@ -5,10 +5,10 @@
 use crate::commands::{command_sets, stack_frame_commands};
 use crate::connection::JdwpConnection;
 use crate::protocol::{CommandPacket, JdwpResult};
-use crate::reader::{read_u64, read_u8};
-use crate::types::{FrameId, ThreadId, Value, ValueData};
-use bytes::{Buf, BufMut};
-use serde::{Deserialize, Serialize};
+use crate::object::read_value_by_tag;
+use crate::reader::{read_count, read_u8, MAX_PREALLOCATED};
+use crate::types::{FrameId, ObjectId, ThreadId, Value};
+use bytes::BufMut;
 
 /// Variable slot information for GetValues
 #[derive(Debug, Clone)]
@@ -29,8 +29,9 @@ impl JdwpConnection {
         let mut packet = CommandPacket::new(id, command_sets::STACK_FRAME, stack_frame_commands::GET_VALUES);
 
         // Write thread ID and frame ID
-        packet.data.put_u64(thread_id);
-        packet.data.put_u64(frame_id);
+        let sizes = self.id_sizes();
+        sizes.write_object_id(&mut packet.data, thread_id);
+        sizes.write_frame_id(&mut packet.data, frame_id);
 
         // Number of slots to retrieve
         packet.data.put_i32(slots.len() as i32);
@@ -47,12 +48,12 @@ impl JdwpConnection {
         let mut data = reply.data();
 
         // Read number of values (should match slots.len())
-        let values_count = crate::reader::read_i32(&mut data)?;
-        let mut values = Vec::with_capacity(values_count as usize);
+        let values_count = read_count(&mut data, 1, "values")?;
+        let mut values = Vec::with_capacity(values_count.min(MAX_PREALLOCATED));
 
         for _ in 0..values_count {
             let tag = read_u8(&mut data)?;
-            let value_data = read_value_by_tag(tag, &mut data)?;
+            let value_data = read_value_by_tag(tag, &mut data, &sizes)?;
 
             values.push(Value {
                 tag,
@@ -62,34 +63,79 @@ impl JdwpConnection {
 
         Ok(values)
     }
-}
 
-/// Read a value based on its type tag
-fn read_value_by_tag(tag: u8, buf: &mut &[u8]) -> JdwpResult<ValueData> {
-    match tag {
-        // 'B' = byte
-        66 => Ok(ValueData::Byte(buf.get_i8())),
-        // 'C' = char
-        67 => Ok(ValueData::Char(buf.get_u16())),
-        // 'D' = double
-        68 => Ok(ValueData::Double(buf.get_f64())),
-        // 'F' = float
-        70 => Ok(ValueData::Float(buf.get_f32())),
-        // 'I' = int
-        73 => Ok(ValueData::Int(buf.get_i32())),
-        // 'J' = long
-        74 => Ok(ValueData::Long(buf.get_i64())),
-        // 'S' = short
-        83 => Ok(ValueData::Short(buf.get_i16())),
-        // 'Z' = boolean
-        90 => Ok(ValueData::Boolean(buf.get_u8() != 0)),
-        // 'V' = void
-        86 => Ok(ValueData::Void),
-        // Object types (L, s, t, g, l, c, [)
-        76 | 115 | 116 | 103 | 108 | 99 | 91 => {
-            let object_id = read_u64(buf)?;
-            Ok(ValueData::Object(object_id))
+    /// Set local variables in a frame (StackFrame.SetValues command)
+    ///
+    /// Each value's tag must match the variable's type, see
+    /// [`tag_for_signature`](crate::types::tag_for_signature).
+    pub async fn set_frame_values(
+        &mut self,
+        thread_id: ThreadId,
+        frame_id: FrameId,
+        values: Vec<(i32, Value)>,
+    ) -> JdwpResult<()> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::STACK_FRAME, stack_frame_commands::SET_VALUES);
+
+        let sizes = self.id_sizes();
+        sizes.write_object_id(&mut packet.data, thread_id);
+        sizes.write_frame_id(&mut packet.data, frame_id);
+        packet.data.put_i32(values.len() as i32);
+
+        for (slot, value) in &values {
+            packet.data.put_i32(*slot);
+            value.write_tagged(&mut packet.data, &sizes);
         }
-        _ => Err(crate::protocol::JdwpError::Protocol(format!("Unknown value tag: {}", tag))),
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        Ok(())
+    }
+
+    /// Get the `this` object of a frame (StackFrame.ThisObject command)
+    ///
+    /// Returns `None` for static and native methods, which have no receiver.
+    pub async fn get_frame_this(
+        &mut self,
+        thread_id: ThreadId,
+        frame_id: FrameId,
+    ) -> JdwpResult<Option<ObjectId>> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::STACK_FRAME, stack_frame_commands::THIS_OBJECT);
+
+        let sizes = self.id_sizes();
+        sizes.write_object_id(&mut packet.data, thread_id);
+        sizes.write_frame_id(&mut packet.data, frame_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+
+        // Tagged object ID (tag is always an object tag)
+        let _tag = read_u8(&mut data)?;
+        let object_id = sizes.read_object_id(&mut data)?;
+
+        Ok(if object_id == 0 { None } else { Some(object_id) })
+    }
+
+    /// Pop a frame and all frames above it (StackFrame.PopFrames command)
+    ///
+    /// The thread must be suspended and the VM must support `can_pop_frames`.
+    /// Afterwards the thread is positioned at the call that invoked the popped
+    /// frame, and all previously obtained frame IDs are invalid.
+    pub async fn pop_frames(&mut self, thread_id: ThreadId, frame_id: FrameId) -> JdwpResult<()> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::STACK_FRAME, stack_frame_commands::POP_FRAMES);
+
+        let sizes = self.id_sizes();
+        sizes.write_object_id(&mut packet.data, thread_id);
+        sizes.write_frame_id(&mut packet.data, frame_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        Ok(())
     }
 }
@@ -0,0 +1,92 @@
+// Connection statistics
+//
+// Counters shared by a connection and its event loop, for diagnosing slow sessions
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Live counters, updated as commands and events flow through a connection
+#[derive(Debug, Default)]
+pub struct ConnectionStats {
+    commands_sent: AtomicU64,
+    replies_received: AtomicU64,
+    events_received: AtomicU64,
+    total_reply_latency_micros: AtomicU64,
+    errors_by_code: Mutex<BTreeMap<u16, u64>>,
+}
+
+impl ConnectionStats {
+    pub(crate) fn record_command(&self) {
+        self.commands_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a reply; `error_code` is 0 for success
+    pub(crate) fn record_reply(&self, error_code: u16, latency: Duration) {
+        self.replies_received.fetch_add(1, Ordering::Relaxed);
+        self.total_reply_latency_micros
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+
+        if error_code != 0 {
+            let mut errors = self.errors_by_code.lock().unwrap();
+            *errors.entry(error_code).or_default() += 1;
+        }
+    }
+
+    pub(crate) fn record_events(&self, count: usize) {
+        self.events_received.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    /// Copy the current counters
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let replies_received = self.replies_received.load(Ordering::Relaxed);
+        let total_latency = self.total_reply_latency_micros.load(Ordering::Relaxed);
+
+        StatsSnapshot {
+            commands_sent: self.commands_sent.load(Ordering::Relaxed),
+            replies_received,
+            events_received: self.events_received.load(Ordering::Relaxed),
+            average_reply_latency: total_latency
+                .checked_div(replies_received)
+                .map(Duration::from_micros),
+            errors_by_code: self.errors_by_code.lock().unwrap().clone(),
+        }
+    }
+}
+
+/// Point-in-time copy of [`ConnectionStats`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatsSnapshot {
+    pub commands_sent: u64,
+    pub replies_received: u64,
+    pub events_received: u64,
+    /// None until the first reply arrives
+    pub average_reply_latency: Option<Duration>,
+    /// Error replies, keyed by JDWP error code
+    pub errors_by_code: BTreeMap<u16, u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_counts_and_averages() {
+        let stats = ConnectionStats::default();
+        assert_eq!(stats.snapshot().average_reply_latency, None);
+
+        stats.record_command();
+        stats.record_command();
+        stats.record_reply(0, Duration::from_millis(2));
+        stats.record_reply(30, Duration::from_millis(4));
+        stats.record_events(3);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.commands_sent, 2);
+        assert_eq!(snapshot.replies_received, 2);
+        assert_eq!(snapshot.events_received, 3);
+        assert_eq!(snapshot.average_reply_latency, Some(Duration::from_millis(3)));
+        assert_eq!(snapshot.errors_by_code, BTreeMap::from([(30, 1)]));
+    }
+}
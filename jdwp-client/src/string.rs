@@ -7,7 +7,6 @@ use crate::connection::JdwpConnection;
 use crate::protocol::{CommandPacket, JdwpResult};
 use crate::reader::read_string;
 use crate::types::ObjectId;
-use bytes::BufMut;
 
 impl JdwpConnection {
     /// Get the string value from a String object (StringReference.Value command)
@@ -20,8 +19,13 @@ impl JdwpConnection {
     ///
     /// # Example
     /// ```no_run
+    /// # use jdwp_client::types::ObjectId;
+    /// # use jdwp_client::{JdwpConnection, JdwpResult};
+    /// # async fn f(connection: &mut JdwpConnection, string_object_id: ObjectId) -> JdwpResult<()> {
     /// let value = connection.get_string_value(string_object_id).await?;
     /// println!("String value: {}", value);
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn get_string_value(&mut self, string_id: ObjectId) -> JdwpResult<String> {
         let id = self.next_id();
@@ -32,7 +36,7 @@ impl JdwpConnection {
         );
 
         // Write the string object ID
-        packet.data.put_u64(string_id);
+        self.id_sizes().write_object_id(&mut packet.data, string_id);
 
         let reply = self.send_command(packet).await?;
         reply.check_error()?;
@@ -48,8 +52,6 @@ impl JdwpConnection {
 
 #[cfg(test)]
 mod tests {
-    use super::*;
-
     #[test]
     fn test_string_value_packet() {
         // Test that packet is constructed correctly
@@ -2,13 +2,14 @@
 //
 // Commands for working with threads (frames, status, suspend/resume)
 
-use crate::commands::{command_sets, thread_commands};
+use crate::commands::{command_sets, error_codes, thread_commands};
 use crate::connection::JdwpConnection;
-use crate::protocol::{CommandPacket, JdwpResult};
-use crate::reader::{read_i32, read_u64};
-use crate::types::{FrameId, Location, MethodId, ReferenceTypeId, ThreadId};
+use crate::protocol::{CommandPacket, JdwpError, JdwpResult};
+use crate::reader::{read_count, read_i32, read_string, read_u8, MAX_PREALLOCATED};
+use crate::types::{FrameId, Location, ObjectId, SuspendStatus, ThreadId, ThreadStatus, Value};
 use bytes::BufMut;
 use serde::{Deserialize, Serialize};
+use tokio::task::JoinSet;
 
 /// Stack frame information
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,6 +18,23 @@ pub struct Frame {
     pub location: Location,
 }
 
+/// A thread with its name and state, from `list_threads_detailed`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThreadDetail {
+    pub id: ThreadId,
+    pub name: String,
+    pub status: ThreadStatus,
+    /// Suspensions outstanding, counting those by events; the thread runs
+    /// again once it has been resumed this many times
+    pub suspend_count: i32,
+}
+
+impl ThreadDetail {
+    pub fn is_suspended(&self) -> bool {
+        self.suspend_count > 0
+    }
+}
+
 impl JdwpConnection {
     /// Get stack frames for a thread (ThreadReference.Frames command)
     pub async fn get_frames(
@@ -29,7 +47,8 @@ impl JdwpConnection {
         let mut packet = CommandPacket::new(id, command_sets::THREAD_REFERENCE, thread_commands::FRAMES);
 
         // Write thread ID
-        packet.data.put_u64(thread_id);
+        let sizes = self.id_sizes();
+        sizes.write_object_id(&mut packet.data, thread_id);
         // Start frame (0 = current/top frame)
         packet.data.put_i32(start_frame);
         // Length (-1 = all frames)
@@ -40,33 +59,155 @@ impl JdwpConnection {
 
         let mut data = reply.data();
 
-        // Read number of frames
-        let frames_count = read_i32(&mut data)?;
-        let mut frames = Vec::with_capacity(frames_count as usize);
+        // Read number of frames; each is a frame ID and a location
+        let frame_size = (sizes.frame_id_size + 1 + sizes.reference_type_id_size + sizes.method_id_size + 8) as usize;
+        let frames_count = read_count(&mut data, frame_size, "frames")?;
+        let mut frames = Vec::with_capacity(frames_count.min(MAX_PREALLOCATED));
 
         for _ in 0..frames_count {
-            let frame_id = read_u64(&mut data)?;
-
-            // Read location
-            let type_tag = crate::reader::read_u8(&mut data)?;
-            let class_id = read_u64(&mut data)?;
-            let method_id = read_u64(&mut data)?;
-            let index = read_u64(&mut data)?;
-
-            frames.push(Frame {
-                frame_id,
-                location: Location {
-                    type_tag,
-                    class_id,
-                    method_id,
-                    index,
-                },
-            });
+            let frame_id = sizes.read_frame_id(&mut data)?;
+            let location = sizes.read_location(&mut data)?;
+
+            frames.push(Frame { frame_id, location });
         }
 
         Ok(frames)
     }
 
+    /// Get the number of frames on a suspended thread's stack
+    /// (ThreadReference.FrameCount)
+    pub async fn get_frame_count(&mut self, thread_id: ThreadId) -> JdwpResult<i32> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::THREAD_REFERENCE, thread_commands::FRAME_COUNT);
+
+        self.id_sizes().write_object_id(&mut packet.data, thread_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        read_i32(&mut data)
+    }
+
+    /// Get a thread's name (ThreadReference.Name)
+    pub async fn get_thread_name(&mut self, thread_id: ThreadId) -> JdwpResult<String> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::THREAD_REFERENCE, thread_commands::NAME);
+
+        self.id_sizes().write_object_id(&mut packet.data, thread_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        read_string(&mut data)
+    }
+
+    /// Get a thread's state and whether it is suspended (ThreadReference.Status)
+    pub async fn get_thread_status(&mut self, thread_id: ThreadId) -> JdwpResult<(ThreadStatus, SuspendStatus)> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::THREAD_REFERENCE, thread_commands::STATUS);
+
+        self.id_sizes().write_object_id(&mut packet.data, thread_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        let thread_status = read_i32(&mut data)?;
+        let suspend_status = read_i32(&mut data)?;
+
+        let thread_status = ThreadStatus::from_i32(thread_status)
+            .ok_or_else(|| JdwpError::Protocol(format!("Unknown thread status {}", thread_status)))?;
+
+        Ok((thread_status, SuspendStatus::from_i32(suspend_status)))
+    }
+
+    /// Get how many times a thread has been suspended (ThreadReference.SuspendCount)
+    ///
+    /// The thread only runs again after that many resumes.
+    pub async fn get_suspend_count(&mut self, thread_id: ThreadId) -> JdwpResult<i32> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::THREAD_REFERENCE, thread_commands::SUSPEND_COUNT);
+
+        self.id_sizes().write_object_id(&mut packet.data, thread_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        read_i32(&mut data)
+    }
+
+    /// Get the monitors a thread owns (ThreadReference.OwnedMonitors)
+    ///
+    /// The thread must be suspended and the VM must support
+    /// `can_get_owned_monitor_info`.
+    pub async fn get_owned_monitors(&mut self, thread_id: ThreadId) -> JdwpResult<Vec<ObjectId>> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::THREAD_REFERENCE, thread_commands::OWNED_MONITORS);
+
+        let sizes = self.id_sizes();
+        sizes.write_object_id(&mut packet.data, thread_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        let count = read_count(&mut data, 1 + sizes.object_id_size as usize, "monitors")?;
+        let mut monitors = Vec::with_capacity(count.min(MAX_PREALLOCATED));
+
+        for _ in 0..count {
+            let _tag = read_u8(&mut data)?;
+            monitors.push(sizes.read_object_id(&mut data)?);
+        }
+
+        Ok(monitors)
+    }
+
+    /// Get the monitor a thread is waiting to enter, or waiting on with
+    /// Object.wait (ThreadReference.CurrentContendedMonitor)
+    ///
+    /// The thread must be suspended and the VM must support
+    /// `can_get_current_contended_monitor`.
+    pub async fn get_current_contended_monitor(&mut self, thread_id: ThreadId) -> JdwpResult<Option<ObjectId>> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::THREAD_REFERENCE, thread_commands::CURRENT_CONTENDED_MONITOR);
+
+        let sizes = self.id_sizes();
+        sizes.write_object_id(&mut packet.data, thread_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        let _tag = read_u8(&mut data)?;
+        let monitor = sizes.read_object_id(&mut data)?;
+
+        Ok((monitor != 0).then_some(monitor))
+    }
+
+    /// Make the thread's current method return `value` without running the
+    /// rest of it (ThreadReference.ForceEarlyReturn)
+    ///
+    /// The value must match the method's return type (a void value for void
+    /// methods). The thread must be suspended, the top frame must not be
+    /// native, and the VM must support `can_force_early_return`. The return
+    /// happens when the thread is next resumed or stepped.
+    pub async fn force_early_return(&mut self, thread_id: ThreadId, value: Value) -> JdwpResult<()> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::THREAD_REFERENCE, thread_commands::FORCE_EARLY_RETURN);
+
+        let sizes = self.id_sizes();
+        sizes.write_object_id(&mut packet.data, thread_id);
+        value.write_tagged(&mut packet.data, &sizes);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        Ok(())
+    }
+
     /// Get all threads (VirtualMachine.AllThreads)
     pub async fn get_all_threads(&mut self) -> JdwpResult<Vec<ThreadId>> {
         let id = self.next_id();
@@ -76,17 +217,87 @@ impl JdwpConnection {
         reply.check_error()?;
 
         let mut data = reply.data();
+        let sizes = self.id_sizes();
 
-        let threads_count = read_i32(&mut data)?;
-        let mut threads = Vec::with_capacity(threads_count as usize);
+        let threads_count = read_count(&mut data, sizes.object_id_size as usize, "threads")?;
+        let mut threads = Vec::with_capacity(threads_count.min(MAX_PREALLOCATED));
 
         for _ in 0..threads_count {
-            threads.push(read_u64(&mut data)?);
+            threads.push(sizes.read_object_id(&mut data)?);
         }
 
         Ok(threads)
     }
 
+    /// Get every live thread with its name, status and suspend count
+    ///
+    /// After AllThreads, the per-thread queries for all threads are in
+    /// flight at once instead of taking a round trip each in turn. Threads
+    /// that exit meanwhile are left out; the rest keep AllThreads' order.
+    pub async fn list_threads_detailed(&mut self) -> JdwpResult<Vec<ThreadDetail>> {
+        let threads = self.get_all_threads().await?;
+
+        let mut queries = JoinSet::new();
+        for (index, thread_id) in threads.into_iter().enumerate() {
+            let mut connection = self.clone();
+            queries.spawn(async move { (index, connection.get_thread_detail(thread_id).await) });
+        }
+
+        let mut details = Vec::with_capacity(queries.len());
+        while let Some(joined) = queries.join_next().await {
+            let (index, result) = joined
+                .map_err(|e| JdwpError::Protocol(format!("Thread query task failed: {}", e)))?;
+            match result {
+                Ok(detail) => details.push((index, detail)),
+                Err(e) if e.error_code() == Some(error_codes::INVALID_THREAD) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        details.sort_by_key(|(index, _)| *index);
+        Ok(details.into_iter().map(|(_, detail)| detail).collect())
+    }
+
+    /// Get one thread's name, status and suspend count
+    pub async fn get_thread_detail(&mut self, thread_id: ThreadId) -> JdwpResult<ThreadDetail> {
+        let name = self.get_thread_name(thread_id).await?;
+        let (status, _) = self.get_thread_status(thread_id).await?;
+        let suspend_count = self.get_suspend_count(thread_id).await?;
+
+        Ok(ThreadDetail { id: thread_id, name, status, suspend_count })
+    }
+
+    /// Suspend one thread (ThreadReference.Suspend)
+    ///
+    /// Suspensions are counted: a thread suspended twice needs two resumes.
+    pub async fn suspend_thread(&mut self, thread_id: ThreadId) -> JdwpResult<()> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::THREAD_REFERENCE, thread_commands::SUSPEND);
+
+        self.id_sizes().write_object_id(&mut packet.data, thread_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        Ok(())
+    }
+
+    /// Resume one thread (ThreadReference.Resume)
+    ///
+    /// Decrements the thread's suspend count; it only runs again once the
+    /// count reaches zero.
+    pub async fn resume_thread(&mut self, thread_id: ThreadId) -> JdwpResult<()> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::THREAD_REFERENCE, thread_commands::RESUME);
+
+        self.id_sizes().write_object_id(&mut packet.data, thread_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        Ok(())
+    }
+
     /// Suspend all threads (VirtualMachine.Suspend)
     pub async fn suspend_all(&mut self) -> JdwpResult<()> {
         let id = self.next_id();
@@ -109,3 +320,36 @@ impl JdwpConnection {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_vm::{self, read_command, send_reply};
+
+    #[tokio::test]
+    async fn test_frames_reply_with_bogus_count_is_rejected() {
+        let (mut connection, mut vm) = fake_vm::connect(8).await;
+
+        let jvm = tokio::spawn(async move {
+            // Claims two billion frames but holds one
+            let (header, _) = read_command(&mut vm, (command_sets::THREAD_REFERENCE, thread_commands::FRAMES)).await;
+            let mut data = Vec::new();
+            data.put_i32(i32::MAX);
+            data.put_u64(0x100);
+            data.put_u8(1);
+            data.put_u64(0x10);
+            data.put_u64(0x20);
+            data.put_u64(5);
+            send_reply(&mut vm, &header, &data).await;
+            vm
+        });
+
+        let err = connection.get_frames(0x1, 0, -1).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Protocol error: Truncated reply: 2147483647 frames need at least 70866960351 bytes, got 33"
+        );
+
+        jvm.await.unwrap();
+    }
+}
@@ -0,0 +1,136 @@
+// ThreadGroupReference command implementations
+//
+// Commands for walking the thread group hierarchy
+
+use crate::commands::{command_sets, thread_group_commands, vm_commands};
+use crate::connection::JdwpConnection;
+use crate::protocol::{CommandPacket, JdwpResult};
+use crate::reader::{read_count, read_string, MAX_PREALLOCATED};
+use crate::types::{ThreadGroupId, ThreadId};
+
+/// Direct members of a thread group
+#[derive(Debug, Clone, Default)]
+pub struct ThreadGroupChildren {
+    /// Live threads in the group
+    pub threads: Vec<ThreadId>,
+    /// Active subgroups
+    pub groups: Vec<ThreadGroupId>,
+}
+
+impl JdwpConnection {
+    /// Get the thread groups with no parent, usually just "system"
+    /// (VirtualMachine.TopLevelThreadGroups)
+    pub async fn get_top_level_thread_groups(&mut self) -> JdwpResult<Vec<ThreadGroupId>> {
+        let id = self.next_id();
+        let packet = CommandPacket::new(id, command_sets::VIRTUAL_MACHINE, vm_commands::TOP_LEVEL_THREAD_GROUPS);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        let sizes = self.id_sizes();
+
+        let count = read_count(&mut data, sizes.object_id_size as usize, "thread groups")?;
+        let mut groups = Vec::with_capacity(count.min(MAX_PREALLOCATED));
+        for _ in 0..count {
+            groups.push(sizes.read_object_id(&mut data)?);
+        }
+
+        Ok(groups)
+    }
+
+    /// Get a thread group's name (ThreadGroupReference.Name)
+    pub async fn get_thread_group_name(&mut self, group_id: ThreadGroupId) -> JdwpResult<String> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::THREAD_GROUP_REFERENCE, thread_group_commands::NAME);
+
+        self.id_sizes().write_object_id(&mut packet.data, group_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        read_string(&mut data)
+    }
+
+    /// Get a thread group's parent, or None for a top-level group
+    /// (ThreadGroupReference.Parent)
+    pub async fn get_thread_group_parent(&mut self, group_id: ThreadGroupId) -> JdwpResult<Option<ThreadGroupId>> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::THREAD_GROUP_REFERENCE, thread_group_commands::PARENT);
+
+        let sizes = self.id_sizes();
+        sizes.write_object_id(&mut packet.data, group_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        let parent = sizes.read_object_id(&mut data)?;
+        Ok((parent != 0).then_some(parent))
+    }
+
+    /// Get the live threads and active subgroups directly in a thread group
+    /// (ThreadGroupReference.Children)
+    pub async fn get_thread_group_children(&mut self, group_id: ThreadGroupId) -> JdwpResult<ThreadGroupChildren> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::THREAD_GROUP_REFERENCE, thread_group_commands::CHILDREN);
+
+        let sizes = self.id_sizes();
+        sizes.write_object_id(&mut packet.data, group_id);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+
+        let thread_count = read_count(&mut data, sizes.object_id_size as usize, "threads")?;
+        let mut threads = Vec::with_capacity(thread_count.min(MAX_PREALLOCATED));
+        for _ in 0..thread_count {
+            threads.push(sizes.read_object_id(&mut data)?);
+        }
+
+        let group_count = read_count(&mut data, sizes.object_id_size as usize, "thread groups")?;
+        let mut groups = Vec::with_capacity(group_count.min(MAX_PREALLOCATED));
+        for _ in 0..group_count {
+            groups.push(sizes.read_object_id(&mut data)?);
+        }
+
+        Ok(ThreadGroupChildren { threads, groups })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::error_codes;
+    use crate::fake_vm;
+    use bytes::BufMut;
+
+    #[tokio::test]
+    async fn test_get_thread_group_children_rejects_negative_counts() {
+        let (mut connection, vm) = fake_vm::connect(8).await;
+
+        // Group 0x10 has threads 0x20 and 0x21 and no subgroups; group 0x11
+        // claims -1 threads
+        tokio::spawn(fake_vm::serve(vm, |_, _, data| {
+            let mut reply = Vec::new();
+            match fake_vm::first_id(data) {
+                0x10 => {
+                    reply.put_i32(2);
+                    reply.put_u64(0x20);
+                    reply.put_u64(0x21);
+                    reply.put_i32(0);
+                }
+                0x11 => reply.put_i32(-1),
+                _ => return Err(error_codes::NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        }));
+
+        let children = connection.get_thread_group_children(0x10).await.unwrap();
+        assert_eq!(children.threads, [0x20, 0x21]);
+        assert!(children.groups.is_empty());
+
+        assert!(connection.get_thread_group_children(0x11).await.is_err());
+    }
+}
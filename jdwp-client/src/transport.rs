@@ -0,0 +1,40 @@
+// JDWP transports
+//
+// The wire protocol is the same whatever carries it: the handshake and
+// packets only need a byte stream that can be split into a reading half for
+// the event loop and a writing half for outgoing commands. dt_socket (TCP) is
+// the only transport the JDK ships everywhere; dt_shmem is Windows-only and
+// not implemented here yet.
+
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream, ReadHalf, WriteHalf};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+
+/// A byte stream to a VM that the event loop can take over
+pub trait Transport: AsyncRead + AsyncWrite + Unpin + Send + 'static {
+    type Reader: AsyncRead + Unpin + Send + 'static;
+    type Writer: AsyncWrite + Unpin + Send + 'static;
+
+    /// Split into independently owned read and write halves
+    fn into_split(self) -> (Self::Reader, Self::Writer);
+}
+
+/// dt_socket
+impl Transport for TcpStream {
+    type Reader = OwnedReadHalf;
+    type Writer = OwnedWriteHalf;
+
+    fn into_split(self) -> (Self::Reader, Self::Writer) {
+        TcpStream::into_split(self)
+    }
+}
+
+/// In-process pipe, e.g. to a fake VM in tests
+impl Transport for DuplexStream {
+    type Reader = ReadHalf<DuplexStream>;
+    type Writer = WriteHalf<DuplexStream>;
+
+    fn into_split(self) -> (Self::Reader, Self::Writer) {
+        tokio::io::split(self)
+    }
+}
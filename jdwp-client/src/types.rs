@@ -2,9 +2,10 @@
 //
 // Common types used across the JDWP protocol
 
+use crate::vm::VmIdSizes;
 use serde::{Deserialize, Serialize};
 
-// Object IDs are 8 bytes in JDWP
+// IDs are held as u64; how many bytes go on the wire is up to the VM (see VmIdSizes)
 pub type ObjectId = u64;
 pub type ThreadId = ObjectId;
 pub type ThreadGroupId = ObjectId;
@@ -22,6 +23,12 @@ pub type MethodId = u64;
 pub type FieldId = u64;
 pub type FrameId = u64;
 
+// Access flags for types, fields and methods (mod_bits)
+pub const ACC_STATIC: i32 = 0x0008;
+pub const ACC_NATIVE: i32 = 0x0100;
+pub const ACC_INTERFACE: i32 = 0x0200;
+pub const ACC_ABSTRACT: i32 = 0x0400;
+
 // Location identifies a code position
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Location {
@@ -50,6 +57,88 @@ pub enum SuspendStatus {
     Suspended = 1,
 }
 
+impl ThreadStatus {
+    /// Decode a ThreadReference.Status thread status; None if out of range
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(Self::Zombie),
+            1 => Some(Self::Running),
+            2 => Some(Self::Sleeping),
+            3 => Some(Self::Monitor),
+            4 => Some(Self::Wait),
+            _ => None,
+        }
+    }
+
+    /// Lowercase description, e.g. "waiting for monitor"
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Zombie => "zombie",
+            Self::Running => "running",
+            Self::Sleeping => "sleeping",
+            Self::Monitor => "waiting for monitor",
+            Self::Wait => "waiting",
+        }
+    }
+}
+
+impl SuspendStatus {
+    /// Decode a ThreadReference.Status suspend status (a bit flag)
+    pub fn from_i32(value: i32) -> Self {
+        if value & Self::Suspended as i32 != 0 {
+            Self::Suspended
+        } else {
+            Self::Running
+        }
+    }
+}
+
+/// Class status bit flags, from ClassesBySignature, AllClasses or ReferenceType.Status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClassStatus(pub i32);
+
+impl ClassStatus {
+    pub const VERIFIED: i32 = 1;
+    pub const PREPARED: i32 = 2;
+    pub const INITIALIZED: i32 = 4;
+    pub const ERROR: i32 = 8;
+
+    pub fn contains(self, flag: i32) -> bool {
+        self.0 & flag != 0
+    }
+
+    /// Whether static initializers have run, so static fields hold real values
+    pub fn is_initialized(self) -> bool {
+        self.contains(Self::INITIALIZED)
+    }
+
+    /// Names of the set flags, in spec order
+    pub fn names(self) -> Vec<&'static str> {
+        [
+            (Self::VERIFIED, "VERIFIED"),
+            (Self::PREPARED, "PREPARED"),
+            (Self::INITIALIZED, "INITIALIZED"),
+            (Self::ERROR, "ERROR"),
+        ]
+        .into_iter()
+        .filter(|&(flag, _)| self.contains(flag))
+        .map(|(_, name)| name)
+        .collect()
+    }
+}
+
+impl std::fmt::Display for ClassStatus {
+    /// e.g. "VERIFIED | PREPARED | INITIALIZED", or "NONE" for no flags
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = self.names();
+        if names.is_empty() {
+            write!(f, "NONE")
+        } else {
+            write!(f, "{}", names.join(" | "))
+        }
+    }
+}
+
 // Type tags for values
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -72,6 +161,26 @@ pub enum TypeTag {
     ClassObject = 99,  // 'c'
 }
 
+/// Value tag for a variable or field of the given JNI signature
+///
+/// e.g. "I" -> 'I' (int), "Ljava/lang/String;" -> 'L' (object), "[I" -> '[' (array).
+/// Unknown signatures are treated as objects.
+pub fn tag_for_signature(signature: &str) -> u8 {
+    match signature.as_bytes().first() {
+        Some(b'B') => TypeTag::Byte as u8,
+        Some(b'C') => TypeTag::Char as u8,
+        Some(b'D') => TypeTag::Double as u8,
+        Some(b'F') => TypeTag::Float as u8,
+        Some(b'I') => TypeTag::Int as u8,
+        Some(b'J') => TypeTag::Long as u8,
+        Some(b'S') => TypeTag::Short as u8,
+        Some(b'Z') => TypeTag::Boolean as u8,
+        Some(b'V') => TypeTag::Void as u8,
+        Some(b'[') => TypeTag::Array as u8,
+        _ => TypeTag::Object as u8,
+    }
+}
+
 // Tagged value
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Value {
@@ -79,8 +188,10 @@ pub struct Value {
     pub data: ValueData,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(untagged)]
+// Serialized with an explicit type tag, e.g. {"type":"Short","value":7},
+// so variants with overlapping JSON shapes (Char/Short/Int) round-trip exactly
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value")]
 pub enum ValueData {
     Byte(i8),
     Char(u16),
@@ -95,11 +206,16 @@ pub enum ValueData {
 }
 
 impl Value {
+    /// Append this value in JDWP "value" form: the tag, then the data
+    pub fn write_tagged(&self, buf: &mut Vec<u8>, sizes: &VmIdSizes) {
+        crate::object::write_tagged_value(buf, self, sizes);
+    }
+
     /// Format value for display
     pub fn format(&self) -> String {
         match &self.data {
             ValueData::Byte(v) => format!("(byte) {}", v),
-            ValueData::Char(v) => format!("(char) '{}'", char::from_u32(*v as u32).unwrap_or('?')),
+            ValueData::Char(v) => format_char(*v),
             ValueData::Float(v) => format!("(float) {}", v),
             ValueData::Double(v) => format!("(double) {}", v),
             ValueData::Int(v) => format!("(int) {}", v),
@@ -107,10 +223,11 @@ impl Value {
             ValueData::Short(v) => format!("(short) {}", v),
             ValueData::Boolean(v) => format!("(boolean) {}", v),
             ValueData::Object(id) => {
+                let kind = reference_kind(self.tag);
                 if *id == 0 {
-                    "(object) null".to_string()
+                    format!("({}) null", kind)
                 } else {
-                    format!("(object) @{:x}", id)
+                    format!("({}) @{:x}", kind, id)
                 }
             }
             ValueData::Void => "(void)".to_string(),
@@ -118,12 +235,49 @@ impl Value {
     }
 }
 
+/// What a reference value's tag says it points at, e.g. 's' -> "string"
+fn reference_kind(tag: u8) -> &'static str {
+    match tag {
+        b's' => "string",
+        b't' => "thread",
+        b'g' => "thread group",
+        b'l' => "class loader",
+        b'c' => "class",
+        b'[' => "array",
+        _ => "object",
+    }
+}
+
+/// Render a Java char as a Java-style literal, escaping anything that
+/// wouldn't print legibly
+fn format_char(unit: u16) -> String {
+    let Some(c) = char::from_u32(unit as u32) else {
+        // A lone surrogate half: not a char on its own, so show its value too
+        return format!("(char) '\\u{:04x}' ({})", unit, unit);
+    };
+    match c {
+        '\0' => "(char) '\\0'".to_string(),
+        '\n' => "(char) '\\n'".to_string(),
+        '\r' => "(char) '\\r'".to_string(),
+        '\t' => "(char) '\\t'".to_string(),
+        '\'' => "(char) '\\''".to_string(),
+        '\\' => "(char) '\\\\'".to_string(),
+        c if c.is_control() || (c.is_whitespace() && c != ' ') => {
+            format!("(char) '\\u{:04x}'", unit)
+        }
+        c => format!("(char) '{}'", c),
+    }
+}
+
 // Variable information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Variable {
     pub code_index: u64,
     pub name: String,
     pub signature: String,
+    /// Generic signature, e.g. "Ljava/util/List<Ljava/lang/String;>;", when
+    /// the variable's type is generic and the VM reported it
+    pub generic_signature: Option<String>,
     pub length: u32,
     pub slot: u32,
 }
@@ -134,3 +288,135 @@ pub struct FrameInfo {
     pub frame_id: FrameId,
     pub location: Location,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(value: &Value) -> Value {
+        let json = serde_json::to_string(value).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_value_data_serde_round_trip() {
+        let values = [
+            Value { tag: b'B', data: ValueData::Byte(-7) },
+            Value { tag: b'C', data: ValueData::Char(0x41) },
+            Value { tag: b'F', data: ValueData::Float(1.5) },
+            Value { tag: b'D', data: ValueData::Double(-2.25) },
+            Value { tag: b'I', data: ValueData::Int(42) },
+            Value { tag: b'J', data: ValueData::Long(1 << 40) },
+            Value { tag: b'S', data: ValueData::Short(42) },
+            Value { tag: b'Z', data: ValueData::Boolean(true) },
+            Value { tag: b'L', data: ValueData::Object(0xdead_beef) },
+            Value { tag: b'V', data: ValueData::Void },
+        ];
+
+        for value in &values {
+            let decoded = round_trip(value);
+            assert_eq!(decoded.tag, value.tag);
+            assert_eq!(decoded.data, value.data);
+        }
+    }
+
+    #[test]
+    fn test_tag_for_signature() {
+        assert_eq!(tag_for_signature("B"), b'B');
+        assert_eq!(tag_for_signature("C"), b'C');
+        assert_eq!(tag_for_signature("D"), b'D');
+        assert_eq!(tag_for_signature("F"), b'F');
+        assert_eq!(tag_for_signature("I"), 73);
+        assert_eq!(tag_for_signature("J"), b'J');
+        assert_eq!(tag_for_signature("S"), b'S');
+        assert_eq!(tag_for_signature("Z"), b'Z');
+        assert_eq!(tag_for_signature("V"), b'V');
+        assert_eq!(tag_for_signature("Ljava/lang/String;"), b'L');
+        assert_eq!(tag_for_signature("Ljava/util/List<TT;>;"), b'L');
+        assert_eq!(tag_for_signature("TT;"), b'L');
+        assert_eq!(tag_for_signature("[I"), b'[');
+        assert_eq!(tag_for_signature("[Ljava/lang/Object;"), b'[');
+    }
+
+    #[test]
+    fn test_class_status_names() {
+        let status = ClassStatus(ClassStatus::VERIFIED | ClassStatus::PREPARED | ClassStatus::INITIALIZED);
+        assert!(status.is_initialized());
+        assert_eq!(status.to_string(), "VERIFIED | PREPARED | INITIALIZED");
+
+        let status = ClassStatus(ClassStatus::VERIFIED | ClassStatus::ERROR);
+        assert!(!status.is_initialized());
+        assert_eq!(status.names(), ["VERIFIED", "ERROR"]);
+
+        assert_eq!(ClassStatus(0).to_string(), "NONE");
+    }
+
+    #[test]
+    fn test_thread_status_from_i32() {
+        assert_eq!(ThreadStatus::from_i32(3), Some(ThreadStatus::Monitor));
+        assert_eq!(ThreadStatus::from_i32(-1), None);
+        assert_eq!(ThreadStatus::from_i32(5), None);
+        assert_eq!(SuspendStatus::from_i32(1), SuspendStatus::Suspended);
+        assert_eq!(SuspendStatus::from_i32(0), SuspendStatus::Running);
+    }
+
+    #[test]
+    fn test_write_tagged() {
+        let mut buf = Vec::new();
+        let sizes = VmIdSizes { object_id_size: 4, ..VmIdSizes::default() };
+        Value { tag: b'I', data: ValueData::Int(-2) }.write_tagged(&mut buf, &sizes);
+        Value { tag: b'Z', data: ValueData::Boolean(true) }.write_tagged(&mut buf, &sizes);
+        Value { tag: b'L', data: ValueData::Object(0x1234) }.write_tagged(&mut buf, &sizes);
+
+        assert_eq!(
+            buf,
+            [
+                &[b'I', 0xff, 0xff, 0xff, 0xfe][..],
+                &[b'Z', 1],
+                &[b'L', 0, 0, 0x12, 0x34],
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn test_format_char_escapes_unprintable() {
+        let format = |unit: u16| Value { tag: b'C', data: ValueData::Char(unit) }.format();
+
+        assert_eq!(format(0x41), "(char) 'A'");
+        assert_eq!(format(0xe9), "(char) 'é'");
+        assert_eq!(format(0), r"(char) '\0'");
+        assert_eq!(format(0x0a), r"(char) '\n'");
+        assert_eq!(format(0x09), r"(char) '\t'");
+        assert_eq!(format(0x27), r"(char) '\''");
+        assert_eq!(format(0x07), r"(char) '\u0007'");
+        assert_eq!(format(0x2028), r"(char) '\u2028'");
+        assert_eq!(format(0xd83d), r"(char) '\ud83d' (55357)");
+    }
+
+    #[test]
+    fn test_format_names_reference_kinds() {
+        let format = |tag: u8, id| Value { tag, data: ValueData::Object(id) }.format();
+
+        assert_eq!(format(b'L', 0x1f), "(object) @1f");
+        assert_eq!(format(b's', 0x1f), "(string) @1f");
+        assert_eq!(format(b't', 0x1f), "(thread) @1f");
+        assert_eq!(format(b'g', 0x1f), "(thread group) @1f");
+        assert_eq!(format(b'l', 0x1f), "(class loader) @1f");
+        assert_eq!(format(b'c', 0x1f), "(class) @1f");
+        assert_eq!(format(b'[', 0x1f), "(array) @1f");
+        assert_eq!(format(b'[', 0), "(array) null");
+    }
+
+    #[test]
+    fn test_value_data_serde_is_explicitly_typed() {
+        // Same numeric payload must stay distinguishable by variant
+        let short = serde_json::to_value(ValueData::Short(65)).unwrap();
+        let char_value = serde_json::to_value(ValueData::Char(65)).unwrap();
+        let int = serde_json::to_value(ValueData::Int(65)).unwrap();
+
+        assert_eq!(short, serde_json::json!({"type": "Short", "value": 65}));
+        assert_eq!(char_value, serde_json::json!({"type": "Char", "value": 65}));
+        assert_eq!(int, serde_json::json!({"type": "Int", "value": 65}));
+    }
+}
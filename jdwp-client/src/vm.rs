@@ -4,9 +4,9 @@
 
 use crate::commands::{command_sets, vm_commands};
 use crate::connection::JdwpConnection;
-use crate::protocol::{CommandPacket, JdwpResult};
-use crate::reader::{read_i32, read_string, read_u32, read_u8};
-use crate::types::ReferenceTypeId;
+use crate::protocol::{CommandPacket, JdwpError, JdwpResult};
+use crate::reader::{read_count, read_i32, read_id, read_string, read_u64, read_u8, MAX_PREALLOCATED};
+use crate::types::{ClassStatus, FieldId, FrameId, Location, MethodId, ObjectId, ReferenceTypeId};
 use bytes::BufMut;
 use serde::{Deserialize, Serialize};
 
@@ -21,7 +21,10 @@ pub struct VmVersion {
 }
 
 /// ID sizes used by the JVM
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Every command encodes its IDs through these, since a VM may use fewer
+/// than 8 bytes for some kinds of ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct VmIdSizes {
     pub field_id_size: i32,
     pub method_id_size: i32,
@@ -30,6 +33,142 @@ pub struct VmIdSizes {
     pub frame_id_size: i32,
 }
 
+impl Default for VmIdSizes {
+    /// 8 bytes for everything, as HotSpot reports; used until IDSizes is known
+    fn default() -> Self {
+        Self {
+            field_id_size: 8,
+            method_id_size: 8,
+            object_id_size: 8,
+            reference_type_id_size: 8,
+            frame_id_size: 8,
+        }
+    }
+}
+
+impl VmIdSizes {
+    pub fn read_object_id(&self, buf: &mut &[u8]) -> JdwpResult<ObjectId> {
+        read_id(buf, self.object_id_size)
+    }
+
+    pub fn read_reference_type_id(&self, buf: &mut &[u8]) -> JdwpResult<ReferenceTypeId> {
+        read_id(buf, self.reference_type_id_size)
+    }
+
+    pub fn read_method_id(&self, buf: &mut &[u8]) -> JdwpResult<MethodId> {
+        read_id(buf, self.method_id_size)
+    }
+
+    pub fn read_field_id(&self, buf: &mut &[u8]) -> JdwpResult<FieldId> {
+        read_id(buf, self.field_id_size)
+    }
+
+    pub fn read_frame_id(&self, buf: &mut &[u8]) -> JdwpResult<FrameId> {
+        read_id(buf, self.frame_id_size)
+    }
+
+    /// Read a location: type tag, class ID, method ID and 8-byte code index
+    pub fn read_location(&self, buf: &mut &[u8]) -> JdwpResult<Location> {
+        Ok(Location {
+            type_tag: read_u8(buf)?,
+            class_id: self.read_reference_type_id(buf)?,
+            method_id: self.read_method_id(buf)?,
+            index: read_u64(buf)?,
+        })
+    }
+
+    pub fn write_object_id(&self, buf: &mut Vec<u8>, id: ObjectId) {
+        buf.put_uint(id, self.object_id_size as usize);
+    }
+
+    pub fn write_reference_type_id(&self, buf: &mut Vec<u8>, id: ReferenceTypeId) {
+        buf.put_uint(id, self.reference_type_id_size as usize);
+    }
+
+    pub fn write_method_id(&self, buf: &mut Vec<u8>, id: MethodId) {
+        buf.put_uint(id, self.method_id_size as usize);
+    }
+
+    pub fn write_field_id(&self, buf: &mut Vec<u8>, id: FieldId) {
+        buf.put_uint(id, self.field_id_size as usize);
+    }
+
+    pub fn write_frame_id(&self, buf: &mut Vec<u8>, id: FrameId) {
+        buf.put_uint(id, self.frame_id_size as usize);
+    }
+
+    /// Reject sizes the ID helpers can't encode
+    fn validate(&self) -> JdwpResult<()> {
+        let sizes = [
+            self.field_id_size,
+            self.method_id_size,
+            self.object_id_size,
+            self.reference_type_id_size,
+            self.frame_id_size,
+        ];
+        if sizes.iter().all(|size| (1..=8).contains(size)) {
+            Ok(())
+        } else {
+            Err(JdwpError::Protocol(format!("Unsupported ID sizes: {:?}", self)))
+        }
+    }
+}
+
+/// Optional features supported by the JVM (from CapabilitiesNew)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VmCapabilities {
+    pub can_watch_field_modification: bool,
+    pub can_watch_field_access: bool,
+    pub can_get_bytecodes: bool,
+    pub can_get_synthetic_attribute: bool,
+    pub can_get_owned_monitor_info: bool,
+    pub can_get_current_contended_monitor: bool,
+    pub can_get_monitor_info: bool,
+    pub can_redefine_classes: bool,
+    pub can_add_method: bool,
+    pub can_unrestrictedly_redefine_classes: bool,
+    pub can_pop_frames: bool,
+    pub can_use_instance_filters: bool,
+    pub can_get_source_debug_extension: bool,
+    pub can_request_vm_death_event: bool,
+    pub can_set_default_stratum: bool,
+    pub can_get_instance_info: bool,
+    pub can_request_monitor_events: bool,
+    pub can_get_monitor_frame_info: bool,
+    pub can_use_source_name_filters: bool,
+    pub can_get_constant_pool: bool,
+    pub can_force_early_return: bool,
+}
+
+impl VmCapabilities {
+    /// Capability names (as in the JDWP spec) paired with whether they're supported
+    pub fn entries(&self) -> Vec<(&'static str, bool)> {
+        vec![
+            ("canWatchFieldModification", self.can_watch_field_modification),
+            ("canWatchFieldAccess", self.can_watch_field_access),
+            ("canGetBytecodes", self.can_get_bytecodes),
+            ("canGetSyntheticAttribute", self.can_get_synthetic_attribute),
+            ("canGetOwnedMonitorInfo", self.can_get_owned_monitor_info),
+            ("canGetCurrentContendedMonitor", self.can_get_current_contended_monitor),
+            ("canGetMonitorInfo", self.can_get_monitor_info),
+            ("canRedefineClasses", self.can_redefine_classes),
+            ("canAddMethod", self.can_add_method),
+            ("canUnrestrictedlyRedefineClasses", self.can_unrestrictedly_redefine_classes),
+            ("canPopFrames", self.can_pop_frames),
+            ("canUseInstanceFilters", self.can_use_instance_filters),
+            ("canGetSourceDebugExtension", self.can_get_source_debug_extension),
+            ("canRequestVMDeathEvent", self.can_request_vm_death_event),
+            ("canSetDefaultStratum", self.can_set_default_stratum),
+            ("canGetInstanceInfo", self.can_get_instance_info),
+            ("canRequestMonitorEvents", self.can_request_monitor_events),
+            ("canGetMonitorFrameInfo", self.can_get_monitor_frame_info),
+            ("canUseSourceNameFilters", self.can_use_source_name_filters),
+            ("canGetConstantPool", self.can_get_constant_pool),
+            ("canForceEarlyReturn", self.can_force_early_return),
+        ]
+    }
+}
+
 /// Class information from ClassesBySignature
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClassInfo {
@@ -39,6 +178,32 @@ pub struct ClassInfo {
     pub status: i32,
 }
 
+impl ClassInfo {
+    /// Decoded `status` flags
+    pub fn class_status(&self) -> ClassStatus {
+        ClassStatus(self.status)
+    }
+
+    /// "class", "interface" or "array"
+    pub fn kind(&self) -> &'static str {
+        match self.ref_type_tag {
+            2 => "interface",
+            3 => "array",
+            _ => "class",
+        }
+    }
+}
+
+/// Where the VM loads classes from, from ClassPaths
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClassPaths {
+    /// Directory relative classpath entries are resolved against
+    pub base_dir: String,
+    pub classpaths: Vec<String>,
+    /// Always empty on Java 9+, which has no boot classpath
+    pub bootclasspaths: Vec<String>,
+}
+
 impl JdwpConnection {
     /// Get JVM version information (VirtualMachine.Version command)
     pub async fn get_version(&mut self) -> JdwpResult<VmVersion> {
@@ -56,17 +221,24 @@ impl JdwpConnection {
         let vm_version = read_string(&mut data)?;
         let vm_name = read_string(&mut data)?;
 
-        Ok(VmVersion {
+        let version = VmVersion {
             description,
             jdwp_major,
             jdwp_minor,
             vm_version,
             vm_name,
-        })
+        };
+
+        // Remember it for feature gating; the version can't change
+        let _ = self.version.set(version.clone());
+
+        Ok(version)
     }
 
     /// Get ID sizes (VirtualMachine.IDSizes command)
     /// This tells us how many bytes are used for various ID types
+    ///
+    /// The sizes are remembered and used to encode every later command.
     pub async fn get_id_sizes(&mut self) -> JdwpResult<VmIdSizes> {
         let id = self.next_id();
         let packet = CommandPacket::new(id, command_sets::VIRTUAL_MACHINE, vm_commands::ID_SIZES);
@@ -82,38 +254,159 @@ impl JdwpConnection {
         let reference_type_id_size = read_i32(&mut data)?;
         let frame_id_size = read_i32(&mut data)?;
 
-        Ok(VmIdSizes {
+        let sizes = VmIdSizes {
             field_id_size,
             method_id_size,
             object_id_size,
             reference_type_id_size,
             frame_id_size,
+        };
+        sizes.validate()?;
+
+        // Also needed by the event loop to parse events; the sizes can't change
+        self.event_loop().set_id_sizes(sizes);
+
+        Ok(sizes)
+    }
+
+    /// Get the optional features the VM supports (VirtualMachine.CapabilitiesNew command)
+    ///
+    /// Requires JDWP 1.4; VMs known to be older report no optional capabilities.
+    pub async fn get_capabilities_new(&mut self) -> JdwpResult<VmCapabilities> {
+        if self.version().is_some() && !self.supports_command_set_version(1, 4) {
+            return Ok(VmCapabilities::default());
+        }
+
+        let id = self.next_id();
+        let packet = CommandPacket::new(id, command_sets::VIRTUAL_MACHINE, vm_commands::CAPABILITIES_NEW);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        let mut flag = || -> JdwpResult<bool> { Ok(read_u8(&mut data)? != 0) };
+
+        // Fields are in reply order; the trailing reserved flags are ignored
+        Ok(VmCapabilities {
+            can_watch_field_modification: flag()?,
+            can_watch_field_access: flag()?,
+            can_get_bytecodes: flag()?,
+            can_get_synthetic_attribute: flag()?,
+            can_get_owned_monitor_info: flag()?,
+            can_get_current_contended_monitor: flag()?,
+            can_get_monitor_info: flag()?,
+            can_redefine_classes: flag()?,
+            can_add_method: flag()?,
+            can_unrestrictedly_redefine_classes: flag()?,
+            can_pop_frames: flag()?,
+            can_use_instance_filters: flag()?,
+            can_get_source_debug_extension: flag()?,
+            can_request_vm_death_event: flag()?,
+            can_set_default_stratum: flag()?,
+            can_get_instance_info: flag()?,
+            can_request_monitor_events: flag()?,
+            can_get_monitor_frame_info: flag()?,
+            can_use_source_name_filters: flag()?,
+            can_get_constant_pool: flag()?,
+            can_force_early_return: flag()?,
         })
     }
 
+    /// Release the debugger's hold on the VM (VirtualMachine.Dispose command)
+    ///
+    /// The VM clears all event requests, resumes suspended threads and
+    /// releases object references held for us. The connection should not be
+    /// used afterwards.
+    pub async fn dispose(&mut self) -> JdwpResult<()> {
+        let id = self.next_id();
+        let packet = CommandPacket::new(id, command_sets::VIRTUAL_MACHINE, vm_commands::DISPOSE);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        Ok(())
+    }
+
+    /// Terminate the target VM with the given exit code (VirtualMachine.Exit command)
+    ///
+    /// The VM may shut down before the reply arrives, in which case this
+    /// returns `JdwpError::ConnectionClosed`.
+    pub async fn exit(&mut self, exit_code: i32) -> JdwpResult<()> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::VIRTUAL_MACHINE, vm_commands::EXIT);
+        packet.data.put_i32(exit_code);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        Ok(())
+    }
+
+    /// Create a String in the target VM (VirtualMachine.CreateString command)
+    ///
+    /// The VM may collect the string unless it's stored somewhere promptly.
+    pub async fn create_string(&mut self, value: &str) -> JdwpResult<ObjectId> {
+        let id = self.next_id();
+        let mut packet = CommandPacket::new(id, command_sets::VIRTUAL_MACHINE, vm_commands::CREATE_STRING);
+        write_string(&mut packet.data, value);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        self.id_sizes().read_object_id(&mut data)
+    }
+
+    /// Stop the VM from sending events until `release_events` (VirtualMachine.HoldEvents command)
+    ///
+    /// Events generated in the meantime are queued by the VM, not lost. This
+    /// is one VM-wide switch, not a count: holds don't nest, and the first
+    /// `release_events` lets everything through. Every hold must be paired
+    /// with a release, or no further events (breakpoints, steps) arrive.
+    pub async fn hold_events(&mut self) -> JdwpResult<()> {
+        let id = self.next_id();
+        let packet = CommandPacket::new(id, command_sets::VIRTUAL_MACHINE, vm_commands::HOLD_EVENTS);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        Ok(())
+    }
+
+    /// Resume sending events held by `hold_events` (VirtualMachine.ReleaseEvents command)
+    pub async fn release_events(&mut self) -> JdwpResult<()> {
+        let id = self.next_id();
+        let packet = CommandPacket::new(id, command_sets::VIRTUAL_MACHINE, vm_commands::RELEASE_EVENTS);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        Ok(())
+    }
+
     /// Find classes by signature (VirtualMachine.ClassesBySignature command)
     /// Signature format: "Lcom/example/MyClass;" for classes
     pub async fn classes_by_signature(&mut self, signature: &str) -> JdwpResult<Vec<ClassInfo>> {
         let id = self.next_id();
         let mut packet = CommandPacket::new(id, command_sets::VIRTUAL_MACHINE, vm_commands::CLASSES_BY_SIGNATURE);
 
-        // Write signature as JDWP string (4-byte length + UTF-8 bytes)
-        let sig_bytes = signature.as_bytes();
-        packet.data.put_u32(sig_bytes.len() as u32);
-        packet.data.extend_from_slice(sig_bytes);
+        write_string(&mut packet.data, signature);
 
         let reply = self.send_command(packet).await?;
         reply.check_error()?;
 
         let mut data = reply.data();
 
-        // Read number of classes
-        let classes_count = read_i32(&mut data)?;
-        let mut classes = Vec::with_capacity(classes_count as usize);
+        let sizes = self.id_sizes();
+
+        // Read number of classes; each has a tag, ID and status
+        let class_size = 1 + sizes.reference_type_id_size as usize + 4;
+        let classes_count = read_count(&mut data, class_size, "classes")?;
+        let mut classes = Vec::with_capacity(classes_count.min(MAX_PREALLOCATED));
 
         for _ in 0..classes_count {
             let ref_type_tag = read_u8(&mut data)?;
-            let type_id = crate::reader::read_u64(&mut data)?;
+            let type_id = sizes.read_reference_type_id(&mut data)?;
             let status = read_i32(&mut data)?;
 
             classes.push(ClassInfo {
@@ -126,4 +419,108 @@ impl JdwpConnection {
 
         Ok(classes)
     }
+
+    /// Get all loaded classes (VirtualMachine.AllClasses command)
+    pub async fn get_all_classes(&mut self) -> JdwpResult<Vec<ClassInfo>> {
+        let id = self.next_id();
+        let packet = CommandPacket::new(id, command_sets::VIRTUAL_MACHINE, vm_commands::ALL_CLASSES);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        let mut data = reply.data();
+        let sizes = self.id_sizes();
+
+        // Each class has a tag, ID, signature and status
+        let class_size = 1 + sizes.reference_type_id_size as usize + 8;
+        let classes_count = read_count(&mut data, class_size, "classes")?;
+        let mut classes = Vec::with_capacity(classes_count.min(MAX_PREALLOCATED));
+
+        for _ in 0..classes_count {
+            let ref_type_tag = read_u8(&mut data)?;
+            let type_id = sizes.read_reference_type_id(&mut data)?;
+            let signature = read_string(&mut data)?;
+            let status = read_i32(&mut data)?;
+
+            classes.push(ClassInfo {
+                ref_type_tag,
+                type_id,
+                signature,
+                status,
+            });
+        }
+
+        Ok(classes)
+    }
+
+    /// Get the VM's classpath and boot classpath (VirtualMachine.ClassPaths command)
+    pub async fn get_class_paths(&mut self) -> JdwpResult<ClassPaths> {
+        let id = self.next_id();
+        let packet = CommandPacket::new(id, command_sets::VIRTUAL_MACHINE, vm_commands::CLASS_PATHS);
+
+        let reply = self.send_command(packet).await?;
+        reply.check_error()?;
+
+        read_class_paths(&mut reply.data())
+    }
+}
+
+fn read_class_paths(data: &mut &[u8]) -> JdwpResult<ClassPaths> {
+    let base_dir = read_string(data)?;
+    let classpaths = read_strings(data, "classpath entries")?;
+    let bootclasspaths = read_strings(data, "bootclasspath entries")?;
+    Ok(ClassPaths { base_dir, classpaths, bootclasspaths })
+}
+
+/// Read a count-prefixed list of strings
+fn read_strings(data: &mut &[u8], what: &str) -> JdwpResult<Vec<String>> {
+    // Each entry is at least its 4-byte length
+    let count = read_count(data, 4, what)?;
+    let mut strings = Vec::with_capacity(count.min(MAX_PREALLOCATED));
+    for _ in 0..count {
+        strings.push(read_string(data)?);
+    }
+    Ok(strings)
+}
+
+/// Write a JDWP string: a 4-byte byte length, then the UTF-8 bytes
+pub(crate) fn write_string(buf: &mut Vec<u8>, value: &str) {
+    buf.put_u32(value.len() as u32);
+    buf.put_slice(value.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_string_prefixes_byte_length() {
+        let mut buf = Vec::new();
+        write_string(&mut buf, "héllo");
+        // 'é' is two bytes, so the length is 6, not 5
+        assert_eq!(buf, b"\0\0\0\x06h\xc3\xa9llo");
+
+        let mut buf = Vec::new();
+        write_string(&mut buf, "");
+        assert_eq!(buf, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_read_class_paths() {
+        let mut buf = Vec::new();
+        write_string(&mut buf, "/srv/app");
+        buf.put_i32(2);
+        write_string(&mut buf, "/srv/app/classes");
+        write_string(&mut buf, "lib/dep.jar");
+        buf.put_i32(0);
+
+        let paths = read_class_paths(&mut buf.as_slice()).unwrap();
+        assert_eq!(paths.base_dir, "/srv/app");
+        assert_eq!(paths.classpaths, ["/srv/app/classes", "lib/dep.jar"]);
+        assert!(paths.bootclasspaths.is_empty());
+
+        // Cut off in the middle of the classpath list
+        let truncated = &buf[..buf.len() - 8];
+        assert!(read_class_paths(&mut &truncated[..]).is_err());
+    }
 }
@@ -0,0 +1,736 @@
+// Expression evaluation
+//
+// Resolves a local variable, `this`, or a chain of field reads and method
+// calls such as `this.config.timeout`, `user.getAddress().city` or
+// `map.get(key)` in a suspended frame. Calls take at most one argument;
+// operators are not supported.
+
+use jdwp_client::commands::invoke_options;
+use jdwp_client::reftype::MethodInfo;
+use jdwp_client::signature::type_name;
+use jdwp_client::stackframe::VariableSlot;
+use jdwp_client::thread::Frame;
+use jdwp_client::types::{ClassId, FrameId, ObjectId, ThreadId, Value, ValueData, ACC_STATIC};
+use jdwp_client::{JdwpConnection, JdwpError};
+
+/// Longest chain followed after the root, e.g. `a.b.c()` has depth 2.
+/// Keeps a runaway expression from issuing an unbounded number of round trips.
+pub const MAX_CHAIN_DEPTH: usize = 8;
+
+/// Outcome of evaluating an expression
+#[derive(Debug, Clone)]
+pub enum Evaluation {
+    Value(Value),
+    /// A reference along the chain was null; holds the path up to it (e.g. "user.address")
+    NullAt(String),
+}
+
+/// One link of an expression chain
+#[derive(Debug, Clone, PartialEq)]
+enum Step<'a> {
+    Field(&'a str),
+    Call(&'a str, Option<Arg<'a>>),
+}
+
+impl Step<'_> {
+    /// How the step appears in a path, e.g. "address" or "getName()"
+    fn label(&self) -> String {
+        match self {
+            Step::Field(name) => name.to_string(),
+            Step::Call(name, _) => format!("{}()", name),
+        }
+    }
+}
+
+/// A method call argument
+#[derive(Debug, Clone, PartialEq)]
+enum Arg<'a> {
+    Literal(ValueData),
+    String(&'a str),
+    /// A local variable, or a field of `this`
+    Variable(&'a str),
+}
+
+/// Evaluate `expression` in frame `frame_index` of a suspended thread
+///
+/// The first name is `this`, a local variable in scope, or otherwise a field
+/// or method of `this` (a static method in a static frame); every following
+/// step reads a field of, or calls a method on, the previous value. Methods
+/// run on the suspended thread with all other threads kept suspended.
+pub async fn evaluate(
+    connection: &mut JdwpConnection,
+    thread_id: ThreadId,
+    frame_index: i32,
+    expression: &str,
+) -> Result<Evaluation, String> {
+    let steps = parse_expression(expression)?;
+
+    let frame = connection.get_frames(thread_id, frame_index, 1).await
+        .map_err(|e| format!("Failed to get frame {}: {}", frame_index, e))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("Thread 0x{:x} has no frame {}", thread_id, frame_index))?;
+
+    let (mut value, mut path, rest) = match &steps[0] {
+        Step::Field("this") => (this_value(connection, thread_id, frame.frame_id).await?, "this".to_string(), &steps[1..]),
+        Step::Field(name) => match local_value(connection, thread_id, &frame, name).await? {
+            Some(value) => (value, name.to_string(), &steps[1..]),
+            // Not a local, so treat it as an implicit `this.name`
+            None => (this_value(connection, thread_id, frame.frame_id).await?, "this".to_string(), &steps[..]),
+        },
+        call @ Step::Call(name, arg) => {
+            let this = connection.get_frame_this(thread_id, frame.frame_id).await
+                .map_err(|e| format!("Failed to get `this`: {}", e))?;
+            match this {
+                Some(this) => (Value { tag: b'L', data: ValueData::Object(this) }, "this".to_string(), &steps[..]),
+                // A static frame can still call static methods of its own class
+                None => {
+                    let args = argument_values(connection, thread_id, &frame, arg).await?;
+                    let value = call_method(connection, thread_id, None, frame.location.class_id, name, args).await
+                        .map_err(|e| format!("{}: {}", call.label(), e))?;
+                    (value, call.label(), &steps[1..])
+                }
+            }
+        }
+    };
+
+    for step in rest {
+        let object_id = match value.data {
+            ValueData::Object(0) => return Ok(Evaluation::NullAt(path)),
+            ValueData::Object(object_id) => object_id,
+            _ => {
+                let member = match step {
+                    Step::Field(name) => format!("field '{}'", name),
+                    Step::Call(name, _) => format!("method '{}()'", name),
+                };
+                return Err(format!("{} is a primitive ({}), it has no {}", path, value.format(), member));
+            }
+        };
+
+        value = match step {
+            Step::Field(name) => field_value(connection, object_id, name).await,
+            Step::Call(name, arg) => {
+                let args = argument_values(connection, thread_id, &frame, arg).await?;
+                let class_id = connection.get_object_reference_type(object_id).await
+                    .map_err(|e| format!("Failed to get object type: {}", e))?;
+                call_method(connection, thread_id, Some(object_id), class_id, name, args).await
+            }
+        }
+        .map_err(|e| format!("{}.{}: {}", path, step.label(), e))?;
+        path = format!("{}.{}", path, step.label());
+    }
+
+    Ok(Evaluation::Value(value))
+}
+
+/// Split "a.b().c(x)" into steps, rejecting anything else
+fn parse_expression(expression: &str) -> Result<Vec<Step<'_>>, String> {
+    let unsupported = || format!(
+        "Unsupported expression '{}': only variables, `this`, field access and calls with at most one argument (a.b.getC(x)) can be evaluated",
+        expression.trim()
+    );
+
+    let mut steps = Vec::new();
+    let mut rest = expression.trim();
+
+    loop {
+        let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$')).unwrap_or(rest.len());
+        let name = &rest[..end];
+        if !is_identifier(name) {
+            return Err(unsupported());
+        }
+        rest = rest[end..].trim_start();
+
+        match rest.strip_prefix('(') {
+            Some(after) => {
+                let close = closing_paren(after).ok_or_else(unsupported)?;
+                let arg = match after[..close].trim() {
+                    "" => None,
+                    text => Some(parse_arg(text).ok_or_else(unsupported)?),
+                };
+                steps.push(Step::Call(name, arg));
+                rest = after[close + 1..].trim_start();
+            }
+            None => steps.push(Step::Field(name)),
+        }
+
+        if rest.is_empty() {
+            break;
+        }
+        rest = rest.strip_prefix('.').ok_or_else(unsupported)?.trim_start();
+    }
+
+    if steps.len() - 1 > MAX_CHAIN_DEPTH {
+        return Err(format!("Expression is too long (at most {} steps after the first name)", MAX_CHAIN_DEPTH));
+    }
+
+    Ok(steps)
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Index of the ')' ending an argument list, skipping over a string literal
+fn closing_paren(text: &str) -> Option<usize> {
+    let mut in_string = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            ')' if !in_string => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parse a literal (`42`, `42L`, `true`, `null`, `"text"`) or a variable name
+fn parse_arg(text: &str) -> Option<Arg<'_>> {
+    match text {
+        "true" => return Some(Arg::Literal(ValueData::Boolean(true))),
+        "false" => return Some(Arg::Literal(ValueData::Boolean(false))),
+        "null" => return Some(Arg::Literal(ValueData::Object(0))),
+        _ => {}
+    }
+
+    if let Some(inner) = text.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+        return (!inner.contains('"')).then_some(Arg::String(inner));
+    }
+    if let Some(digits) = text.strip_suffix(['L', 'l']) {
+        if let Ok(v) = digits.parse() {
+            return Some(Arg::Literal(ValueData::Long(v)));
+        }
+    }
+    if let Ok(v) = text.parse() {
+        return Some(Arg::Literal(ValueData::Int(v)));
+    }
+    if let Ok(v) = text.parse() {
+        return Some(Arg::Literal(ValueData::Long(v)));
+    }
+
+    is_identifier(text).then_some(Arg::Variable(text))
+}
+
+async fn this_value(connection: &mut JdwpConnection, thread_id: ThreadId, frame_id: FrameId) -> Result<Value, String> {
+    let this = connection.get_frame_this(thread_id, frame_id).await
+        .map_err(|e| format!("Failed to get `this`: {}", e))?
+        .ok_or_else(|| "No `this` in a static or native method".to_string())?;
+
+    Ok(Value { tag: b'L', data: ValueData::Object(this) })
+}
+
+/// Value of a local variable in scope at the frame's location, if there is one by that name
+async fn local_value(
+    connection: &mut JdwpConnection,
+    thread_id: ThreadId,
+    frame: &Frame,
+    name: &str,
+) -> Result<Option<Value>, String> {
+    // Without debug info there are no named locals; fields may still resolve
+    let Ok(var_table) = connection.get_variable_table(frame.location.class_id, frame.location.method_id).await else {
+        return Ok(None);
+    };
+
+    let index = frame.location.index;
+    let Some(variable) = var_table.iter()
+        .find(|v| v.name == name && index >= v.code_index && index < v.code_index + v.length as u64)
+    else {
+        return Ok(None);
+    };
+
+    let sig_byte = variable.signature.bytes().next()
+        .ok_or_else(|| format!("No type signature for '{}'", name))?;
+    let slot = VariableSlot { slot: variable.slot as i32, sig_byte };
+    let value = connection.get_frame_values(thread_id, frame.frame_id, vec![slot]).await
+        .map_err(|e| format!("Failed to read '{}': {}", name, e))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("No value returned for '{}'", name))?;
+
+    Ok(Some(value))
+}
+
+/// Read an instance field (declared or inherited) of an object
+async fn field_value(connection: &mut JdwpConnection, object_id: u64, name: &str) -> Result<Value, String> {
+    let ref_type = connection.get_object_reference_type(object_id).await
+        .map_err(|e| format!("Failed to get object type: {}", e))?;
+
+    let fields = connection.get_all_fields(ref_type).await
+        .map_err(|e| format!("Failed to get fields: {}", e))?;
+
+    let field = fields.iter()
+        .find(|f| f.field.name == name && f.field.mod_bits & ACC_STATIC == 0)
+        .ok_or_else(|| format!("no instance field '{}'", name))?;
+
+    connection.get_object_values(object_id, vec![field.field.field_id]).await
+        .map_err(|e| format!("Failed to read field: {}", e))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| "No value returned".to_string())
+}
+
+/// Resolve a call's argument, if it has one
+async fn argument_values(
+    connection: &mut JdwpConnection,
+    thread_id: ThreadId,
+    frame: &Frame,
+    arg: &Option<Arg<'_>>,
+) -> Result<Vec<Value>, String> {
+    let value = match arg {
+        None => return Ok(Vec::new()),
+        Some(Arg::Literal(data)) => {
+            let tag = match data {
+                ValueData::Boolean(_) => b'Z',
+                ValueData::Int(_) => b'I',
+                ValueData::Long(_) => b'J',
+                _ => b'L',
+            };
+            Value { tag, data: data.clone() }
+        }
+        Some(Arg::String(text)) => {
+            let string_id = connection.create_string(text).await
+                .map_err(|e| format!("Failed to create string argument: {}", e))?;
+            Value { tag: b's', data: ValueData::Object(string_id) }
+        }
+        Some(Arg::Variable(name)) => match local_value(connection, thread_id, frame, name).await? {
+            Some(value) => value,
+            None => {
+                let this = connection.get_frame_this(thread_id, frame.frame_id).await.ok().flatten()
+                    .ok_or_else(|| format!("Unknown variable '{}'", name))?;
+                field_value(connection, this, name).await
+                    .map_err(|_| format!("Unknown variable '{}'", name))?
+            }
+        },
+    };
+
+    Ok(vec![value])
+}
+
+/// Call a method by name on an object, or a static method of `class_id`
+/// when there is no receiver
+///
+/// Looks the method up in `class_id` and its superclasses, preferring an
+/// overload whose parameter types match the arguments exactly.
+async fn call_method(
+    connection: &mut JdwpConnection,
+    thread_id: ThreadId,
+    receiver: Option<ObjectId>,
+    class_id: ClassId,
+    name: &str,
+    args: Vec<Value>,
+) -> Result<Value, String> {
+    let (declaring_class, method, args) = find_method(connection, class_id, name, args, receiver.is_none()).await?;
+
+    let result = match receiver {
+        Some(object_id) if method.mod_bits & ACC_STATIC == 0 => {
+            connection.invoke_object_method(
+                object_id,
+                thread_id,
+                declaring_class,
+                method.method_id,
+                args,
+                invoke_options::INVOKE_SINGLE_THREADED,
+            ).await
+        }
+        _ => {
+            connection.invoke_static_method(
+                declaring_class,
+                thread_id,
+                method.method_id,
+                args,
+                invoke_options::INVOKE_SINGLE_THREADED,
+            ).await
+        }
+    };
+
+    match result {
+        Ok(value) => Ok(value),
+        Err(JdwpError::InvocationException(exception)) => {
+            let class_name = match connection.get_object_reference_type(exception).await {
+                Ok(type_id) => connection.get_signature(type_id).await.map(|sig| type_name(&sig)).ok(),
+                Err(_) => None,
+            };
+            Err(format!("threw {}@{:x}", class_name.as_deref().unwrap_or("exception"), exception))
+        }
+        Err(e) => Err(format!("Failed to invoke: {}", e)),
+    }
+}
+
+/// Find an overload of `name` that accepts `args`, converting the
+/// arguments to its parameter types
+async fn find_method(
+    connection: &mut JdwpConnection,
+    class_id: ClassId,
+    name: &str,
+    args: Vec<Value>,
+    static_only: bool,
+) -> Result<(ClassId, MethodInfo, Vec<Value>), String> {
+    let mut converted_match = None;
+    let mut found_name = false;
+    let mut current = Some(class_id);
+
+    while let Some(declaring_class) = current {
+        let methods = connection.get_methods(declaring_class).await
+            .map_err(|e| format!("Failed to get methods: {}", e))?;
+
+        for method in methods {
+            if method.name != name || (static_only && method.mod_bits & ACC_STATIC == 0) {
+                continue;
+            }
+            found_name = true;
+
+            let params = parameter_signatures(&method.signature);
+            if params.len() != args.len() {
+                continue;
+            }
+            let Some(converted) = params.iter().zip(&args)
+                .map(|(param, arg)| convert_argument(param, arg))
+                .collect::<Option<Vec<Value>>>()
+            else {
+                continue;
+            };
+
+            let exact = converted.iter().zip(&args).all(|(c, a)| c.tag == a.tag);
+            if exact {
+                return Ok((declaring_class, method, converted));
+            }
+            converted_match.get_or_insert((declaring_class, method, converted));
+        }
+
+        current = connection.get_superclass(declaring_class).await
+            .map_err(|e| format!("Failed to get superclass: {}", e))?;
+    }
+
+    converted_match.ok_or_else(|| match (found_name, args.len()) {
+        (false, _) if static_only => format!("no static method '{}'", name),
+        (false, _) => format!("no method '{}'", name),
+        (true, 0) => format!("'{}' needs arguments", name),
+        (true, _) => format!("no overload of '{}' accepts {}", name, args[0].format()),
+    })
+}
+
+/// Parameter signatures of a method signature, e.g. "(I[JLFoo;)V" -> ["I", "[J", "LFoo;"]
+fn parameter_signatures(signature: &str) -> Vec<&str> {
+    let Some(params) = signature.strip_prefix('(').and_then(|s| s.split(')').next()) else {
+        return Vec::new();
+    };
+
+    let bytes = params.as_bytes();
+    let mut result = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'[' => {
+                i += 1;
+                continue;
+            }
+            b'L' => i = params[i..].find(';').map_or(bytes.len(), |end| i + end + 1),
+            _ => i += 1,
+        }
+        result.push(&params[start..i]);
+        start = i;
+    }
+    result
+}
+
+/// Convert an argument to a parameter type, widening integers where Java
+/// would; None if it can't be passed (no boxing or unboxing)
+fn convert_argument(param: &str, arg: &Value) -> Option<Value> {
+    let tag = param.bytes().next()?;
+    let data = match (tag, &arg.data) {
+        (b'L' | b'[', ValueData::Object(_)) => return Some(arg.clone()),
+        (b'Z', ValueData::Boolean(v)) => ValueData::Boolean(*v),
+        (b'I', ValueData::Int(v)) => ValueData::Int(*v),
+        (b'J', ValueData::Int(v)) => ValueData::Long(*v as i64),
+        (b'J', ValueData::Long(v)) => ValueData::Long(*v),
+        (b'S', ValueData::Int(v)) => ValueData::Short(i16::try_from(*v).ok()?),
+        (b'B', ValueData::Int(v)) => ValueData::Byte(i8::try_from(*v).ok()?),
+        (b'F', ValueData::Int(v)) => ValueData::Float(*v as f32),
+        (b'D', ValueData::Int(v)) => ValueData::Double(*v as f64),
+        (b'D', ValueData::Long(v)) => ValueData::Double(*v as f64),
+        _ => return None,
+    };
+
+    Some(Value { tag, data })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::{Buf, BufMut};
+    use jdwp_client::commands::error_codes::NOT_IMPLEMENTED;
+    use jdwp_client::commands::{
+        class_type_commands, command_sets, method_commands, object_reference_commands,
+        reference_type_commands, stack_frame_commands, thread_commands,
+    };
+    use jdwp_client::fake_vm::{self, first_id, put_string};
+    use std::collections::HashMap;
+
+    const THREAD: u64 = 1;
+    const THIS: u64 = 1000;
+
+    fn object(id: u64) -> Vec<u8> {
+        let mut value = vec![b'L'];
+        value.put_u64(id);
+        value
+    }
+
+    fn int(v: i32) -> Vec<u8> {
+        let mut value = vec![b'I'];
+        value.put_i32(v);
+        value
+    }
+
+    /// A frame in `Service` (this = 1000) with locals `user` and `guest`, and
+    /// `broken` whose VariableTable entry has no signature:
+    ///
+    /// this.config.timeout = 30, this.count = 3,
+    /// user.address.zip = 94107, guest.address = null
+    ///
+    /// Methods: user.getAddress(), this.scaled(long) returning twice its
+    /// argument, and this.fail() throwing an IllegalStateException
+    async fn connect() -> JdwpConnection {
+        // class -> [(field id, name, signature)]
+        let fields: HashMap<u64, Vec<(u64, &str, &str)>> = HashMap::from([
+            (10, vec![(501, "config", "Lcom/example/Config;"), (505, "count", "I")]),
+            (11, vec![(502, "timeout", "I")]),
+            (12, vec![(503, "address", "Lcom/example/Address;")]),
+            (13, vec![(504, "zip", "I")]),
+        ]);
+        let classes: HashMap<u64, u64> = HashMap::from([(THIS, 10), (1001, 11), (2000, 12), (2001, 13), (2002, 12), (9000, 14)]);
+        let values: HashMap<(u64, u64), Vec<u8>> = HashMap::from([
+            ((THIS, 501), object(1001)),
+            ((THIS, 505), int(3)),
+            ((1001, 502), int(30)),
+            ((2000, 503), object(2001)),
+            ((2001, 504), int(94107)),
+            ((2002, 503), object(0)),
+        ]);
+        let locals: HashMap<i32, Vec<u8>> = HashMap::from([(1, object(2000)), (2, object(2002))]);
+        // class -> [(method id, name, signature)]
+        let methods: HashMap<u64, Vec<(u64, &str, &str)>> = HashMap::from([
+            (10, vec![(602, "scaled", "(J)J"), (603, "fail", "()V")]),
+            (12, vec![(601, "getAddress", "()Lcom/example/Address;")]),
+        ]);
+
+        fake_vm::connect_with(move |command_set, command, mut data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::THREAD_REFERENCE, thread_commands::FRAMES) => {
+                    reply.put_i32(1);
+                    reply.put_u64(100);
+                    reply.put_u8(1);
+                    reply.put_u64(10);
+                    reply.put_u64(20);
+                    reply.put_u64(5);
+                }
+                (command_sets::STACK_FRAME, stack_frame_commands::THIS_OBJECT) => reply = object(THIS),
+                (command_sets::METHOD, method_commands::VARIABLE_TABLE) => {
+                    reply.put_i32(0);
+                    reply.put_i32(3);
+                    let user = "Lcom/example/User;";
+                    for (name, signature, slot) in [("user", user, 1), ("guest", user, 2), ("broken", "", 3)] {
+                        reply.put_u64(0);
+                        put_string(&mut reply, name);
+                        put_string(&mut reply, signature);
+                        reply.put_u32(100);
+                        reply.put_u32(slot);
+                    }
+                }
+                (command_sets::STACK_FRAME, stack_frame_commands::GET_VALUES) => {
+                    data.advance(16);
+                    let count = data.get_i32();
+                    reply.put_i32(count);
+                    for _ in 0..count {
+                        let slot = data.get_i32();
+                        data.advance(1);
+                        reply.extend_from_slice(&locals[&slot]);
+                    }
+                }
+                (command_sets::OBJECT_REFERENCE, object_reference_commands::REFERENCE_TYPE) => {
+                    reply.put_u8(1);
+                    reply.put_u64(classes[&first_id(data)]);
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::FIELDS) => {
+                    let declared = &fields[&first_id(data)];
+                    reply.put_i32(declared.len() as i32);
+                    for (field_id, name, signature) in declared {
+                        reply.put_u64(*field_id);
+                        put_string(&mut reply, name);
+                        put_string(&mut reply, signature);
+                        reply.put_i32(0);
+                    }
+                }
+                (command_sets::CLASS_TYPE, class_type_commands::SUPERCLASS) => reply.put_u64(0),
+                (command_sets::REFERENCE_TYPE, reference_type_commands::MODIFIERS) => reply.put_i32(0x1),
+                (command_sets::REFERENCE_TYPE, reference_type_commands::METHODS) => {
+                    let declared = methods.get(&first_id(data)).cloned().unwrap_or_default();
+                    reply.put_i32(declared.len() as i32);
+                    for (method_id, name, signature) in declared {
+                        reply.put_u64(method_id);
+                        put_string(&mut reply, name);
+                        put_string(&mut reply, signature);
+                        reply.put_i32(0x1);
+                    }
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::SIGNATURE) => {
+                    put_string(&mut reply, "Ljava/lang/IllegalStateException;");
+                }
+                (command_sets::OBJECT_REFERENCE, object_reference_commands::INVOKE_METHOD) => {
+                    data.advance(24); // object, thread, class
+                    let method_id = data.get_u64();
+                    let mut exception = 0;
+                    match method_id {
+                        601 => reply.extend_from_slice(&object(2001)),
+                        602 => {
+                            assert_eq!(data.get_i32(), 1);
+                            assert_eq!(data.get_u8(), b'J');
+                            reply.put_u8(b'J');
+                            reply.put_i64(data.get_i64() * 2);
+                        }
+                        _ => {
+                            reply.put_u8(b'V');
+                            exception = 9000;
+                        }
+                    }
+                    reply.extend_from_slice(&object(exception));
+                }
+                (command_sets::OBJECT_REFERENCE, object_reference_commands::GET_VALUES) => {
+                    let object_id = data.get_u64();
+                    let count = data.get_i32();
+                    reply.put_i32(count);
+                    for _ in 0..count {
+                        reply.extend_from_slice(&values[&(object_id, data.get_u64())]);
+                    }
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await
+    }
+
+    async fn eval(connection: &mut JdwpConnection, expression: &str) -> Result<Evaluation, String> {
+        evaluate(connection, THREAD, 0, expression).await
+    }
+
+    fn value_of(evaluation: Evaluation) -> ValueData {
+        match evaluation {
+            Evaluation::Value(value) => value.data,
+            other => panic!("expected a value, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_two_level_chain() {
+        let mut connection = connect().await;
+
+        let result = eval(&mut connection, "this.config.timeout").await.unwrap();
+        assert_eq!(value_of(result), ValueData::Int(30));
+
+        // A name that isn't a local is a field of `this`
+        let result = eval(&mut connection, "config.timeout").await.unwrap();
+        assert_eq!(value_of(result), ValueData::Int(30));
+    }
+
+    #[tokio::test]
+    async fn test_three_level_chain() {
+        let mut connection = connect().await;
+
+        let result = eval(&mut connection, "user.address.zip").await.unwrap();
+        assert_eq!(value_of(result), ValueData::Int(94107));
+    }
+
+    #[tokio::test]
+    async fn test_null_in_chain_stops_there() {
+        let mut connection = connect().await;
+
+        match eval(&mut connection, "guest.address.zip").await.unwrap() {
+            Evaluation::NullAt(path) => assert_eq!(path, "guest.address"),
+            other => panic!("expected null, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_field_of_primitive_or_missing_field_is_an_error() {
+        let mut connection = connect().await;
+
+        let error = eval(&mut connection, "this.count.value").await.unwrap_err();
+        assert!(error.contains("this.count is a primitive"), "{}", error);
+
+        let error = eval(&mut connection, "user.nickname").await.unwrap_err();
+        assert!(error.contains("no instance field 'nickname'"), "{}", error);
+
+        let error = eval(&mut connection, "broken").await.unwrap_err();
+        assert!(error.contains("No type signature for 'broken'"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_method_calls() {
+        let mut connection = connect().await;
+
+        let result = eval(&mut connection, "user.getAddress().zip").await.unwrap();
+        assert_eq!(value_of(result), ValueData::Int(94107));
+
+        // Implicit `this`, with the int literal widened to the long parameter
+        let result = eval(&mut connection, "scaled(21)").await.unwrap();
+        assert_eq!(value_of(result), ValueData::Long(42));
+    }
+
+    #[tokio::test]
+    async fn test_method_call_errors() {
+        let mut connection = connect().await;
+
+        let error = eval(&mut connection, "this.fail()").await.unwrap_err();
+        assert!(error.contains("this.fail(): threw IllegalStateException@2328"), "{}", error);
+
+        let error = eval(&mut connection, "user.rename()").await.unwrap_err();
+        assert!(error.contains("no method 'rename'"), "{}", error);
+
+        let error = eval(&mut connection, "this.scaled(true)").await.unwrap_err();
+        assert!(error.contains("no overload of 'scaled' accepts (boolean) true"), "{}", error);
+    }
+
+    #[test]
+    fn test_parse_expression() {
+        assert_eq!(
+            parse_expression(" this.a.b ").unwrap(),
+            [Step::Field("this"), Step::Field("a"), Step::Field("b")]
+        );
+        assert_eq!(
+            parse_expression("user.getName().trim()").unwrap(),
+            [Step::Field("user"), Step::Call("getName", None), Step::Call("trim", None)]
+        );
+        assert_eq!(
+            parse_expression(r#"map.get("a.b)")"#).unwrap(),
+            [Step::Field("map"), Step::Call("get", Some(Arg::String("a.b)")))]
+        );
+        assert_eq!(parse_expression("list.get(3)").unwrap()[1], Step::Call("get", Some(Arg::Literal(ValueData::Int(3)))));
+        assert_eq!(parse_expression("f(10L)").unwrap()[0], Step::Call("f", Some(Arg::Literal(ValueData::Long(10)))));
+        assert_eq!(parse_expression("f(key)").unwrap()[0], Step::Call("f", Some(Arg::Variable("key"))));
+        assert!(parse_expression("a + b").is_err());
+        assert!(parse_expression("a..b").is_err());
+        assert!(parse_expression("f(a, b)").is_err());
+        assert!(parse_expression("f(").is_err());
+
+        let longest = format!("a{}", ".f()".repeat(MAX_CHAIN_DEPTH));
+        assert!(parse_expression(&longest).is_ok());
+        assert!(parse_expression(&format!("{}.f", longest)).is_err());
+    }
+
+    #[test]
+    fn test_parameter_signatures() {
+        assert_eq!(parameter_signatures("(I[JLjava/lang/String;[[LFoo;)V"), ["I", "[J", "Ljava/lang/String;", "[[LFoo;"]);
+        assert!(parameter_signatures("()V").is_empty());
+    }
+
+    #[test]
+    fn test_convert_argument_rejects_empty_parameter_type() {
+        let arg = Value { tag: b'I', data: ValueData::Int(1) };
+        assert!(convert_argument("", &arg).is_none());
+        assert_eq!(convert_argument("J", &arg).unwrap().data, ValueData::Long(1));
+    }
+}
@@ -2,28 +2,73 @@
 //
 // Handles initialize, list tools, and debug tool execution
 
+use crate::eval::{self, Evaluation};
+use crate::known_types::render_known_type;
+use crate::location::{parse_location, LocationSpec};
+use crate::model::{FrameDump, StackDump, VarDump};
 use crate::protocol::*;
-use crate::session::SessionManager;
+use crate::session::{
+    BreakpointInfo, DebugSession, DeferredBreakpoint, ExceptionBreakpointInfo, SessionId, SessionManager,
+    WatchpointInfo,
+};
 use crate::tools;
+use jdwp_client::commands::{error_codes, event_kinds, invoke_options, step_depths, step_sizes};
+use jdwp_client::events::{Event, EventKind};
+use jdwp_client::protocol::error_name;
+use jdwp_client::method::{LineTable, LineTableEntry};
+use jdwp_client::reftype::{DeclaredField, MethodInfo};
+use jdwp_client::signature::type_name;
+use jdwp_client::types::{
+    tag_for_signature, ClassStatus, FieldId, Location, ObjectId, ReferenceTypeId, SuspendStatus, ThreadGroupId, ThreadId,
+    ThreadStatus, Value, ValueData, ACC_ABSTRACT, ACC_NATIVE, ACC_STATIC,
+};
+use jdwp_client::vm::{ClassInfo, VmCapabilities};
+use jdwp_client::{EventSet, JdwpConnection, JdwpError, SuspendPolicy};
 use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc, Mutex};
 use tracing::{debug, info, warn};
 
+/// How long a step may take before we give up waiting for its event
+const STEP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default time debug.run waits for a breakpoint or exception
+const DEFAULT_RUN_TIMEOUT: Duration = Duration::from_secs(30);
+
 pub struct RequestHandler {
     session_manager: SessionManager,
+    /// Cap on a tool result's size in bytes (0 means no cap)
+    max_response_bytes: AtomicUsize,
+    /// Where event notifications for the client go, once the transport is set up
+    notifications: OnceLock<mpsc::Sender<serde_json::Value>>,
 }
 
 impl RequestHandler {
     pub fn new() -> Self {
         Self {
             session_manager: SessionManager::new(),
+            max_response_bytes: AtomicUsize::new(0),
+            notifications: OnceLock::new(),
         }
     }
 
+    /// Push breakpoint hits, other stops and lost connections to the client
+    /// through `sender`
+    /// (sessions attached afterwards only)
+    pub fn set_notification_sender(&self, sender: mpsc::Sender<serde_json::Value>) {
+        let _ = self.notifications.set(sender);
+    }
+
     pub async fn handle_request(&self, request: JsonRpcRequest) -> JsonRpcResponse {
         let result = match request.method.as_str() {
             "initialize" => self.handle_initialize(request.params),
-            "tools/list" => self.handle_list_tools(),
+            "tools/list" => self.handle_list_tools().await,
             "tools/call" => self.handle_call_tool(request.params).await,
+            // Every event notification is sent at "info"; there's nothing to filter
+            "logging/setLevel" => Ok(json!({})),
             _ => Err(JsonRpcError {
                 code: METHOD_NOT_FOUND,
                 message: format!("Method not found: {}", request.method),
@@ -55,6 +100,21 @@ impl RequestHandler {
             "notifications/cancelled" => {
                 debug!("Request cancelled");
             }
+            "notifications/progress" => {
+                // Tool calls don't hand out progress tokens yet, so there is
+                // nothing to update; just note it
+                let params = notification.params.unwrap_or_default();
+                debug!(
+                    "Progress {} for token {}",
+                    params.get("progress").unwrap_or(&serde_json::Value::Null),
+                    params.get("progressToken").unwrap_or(&serde_json::Value::Null)
+                );
+            }
+            // Protocol-level extras some clients send (e.g. "$/setTrace")
+            method if method.starts_with("$/") => {
+                debug!("Ignoring notification: {}", method);
+            }
+            // Notifications never get a response, so unknown ones are only logged
             _ => {
                 warn!("Unknown notification: {}", notification.method);
             }
@@ -62,17 +122,46 @@ impl RequestHandler {
     }
 
     fn handle_initialize(&self, params: Option<serde_json::Value>) -> Result<serde_json::Value, JsonRpcError> {
-        let _params: InitializeParams = serde_json::from_value(params.unwrap_or(json!({})))
+        let params: InitializeParams = serde_json::from_value(params.unwrap_or(json!({})))
             .map_err(|e| JsonRpcError {
                 code: INVALID_PARAMS,
                 message: format!("Invalid initialize params: {}", e),
                 data: None,
             })?;
 
+        let options = params.initialization_options.unwrap_or_default();
+        if let Some(ttl_secs) = options.session_ttl_secs {
+            let ttl = (ttl_secs > 0).then(|| Duration::from_secs(ttl_secs));
+            info!("Session idle TTL set to {:?}", ttl);
+            self.session_manager.set_session_ttl(ttl);
+        }
+        if let Some(auto_reconnect) = options.auto_reconnect {
+            info!("Auto-reconnect {}", if auto_reconnect { "enabled" } else { "disabled" });
+            self.session_manager.set_auto_reconnect(auto_reconnect);
+        }
+        if let Some(max_bytes) = options.max_response_bytes {
+            info!("Tool results capped at {} bytes", max_bytes);
+            self.max_response_bytes.store(max_bytes, Ordering::SeqCst);
+        }
+        if let Some(secs) = options.reply_timeout_secs.filter(|&secs| secs > 0) {
+            info!("JDWP reply timeout set to {}s", secs);
+            self.session_manager.set_reply_timeout(Duration::from_secs(secs));
+        }
+        if let Some(max_bytes) = options.max_packet_bytes.filter(|&bytes| bytes > 0) {
+            info!("JDWP packets capped at {} bytes", max_bytes);
+            self.session_manager.set_max_packet_size(max_bytes);
+        }
+        if let Some(secs) = options.keepalive_secs {
+            let interval = (secs > 0).then(|| Duration::from_secs(secs));
+            info!("Keepalive interval set to {:?}", interval);
+            self.session_manager.set_keepalive(interval);
+        }
+
         let result = InitializeResult {
             protocol_version: "2024-11-05".to_string(),
             capabilities: ServerCapabilities {
                 tools: ToolsCapability {},
+                logging: LoggingCapability {},
             },
             server_info: ServerInfo {
                 name: "jdwp-mcp".to_string(),
@@ -86,15 +175,24 @@ impl RequestHandler {
             ),
         };
 
-        Ok(serde_json::to_value(result).unwrap())
+        result_value(result)
     }
 
-    fn handle_list_tools(&self) -> Result<serde_json::Value, JsonRpcError> {
-        let result = ListToolsResult {
-            tools: tools::get_tools(),
-        };
+    async fn handle_list_tools(&self) -> Result<serde_json::Value, JsonRpcError> {
+        // Once attached, hide tools the JVM can't support
+        let capabilities = self.current_capabilities().await;
+        let tools = tools::get_tools()
+            .into_iter()
+            .filter(|tool| {
+                capabilities
+                    .as_ref()
+                    .is_none_or(|caps| tools::is_supported(&tool.name, caps))
+            })
+            .collect();
 
-        Ok(serde_json::to_value(result).unwrap())
+        let result = ListToolsResult { tools };
+
+        result_value(result)
     }
 
     async fn handle_call_tool(&self, params: Option<serde_json::Value>) -> Result<serde_json::Value, JsonRpcError> {
@@ -105,198 +203,672 @@ impl RequestHandler {
                 data: None,
             })?;
 
+        // Any tool call keeps the session it addresses alive
+        let target = self.target_session_id(&call_params.arguments).await.ok();
+        if let Some(session_id) = &target {
+            self.session_manager.touch_session(session_id).await;
+        }
+
+        // Refuse operations the JVM has told us it can't do
+        let missing = match &target {
+            Some(session_id) => self.session_capabilities(session_id).await
+                .and_then(|caps| tools::missing_capability(&call_params.name, &caps)),
+            None => None,
+        };
+
         // Route to appropriate handler based on tool name
-        let result = match call_params.name.as_str() {
-            "debug.attach" => self.handle_attach(call_params.arguments).await,
-            "debug.set_breakpoint" => self.handle_set_breakpoint(call_params.arguments).await,
-            "debug.list_breakpoints" => self.handle_list_breakpoints(call_params.arguments).await,
-            "debug.clear_breakpoint" => self.handle_clear_breakpoint(call_params.arguments).await,
-            "debug.continue" => self.handle_continue(call_params.arguments).await,
-            "debug.step_over" => self.handle_step_over(call_params.arguments).await,
-            "debug.step_into" => self.handle_step_into(call_params.arguments).await,
-            "debug.step_out" => self.handle_step_out(call_params.arguments).await,
-            "debug.get_stack" => self.handle_get_stack(call_params.arguments).await,
-            "debug.evaluate" => self.handle_evaluate(call_params.arguments).await,
-            "debug.list_threads" => self.handle_list_threads(call_params.arguments).await,
-            "debug.pause" => self.handle_pause(call_params.arguments).await,
-            "debug.disconnect" => self.handle_disconnect(call_params.arguments).await,
-            "debug.get_last_event" => self.handle_get_last_event(call_params.arguments).await,
-            _ => Err(format!("Unknown tool: {}", call_params.name)),
+        let result = match missing {
+            Some(missing) => Err(format!("{}: this JVM does not support {}", call_params.name, missing)),
+            None => self.run_tool(&call_params.name, call_params.arguments).await,
+        };
+
+        // Keep one oversized result from flooding the transport and the client's context
+        let max_bytes = self.max_response_bytes.load(Ordering::SeqCst);
+        let result = match result {
+            Ok(content) if max_bytes > 0 => Ok(cap_response(content, max_bytes)),
+            Err(error) if max_bytes > 0 => Err(truncate_text(error, max_bytes)),
+            result => result,
         };
 
         match result {
             Ok(content) => {
                 let call_result = CallToolResult {
-                    content: vec![ContentBlock::Text { text: content }],
+                    content,
                     is_error: None,
                 };
-                Ok(serde_json::to_value(call_result).unwrap())
+                result_value(call_result)
             }
             Err(error) => {
                 let call_result = CallToolResult {
                     content: vec![ContentBlock::Text { text: error.clone() }],
                     is_error: Some(true),
                 };
-                Ok(serde_json::to_value(call_result).unwrap())
+                result_value(call_result)
+            }
+        }
+    }
+
+    /// Run a tool, first reconnecting a dropped session if that's enabled
+    ///
+    /// A session whose VM has exited is removed, before the tool runs or
+    /// after it if the VM died meanwhile.
+    async fn run_tool(&self, name: &str, args: serde_json::Value) -> Result<Vec<ContentBlock>, String> {
+        // The session this call addresses, for the checks around it
+        let target = match name {
+            "debug.attach" | "debug.list_sessions" => None,
+            _ => self.target_session_id(&args).await.ok(),
+        };
+
+        if let Some(session_id) = &target {
+            if let Some(ended) = self.end_session_if_vm_gone(session_id).await {
+                return match name {
+                    "debug.disconnect" => Ok(vec![ContentBlock::text(ended)]),
+                    _ => Err(ended),
+                };
+            }
+        }
+
+        let reconnect_note = match (name, &target) {
+            ("debug.disconnect", _) | (_, None) => None,
+            (_, Some(session_id)) => self.reconnect_if_needed(session_id).await?,
+        };
+
+        let result = match name {
+            "debug.get_stack" => self.handle_get_stack(args).await,
+            name => self.call_text_tool(name, args).await
+                .map(|text| vec![ContentBlock::text(text)]),
+        };
+
+        let ended = match &target {
+            Some(session_id) => self.end_session_if_vm_gone(session_id).await,
+            None => None,
+        };
+        let result = match ended {
+            Some(ended) => match result {
+                Ok(mut content) => {
+                    content.push(ContentBlock::text(ended));
+                    Ok(content)
+                }
+                Err(error) => Err(format!("{}\n\n{}", ended, error)),
+            },
+            None => result,
+        };
+
+        // Tell the caller we reconnected, since breakpoint IDs will have changed
+        match reconnect_note {
+            Some(note) => match result {
+                Ok(mut content) => {
+                    content.insert(0, ContentBlock::text(note));
+                    Ok(content)
+                }
+                Err(error) => Err(format!("{}\n\n{}", note, error)),
+            },
+            None => result,
+        }
+    }
+
+    /// Route a tool whose result is a single block of text
+    async fn call_text_tool(&self, name: &str, args: serde_json::Value) -> Result<String, String> {
+        match name {
+            "debug.attach" => self.handle_attach(args).await,
+            "debug.list_sessions" => self.handle_list_sessions(args).await,
+            "debug.set_breakpoint" => self.handle_set_breakpoint(args).await,
+            "debug.set_breakpoints" => self.handle_set_breakpoints(args).await,
+            "debug.break_next_line" => self.handle_break_next_line(args).await,
+            "debug.list_breakpoints" => self.handle_list_breakpoints(args).await,
+            "debug.clear_breakpoint" => self.handle_clear_breakpoint(args).await,
+            "debug.enable_breakpoint" => self.handle_enable_breakpoint(args).await,
+            "debug.disable_breakpoint" => self.handle_disable_breakpoint(args).await,
+            "debug.break_on_exception" => self.handle_break_on_exception(args).await,
+            "debug.set_watchpoint" => self.handle_set_watchpoint(args).await,
+            "debug.clear_watchpoint" => self.handle_clear_watchpoint(args).await,
+            "debug.wait_for_watchpoint" => self.handle_wait_for_watchpoint(args).await,
+            "debug.wait_for_event" => self.handle_wait_for_event(args).await,
+            "debug.continue" => self.handle_continue(args).await,
+            "debug.run" => self.handle_run(args).await,
+            "debug.step_over" => self.handle_step_over(args).await,
+            "debug.step_into" => self.handle_step_into(args).await,
+            "debug.step_out" => self.handle_step_out(args).await,
+            "debug.evaluate" => self.handle_evaluate(args).await,
+            "debug.list_threads" => self.handle_list_threads(args).await,
+            "debug.thread_tree" => self.handle_thread_tree(args).await,
+            "debug.pause" => self.handle_pause(args).await,
+            "debug.snapshot" => self.handle_snapshot(args).await,
+            "debug.detect_deadlock" => self.handle_detect_deadlock(args).await,
+            "debug.disconnect" => self.handle_disconnect(args).await,
+            "debug.get_last_event" => self.handle_get_last_event(args).await,
+            "debug.inspect_object" => self.handle_inspect_object(args).await,
+            "debug.get_this" => self.handle_get_this(args).await,
+            "debug.set_variable" => self.handle_set_variable(args).await,
+            "debug.inspect_array" => self.handle_inspect_array(args).await,
+            "debug.set_array_element" => self.handle_set_array_element(args).await,
+            "debug.stats" => self.handle_stats(args).await,
+            "debug.inspect_collection" => self.handle_inspect_collection(args).await,
+            "debug.exit_vm" => self.handle_exit_vm(args).await,
+            "debug.list_members" => self.handle_list_members(args).await,
+            "debug.find_classes" => self.handle_find_classes(args).await,
+            "debug.describe_class" => self.handle_describe_class(args).await,
+            "debug.bytecodes" => self.handle_bytecodes(args).await,
+            "debug.capabilities" => self.handle_capabilities(args).await,
+            "debug.classpath" => self.handle_classpath(args).await,
+            "debug.pop_frame" => self.handle_pop_frame(args).await,
+            "debug.force_return" => self.handle_force_return(args).await,
+            _ => Err(format!("Unknown tool: {}", name)),
+        }
+    }
+
+    /// The session a tool call addresses: its `session_id` argument if
+    /// given, otherwise the current session
+    async fn target_session_id(&self, args: &serde_json::Value) -> Result<SessionId, String> {
+        match args.get("session_id").and_then(|v| v.as_str()) {
+            Some(session_id) => match self.session_manager.get_session(session_id).await {
+                Some(_) => Ok(session_id.to_string()),
+                None => Err(format!("Unknown session: {} (see debug.list_sessions)", session_id)),
+            },
+            None => self.session_manager.get_current_session_id().await
+                .ok_or_else(|| "No active debug session".to_string()),
+        }
+    }
+
+    /// The session a tool call addresses (see `target_session_id`)
+    async fn session_for(&self, args: &serde_json::Value) -> Result<Arc<Mutex<DebugSession>>, String> {
+        let session_id = self.target_session_id(args).await?;
+        self.session_manager.get_session(&session_id).await
+            .ok_or_else(|| format!("Session {} has ended", session_id))
+    }
+
+    /// Remove a session if its VM exited, or if the connection dropped and
+    /// auto-reconnect won't revive it
+    ///
+    /// Returns a note saying the session ended, or None if it's still usable.
+    async fn end_session_if_vm_gone(&self, session_id: &str) -> Option<String> {
+        let session_guard = self.session_manager.get_session(session_id).await?;
+
+        let reason = {
+            let session = session_guard.lock().await;
+            if session.connection.vm_exited() {
+                "VM exited"
+            } else if !session.connection.is_connected() && (session.listening || !self.session_manager.auto_reconnect()) {
+                "connection to the VM closed"
+            } else {
+                return None;
+            }
+        };
+
+        self.session_manager.terminate_session(session_id).await;
+        info!("Session {} ended: {}", session_id, reason);
+
+        Some(format!(
+            "🛑 Debug session ended ({})\n   Session {} was removed; use debug.attach to start a new one",
+            reason, session_id
+        ))
+    }
+
+    /// Reconnect a session if its connection dropped and auto-reconnect is
+    /// enabled
+    ///
+    /// Returns a note describing the reconnect, or None if nothing was done.
+    async fn reconnect_if_needed(&self, session_id: &str) -> Result<Option<String>, String> {
+        if !self.session_manager.auto_reconnect() {
+            return Ok(None);
+        }
+        let Some(session_guard) = self.session_manager.get_session(session_id).await else {
+            return Ok(None);
+        };
+
+        let mut session = session_guard.lock().await;
+        if session.connection.is_connected() {
+            return Ok(None);
+        }
+
+        warn!("Connection to {}:{} lost, reconnecting", session.host, session.port);
+
+        let mut connection = self.session_manager.connection_builder(&session.host, session.port)
+            .connect()
+            .await
+            .map_err(|e| format!(
+                "Connection to the JVM at {}:{} was lost and reconnecting failed: {}",
+                session.host, session.port, e
+            ))?;
+
+        session.capabilities = match connection.get_capabilities_new().await {
+            Ok(capabilities) => Some(capabilities),
+            Err(e) => {
+                warn!("Failed to get JVM capabilities: {}", e);
+                None
+            }
+        };
+
+        if let Some(task) = session.event_listener_task.take() {
+            task.abort();
+        }
+        session.connection = connection;
+        session.method_cache.clear();
+        session.last_event = None;
+        spawn_event_listener(&self.session_manager, session_id, &mut session, self.notifications.get().cloned());
+        spawn_keepalive(&self.session_manager, session_id, &mut session, self.notifications.get().cloned());
+
+        // Request IDs belonged to the old connection, so set every breakpoint again
+        // Hit-limited breakpoints that already fired are gone from the VM, and
+        // disabled ones have no request to restore
+        session.breakpoints.retain(|_, bp| !bp.expired());
+        let mut previous: Vec<BreakpointInfo> = session.breakpoints.values()
+            .filter(|bp| bp.enabled)
+            .cloned()
+            .collect();
+        session.breakpoints.retain(|_, bp| !bp.enabled);
+        previous.sort_by_key(|bp| bp.request_id);
+
+        // Nothing should fire until every request is back in place
+        let hold = match EventHold::hold(&session.connection).await {
+            Ok(hold) => Some(hold),
+            Err(e) => {
+                warn!("Failed to hold events while re-registering requests: {}", e);
+                None
+            }
+        };
+
+        let mut note = format!("🔄 Reconnected to JVM at {}:{}", session.host, session.port);
+        if !previous.is_empty() {
+            let mut failures = Vec::new();
+            for bp in &previous {
+                if let Err(e) = request_breakpoint(&mut session, bp).await {
+                    failures.push(format!("   ❌ {} ({}): {}", bp.location(), bp.id, e));
+                }
+            }
+
+            note.push_str(&format!(
+                "\n   Re-registered {} of {} breakpoints (breakpoint IDs have changed)",
+                previous.len() - failures.len(), previous.len()
+            ));
+            for failure in failures {
+                note.push('\n');
+                note.push_str(&failure);
+            }
+        }
+
+        // Failed ones stay listed; they weren't watching for anything
+        let mut previous: Vec<DeferredBreakpoint> = session.deferred_breakpoints.values()
+            .filter(|deferred| deferred.failure.is_none())
+            .cloned()
+            .collect();
+        session.deferred_breakpoints.retain(|_, deferred| deferred.failure.is_some());
+        previous.sort_by_key(|deferred| deferred.request_id);
+
+        if !previous.is_empty() {
+            let mut failures = Vec::new();
+            for deferred in &previous {
+                let options = BreakpointOptions {
+                    hit_limit: deferred.hit_limit,
+                    thread: deferred.thread,
+                    suspend_policy: deferred.suspend_policy,
+                };
+                let placed = place_breakpoint(
+                    &mut session, &deferred.class_pattern, deferred.line, deferred.method.as_deref(), options,
+                ).await;
+                if let Err(e) = placed {
+                    failures.push(format!("   ❌ {} ({}): {}", deferred.location(), deferred.id, e));
+                }
+            }
+
+            note.push_str(&format!(
+                "\n   Re-registered {} of {} deferred breakpoints (breakpoint IDs have changed)",
+                previous.len() - failures.len(), previous.len()
+            ));
+            for failure in failures {
+                note.push('\n');
+                note.push_str(&failure);
+            }
+        }
+
+        let mut previous: Vec<WatchpointInfo> = session.watchpoints.drain().map(|(_, wp)| wp).collect();
+        previous.sort_by_key(|wp| wp.request_id);
+
+        if !previous.is_empty() {
+            let mut failures = Vec::new();
+            for wp in &previous {
+                if let Err(e) = set_watchpoint_at(&mut session, &wp.class_pattern, &wp.field, wp.event_kind).await {
+                    failures.push(format!("   ❌ {}.{} ({}): {}", wp.class_pattern, wp.field, wp.id, e));
+                }
+            }
+
+            note.push_str(&format!(
+                "\n   Re-registered {} of {} watchpoints (watchpoint IDs have changed)",
+                previous.len() - failures.len(), previous.len()
+            ));
+            for failure in failures {
+                note.push('\n');
+                note.push_str(&failure);
+            }
+        }
+
+        let mut previous: Vec<ExceptionBreakpointInfo> = session.exception_breakpoints.drain().map(|(_, eb)| eb).collect();
+        previous.sort_by_key(|eb| eb.request_id);
+
+        if !previous.is_empty() {
+            let mut failures = Vec::new();
+            for eb in &previous {
+                if let Err(e) = set_exception_breakpoint_at(&mut session, eb.exception_class.as_deref(), eb.caught, eb.uncaught).await {
+                    failures.push(format!("   ❌ {} ({}): {}", exception_label(eb), eb.id, e));
+                }
+            }
+
+            note.push_str(&format!(
+                "\n   Re-registered {} of {} exception breakpoints (breakpoint IDs have changed)",
+                previous.len() - failures.len(), previous.len()
+            ));
+            for failure in failures {
+                note.push('\n');
+                note.push_str(&failure);
             }
         }
+
+        if let Some(hold) = hold {
+            if let Err(e) = hold.release().await {
+                warn!("Failed to release events: {}", e);
+            }
+        }
+
+        info!("{}", note);
+        Ok(Some(note))
+    }
+
+    /// Capabilities of the current session's JVM, if attached and known
+    async fn current_capabilities(&self) -> Option<VmCapabilities> {
+        let session_id = self.session_manager.get_current_session_id().await?;
+        self.session_capabilities(&session_id).await
+    }
+
+    /// Capabilities of a session's JVM, if known
+    async fn session_capabilities(&self, session_id: &str) -> Option<VmCapabilities> {
+        let session_guard = self.session_manager.get_session(session_id).await?;
+        let session = session_guard.lock().await;
+        session.capabilities.clone()
     }
 
     // Tool implementations (stubs for now)
     async fn handle_attach(&self, args: serde_json::Value) -> Result<String, String> {
+        match args.get("transport").and_then(|v| v.as_str()).unwrap_or("socket") {
+            "socket" => {}
+            "shmem" => {
+                let name = args.get("name").and_then(|v| v.as_str())
+                    .ok_or_else(|| "Missing 'name': the shared memory name the JVM was started with".to_string())?;
+                return Err(format!(
+                    "The dt_shmem transport isn't supported yet, so can't attach to '{}'; start the JVM with transport=dt_socket instead",
+                    name
+                ));
+            }
+            other => return Err(format!("Unknown transport '{}' (expected socket or shmem)", other)),
+        }
         let host = args.get("host").and_then(|v| v.as_str()).unwrap_or("localhost");
         let port = args.get("port").and_then(|v| v.as_u64()).unwrap_or(5005) as u16;
+        let bind = args.get("bind").and_then(|v| v.as_str()).unwrap_or("127.0.0.1:5005");
+        let listen = match args.get("mode").and_then(|v| v.as_str()).unwrap_or("attach") {
+            "attach" => false,
+            "listen" => true,
+            other => return Err(format!("Unknown mode '{}' (expected attach or listen)", other)),
+        };
+        // A JVM being launched needs longer to dial in than an attach takes
+        let default_timeout_ms = if listen { 60_000 } else { 5000 };
+        let timeout_ms = args.get("timeout_ms").and_then(|v| v.as_u64()).unwrap_or(default_timeout_ms);
 
-        match jdwp_client::JdwpConnection::connect(host, port).await {
-            Ok(connection) => {
-                // Create session
-                let session_id = self.session_manager.create_session(connection).await;
+        let builder = self.session_manager.connection_builder(host, port)
+            .connect_timeout(Duration::from_millis(timeout_ms));
+        let connect = if listen { builder.listen(bind).await } else { builder.connect().await };
+        match connect {
+            Ok(mut connection) => {
+                // Cached so tools the JVM can't support can be hidden
+                let capabilities = match connection.get_capabilities_new().await {
+                    Ok(capabilities) => Some(capabilities),
+                    Err(e) => {
+                        warn!("Failed to get JVM capabilities: {}", e);
+                        None
+                    }
+                };
+
+                // Create session; a listening session records its bind address
+                let (host, port) = if listen {
+                    bind.rsplit_once(':')
+                        .and_then(|(host, port)| Some((host, port.parse().ok()?)))
+                        .unwrap_or((bind, 0))
+                } else {
+                    (host, port)
+                };
+                let session_id = self.session_manager.create_session(connection, host, port).await;
 
                 // Get session guard once to prevent race between spawn and store
-                let session_guard = self.session_manager.get_current_session().await
+                let session_guard = self.session_manager.get_session(&session_id).await
                     .ok_or_else(|| "Failed to get session after creation".to_string())?;
 
-                // Clone connection, spawn task, and store handle in single critical section
+                // Spawn the listener and store its handle in a single critical
+                // section - prevents a race with disconnect
                 {
                     let mut session = session_guard.lock().await;
-                    let connection_clone = session.connection.clone();
-
-                    // Spawn event listener task
-                    let session_manager = self.session_manager.clone();
-                    let task_handle = tokio::spawn(async move {
-                        loop {
-                            // Receive event without holding any locks!
-                            let event_opt = connection_clone.recv_event().await;
-
-                            // Store event (brief lock acquisition)
-                            if let Some(event_set) = event_opt {
-                                if let Some(session_guard) = session_manager.get_current_session().await {
-                                    let mut session = session_guard.lock().await;
-                                    session.last_event = Some(event_set);
-                                } else {
-                                    break; // Session gone
-                                }
-                            } else {
-                                break; // Connection closed
-                            }
-                        }
-                        info!("Event listener task stopped");
-                    });
-
-                    // Store task handle before releasing lock - prevents race with disconnect
-                    session.event_listener_task = Some(task_handle);
+                    session.capabilities = capabilities;
+                    session.listening = listen;
+                    spawn_event_listener(&self.session_manager, &session_id, &mut session, self.notifications.get().cloned());
+                    spawn_keepalive(&self.session_manager, &session_id, &mut session, self.notifications.get().cloned());
                 }
 
-                Ok(format!("Connected to JVM at {}:{} (session: {})", host, port, session_id))
+                let connected = if listen {
+                    format!("Accepted JVM connection on {}", bind)
+                } else {
+                    format!("Connected to JVM at {}:{}", host, port)
+                };
+                Ok(format!(
+                    "{} (session: {})\n   Other tools use this session unless given another session_id",
+                    connected, session_id
+                ))
             }
             Err(e) => Err(format!("Failed to connect: {}", e)),
         }
     }
 
     async fn handle_set_breakpoint(&self, args: serde_json::Value) -> Result<String, String> {
-        let class_pattern = args.get("class_pattern")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| "Missing 'class_pattern' parameter".to_string())?;
+        let location = location_args(&args)?;
 
-        let line = args.get("line")
-            .and_then(|v| v.as_i64())
-            .ok_or_else(|| "Missing 'line' parameter".to_string())? as i32;
+        let options = BreakpointOptions {
+            hit_limit: hit_limit_arg(&args)?,
+            thread: optional_thread_id(&args)?,
+            suspend_policy: suspend_policy_arg(&args)?,
+        };
 
-        let method_hint = args.get("method").and_then(|v| v.as_str());
+        if location.line.is_none() && location.method.is_none() {
+            return Err("Missing 'line' parameter (or pass 'method' to break on method entry)".to_string());
+        }
+        let validate_only = args.get("validate_only").and_then(|v| v.as_bool()).unwrap_or(false);
 
         // Get current session
-        let session_guard = self.session_manager.get_current_session().await
-            .ok_or_else(|| "No active debug session. Use debug.attach first.".to_string())?;
+        let session_guard = self.session_for(&args).await?;
 
         let mut session = session_guard.lock().await;
 
-        // Convert class name to JVM signature format
-        // e.g., "com.example.MyClass" -> "Lcom/example/MyClass;"
-        let signature = if class_pattern.starts_with('L') && class_pattern.ends_with(';') {
-            class_pattern.to_string()
-        } else {
-            format!("L{};", class_pattern.replace('.', "/"))
+        if validate_only {
+            return validate_breakpoint(&mut session, &location).await;
+        }
+
+        let (class, method) = (location.class.as_str(), location.method.as_deref());
+        let bp = match place_breakpoint(&mut session, class, location.line, method, options).await? {
+            Placement::Set(bp) => bp,
+            Placement::Deferred(deferred) => {
+                let mut output = format!(
+                    "⏳ Breakpoint deferred: {} isn't loaded yet\n   Breakpoint ID: {}\n   It will be set when the class loads",
+                    deferred.location(), deferred.id
+                );
+                let suggestions = suggest_loaded_classes(&mut session.connection, class).await;
+                if !suggestions.is_empty() {
+                    output.push_str(&format!(
+                        "\n   ⚠️  Loaded classes with that name: {} (check the package)",
+                        suggestions.join(", ")
+                    ));
+                }
+                return Ok(output);
+            }
         };
 
-        // Find the class
-        let classes = session.connection.classes_by_signature(&signature).await
-            .map_err(|e| format!("Failed to find class: {}", e))?;
+        let mut output = format!(
+            "✅ Breakpoint set at {}\n   Method: {}\n   Breakpoint ID: {}\n   JDWP Request ID: {}",
+            bp.location(), bp.method.as_deref().unwrap_or("?"), bp.id, bp.request_id
+        );
+        if let Some(thread) = bp.thread {
+            let name = thread_name(&mut session.connection, thread).await;
+            output.push_str(&format!("\n   Only on thread {} (0x{:x})", name, thread));
+        }
+        if let Some(limit) = bp.hit_limit {
+            output.push_str(&format!("\n   Stops on hit {} only, then expires", limit));
+        }
+        output.push_str(match bp.suspend_policy {
+            SuspendPolicy::None => "\n   Suspends: none (hits are reported, nothing stops)",
+            SuspendPolicy::EventThread => "\n   Suspends: thread (other threads keep running)",
+            SuspendPolicy::All => "\n   Suspends: all threads",
+        });
+
+        Ok(output)
+    }
+
+    async fn handle_set_breakpoints(&self, args: serde_json::Value) -> Result<String, String> {
+        let specs = args.get("breakpoints")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| "Missing 'breakpoints' parameter".to_string())?;
 
-        if classes.is_empty() {
-            return Err(format!("Class not found: {}", class_pattern));
+        if specs.is_empty() {
+            return Err("'breakpoints' must contain at least one breakpoint".to_string());
         }
 
-        let class = &classes[0];
+        let session_guard = self.session_for(&args).await?;
 
-        // Get methods
-        let methods = session.connection.get_methods(class.type_id).await
-            .map_err(|e| format!("Failed to get methods: {}", e))?;
+        let mut session = session_guard.lock().await;
 
-        // Find the right method (use hint if provided, otherwise find first method containing the line)
-        let mut target_method = None;
+        // Hold events so a breakpoint that fires mid-batch doesn't reach us
+        // before the rest are in place
+        let hold = EventHold::hold(&session.connection).await
+            .map_err(|e| format!("Failed to hold events: {}", e))?;
 
-        for method in &methods {
-            if let Some(hint) = method_hint {
-                if method.name == hint {
-                    target_method = Some(method);
-                    break;
+        let mut report = Vec::with_capacity(specs.len());
+        let mut set_count = 0;
+
+        for (idx, spec) in specs.iter().enumerate() {
+            let location = match location_args(spec) {
+                Ok(location) if location.line.is_some() || location.method.is_some() => location,
+                Err(e) if spec.get("class_pattern").is_some() => {
+                    report.push(format!("  ❌ #{}: {}", idx + 1, e));
+                    continue;
                 }
+                _ => {
+                    report.push(format!("  ❌ #{}: needs 'class_pattern' and 'line' (or 'method')", idx + 1));
+                    continue;
+                }
+            };
+            let target = match (location.line, location.method.as_deref()) {
+                (Some(line), _) => format!("{}:{}", location.class, line),
+                (None, method) => format!("{}.{} entry", location.class, method.unwrap_or("?")),
+            };
+            let hit_limit = match hit_limit_arg(spec) {
+                Ok(hit_limit) => hit_limit,
+                Err(e) => {
+                    report.push(format!("  ❌ {}: {}", target, e));
+                    continue;
+                }
+            };
+
+            let options = BreakpointOptions { hit_limit, ..BreakpointOptions::default() };
+            let result = set_breakpoint_on(
+                &mut session, &location.class, location.line, location.method.as_deref(), options,
+            ).await;
+            match result {
+                Ok(bp) => {
+                    set_count += 1;
+                    report.push(format!(
+                        "  ✅ {} in {} ({})",
+                        target, bp.method.as_deref().unwrap_or("?"), bp.id
+                    ));
+                }
+                Err(e) => report.push(format!("  ❌ {}: {}", target, e)),
             }
+        }
+
+        if let Err(e) = hold.release().await {
+            warn!("Failed to release events: {}", e);
+        }
+
+        Ok(format!(
+            "📍 Set {} of {} breakpoints\n{}",
+            set_count,
+            specs.len(),
+            report.join("\n")
+        ))
+    }
+
+    async fn handle_break_next_line(&self, args: serde_json::Value) -> Result<String, String> {
+        let location = location_args(&args)?;
+        let class_pattern = location.class.as_str();
+        let line = location.line.ok_or_else(|| "Missing 'line' parameter".to_string())? as i32;
+        let method_name = location.method.as_deref();
+
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+
+        let class = find_class(&mut session.connection, class_pattern).await?;
 
-            // Check if this method contains the line
-            if let Ok(line_table) = session.connection.get_line_table(class.type_id, method.method_id).await {
-                if line_table.lines.iter().any(|e| e.line_number == line) {
-                    target_method = Some(method);
-                    break;
+        let methods = session.methods(class.type_id).await
+            .map_err(|e| format!("Failed to get methods: {}", e))?;
+
+        let candidates: Vec<&MethodInfo> = methods.iter()
+            .filter(|m| method_name.is_none_or(|name| m.name == name))
+            .collect();
+        if candidates.is_empty() {
+            return Err(format!("No method {} in class {}", method_name.unwrap_or("?"), class_pattern));
+        }
+
+        // Pick the closest executable line across the candidates (overloads,
+        // or every method when none was named)
+        let mut best: Option<(&MethodInfo, LineTableEntry)> = None;
+        for method in candidates {
+            let Ok(line_table) = session.line_table(class.type_id, method.method_id).await else {
+                continue;
+            };
+            if let Some(entry) = line_table.next_executable_line(line) {
+                if best.as_ref().is_none_or(|(_, b)| entry.line_number < b.line_number) {
+                    best = Some((method, entry.clone()));
                 }
             }
         }
 
-        let method = target_method.ok_or_else(|| {
-            format!("No method found containing line {} in class {}", line, class_pattern)
+        let (method, entry) = best.ok_or_else(|| match method_name {
+            Some(name) => format!("No executable line at or after {} in {}.{}", line, class_pattern, name),
+            None => format!("No executable line at or after {} in {}", line, class_pattern),
         })?;
 
-        // Get line table and find bytecode index for the line
-        let line_table = session.connection.get_line_table(class.type_id, method.method_id).await
-            .map_err(|e| format!("Failed to get line table: {}", e))?;
-
-        let line_entry = line_table.lines.iter()
-            .find(|e| e.line_number == line)
-            .ok_or_else(|| format!("Line {} not found in method {}", line, method.name))?;
-
-        // Set the breakpoint!
-        let request_id = session.connection.set_breakpoint(
-            class.type_id,
-            method.method_id,
-            line_entry.line_code_index,
-            jdwp_client::SuspendPolicy::All,
-        ).await.map_err(|e| format!("Failed to set breakpoint: {}", e))?;
-
-        // Track the breakpoint in session
-        let bp_id = format!("bp_{}", request_id);
-        session.breakpoints.insert(bp_id.clone(), crate::session::BreakpointInfo {
-            id: bp_id.clone(),
-            request_id,
-            class_pattern: class_pattern.to_string(),
-            line: line as u32,
-            method: Some(method.name.clone()),
-            enabled: true,
-            hit_count: 0,
-        });
+        let bp = register_breakpoint(
+            &mut session, class_pattern, class.type_id, method, entry.line_code_index, Some(entry.line_number as u32),
+            BreakpointOptions::default(),
+        ).await?;
 
-        Ok(format!(
-            "✅ Breakpoint set at {}:{}\n   Method: {}\n   Breakpoint ID: {}\n   JDWP Request ID: {}",
-            class_pattern, line, method.name, bp_id, request_id
-        ))
+        let mut output = format!(
+            "✅ Breakpoint set at {}:{}\n   Method: {}\n   Breakpoint ID: {}",
+            class_pattern, entry.line_number, method.name, bp.id
+        );
+        if entry.line_number != line {
+            output.push_str(&format!(
+                "\n   ⚠️  Line {} has no code; moved to the next executable line {}",
+                line, entry.line_number
+            ));
+        }
+
+        Ok(output)
     }
 
-    async fn handle_list_breakpoints(&self, _args: serde_json::Value) -> Result<String, String> {
-        let session_guard = self.session_manager.get_current_session().await
-            .ok_or_else(|| "No active debug session".to_string())?;
+    async fn handle_list_breakpoints(&self, args: serde_json::Value) -> Result<String, String> {
+        let session_guard = self.session_for(&args).await?;
 
         let session = session_guard.lock().await;
 
-        if session.breakpoints.is_empty() {
+        if session.breakpoints.is_empty()
+            && session.deferred_breakpoints.is_empty()
+            && session.watchpoints.is_empty()
+            && session.exception_breakpoints.is_empty()
+        {
             return Ok("No breakpoints set".to_string());
         }
 
@@ -304,18 +876,60 @@ impl RequestHandler {
 
         for (_, bp) in session.breakpoints.iter() {
             output.push_str(&format!(
-                "  {} [{}] {}:{}\n",
+                "  {} [{}] {}\n",
                 if bp.enabled { "✓" } else { "✗" },
                 bp.id,
-                bp.class_pattern,
-                bp.line
+                bp.location()
             ));
             if let Some(method) = &bp.method {
                 output.push_str(&format!("     Method: {}\n", method));
             }
+            if let Some(thread) = bp.thread {
+                output.push_str(&format!("     Thread: 0x{:x}\n", thread));
+            }
+            output.push_str(&format!("     Suspends: {}\n", suspend_policy_name(bp.suspend_policy)));
             if bp.hit_count > 0 {
                 output.push_str(&format!("     Hits: {}\n", bp.hit_count));
             }
+            match bp.hit_limit {
+                Some(limit) if bp.expired() => output.push_str(&format!("     Hit limit: {} (reached, expired)\n", limit)),
+                Some(limit) => output.push_str(&format!("     Hit limit: {}\n", limit)),
+                None => {}
+            }
+        }
+
+        if !session.deferred_breakpoints.is_empty() {
+            output.push_str(&format!(
+                "\n⏳ {} deferred breakpoint(s), waiting for their class to load:\n\n",
+                session.deferred_breakpoints.len()
+            ));
+            for deferred in session.deferred_breakpoints.values() {
+                output.push_str(&format!("  [{}] {}\n", deferred.id, deferred.location()));
+                if let Some(thread) = deferred.thread {
+                    output.push_str(&format!("     Thread: 0x{:x}\n", thread));
+                }
+                output.push_str(&format!("     Suspends: {}\n", suspend_policy_name(deferred.suspend_policy)));
+                if let Some(failure) = &deferred.failure {
+                    output.push_str(&format!("     ❌ Class loaded, but the breakpoint failed: {}\n", failure));
+                }
+            }
+        }
+
+        if !session.watchpoints.is_empty() {
+            output.push_str(&format!("\n👁️ {} watchpoint(s):\n\n", session.watchpoints.len()));
+            for wp in session.watchpoints.values() {
+                output.push_str(&format!(
+                    "  [{}] {}.{} ({})\n",
+                    wp.id, wp.class_pattern, wp.field, watch_kind_label(wp.event_kind)
+                ));
+            }
+        }
+
+        if !session.exception_breakpoints.is_empty() {
+            output.push_str(&format!("\n💥 {} exception breakpoint(s):\n\n", session.exception_breakpoints.len()));
+            for eb in session.exception_breakpoints.values() {
+                output.push_str(&format!("  [{}] {}\n", eb.id, exception_label(eb)));
+            }
         }
 
         Ok(output)
@@ -326,110 +940,611 @@ impl RequestHandler {
             .and_then(|v| v.as_str())
             .ok_or_else(|| "Missing 'breakpoint_id' parameter".to_string())?;
 
-        let session_guard = self.session_manager.get_current_session().await
-            .ok_or_else(|| "No active debug session".to_string())?;
+        let session_guard = self.session_for(&args).await?;
 
         let mut session = session_guard.lock().await;
 
+        if let Some(eb) = session.exception_breakpoints.get(bp_id).cloned() {
+            session.connection.clear_event_request(event_kinds::EXCEPTION, eb.request_id).await
+                .map_err(|e| format!("Failed to clear breakpoint: {}", e))?;
+            session.exception_breakpoints.remove(bp_id);
+
+            return Ok(format!(
+                "✅ Breakpoint cleared: {} on {}\n   JDWP Request ID: {}",
+                bp_id, exception_label(&eb), eb.request_id
+            ));
+        }
+
+        if let Some(deferred) = session.deferred_breakpoints.get(bp_id).cloned() {
+            // A failed one has already stopped watching for its class
+            if deferred.failure.is_none() {
+                session.connection.clear_event_request(event_kinds::CLASS_PREPARE, deferred.request_id).await
+                    .map_err(|e| format!("Failed to clear breakpoint: {}", e))?;
+            }
+            session.deferred_breakpoints.remove(bp_id);
+
+            return Ok(format!("✅ Deferred breakpoint cleared: {} at {}", bp_id, deferred.location()));
+        }
+
         // Find the breakpoint
         let bp_info = session.breakpoints.get(bp_id)
             .ok_or_else(|| format!("Breakpoint not found: {}", bp_id))?
             .clone();
 
-        // Clear the breakpoint in the JVM
-        session.connection.clear_breakpoint(bp_info.request_id).await
-            .map_err(|e| format!("Failed to clear breakpoint: {}", e))?;
+        // Clear the breakpoint in the JVM, unless it already expired there
+        if bp_info.enabled {
+            session.connection.clear_breakpoint(bp_info.request_id).await
+                .map_err(|e| format!("Failed to clear breakpoint: {}", e))?;
+        }
 
         // Remove from session
         session.breakpoints.remove(bp_id);
 
         Ok(format!(
-            "✅ Breakpoint cleared: {} at {}:{}\n   JDWP Request ID: {}",
-            bp_id, bp_info.class_pattern, bp_info.line, bp_info.request_id
+            "✅ Breakpoint cleared: {} at {}\n   JDWP Request ID: {}",
+            bp_id, bp_info.location(), bp_info.request_id
         ))
     }
 
-    async fn handle_continue(&self, _args: serde_json::Value) -> Result<String, String> {
-        let session_guard = self.session_manager.get_current_session().await
-            .ok_or_else(|| "No active debug session".to_string())?;
+    async fn handle_enable_breakpoint(&self, args: serde_json::Value) -> Result<String, String> {
+        let bp_id = args.get("breakpoint_id")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| "Missing 'breakpoint_id' parameter".to_string())?;
+
+        let session_guard = self.session_for(&args).await?;
 
         let mut session = session_guard.lock().await;
 
-        session.connection.resume_all().await
-            .map_err(|e| format!("Failed to resume: {}", e))?;
+        let mut bp = session.breakpoints.get(bp_id)
+            .ok_or_else(|| format!("Breakpoint not found: {}", bp_id))?
+            .clone();
 
-        Ok("▶️  Execution resumed".to_string())
-    }
+        if bp.enabled {
+            return Ok(format!("Breakpoint {} at {} is already enabled", bp_id, bp.location()));
+        }
 
-    async fn handle_step_over(&self, _args: serde_json::Value) -> Result<String, String> {
-        // TODO: Implement step over
-        Ok("Step over not yet implemented".to_string())
-    }
+        // JDWP has no "enable", so set the breakpoint again under a new request
+        let request = request_breakpoint(&mut session, &bp).await?;
+        session.breakpoints.remove(&request.id);
 
-    async fn handle_step_into(&self, _args: serde_json::Value) -> Result<String, String> {
-        // TODO: Implement step into
-        Ok("Step into not yet implemented".to_string())
-    }
+        bp.request_id = request.request_id;
+        bp.enabled = true;
+        if bp.hit_limit.is_some() {
+            // The VM counts towards the limit afresh
+            bp.hit_count = 0;
+        }
+        session.breakpoints.insert(bp.id.clone(), bp.clone());
 
-    async fn handle_step_out(&self, _args: serde_json::Value) -> Result<String, String> {
-        // TODO: Implement step out
-        Ok("Step out not yet implemented".to_string())
+        Ok(format!(
+            "✅ Breakpoint enabled: {} at {}\n   JDWP Request ID: {}",
+            bp_id, bp.location(), bp.request_id
+        ))
     }
 
-    async fn handle_get_stack(&self, args: serde_json::Value) -> Result<String, String> {
-        let session_guard = self.session_manager.get_current_session().await
-            .ok_or_else(|| "No active debug session".to_string())?;
+    async fn handle_disable_breakpoint(&self, args: serde_json::Value) -> Result<String, String> {
+        let bp_id = args.get("breakpoint_id")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| "Missing 'breakpoint_id' parameter".to_string())?;
+
+        let session_guard = self.session_for(&args).await?;
 
         let mut session = session_guard.lock().await;
 
-        let thread_id = args.get("thread_id")
-            .and_then(|v| v.as_str())
-            .and_then(|s| u64::from_str_radix(s.trim_start_matches("0x"), 16).ok());
+        let bp = session.breakpoints.get(bp_id)
+            .ok_or_else(|| format!("Breakpoint not found: {}", bp_id))?
+            .clone();
 
-        let max_frames = args.get("max_frames")
-            .and_then(|v| v.as_i64())
-            .unwrap_or(20) as usize;
+        if !bp.enabled {
+            return Ok(format!("Breakpoint {} at {} is already disabled", bp_id, bp.location()));
+        }
 
-        let include_variables = args.get("include_variables")
-            .and_then(|v| v.as_bool())
-            .unwrap_or(true);
+        // Clear the VM request but keep the breakpoint so it can be enabled again
+        session.connection.clear_breakpoint(bp.request_id).await
+            .map_err(|e| format!("Failed to disable breakpoint: {}", e))?;
 
-        // If no thread specified, get all threads and use the first suspended one
-        let target_thread = if let Some(tid) = thread_id {
-            tid
-        } else {
-            let threads = session.connection.get_all_threads().await
-                .map_err(|e| format!("Failed to get threads: {}", e))?;
+        if let Some(bp) = session.breakpoints.get_mut(bp_id) {
+            bp.enabled = false;
+        }
 
-            *threads.first().ok_or_else(|| "No threads found".to_string())?
+        Ok(format!(
+            "⏸️  Breakpoint disabled: {} at {}\n   Use debug.enable_breakpoint to turn it back on",
+            bp_id, bp.location()
+        ))
+    }
+
+    async fn handle_break_on_exception(&self, args: serde_json::Value) -> Result<String, String> {
+        let exception_class = match args.get("exception_class").and_then(|v| v.as_str()) {
+            Some(class) if !class.is_empty() => {
+                let location = parse_location(class)?;
+                if location.method.is_some() || location.line.is_some() {
+                    return Err(format!("'exception_class' takes a class name, not a method or line: {}", class));
+                }
+                Some(location.class)
+            }
+            _ => None,
         };
 
-        // Get frames (-1 means all frames to avoid INVALID_LENGTH errors)
-        let mut frames = session.connection.get_frames(target_thread, 0, -1).await
-            .map_err(|e| format!("Failed to get frames: {}", e))?;
+        let caught = args.get("caught")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
 
-        // Truncate to max_frames
-        frames.truncate(max_frames);
+        let uncaught = args.get("uncaught")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
 
-        if frames.is_empty() {
-            return Ok(format!("Thread {:x} has no stack frames", target_thread));
+        if !caught && !uncaught {
+            return Err("At least one of 'caught' and 'uncaught' must be true".to_string());
         }
 
-        let mut output = format!("🔍 Stack for thread {:x} ({} frames):\n\n", target_thread, frames.len());
+        let session_guard = self.session_for(&args).await?;
 
-        for (idx, frame) in frames.iter().enumerate() {
-            output.push_str(&format!("Frame {}:\n", idx));
-            output.push_str(&format!("  Location: class={:x}, method={:x}, index={}\n",
-                frame.location.class_id, frame.location.method_id, frame.location.index));
+        let mut session = session_guard.lock().await;
 
-            // Try to get method name
-            if let Ok(methods) = session.connection.get_methods(frame.location.class_id).await {
-                if let Some(method) = methods.iter().find(|m| m.method_id == frame.location.method_id) {
-                    output.push_str(&format!("  Method: {}\n", method.name));
+        let eb = set_exception_breakpoint_at(&mut session, exception_class.as_deref(), caught, uncaught).await?;
 
-                    // Get variables if requested
+        Ok(format!(
+            "💥 Exception breakpoint {} set on {}\n   JDWP Request ID: {}\n   Use debug.run to run until it fires",
+            eb.id, exception_label(&eb), eb.request_id
+        ))
+    }
+
+    async fn handle_set_watchpoint(&self, args: serde_json::Value) -> Result<String, String> {
+        let class_pattern = args.get("class_pattern")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| "Missing 'class_pattern' parameter".to_string())?;
+
+        let field = args.get("field")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| "Missing 'field' parameter".to_string())?;
+
+        let kinds: &[u8] = match args.get("kind").and_then(|v| v.as_str()).unwrap_or("modification") {
+            "access" => &[event_kinds::FIELD_ACCESS],
+            "modification" => &[event_kinds::FIELD_MODIFICATION],
+            "both" => &[event_kinds::FIELD_ACCESS, event_kinds::FIELD_MODIFICATION],
+            other => return Err(format!(
+                "Unknown watchpoint kind '{}' (expected access, modification or both)", other
+            )),
+        };
+
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+
+        if let Some(capabilities) = &session.capabilities {
+            for &kind in kinds {
+                let (supported, name) = if kind == event_kinds::FIELD_ACCESS {
+                    (capabilities.can_watch_field_access, "canWatchFieldAccess")
+                } else {
+                    (capabilities.can_watch_field_modification, "canWatchFieldModification")
+                };
+                if !supported {
+                    return Err(format!(
+                        "This JVM does not support {} watchpoints ({} is false)",
+                        watch_kind_label(kind), name
+                    ));
+                }
+            }
+        }
+
+        let mut output = String::new();
+        for &kind in kinds {
+            let wp = set_watchpoint_at(&mut session, class_pattern, field, kind).await?;
+            output.push_str(&format!(
+                "👁️ Watchpoint {} set on {}.{} ({})\n   JDWP Request ID: {}\n",
+                wp.id, class_pattern, field, watch_kind_label(kind), wp.request_id
+            ));
+        }
+        output.push_str("   Use debug.wait_for_watchpoint to run until it triggers");
+
+        Ok(output)
+    }
+
+    async fn handle_clear_watchpoint(&self, args: serde_json::Value) -> Result<String, String> {
+        let wp_id = args.get("watchpoint_id")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| "Missing 'watchpoint_id' parameter".to_string())?;
+
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+
+        let wp = session.watchpoints.get(wp_id)
+            .ok_or_else(|| format!("Watchpoint not found: {}", wp_id))?
+            .clone();
+
+        session.connection.clear_event_request(wp.event_kind, wp.request_id).await
+            .map_err(|e| format!("Failed to clear watchpoint: {}", e))?;
+
+        session.watchpoints.remove(wp_id);
+
+        Ok(format!(
+            "✅ Watchpoint cleared: {} on {}.{} ({})",
+            wp_id, wp.class_pattern, wp.field, watch_kind_label(wp.event_kind)
+        ))
+    }
+
+    async fn handle_continue(&self, args: serde_json::Value) -> Result<String, String> {
+        let thread_id = optional_thread_id(&args)?;
+
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+
+        if let Some(thread_id) = thread_id {
+            session.connection.resume_thread(thread_id).await
+                .map_err(|e| format!("Failed to resume thread 0x{:x}: {}", thread_id, describe_error(&e)))?;
+            let name = thread_name(&mut session.connection, thread_id).await;
+            return Ok(format!("▶️  Thread {} (0x{:x}) resumed", name, thread_id));
+        }
+
+        session.connection.resume_all().await
+            .map_err(|e| format!("Failed to resume: {}", e))?;
+
+        Ok("▶️  Execution resumed".to_string())
+    }
+
+    async fn handle_run(&self, args: serde_json::Value) -> Result<String, String> {
+        let timeout = args.get("timeout_secs")
+            .and_then(|v| v.as_u64())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_RUN_TIMEOUT);
+
+        let session_guard = self.session_for(&args).await?;
+
+        // Subscribe before resuming so a breakpoint hit right away isn't missed,
+        // and release the session lock while the program runs
+        let (mut connection, mut events) = {
+            let session = session_guard.lock().await;
+            (session.connection.clone(), session.events.subscribe())
+        };
+
+        connection.resume_all().await
+            .map_err(|e| format!("Failed to resume: {}", e))?;
+
+        let stop = wait_for_event(&mut events, timeout, |event_set| event_set.stop_event().cloned()).await?;
+
+        let Some(event) = stop else {
+            return Ok(format!(
+                "▶️  Running; no breakpoint or exception within {}s\n   Use debug.run to keep waiting or debug.pause to stop",
+                timeout.as_secs()
+            ));
+        };
+
+        let session = session_guard.lock().await;
+        Ok(describe_stop(&mut connection, &session, &event).await)
+    }
+
+    async fn handle_wait_for_watchpoint(&self, args: serde_json::Value) -> Result<String, String> {
+        let timeout = args.get("timeout_secs")
+            .and_then(|v| v.as_u64())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_RUN_TIMEOUT);
+
+        let session_guard = self.session_for(&args).await?;
+
+        let (mut connection, mut events) = {
+            let session = session_guard.lock().await;
+            if session.watchpoints.is_empty() {
+                return Err("No watchpoints set; use debug.set_watchpoint first".to_string());
+            }
+            (session.connection.clone(), session.events.subscribe())
+        };
+
+        connection.resume_all().await
+            .map_err(|e| format!("Failed to resume: {}", e))?;
+
+        // A breakpoint or exception also suspends the VM, so stop waiting on those too
+        let stop = wait_for_event(&mut events, timeout, |event_set| event_set.stop_event().cloned()).await?;
+
+        let Some(event) = stop else {
+            return Ok(format!(
+                "▶️  Running; no watchpoint triggered within {}s\n   Use debug.wait_for_watchpoint to keep waiting or debug.pause to stop",
+                timeout.as_secs()
+            ));
+        };
+
+        let session = session_guard.lock().await;
+        let description = describe_stop(&mut connection, &session, &event).await;
+
+        match event.details {
+            EventKind::FieldAccess { .. } | EventKind::FieldModification { .. } => Ok(description),
+            _ => Ok(format!("⚠️  Stopped before any watchpoint triggered\n{}", description)),
+        }
+    }
+
+    async fn handle_wait_for_event(&self, args: serde_json::Value) -> Result<String, String> {
+        let timeout = args.get("timeout_secs")
+            .and_then(|v| v.as_u64())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_RUN_TIMEOUT);
+
+        let session_guard = self.session_for(&args).await?;
+
+        // Unlike debug.run, leave the VM as it is: the caller has already
+        // resumed it, or something else is about to trigger the stop
+        let (mut connection, mut events) = {
+            let session = session_guard.lock().await;
+            (session.connection.clone(), session.events.subscribe())
+        };
+
+        let stop = wait_for_event(&mut events, timeout, |event_set| event_set.stop_event().cloned()).await?;
+
+        let Some(event) = stop else {
+            return Ok(format!(
+                "⏳ No breakpoint, exception or watchpoint within {}s\n   Use debug.wait_for_event to keep waiting or debug.pause to stop",
+                timeout.as_secs()
+            ));
+        };
+
+        let session = session_guard.lock().await;
+        Ok(describe_stop(&mut connection, &session, &event).await)
+    }
+
+    async fn handle_step_over(&self, args: serde_json::Value) -> Result<String, String> {
+        self.single_step(args, step_depths::OVER).await
+    }
+
+    async fn handle_step_into(&self, args: serde_json::Value) -> Result<String, String> {
+        self.single_step(args, step_depths::INTO).await
+    }
+
+    async fn handle_step_out(&self, args: serde_json::Value) -> Result<String, String> {
+        self.single_step(args, step_depths::OUT).await
+    }
+
+    /// Step a thread once and wait for the resulting step event
+    async fn single_step(&self, args: serde_json::Value, depth: i32) -> Result<String, String> {
+        let thread_id = args.get("thread_id")
+            .and_then(|v| v.as_str())
+            .and_then(|s| u64::from_str_radix(s.trim_start_matches("0x"), 16).ok())
+            .ok_or_else(|| "Missing or invalid 'thread_id' parameter".to_string())?;
+
+        // MIN stops at every bytecode instruction, so a line can take many
+        // steps; each step is still a single request matched by its id
+        let size = match args.get("step_size").and_then(|v| v.as_str()).unwrap_or("line") {
+            "line" => step_sizes::LINE,
+            "min" => step_sizes::MIN,
+            other => return Err(format!("Invalid step_size '{}' (expected 'line' or 'min')", other)),
+        };
+
+        let step_filters = step_filters_arg(&args, depth)?;
+
+        let session_guard = self.session_for(&args).await?;
+
+        // Subscribe before resuming so the step event can't be missed, and
+        // release the session lock while the thread runs
+        let (mut connection, mut events, suspend_policy) = {
+            let session = session_guard.lock().await;
+            (session.connection.clone(), session.events.subscribe(), stop_suspend_policy(&session, thread_id))
+        };
+
+        let request_id = connection.set_step_request(
+            thread_id,
+            size,
+            depth,
+            &step_filters,
+            suspend_policy,
+        ).await.map_err(|e| format!("Failed to create step request: {}", describe_error(&e)))?;
+
+        // After a thread-only stop the other threads are still running
+        let resumed = match suspend_policy {
+            SuspendPolicy::EventThread => connection.resume_thread(thread_id).await,
+            _ => connection.resume_all().await,
+        };
+        let result = match resumed {
+            Ok(()) => wait_for_step(&mut events, request_id).await,
+            Err(e) => Err(format!("Failed to resume: {}", e)),
+        };
+
+        // The step request would otherwise fire again on the next step
+        if let Err(e) = connection.clear_step_request(request_id).await {
+            warn!("Failed to clear step request {}: {}", request_id, e);
+        }
+
+        let (thread, location) = result?;
+        let place = LocationResolver::default().describe(&mut connection, &location).await;
+
+        Ok(format!("⏭️  Stepped thread 0x{:x}\n   Location: {}", thread, place))
+    }
+
+    /// Returns a summary block, one text block per frame, and the same frames
+    /// as a JSON resource; with `"format": "json"`, only the JSON, as text
+    async fn handle_get_stack(&self, args: serde_json::Value) -> Result<Vec<ContentBlock>, String> {
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+
+        let thread_id = args.get("thread_id")
+            .and_then(|v| v.as_str())
+            .and_then(|s| u64::from_str_radix(s.trim_start_matches("0x"), 16).ok());
+
+        let max_frames = args.get("max_frames")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(20)
+            .clamp(0, i32::MAX as i64) as i32;
+
+        let start_frame = args.get("start_frame")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0);
+        if !(0..=i32::MAX as i64).contains(&start_frame) {
+            return Err(format!("'start_frame' must not be negative (got {})", start_frame));
+        }
+        let start_frame = start_frame as i32;
+
+        let include_variables = args.get("include_variables")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+
+        let max_variable_depth = args.get("max_variable_depth")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(2)
+            .min(MAX_VARIABLE_DEPTH as u64) as u32;
+
+        let max_result_length = args.get("max_result_length")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(200) as usize;
+
+        let json_only = match args.get("format").and_then(|v| v.as_str()).unwrap_or("text") {
+            "text" => false,
+            "json" => true,
+            other => return Err(format!("Unknown format '{}' (expected text or json)", other)),
+        };
+
+        // Expanded fields only appear in the text, so don't fetch them for JSON
+        let max_variable_depth = if json_only { 0 } else { max_variable_depth };
+
+        // Objects are expanded once per stack, however many locals refer to them
+        let mut expanded_objects = HashSet::new();
+
+        // Roots of expanded object graphs, kept from being collected until the
+        // dump is done
+        let mut pinned_objects = Vec::new();
+
+        // If no thread specified, get all threads and use the first suspended one
+        let target_thread = if let Some(tid) = thread_id {
+            tid
+        } else {
+            let threads = session.connection.get_all_threads().await
+                .map_err(|e| format!("Failed to get threads: {}", e))?;
+
+            *threads.first().ok_or_else(|| "No threads found".to_string())?
+        };
+
+        // Fetch only the requested page; deep recursion can leave thousands of frames
+        let frame_count = session.connection.get_frame_count(target_thread).await
+            .map_err(|e| format!("Failed to get frame count: {}", describe_error(&e)))?;
+
+        if frame_count == 0 {
+            if json_only {
+                let dump = StackDump { thread_id: format!("0x{:x}", target_thread), frame_count, frames: Vec::new() };
+                return Ok(vec![ContentBlock::text(serde_json::to_string_pretty(&dump).unwrap())]);
+            }
+            return Ok(vec![ContentBlock::text(format!("Thread {:x} has no stack frames", target_thread))]);
+        }
+        if start_frame >= frame_count {
+            return Err(format!(
+                "'start_frame' {} is past the last frame (the stack has {} frames)",
+                start_frame, frame_count
+            ));
+        }
+
+        let length = max_frames.min(frame_count - start_frame);
+        let frames = if length > 0 {
+            session.connection.get_frames(target_thread, start_frame, length).await
+                .map_err(|e| format!("Failed to get frames: {}", describe_error(&e)))?
+        } else {
+            Vec::new()
+        };
+
+        let mut header = match frames.len() {
+            0 => format!("🔍 Stack for thread {:x} (no frames shown of {}):", target_thread, frame_count),
+            shown => format!(
+                "🔍 Stack for thread {:x} (frames {}–{} of {}):",
+                target_thread, start_frame, start_frame as usize + shown - 1, frame_count
+            ),
+        };
+        let next_frame = start_frame as usize + frames.len();
+        if next_frame < frame_count as usize {
+            header.push_str(&format!("\n   (use start_frame={} for more)", next_frame));
+        }
+
+        let mut content = vec![ContentBlock::text(header)];
+        let mut frame_dumps = Vec::with_capacity(frames.len());
+
+        // Only VMs that can redefine classes leave frames in obsolete methods
+        let can_redefine = session.capabilities.as_ref().is_some_and(|caps| caps.can_redefine_classes);
+
+        // Walking object graphs takes many round trips; hold events so none
+        // arrive halfway through
+        let hold = if include_variables && max_variable_depth > 0 {
+            match EventHold::hold(&session.connection).await {
+                Ok(hold) => Some(hold),
+                Err(e) => {
+                    debug!("Failed to hold events during stack dump: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        for (offset, frame) in frames.iter().enumerate() {
+            let idx = start_frame as usize + offset;
+            let obsolete = can_redefine && matches!(
+                session.connection.is_method_obsolete(frame.location.class_id, frame.location.method_id).await,
+                Ok(true)
+            );
+
+            // An obsolete method's line table describes the new bytecode
+            let line = if obsolete {
+                None
+            } else {
+                session.line_table(frame.location.class_id, frame.location.method_id).await
+                    .ok()
+                    .and_then(|table| table.line_at(frame.location.index))
+            };
+
+            let class_signature = session.class_signature(frame.location.class_id).await.ok();
+            let class_name = class_signature.as_deref().map(class_name_from_signature);
+            let source_file = session.source_file(frame.location.class_id).await.ok().flatten();
+
+            let mut output = format!("Frame {}:\n", idx);
+            output.push_str(&format!("  Location: class={:x}, method={:x}, index={}, line={}\n",
+                frame.location.class_id, frame.location.method_id, frame.location.index, line_label(line)));
+            if let Some(source) = source_file.as_ref().or(class_name.as_ref()) {
+                output.push_str(&format!("  Source: {}\n", source_position(source, line)));
+            }
+
+            let mut method_name = None;
+            let mut variables = Vec::new();
+
+            // An obsolete method's variable table no longer matches its frame
+            if obsolete {
+                output.push_str("  Method: (obsolete method)\n");
+                method_name = Some("(obsolete method)".to_string());
+            } else if let Ok(methods) = session.methods(frame.location.class_id).await {
+                if let Some(method) = methods.iter().find(|m| m.method_id == frame.location.method_id) {
+                    output.push_str(&format!("  Method: {}\n", method.declaration()));
+                    method_name = Some(method.declaration());
+
+                    // Get variables if requested
                     if include_variables {
-                        match session.connection.get_variable_table(frame.location.class_id, frame.location.method_id).await {
+                        let is_static = method.mod_bits & ACC_STATIC != 0;
+                        let (this, this_id) = if is_static {
+                            ("<static>".to_string(), None)
+                        } else {
+                            match session.connection.get_frame_this(target_thread, frame.frame_id).await {
+                                Ok(Some(this_id)) => {
+                                    let this = Value { tag: b'L', data: ValueData::Object(this_id) };
+                                    (format_value(&mut session.connection, &this).await, Some(this_id))
+                                }
+                                // Native frames have no `this` either
+                                Ok(None) => ("<unavailable>".to_string(), None),
+                                Err(e) => (format!("<unavailable: {}>", describe_error(&e)), None),
+                            }
+                        };
+                        output.push_str(&format!("  this = {}\n", this));
+                        if let Some(this_id) = this_id {
+                            if max_variable_depth > 0 && !expanded_objects.contains(&this_id) {
+                                pin_object(&mut session.connection, this_id, &mut pinned_objects).await;
+                            }
+                            expand_object(&mut session.connection, this_id, max_variable_depth, 4, &mut expanded_objects, &mut output).await;
+                        }
+                        if !is_static {
+                            variables.push(VarDump {
+                                name: "this".to_string(),
+                                type_name: class_signature.as_deref().map(type_name).unwrap_or_else(|| "?".to_string()),
+                                value: this,
+                            });
+                        }
+
+                        match session.variable_table(frame.location.class_id, frame.location.method_id).await {
+                            Err(e) if e.error_code() == Some(error_codes::ABSENT_INFORMATION) => {
+                                output.push_str("  <no local variable info; compile with -g>\n");
+                            }
+                            Err(e) => {
+                                output.push_str(&format!("  Variables: (unavailable: {})\n", describe_error(&e)));
+                            }
                             Ok(var_table) => {
                                 let current_index = frame.location.index;
                                 let active_vars: Vec<_> = var_table.iter()
@@ -446,129 +1561,590 @@ impl RequestHandler {
                                         })
                                         .collect();
 
-                                    if let Ok(values) = session.connection.get_frame_values(target_thread, frame.frame_id, slots).await {
-                                        for (var, value) in active_vars.iter().zip(values.iter()) {
-                                            // Check if this is a string object (tag 115 = 's')
-                                            let formatted_value = if value.tag == 115 {
-                                                // This is a String object
-                                                if let jdwp_client::types::ValueData::Object(object_id) = &value.data {
+                                    match session.connection.get_frame_values(target_thread, frame.frame_id, slots).await {
+                                        Ok(values) => {
+                                            for (var, value) in active_vars.iter().zip(values.iter()) {
+                                                // Collections and boxes read better by content than by their fields
+                                                let rendered = match (value.tag, &value.data) {
+                                                    (b'L', ValueData::Object(object_id)) if *object_id != 0 => {
+                                                        render_known_type(&mut session.connection, *object_id).await
+                                                    }
+                                                    _ => None,
+                                                };
+                                                let formatted_value = match &rendered {
+                                                    Some(text) => text.clone(),
+                                                    None => format_local(&mut session.connection, value, max_result_length).await,
+                                                };
+                                                output.push_str(&format!("    {} = {}\n", var.name, formatted_value));
+                                                variables.push(VarDump {
+                                                    name: var.name.clone(),
+                                                    type_name: type_name(var.generic_signature.as_deref().unwrap_or(&var.signature)),
+                                                    value: formatted_value,
+                                                });
+
+                                                if let (b'L', ValueData::Object(object_id), None) = (value.tag, &value.data, &rendered) {
                                                     if *object_id != 0 {
-                                                        // Try to get the string value
-                                                        match session.connection.get_string_value(*object_id).await {
-                                                            Ok(string_val) => format!("(String) \"{}\"", string_val),
-                                                            Err(_) => value.format(), // Fall back to object ID
+                                                        if max_variable_depth > 0 && !expanded_objects.contains(object_id) {
+                                                            pin_object(&mut session.connection, *object_id, &mut pinned_objects).await;
                                                         }
-                                                    } else {
-                                                        "(String) null".to_string()
+                                                        expand_object(&mut session.connection, *object_id, max_variable_depth, 6, &mut expanded_objects, &mut output).await;
                                                     }
-                                                } else {
-                                                    value.format()
                                                 }
-                                            } else {
-                                                value.format()
-                                            };
-                                            output.push_str(&format!("    {} = {}\n", var.name, formatted_value));
+                                            }
+                                        }
+                                        Err(e) => {
+                                            output.push_str(&format!("    (unavailable: {})\n", describe_error(&e)));
                                         }
                                     }
                                 }
                             }
-                            Err(_) => {}
                         }
                     }
                 }
             }
 
-            output.push_str("\n");
+            content.push(ContentBlock::text(output));
+            frame_dumps.push(FrameDump {
+                index: idx,
+                frame_id: format!("0x{:x}", frame.frame_id),
+                class_id: format!("0x{:x}", frame.location.class_id),
+                method_id: format!("0x{:x}", frame.location.method_id),
+                code_index: frame.location.index,
+                class: class_name,
+                source_file,
+                method: method_name,
+                line,
+                variables,
+            });
         }
 
-        Ok(output)
+        unpin_objects(&mut session.connection, pinned_objects).await;
+        if let Some(hold) = hold {
+            if let Err(e) = hold.release().await {
+                warn!("Failed to release events after stack dump: {}", e);
+            }
+        }
+
+        let dump = StackDump {
+            thread_id: format!("0x{:x}", target_thread),
+            frame_count,
+            frames: frame_dumps,
+        };
+        if json_only {
+            return Ok(vec![ContentBlock::text(serde_json::to_string_pretty(&dump).unwrap())]);
+        }
+        content.push(ContentBlock::json(format!("jdwp://threads/{:x}/stack", target_thread), &dump));
+
+        Ok(content)
     }
 
-    async fn handle_evaluate(&self, _args: serde_json::Value) -> Result<String, String> {
-        // TODO: Implement expression evaluation
-        Ok("Expression evaluation not yet implemented".to_string())
+    async fn handle_evaluate(&self, args: serde_json::Value) -> Result<String, String> {
+        let thread_id = args.get("thread_id")
+            .and_then(|v| v.as_str())
+            .and_then(|s| u64::from_str_radix(s.trim_start_matches("0x"), 16).ok())
+            .ok_or_else(|| "Missing or invalid 'thread_id' parameter".to_string())?;
+
+        let frame_index = args.get("frame_index")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0) as i32;
+
+        let expression = args.get("expression")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| "Missing 'expression' parameter".to_string())?;
+
+        let max_result_length = args.get("max_result_length")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(500) as usize;
+
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+
+        let result = match eval::evaluate(&mut session.connection, thread_id, frame_index, expression).await? {
+            Evaluation::Value(value) => format_value(&mut session.connection, &value).await,
+            Evaluation::NullAt(path) if path == expression.trim() => "null".to_string(),
+            Evaluation::NullAt(path) => format!("null ({} is null)", path),
+        };
+
+        let result = truncate_chars(result, max_result_length);
+
+        Ok(format!("🧮 {} = {}", expression.trim(), result))
     }
 
-    async fn handle_list_threads(&self, _args: serde_json::Value) -> Result<String, String> {
-        let session_guard = self.session_manager.get_current_session().await
-            .ok_or_else(|| "No active debug session".to_string())?;
+    async fn handle_list_threads(&self, args: serde_json::Value) -> Result<String, String> {
+        let session_guard = self.session_for(&args).await?;
 
         let mut session = session_guard.lock().await;
 
-        let threads = session.connection.get_all_threads().await
-            .map_err(|e| format!("Failed to get threads: {}", e))?;
+        let threads = session.connection.list_threads_detailed().await
+            .map_err(|e| format!("Failed to get threads: {}", describe_error(&e)))?;
 
         let mut output = format!("🧵 {} thread(s):\n\n", threads.len());
 
-        for (idx, thread_id) in threads.iter().enumerate() {
-            output.push_str(&format!("  Thread {} (ID: 0x{:x})\n", idx + 1, thread_id));
+        for thread in &threads {
+            output.push_str(&format!("  {} (0x{:x})\n", thread.name, thread.id));
+            output.push_str(&format!("     Status: {}\n", thread_state_label(thread.status, thread.suspend_count)));
+        }
 
-            // Try to get frame count
-            match session.connection.get_frames(*thread_id, 0, 1).await {
-                Ok(frames) if !frames.is_empty() => {
-                    output.push_str("     Status: Has frames (possibly suspended)\n");
-                }
-                Ok(_) => {
-                    output.push_str("     Status: Running (no frames)\n");
-                }
-                Err(_) => {
-                    output.push_str("     Status: Cannot inspect\n");
+        session.threads = threads.into_iter().map(|thread| (thread.id, thread)).collect();
+
+        Ok(output)
+    }
+
+    async fn handle_thread_tree(&self, args: serde_json::Value) -> Result<String, String> {
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+        let connection = &mut session.connection;
+
+        let top_level = connection.get_top_level_thread_groups().await
+            .map_err(|e| format!("Failed to get thread groups: {}", describe_error(&e)))?;
+
+        let mut output = "🌳 Thread groups:\n\n".to_string();
+
+        // Depth-first, so each group's members follow it
+        let mut pending: Vec<(ThreadGroupId, usize)> = top_level.into_iter().rev().map(|group| (group, 0)).collect();
+        let mut visited = HashSet::new();
+        while let Some((group_id, depth)) = pending.pop() {
+            if !visited.insert(group_id) {
+                continue;
+            }
+            let pad = "  ".repeat(depth + 1);
+            let name = connection.get_thread_group_name(group_id).await
+                .unwrap_or_else(|_| "?".to_string());
+            output.push_str(&format!("{}📁 {} (0x{:x})\n", pad, name, group_id));
+
+            let children = match connection.get_thread_group_children(group_id).await {
+                Ok(children) => children,
+                Err(e) => {
+                    output.push_str(&format!("{}  (members unavailable: {})\n", pad, describe_error(&e)));
+                    continue;
                 }
+            };
+            for thread_id in children.threads {
+                let name = thread_name(connection, thread_id).await;
+                let status = thread_status(connection, thread_id).await;
+                output.push_str(&format!("{}  🧵 {} (0x{:x}): {}\n", pad, name, thread_id, status));
             }
+            pending.extend(children.groups.into_iter().rev().map(|group| (group, depth + 1)));
         }
 
         Ok(output)
     }
 
-    async fn handle_pause(&self, _args: serde_json::Value) -> Result<String, String> {
-        let session_guard = self.session_manager.get_current_session().await
-            .ok_or_else(|| "No active debug session".to_string())?;
+    async fn handle_pause(&self, args: serde_json::Value) -> Result<String, String> {
+        let thread_id = optional_thread_id(&args)?;
+
+        let session_guard = self.session_for(&args).await?;
 
         let mut session = session_guard.lock().await;
 
+        if let Some(thread_id) = thread_id {
+            session.connection.suspend_thread(thread_id).await
+                .map_err(|e| format!("Failed to suspend thread 0x{:x}: {}", thread_id, describe_error(&e)))?;
+            let name = thread_name(&mut session.connection, thread_id).await;
+            return Ok(format!("⏸️  Thread {} (0x{:x}) paused", name, thread_id));
+        }
+
         session.connection.suspend_all().await
             .map_err(|e| format!("Failed to suspend: {}", e))?;
 
         Ok("⏸️  Execution paused (all threads suspended)".to_string())
     }
 
-    async fn handle_disconnect(&self, _args: serde_json::Value) -> Result<String, String> {
-        let current_session_id = self.session_manager.get_current_session_id().await;
+    async fn handle_snapshot(&self, args: serde_json::Value) -> Result<String, String> {
+        let max_frames = args.get("max_frames")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(10) as usize;
+
+        let session_guard = self.session_for(&args).await?;
 
-        if let Some(session_id) = current_session_id {
-            // Remove the session (this will also clear current session)
-            self.session_manager.remove_session(&session_id).await;
-            Ok(format!("✅ Disconnected from debug session: {}", session_id))
-        } else {
-            Err("No active debug session to disconnect".to_string())
+        let mut session = session_guard.lock().await;
+        let connection = &mut session.connection;
+
+        let started = Instant::now();
+        connection.suspend_all().await
+            .map_err(|e| format!("Failed to suspend: {}", e))?;
+        let suspended_at = Instant::now();
+
+        // Resumes even if gathering bails out early
+        let resume = ResumeGuard::new(connection.clone());
+
+        let threads = connection.get_all_threads().await
+            .map_err(|e| format!("Failed to get threads: {}", e))?;
+
+        let mut resolver = LocationResolver::default();
+        let mut report = String::new();
+
+        for thread_id in &threads {
+            let name = thread_name(connection, *thread_id).await;
+            report.push_str(&format!("\nThread \"{}\" (0x{:x}):\n", name, thread_id));
+
+            let frames = match connection.get_frames(*thread_id, 0, -1).await {
+                Ok(frames) => frames,
+                Err(e) => {
+                    report.push_str(&format!("  (frames unavailable: {})\n", describe_error(&e)));
+                    continue;
+                }
+            };
+            if frames.is_empty() {
+                report.push_str("  (no frames)\n");
+            }
+
+            for (idx, frame) in frames.iter().take(max_frames).enumerate() {
+                let location = resolver.describe(connection, &frame.location).await;
+                report.push_str(&format!("  #{} {}\n", idx, location));
+            }
+            if frames.len() > max_frames {
+                report.push_str(&format!("  … {} more frames\n", frames.len() - max_frames));
+            }
         }
-    }
 
-    async fn handle_get_last_event(&self, _args: serde_json::Value) -> Result<String, String> {
-        let session_guard = self.session_manager.get_current_session().await
-            .ok_or_else(|| "No active debug session".to_string())?;
+        resume.resume().await
+            .map_err(|e| format!("Snapshot taken but failed to resume: {}", e))?;
+        let resumed_at = Instant::now();
 
-        let session = session_guard.lock().await;
+        Ok(format!(
+            "📸 Snapshot of {} threads (suspend took {}ms, paused for {}ms)\n{}",
+            threads.len(),
+            suspended_at.duration_since(started).as_millis(),
+            resumed_at.duration_since(suspended_at).as_millis(),
+            report
+        ))
+    }
 
-        if let Some(event_set) = &session.last_event {
-            let mut output = format!("🎯 Last event (suspend_policy={})\n\n", event_set.suspend_policy);
+    async fn handle_detect_deadlock(&self, args: serde_json::Value) -> Result<String, String> {
+        let session_guard = self.session_for(&args).await?;
 
-            for (idx, event) in event_set.events.iter().enumerate() {
-                output.push_str(&format!("Event {}:\n", idx + 1));
-                output.push_str(&format!("  Request ID: {}\n", event.request_id));
+        let mut session = session_guard.lock().await;
+        let connection = &mut session.connection;
+
+        // Monitor ownership only holds still while every thread is suspended
+        connection.suspend_all().await
+            .map_err(|e| format!("Failed to suspend: {}", e))?;
+        let resume = ResumeGuard::new(connection.clone());
+
+        let threads = connection.get_all_threads().await
+            .map_err(|e| format!("Failed to get threads: {}", e))?;
+
+        let mut owners: HashMap<ObjectId, ThreadId> = HashMap::new();
+        let mut blocked_on: HashMap<ThreadId, ObjectId> = HashMap::new();
+        for thread_id in &threads {
+            // Threads that exit meanwhile are skipped
+            if let Ok(monitors) = connection.get_owned_monitors(*thread_id).await {
+                for monitor in monitors {
+                    owners.insert(monitor, *thread_id);
+                }
+            }
+
+            // Threads in Object.wait also report a monitor, but they wait
+            // for a notify rather than for the owner
+            let blocked = matches!(connection.get_thread_status(*thread_id).await, Ok((ThreadStatus::Monitor, _)));
+            if blocked {
+                if let Ok(Some(monitor)) = connection.get_current_contended_monitor(*thread_id).await {
+                    blocked_on.insert(*thread_id, monitor);
+                }
+            }
+        }
+
+        let waits_for: HashMap<ThreadId, (ObjectId, ThreadId)> = blocked_on.iter()
+            .filter_map(|(thread_id, monitor)| owners.get(monitor).map(|owner| (*thread_id, (*monitor, *owner))))
+            .collect();
+
+        let deadlocks = find_deadlocks(&waits_for);
+
+        let mut output = if deadlocks.is_empty() {
+            format!(
+                "✅ No deadlocks among {} threads ({} blocked on a monitor)\n",
+                threads.len(), blocked_on.len()
+            )
+        } else {
+            format!("💀 {} deadlock(s) among {} threads\n", deadlocks.len(), threads.len())
+        };
+
+        let mut monitor_types: HashMap<ObjectId, String> = HashMap::new();
+        for (idx, cycle) in deadlocks.iter().enumerate() {
+            output.push_str(&format!("\nDeadlock {} ({} threads):\n", idx + 1, cycle.len()));
+            for thread_id in cycle {
+                let (monitor, owner) = waits_for[thread_id];
+                if let std::collections::hash_map::Entry::Vacant(entry) = monitor_types.entry(monitor) {
+                    entry.insert(object_class_name(connection, monitor).await);
+                }
+                output.push_str(&format!(
+                    "  \"{}\" (0x{:x}) waits for {} @{:x}, held by \"{}\" (0x{:x})\n",
+                    thread_name(connection, *thread_id).await, thread_id,
+                    monitor_types[&monitor], monitor,
+                    thread_name(connection, owner).await, owner
+                ));
+            }
+        }
+
+        resume.resume().await
+            .map_err(|e| format!("Deadlock check done but failed to resume: {}", e))?;
+
+        Ok(output)
+    }
+
+    async fn handle_disconnect(&self, args: serde_json::Value) -> Result<String, String> {
+        let session_id = self.target_session_id(&args).await
+            .map_err(|_| match args.get("session_id").and_then(|v| v.as_str()) {
+                Some(session_id) => format!("Unknown session: {} (see debug.list_sessions)", session_id),
+                None => "No active debug session to disconnect".to_string(),
+            })?;
+
+        // Remove the session (this also clears the current session if it was this one)
+        self.session_manager.remove_session(&session_id).await;
+        Ok(format!("✅ Disconnected from debug session: {}", session_id))
+    }
+
+    async fn handle_list_sessions(&self, _args: serde_json::Value) -> Result<String, String> {
+        let sessions = self.session_manager.list_sessions().await;
+        if sessions.is_empty() {
+            return Ok("No debug sessions. Use debug.attach to start one.".to_string());
+        }
+
+        let current = self.session_manager.get_current_session_id().await;
+
+        let mut output = format!("🔌 {} debug session(s):\n\n", sessions.len());
+        for (session_id, session_guard) in sessions {
+            let session = session_guard.lock().await;
+            let state = if session.connection.vm_exited() {
+                "VM exited"
+            } else if session.connection.is_connected() {
+                "connected"
+            } else {
+                "disconnected"
+            };
+            output.push_str(&format!(
+                "  {} {} → {}:{} ({}, {} breakpoint(s))\n",
+                if current.as_ref() == Some(&session_id) { "*" } else { " " },
+                session_id, session.host, session.port, state, session.breakpoints.len()
+            ));
+        }
+        output.push_str("\n* = current session, used by tools called without a session_id");
+
+        Ok(output)
+    }
+
+    async fn handle_list_members(&self, args: serde_json::Value) -> Result<String, String> {
+        let class_pattern = args.get("class_pattern")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| "Missing 'class_pattern' parameter".to_string())?;
+
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+
+        let class = find_class(&mut session.connection, class_pattern).await?;
+
+        let fields = session.connection.get_fields_with_generic(class.type_id).await
+            .map_err(|e| format!("Failed to get fields: {}", e))?;
+        let methods = session.methods(class.type_id).await
+            .map_err(|e| format!("Failed to get methods: {}", e))?;
+
+        let mut output = format!("🏛️  {}\n", class_name_from_signature(&class.signature));
+
+        output.push_str(&format!("\nFields ({}):\n", fields.len()));
+        for field in &fields {
+            let modifier = if field.mod_bits & ACC_STATIC != 0 { "static " } else { "" };
+            output.push_str(&format!("  {}{} {}\n", modifier, field.type_name(), field.name));
+        }
+
+        output.push_str(&format!("\nMethods ({}):\n", methods.len()));
+        for method in &methods {
+            let modifier = if method.mod_bits & ACC_STATIC != 0 { "static " } else { "" };
+            output.push_str(&format!("  {}{}  [0x{:x}]\n", modifier, method.declaration(), method.method_id));
+        }
+
+        Ok(output)
+    }
+
+    async fn handle_find_classes(&self, args: serde_json::Value) -> Result<String, String> {
+        // No pattern lists every loaded class
+        let pattern = args.get("pattern")
+            .and_then(|v| v.as_str())
+            .unwrap_or("");
+
+        let max_results = args.get("max_results")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(50) as usize;
+
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+
+        let all_classes = session.connection.get_all_classes().await
+            .map_err(|e| format!("Failed to list classes: {}", e))?;
+
+        let mut matches: Vec<(String, &ClassInfo)> = all_classes.iter()
+            .map(|class| (class_name_from_signature(&class.signature), class))
+            .filter(|(name, _)| class_name_matches(name, pattern))
+            .collect();
+        matches.sort_by(|a, b| a.0.cmp(&b.0));
+
+        if matches.is_empty() {
+            return Ok(format!("No loaded classes match '{}'", pattern));
+        }
+
+        let mut output = if pattern.is_empty() {
+            format!("🔎 {} loaded classes:\n\n", matches.len())
+        } else {
+            format!("🔎 {} loaded classes match '{}':\n\n", matches.len(), pattern)
+        };
+        for (name, class) in matches.iter().take(max_results) {
+            output.push_str(&format!("  {} {} [{}]\n", class.kind(), name, class.class_status()));
+        }
+        if matches.len() > max_results {
+            output.push_str(&format!("  … {} more\n", matches.len() - max_results));
+        }
+
+        Ok(output)
+    }
+
+    async fn handle_bytecodes(&self, args: serde_json::Value) -> Result<String, String> {
+        let class_pattern = args.get("class_pattern")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| "Missing 'class_pattern' parameter".to_string())?;
+
+        let method_spec = args.get("method")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| "Missing 'method' parameter".to_string())?;
+
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+
+        let class = find_class(&mut session.connection, class_pattern).await?;
+
+        let methods = session.methods(class.type_id).await
+            .map_err(|e| format!("Failed to get methods: {}", e))?;
+
+        let method = find_method_by_spec(&methods, method_spec)
+            .map_err(|e| format!("{} in class {}", e, class_pattern))?
+            .clone();
+
+        let bytecodes = session.connection.get_bytecodes(class.type_id, method.method_id).await
+            .map_err(|e| format!("Failed to get bytecode: {}", describe_error(&e)))?;
+
+        let line_table = session.line_table(class.type_id, method.method_id).await.ok();
+
+        let mut output = format!(
+            "🧾 {}.{}{}: {} bytes of bytecode\n\n",
+            class_pattern, method.name, method.signature, bytecodes.len()
+        );
+        output.push_str(&format_bytecodes(&bytecodes, line_table.as_ref()));
+        if line_table.is_none() && !bytecodes.is_empty() {
+            output.push_str("\n(no line table; was the class compiled with -g?)\n");
+        }
+
+        Ok(output)
+    }
+
+    async fn handle_describe_class(&self, args: serde_json::Value) -> Result<String, String> {
+        let class_pattern = args.get("class_pattern")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| "Missing 'class_pattern' parameter".to_string())?;
+
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+
+        let class = find_class(&mut session.connection, class_pattern).await?;
+
+        // The status from the lookup may predate initialization
+        let status = session.connection.get_class_status(class.type_id).await
+            .unwrap_or_else(|_| class.class_status());
+
+        let mut output = format!("🏛️  {} {}\n", class.kind(), class_name_from_signature(&class.signature));
+        output.push_str(&format!("   Type ID: 0x{:x}\n", class.type_id));
+        output.push_str(&format!("   Status: {}\n", status));
+
+        if status.contains(ClassStatus::ERROR) {
+            output.push_str("   ⚠️  Initialization failed; the class is unusable\n");
+        } else if !status.is_initialized() {
+            output.push_str("   ⚠️  Not initialized yet; static fields still hold default values\n");
+        }
+
+        let static_fields: Vec<_> = session.connection.get_fields(class.type_id).await
+            .map_err(|e| format!("Failed to get fields: {}", e))?
+            .into_iter()
+            .filter(|field| field.mod_bits & ACC_STATIC != 0)
+            .collect();
+
+        if !static_fields.is_empty() {
+            let field_ids = static_fields.iter().map(|field| field.field_id).collect();
+            match session.connection.get_reference_type_values(class.type_id, field_ids).await {
+                Ok(values) => {
+                    output.push_str(&format!("\nStatic fields ({}):\n", static_fields.len()));
+                    for (field, value) in static_fields.iter().zip(&values) {
+                        let formatted_value = format_value(&mut session.connection, value).await;
+                        output.push_str(&format!("  {} {} = {}\n", type_name(&field.signature), field.name, formatted_value));
+                    }
+                }
+                Err(e) => output.push_str(&format!("\nStatic fields: unavailable ({})\n", e)),
+            }
+        }
+
+        Ok(output)
+    }
+
+    async fn handle_exit_vm(&self, args: serde_json::Value) -> Result<String, String> {
+        // Terminating the debuggee can't be undone, so require an explicit opt-in
+        if !args.get("confirm").and_then(|v| v.as_bool()).unwrap_or(false) {
+            return Err("debug.exit_vm terminates the target JVM; pass confirm=true to proceed".to_string());
+        }
+
+        let exit_code = args.get("exit_code").and_then(|v| v.as_i64()).unwrap_or(0) as i32;
+
+        let session_id = self.target_session_id(&args).await?;
+        let session_guard = self.session_for(&args).await?;
+
+        let mut connection = session_guard.lock().await.connection.clone();
+
+        match connection.exit(exit_code).await {
+            // The VM may close the socket before it gets to reply
+            Ok(()) | Err(JdwpError::ConnectionClosed) => {}
+            Err(e) => return Err(format!("Failed to exit VM: {}", e)),
+        }
+
+        self.session_manager.terminate_session(&session_id).await;
+
+        Ok(format!(
+            "🛑 Target JVM exited with code {}\n   Session {} terminated",
+            exit_code, session_id
+        ))
+    }
+
+    async fn handle_get_last_event(&self, args: serde_json::Value) -> Result<String, String> {
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+
+        if let Some(event_set) = session.last_event.clone() {
+            let mut output = format!("🎯 Last event (suspend_policy={})\n\n", event_set.suspend_policy);
+
+            for (idx, event) in event_set.events.iter().enumerate() {
+                output.push_str(&format!("Event {}:\n", idx + 1));
+                output.push_str(&format!("  Request ID: {}\n", event.request_id));
 
                 match &event.details {
                     jdwp_client::events::EventKind::Breakpoint { thread, location } => {
+                        let line = session.line_table(location.class_id, location.method_id).await
+                            .ok()
+                            .and_then(|table| table.line_at(location.index));
                         output.push_str("  Type: Breakpoint\n");
                         output.push_str(&format!("  ⚡ Thread ID: 0x{:x}\n", thread));
-                        output.push_str(&format!("  Location: class=0x{:x}, method=0x{:x}, index={}\n",
-                            location.class_id, location.method_id, location.index));
+                        output.push_str(&format!("  Location: class=0x{:x}, method=0x{:x}, index={}, line={}\n",
+                            location.class_id, location.method_id, location.index, line_label(line)));
                     }
                     jdwp_client::events::EventKind::Step { thread, location } => {
+                        let line = session.line_table(location.class_id, location.method_id).await
+                            .ok()
+                            .and_then(|table| table.line_at(location.index));
                         output.push_str("  Type: Step\n");
                         output.push_str(&format!("  Thread ID: 0x{:x}\n", thread));
-                        output.push_str(&format!("  Location: class=0x{:x}, method=0x{:x}, index={}\n",
-                            location.class_id, location.method_id, location.index));
+                        output.push_str(&format!("  Location: class=0x{:x}, method=0x{:x}, index={}, line={}\n",
+                            location.class_id, location.method_id, location.index, line_label(line)));
                     }
                     jdwp_client::events::EventKind::VMStart { thread } => {
                         output.push_str("  Type: VM Start\n");
@@ -595,7 +2171,7 @@ impl RequestHandler {
                     }
                 }
 
-                output.push_str("\n");
+                output.push('\n');
             }
 
             Ok(output)
@@ -603,4 +2179,4687 @@ impl RequestHandler {
             Ok("No events received yet. Set a breakpoint and trigger it.".to_string())
         }
     }
+
+    async fn handle_inspect_object(&self, args: serde_json::Value) -> Result<String, String> {
+        let object_id = args.get("object_id")
+            .and_then(|v| v.as_str())
+            .and_then(|s| u64::from_str_radix(s.trim_start_matches("0x"), 16).ok())
+            .ok_or_else(|| "Missing or invalid 'object_id' parameter".to_string())?;
+
+        let include_inherited = args.get("include_inherited")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+
+        let ref_type = session.connection.get_object_reference_type(object_id).await
+            .map_err(|e| format!("Failed to get object type: {}", e))?;
+
+        let class_signature = session.connection.get_signature(ref_type).await
+            .map_err(|e| format!("Failed to get class signature: {}", e))?;
+
+        let fields: Vec<DeclaredField> = if include_inherited {
+            session.connection.get_all_fields(ref_type).await
+                .map_err(|e| format!("Failed to get fields: {}", e))?
+        } else {
+            session.connection.get_fields(ref_type).await
+                .map_err(|e| format!("Failed to get fields: {}", e))?
+                .into_iter()
+                .map(|field| DeclaredField { declaring_type: ref_type, field })
+                .collect()
+        };
+
+        // Static fields belong to the class, not the instance
+        let fields: Vec<DeclaredField> = fields.into_iter()
+            .filter(|f| f.field.mod_bits & ACC_STATIC == 0)
+            .collect();
+
+        let mut output = format!("📦 Object 0x{:x} ({})\n\n", object_id, class_name_from_signature(&class_signature));
+
+        if fields.is_empty() {
+            output.push_str("  (no instance fields)\n");
+            return Ok(output);
+        }
+
+        let field_ids = fields.iter().map(|f| f.field.field_id).collect();
+        let values = session.connection.get_object_values(object_id, field_ids).await
+            .map_err(|e| format!("Failed to get field values: {}", e))?;
+
+        // Resolve each declaring class name once
+        let mut declaring_names: HashMap<u64, String> = HashMap::new();
+
+        for (field, value) in fields.iter().zip(values.iter()) {
+            let formatted_value = format_value(&mut session.connection, value).await;
+            output.push_str(&format!("  {} = {}", field.field.name, formatted_value));
+
+            if field.declaring_type != ref_type {
+                if let std::collections::hash_map::Entry::Vacant(entry) = declaring_names.entry(field.declaring_type) {
+                    let name = match session.connection.get_signature(field.declaring_type).await {
+                        Ok(sig) => class_name_from_signature(&sig),
+                        Err(_) => format!("0x{:x}", field.declaring_type),
+                    };
+                    entry.insert(name);
+                }
+                output.push_str(&format!("  (from {})", declaring_names[&field.declaring_type]));
+            }
+
+            output.push('\n');
+        }
+
+        Ok(output)
+    }
+
+    async fn handle_get_this(&self, args: serde_json::Value) -> Result<String, String> {
+        let thread_id = args.get("thread_id")
+            .and_then(|v| v.as_str())
+            .and_then(|s| u64::from_str_radix(s.trim_start_matches("0x"), 16).ok())
+            .ok_or_else(|| "Missing or invalid 'thread_id' parameter".to_string())?;
+
+        let frame_index = args.get("frame_index")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0) as i32;
+
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+
+        let frames = session.connection.get_frames(thread_id, frame_index, 1).await
+            .map_err(|e| format!("Failed to get frame {}: {}", frame_index, describe_error(&e)))?;
+
+        let frame = frames.first()
+            .ok_or_else(|| format!("Thread 0x{:x} has no frame {}", thread_id, frame_index))?;
+
+        let this_object = session.connection.get_frame_this(thread_id, frame.frame_id).await
+            .map_err(|e| format!("Failed to get 'this': {}", describe_error(&e)))?;
+
+        let Some(object_id) = this_object else {
+            return Ok(format!(
+                "Frame {} is a static or native method, so 'this' is null",
+                frame_index
+            ));
+        };
+
+        let class_name = match session.connection.get_object_reference_type(object_id).await {
+            Ok(ref_type) => match session.connection.get_signature(ref_type).await {
+                Ok(sig) => class_name_from_signature(&sig),
+                Err(_) => format!("0x{:x}", ref_type),
+            },
+            Err(_) => "<unknown class>".to_string(),
+        };
+
+        Ok(format!(
+            "👤 this = {} @{:x}\n   Object ID: 0x{:x}\n   Use debug.inspect_object to see its fields",
+            class_name, object_id, object_id
+        ))
+    }
+
+    async fn handle_capabilities(&self, args: serde_json::Value) -> Result<String, String> {
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+
+        // Retry if the queries failed on attach
+        if session.connection.version().is_none() {
+            if let Err(e) = session.connection.get_version().await {
+                warn!("Failed to get VM version: {}", e);
+            }
+        }
+        if session.capabilities.is_none() {
+            let capabilities = session.connection.get_capabilities_new().await
+                .map_err(|e| format!("Failed to get capabilities: {}", e))?;
+            session.capabilities = Some(capabilities);
+        }
+        let capabilities = session.capabilities.as_ref().unwrap();
+
+        let mut output = match session.connection.version() {
+            Some(version) => format!(
+                "🧰 {} {} (JDWP {}.{})\n\nCapabilities:\n",
+                version.vm_name, version.vm_version, version.jdwp_major, version.jdwp_minor
+            ),
+            None => "🧰 JVM capabilities (protocol version unknown):\n\n".to_string(),
+        };
+        for (name, supported) in capabilities.entries() {
+            output.push_str(&format!("  {} {}\n", if supported { "✅" } else { "❌" }, name));
+        }
+
+        let unavailable: Vec<String> = tools::get_tools()
+            .into_iter()
+            .filter(|tool| !tools::is_supported(&tool.name, capabilities))
+            .map(|tool| tool.name)
+            .collect();
+        if !unavailable.is_empty() {
+            output.push_str(&format!("\nUnavailable tools: {}\n", unavailable.join(", ")));
+        }
+
+        Ok(output)
+    }
+
+    async fn handle_classpath(&self, args: serde_json::Value) -> Result<String, String> {
+        let session_guard = self.session_for(&args).await?;
+        let mut session = session_guard.lock().await;
+
+        let paths = session.connection.get_class_paths().await
+            .map_err(|e| format!("Failed to get class paths: {}", e))?;
+
+        let mut output = format!("📂 Classpath (relative to {}):\n", paths.base_dir);
+        if paths.classpaths.is_empty() {
+            output.push_str("  (empty)\n");
+        }
+        for entry in &paths.classpaths {
+            output.push_str(&format!("  {}\n", entry));
+        }
+        // Java 9+ has no boot classpath and reports it empty
+        if !paths.bootclasspaths.is_empty() {
+            output.push_str("\nBoot classpath:\n");
+            for entry in &paths.bootclasspaths {
+                output.push_str(&format!("  {}\n", entry));
+            }
+        }
+
+        Ok(output)
+    }
+
+    async fn handle_stats(&self, args: serde_json::Value) -> Result<String, String> {
+        let session_guard = self.session_for(&args).await?;
+
+        let stats = session_guard.lock().await.connection.stats();
+
+        let mut output = "📊 Session statistics\n\n".to_string();
+        output.push_str(&format!("  Commands sent:    {}\n", stats.commands_sent));
+        output.push_str(&format!("  Replies received: {}\n", stats.replies_received));
+        output.push_str(&format!("  Events received:  {}\n", stats.events_received));
+
+        match stats.average_reply_latency {
+            Some(latency) => output.push_str(&format!("  Avg reply latency: {:.2}ms\n", latency.as_secs_f64() * 1000.0)),
+            None => output.push_str("  Avg reply latency: (no replies yet)\n"),
+        }
+
+        if stats.errors_by_code.is_empty() {
+            output.push_str("  Errors: none\n");
+        } else {
+            output.push_str("  Errors:\n");
+            for (code, count) in &stats.errors_by_code {
+                output.push_str(&format!("    {} ({}): {}\n", error_name(*code), code, count));
+            }
+        }
+
+        Ok(output)
+    }
+
+    async fn handle_pop_frame(&self, args: serde_json::Value) -> Result<String, String> {
+        let thread_id = args.get("thread_id")
+            .and_then(|v| v.as_str())
+            .and_then(|s| u64::from_str_radix(s.trim_start_matches("0x"), 16).ok())
+            .ok_or_else(|| "Missing or invalid 'thread_id' parameter".to_string())?;
+
+        let frame_index = args.get("frame_index")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0) as i32;
+
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+
+        let frames = session.connection.get_frames(thread_id, frame_index, 1).await
+            .map_err(|e| format!("Failed to get frame {}: {}", frame_index, describe_error(&e)))?;
+
+        let frame = frames.first()
+            .ok_or_else(|| format!("Thread 0x{:x} has no frame {}", thread_id, frame_index))?;
+
+        session.connection.pop_frames(thread_id, frame.frame_id).await
+            .map_err(|e| format!("Failed to pop frame {}: {}", frame_index, describe_error(&e)))?;
+
+        let mut output = format!(
+            "⏏️  Popped {} frame(s) on thread 0x{:x}\n",
+            frame_index + 1, thread_id
+        );
+
+        // Frame IDs change after a pop, so look the new top frame up again
+        if let Ok(frames) = session.connection.get_frames(thread_id, 0, 1).await {
+            if let Some(top) = frames.first() {
+                let place = LocationResolver::default().describe(&mut session.connection, &top.location).await;
+                output.push_str(&format!("   Now at: {}\n", place));
+                output.push_str("   The call is re-run when the thread steps or resumes\n");
+            }
+        }
+
+        Ok(output)
+    }
+
+    async fn handle_force_return(&self, args: serde_json::Value) -> Result<String, String> {
+        let thread_id = args.get("thread_id")
+            .and_then(|v| v.as_str())
+            .and_then(|s| u64::from_str_radix(s.trim_start_matches("0x"), 16).ok())
+            .ok_or_else(|| "Missing or invalid 'thread_id' parameter".to_string())?;
+
+        let return_value = args.get("value").cloned().unwrap_or(serde_json::Value::Null);
+
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+
+        let frames = session.connection.get_frames(thread_id, 0, 1).await
+            .map_err(|e| format!("Failed to get current frame: {}", describe_error(&e)))?;
+
+        let location = frames.into_iter().next()
+            .ok_or_else(|| format!("Thread 0x{:x} has no frames", thread_id))?
+            .location;
+
+        let methods = session.methods(location.class_id).await
+            .map_err(|e| format!("Failed to get methods: {}", e))?;
+
+        let method = methods.iter()
+            .find(|m| m.method_id == location.method_id)
+            .ok_or_else(|| format!("Method 0x{:x} not found", location.method_id))?;
+
+        let method_name = method.name.clone();
+        let return_signature = method.signature.rsplit(')').next().unwrap_or("V").to_string();
+
+        let connection = &mut session.connection;
+
+        let value = if return_signature == "V" {
+            if !return_value.is_null() {
+                return Err(format!("{} returns void; omit 'value'", method_name));
+            }
+            Value { tag: b'V', data: ValueData::Void }
+        } else if return_signature == "Ljava/lang/String;" && return_value.is_string() {
+            let string_id = connection.create_string(return_value.as_str().unwrap()).await
+                .map_err(|e| format!("Failed to create string: {}", e))?;
+            Value { tag: b's', data: ValueData::Object(string_id) }
+        } else {
+            value_from_json(&return_signature, &return_value)?
+        };
+
+        connection.force_early_return(thread_id, value.clone()).await
+            .map_err(|e| format!("Failed to force return from {}: {}", method_name, describe_error(&e)))?;
+
+        let returned = match value.data {
+            ValueData::Void => "returns".to_string(),
+            _ => format!("returns {}", format_value(connection, &value).await),
+        };
+
+        Ok(format!(
+            "↩️  {} on thread 0x{:x} {} without running the rest of its body\n   The return happens when the thread steps or resumes\n",
+            method_name, thread_id, returned
+        ))
+    }
+
+    async fn handle_set_variable(&self, args: serde_json::Value) -> Result<String, String> {
+        let thread_id = args.get("thread_id")
+            .and_then(|v| v.as_str())
+            .and_then(|s| u64::from_str_radix(s.trim_start_matches("0x"), 16).ok())
+            .ok_or_else(|| "Missing or invalid 'thread_id' parameter".to_string())?;
+
+        let frame_index = args.get("frame_index")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0) as i32;
+
+        let name = args.get("name")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| "Missing 'name' parameter".to_string())?;
+
+        let new_value = args.get("value")
+            .ok_or_else(|| "Missing 'value' parameter".to_string())?;
+
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+        let frame = session.connection.get_frames(thread_id, frame_index, 1).await
+            .map_err(|e| format!("Failed to get frame {}: {}", frame_index, describe_error(&e)))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("Thread 0x{:x} has no frame {}", thread_id, frame_index))?;
+
+        let var_table = session.variable_table(frame.location.class_id, frame.location.method_id).await
+            .map_err(|e| format!("Failed to get variables (was the class compiled with -g?): {}", describe_error(&e)))?;
+
+        let connection = &mut session.connection;
+
+        let current_index = frame.location.index;
+        let variable = var_table.iter()
+            .filter(|v| current_index >= v.code_index && current_index < v.code_index + v.length as u64)
+            .find(|v| v.name == name)
+            .ok_or_else(|| format!("No variable '{}' in scope in frame {}", name, frame_index))?;
+
+        let value = if variable.signature == "Ljava/lang/String;" && new_value.is_string() {
+            let string_id = connection.create_string(new_value.as_str().unwrap()).await
+                .map_err(|e| format!("Failed to create string: {}", e))?;
+            // Tag 's' also lets format_value show the string contents
+            Value { tag: b's', data: ValueData::Object(string_id) }
+        } else {
+            value_from_json(&variable.signature, new_value)?
+        };
+
+        connection.set_frame_values(thread_id, frame.frame_id, vec![(variable.slot as i32, value.clone())]).await
+            .map_err(|e| format!("Failed to set '{}': {}", name, describe_error(&e)))?;
+
+        Ok(format!(
+            "✏️  Set {} ({}) = {}",
+            name, type_name(&variable.signature), format_value(connection, &value).await
+        ))
+    }
+
+    async fn handle_inspect_array(&self, args: serde_json::Value) -> Result<String, String> {
+        let array_id = args.get("array_id")
+            .and_then(|v| v.as_str())
+            .and_then(|s| u64::from_str_radix(s.trim_start_matches("0x"), 16).ok())
+            .ok_or_else(|| "Missing or invalid 'array_id' parameter".to_string())?;
+
+        let first = args.get("first")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0);
+
+        let length = args.get("length")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(20);
+
+        if first < 0 {
+            return Err(format!("'first' must not be negative (got {})", first));
+        }
+        if length < 0 {
+            return Err(format!("'length' must not be negative (got {})", length));
+        }
+
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+
+        let array_length = session.connection.get_array_length(array_id).await
+            .map_err(|e| format!("Failed to get array length: {}", e))?;
+
+        let range = ArrayRange::clamp(first, length, array_length);
+
+        let values = if range.length > 0 {
+            session.connection.get_array_values(array_id, range.first, range.length).await
+                .map_err(|e| format!("Failed to get elements: {}", e))?
+        } else {
+            Vec::new()
+        };
+
+        let mut output = format!("🔢 Array 0x{:x}, length = {}\n", array_id, array_length);
+        if let Some(note) = range.note {
+            output.push_str(&format!("   ({})\n", note));
+        }
+        output.push('\n');
+
+        if values.is_empty() {
+            output.push_str("  (no elements in range)\n");
+        }
+
+        for (offset, value) in values.iter().enumerate() {
+            let formatted_value = format_value(&mut session.connection, value).await;
+            output.push_str(&format!("  [{}] = {}\n", range.first as usize + offset, formatted_value));
+        }
+
+        let remaining = array_length - (range.first + range.length);
+        if remaining > 0 {
+            output.push_str(&format!("  … {} more\n", remaining));
+        }
+
+        Ok(output)
+    }
+
+    async fn handle_set_array_element(&self, args: serde_json::Value) -> Result<String, String> {
+        let array_id = args.get("array_id")
+            .and_then(|v| v.as_str())
+            .and_then(|s| u64::from_str_radix(s.trim_start_matches("0x"), 16).ok())
+            .ok_or_else(|| "Missing or invalid 'array_id' parameter".to_string())?;
+
+        let index = args.get("index")
+            .and_then(|v| v.as_i64())
+            .ok_or_else(|| "Missing 'index' parameter".to_string())?;
+        let index = i32::try_from(index)
+            .ok()
+            .filter(|&index| index >= 0)
+            .ok_or_else(|| format!("'index' must be between 0 and {} (got {})", i32::MAX, index))?;
+
+        let new_value = args.get("value")
+            .ok_or_else(|| "Missing 'value' parameter".to_string())?;
+
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+        let connection = &mut session.connection;
+
+        let type_id = connection.get_object_reference_type(array_id).await
+            .map_err(|e| format!("Failed to get type of 0x{:x}: {}", array_id, describe_error(&e)))?;
+        let signature = connection.get_signature(type_id).await
+            .map_err(|e| format!("Failed to get type of 0x{:x}: {}", array_id, e))?;
+        let component = signature.strip_prefix('[')
+            .ok_or_else(|| format!("0x{:x} is a {}, not an array", array_id, type_name(&signature)))?;
+
+        let value = if component == "Ljava/lang/String;" && new_value.is_string() {
+            let string_id = connection.create_string(new_value.as_str().unwrap()).await
+                .map_err(|e| format!("Failed to create string: {}", e))?;
+            Value { tag: b's', data: ValueData::Object(string_id) }
+        } else {
+            value_from_json(component, new_value)?
+        };
+
+        if let Err(e) = connection.set_array_values(array_id, index, vec![value.clone()]).await {
+            return Err(match e.error_code() {
+                Some(error_codes::INVALID_INDEX | error_codes::INVALID_LENGTH) => {
+                    match connection.get_array_length(array_id).await {
+                        Ok(length) => format!("Index {} is out of bounds for length {}", index, length),
+                        Err(_) => format!("Index {} is out of bounds", index),
+                    }
+                }
+                Some(error_codes::TYPE_MISMATCH) => {
+                    format!("The value's type doesn't match the array's ({})", type_name(&signature))
+                }
+                _ => format!("Failed to set element {}: {}", index, describe_error(&e)),
+            });
+        }
+
+        Ok(format!(
+            "✏️  Set ({} @{:x})[{}] = {}",
+            type_name(&signature), array_id, index, format_value(connection, &value).await
+        ))
+    }
+
+    async fn handle_inspect_collection(&self, args: serde_json::Value) -> Result<String, String> {
+        let thread_id = args.get("thread_id")
+            .and_then(|v| v.as_str())
+            .and_then(|s| u64::from_str_radix(s.trim_start_matches("0x"), 16).ok())
+            .ok_or_else(|| "Missing or invalid 'thread_id' parameter".to_string())?;
+
+        let object_id = args.get("object_id")
+            .and_then(|v| v.as_str())
+            .and_then(|s| u64::from_str_radix(s.trim_start_matches("0x"), 16).ok())
+            .ok_or_else(|| "Missing or invalid 'object_id' parameter".to_string())?;
+
+        let max_elements = args.get("max_elements")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(20)
+            .clamp(1, MAX_COLLECTION_ELEMENTS);
+
+        let session_guard = self.session_for(&args).await?;
+
+        let mut session = session_guard.lock().await;
+        let connection = &mut session.connection;
+
+        let ref_type = connection.get_object_reference_type(object_id).await
+            .map_err(|e| format!("Failed to get object type: {}", e))?;
+
+        let class_name = match connection.get_signature(ref_type).await {
+            Ok(sig) => class_name_from_signature(&sig),
+            Err(_) => format!("0x{:x}", ref_type),
+        };
+
+        let interfaces = connection.get_all_interfaces(ref_type).await
+            .map_err(|e| format!("Failed to get interfaces: {}", e))?;
+
+        let mut interface_signatures = Vec::with_capacity(interfaces.len());
+        for interface in interfaces {
+            if let Ok(sig) = connection.get_signature(interface).await {
+                interface_signatures.push(sig);
+            }
+        }
+
+        let kind = CollectionKind::detect(&interface_signatures).ok_or_else(|| {
+            format!("{} is not a java.util collection or map; use debug.inspect_object instead", class_name)
+        })?;
+
+        // What the invocations return is referenced by nothing in the VM;
+        // keep it from being collected until it has been read
+        let mut pinned_objects = Vec::new();
+        let result = render_collection(
+            connection, thread_id, object_id, &class_name, kind, max_elements, &mut pinned_objects,
+        ).await;
+        unpin_objects(connection, pinned_objects).await;
+        result
+    }
+}
+
+/// Serialize a method's result; failing to is a server bug, reported to
+/// the client as INTERNAL_ERROR instead of panicking the request task
+fn result_value<T: serde::Serialize>(result: T) -> Result<serde_json::Value, JsonRpcError> {
+    serde_json::to_value(result).map_err(|e| JsonRpcError {
+        code: INTERNAL_ERROR,
+        message: format!("Failed to serialize result: {}", e),
+        data: None,
+    })
+}
+
+/// Forward the session's JVM events to its broadcast channel and `last_event`
+///
+/// The task handle is stored on the session so disconnect can stop it.
+fn spawn_event_listener(
+    session_manager: &SessionManager,
+    session_id: &str,
+    session: &mut DebugSession,
+    notifications: Option<mpsc::Sender<serde_json::Value>>,
+) {
+    let mut connection = session.connection.clone();
+    let events = session.events.clone();
+    let session_manager = session_manager.clone();
+    let session_id = session_id.to_string();
+
+    let task_handle = tokio::spawn(async move {
+        let mut resolver = LocationResolver::default();
+
+        // Receive events without holding any locks
+        while let Some(event_set) = connection.recv_event().await {
+            // Publish before locking so waiting handlers see it even
+            // while they hold the session. No subscribers is fine.
+            let _ = events.send(event_set.clone());
+
+            // Tell the client about stops it didn't ask to wait for
+            if let (Some(sender), Some(event)) = (&notifications, event_set.stop_event()) {
+                let mut notification = stop_notification(&mut connection, &mut resolver, &event_set, event).await;
+                notification["params"]["data"]["session_id"] = json!(session_id);
+                if let Err(e) = sender.try_send(notification) {
+                    warn!("Dropped event notification: {}", e);
+                }
+            }
+
+            // Store event (brief lock acquisition)
+            let Some(session_guard) = session_manager.get_session(&session_id).await else {
+                break; // Session gone
+            };
+            let mut session = session_guard.lock().await;
+            // Loading a deferred breakpoint's class isn't worth reporting
+            if install_deferred_breakpoints(&mut session, &event_set).await {
+                continue;
+            }
+            record_breakpoint_hits(&mut session, &event_set);
+            session.last_event = Some(event_set);
+        }
+        info!("Event listener task stopped");
+    });
+
+    session.event_listener_task = Some(task_handle);
+}
+
+/// Spawn a task that checks the session's connection is alive while it's
+/// idle, if keepalive is enabled
+///
+/// Each check is a VirtualMachine.Version command, which is harmless even
+/// while the VM is suspended. If it fails, the connection is closed and the
+/// client told, instead of the next tool call finding out by timing out.
+fn spawn_keepalive(
+    session_manager: &SessionManager,
+    session_id: &str,
+    session: &mut DebugSession,
+    notifications: Option<mpsc::Sender<serde_json::Value>>,
+) {
+    if let Some(task) = session.keepalive_task.take() {
+        task.abort();
+    }
+    let Some(interval) = session_manager.keepalive() else {
+        return;
+    };
+
+    let session_manager = session_manager.clone();
+    let session_id = session_id.to_string();
+
+    let task_handle = tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        ticks.tick().await;
+
+        let reason = loop {
+            ticks.tick().await;
+
+            let Some(session_guard) = session_manager.get_session(&session_id).await else {
+                return; // Session gone
+            };
+            let mut connection = {
+                // A locked session is in use by a tool call, so it isn't idle
+                let Ok(session) = session_guard.try_lock() else {
+                    continue;
+                };
+                if session.last_activity.elapsed() < interval {
+                    continue;
+                }
+                session.connection.clone()
+            };
+
+            // A VM that exited was already reported through its VMDeath event
+            if connection.vm_exited() {
+                return;
+            }
+            if !connection.is_connected() {
+                break "connection to the VM closed".to_string();
+            }
+            if let Err(e) = connection.get_version().await {
+                connection.close();
+                break format!("VM stopped answering: {}", describe_error(&e));
+            }
+        };
+
+        warn!("Session {} disconnected: {}", session_id, reason);
+        if let Some(sender) = notifications {
+            let notification = json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/message",
+                "params": {
+                    "level": "warning",
+                    "logger": "jdwp",
+                    "data": {
+                        "event": "disconnected",
+                        "session_id": session_id,
+                        "reason": reason,
+                    },
+                },
+            });
+            if let Err(e) = sender.try_send(notification) {
+                warn!("Dropped disconnect notification: {}", e);
+            }
+        }
+    });
+
+    session.keepalive_task = Some(task_handle);
+}
+
+/// Upper bound on elements rendered by debug.inspect_collection
+const MAX_COLLECTION_ELEMENTS: i64 = 200;
+
+/// Resumes the VM when dropped, unless `resume` was called
+///
+/// Drop can't await, so an early return or panic resumes from a spawned task.
+struct ResumeGuard {
+    connection: Option<JdwpConnection>,
+}
+
+impl ResumeGuard {
+    fn new(connection: JdwpConnection) -> Self {
+        Self { connection: Some(connection) }
+    }
+
+    async fn resume(mut self) -> Result<(), JdwpError> {
+        match self.connection.take() {
+            Some(mut connection) => connection.resume_all().await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ResumeGuard {
+    fn drop(&mut self) {
+        if let Some(mut connection) = self.connection.take() {
+            tokio::spawn(async move {
+                if let Err(e) = connection.resume_all().await {
+                    warn!("Failed to resume after an interrupted snapshot: {}", e);
+                }
+            });
+        }
+    }
+}
+
+/// Holds the VM's events until `release` is called or the guard is dropped
+///
+/// HoldEvents is one VM-wide switch, so holds don't nest: take at most one
+/// at a time (the session lock ensures this). Drop can't await, so an early
+/// return or panic releases from a spawned task.
+struct EventHold {
+    connection: Option<JdwpConnection>,
+}
+
+impl EventHold {
+    async fn hold(connection: &JdwpConnection) -> Result<Self, JdwpError> {
+        let mut connection = connection.clone();
+        connection.hold_events().await?;
+        Ok(Self { connection: Some(connection) })
+    }
+
+    async fn release(mut self) -> Result<(), JdwpError> {
+        match self.connection.take() {
+            Some(mut connection) => connection.release_events().await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for EventHold {
+    fn drop(&mut self) {
+        if let Some(mut connection) = self.connection.take() {
+            tokio::spawn(async move {
+                if let Err(e) = connection.release_events().await {
+                    warn!("Failed to release held events: {}", e);
+                }
+            });
+        }
+    }
+}
+
+/// Turns frame locations into "Class.method(...) line N", caching lookups
+#[derive(Default)]
+struct LocationResolver {
+    class_names: HashMap<ReferenceTypeId, String>,
+    methods: HashMap<ReferenceTypeId, Vec<MethodInfo>>,
+    line_tables: HashMap<(ReferenceTypeId, u64), Option<LineTable>>,
+}
+
+impl LocationResolver {
+    async fn describe(&mut self, connection: &mut JdwpConnection, location: &Location) -> String {
+        let (class_name, method, line) = self.resolve(connection, location).await;
+
+        match line {
+            Some(line) => format!("{}.{} line {}", class_name, method, line),
+            None => format!("{}.{} (index {}, line {})", class_name, method, location.index, line_label(None)),
+        }
+    }
+
+    /// Class name, method name and source line (if known) of a location
+    async fn resolve(&mut self, connection: &mut JdwpConnection, location: &Location) -> (String, String, Option<i32>) {
+        let class_id = location.class_id;
+
+        if let std::collections::hash_map::Entry::Vacant(entry) = self.class_names.entry(class_id) {
+            let name = match connection.get_signature(class_id).await {
+                Ok(sig) => class_name_from_signature(&sig),
+                Err(_) => format!("0x{:x}", class_id),
+            };
+            entry.insert(name);
+        }
+        if let std::collections::hash_map::Entry::Vacant(entry) = self.methods.entry(class_id) {
+            entry.insert(connection.get_methods(class_id).await.unwrap_or_default());
+        }
+        let key = (class_id, location.method_id);
+        if let std::collections::hash_map::Entry::Vacant(entry) = self.line_tables.entry(key) {
+            entry.insert(connection.get_line_table(class_id, location.method_id).await.ok());
+        }
+
+        let class_name = self.class_names[&class_id].clone();
+        let method = self.methods[&class_id].iter()
+            .find(|m| m.method_id == location.method_id)
+            .map(|m| m.name.clone())
+            .unwrap_or_else(|| "?".to_string());
+        let line = self.line_tables[&key].as_ref()
+            .and_then(|table| table.line_at(location.index));
+
+        (class_name, method, line)
+    }
+}
+
+/// Build a notifications/message telling the client the program stopped
+/// (see `EventSet::stop_event`)
+async fn stop_notification(
+    connection: &mut JdwpConnection,
+    resolver: &mut LocationResolver,
+    event_set: &EventSet,
+    event: &Event,
+) -> serde_json::Value {
+    let kind = match event.details {
+        EventKind::Breakpoint { .. } => "breakpoint",
+        EventKind::Exception { .. } => "exception",
+        EventKind::FieldAccess { .. } => "field_access",
+        EventKind::FieldModification { .. } => "field_modification",
+        _ => "vm_death",
+    };
+    let suspend_policy = match event_set.suspend_policy {
+        0 => "none",
+        1 => "event_thread",
+        _ => "all",
+    };
+
+    let mut data = json!({
+        "event": kind,
+        "request_id": event.request_id,
+        "suspend_policy": suspend_policy,
+    });
+    if let Some(thread) = event.details.thread() {
+        data["thread_id"] = json!(format!("0x{:x}", thread));
+    }
+    if let Some(location) = event.details.location() {
+        let (class_name, method, line) = resolver.resolve(connection, location).await;
+        data["class"] = json!(class_name);
+        data["method"] = json!(method);
+        data["line"] = json!(line);
+    }
+    if let EventKind::Exception { exception, .. } = event.details {
+        data["exception"] = json!(describe_exception(connection, exception).await);
+        data["exception_id"] = json!(format!("0x{:x}", exception));
+    }
+
+    json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/message",
+        "params": {
+            "level": "info",
+            "logger": "jdwp",
+            "data": data,
+        },
+    })
+}
+
+/// Upper bound on elements rendered by debug.inspect_array
+const MAX_ARRAY_ELEMENTS: i64 = 200;
+
+/// The slice of an array debug.inspect_array will actually read
+#[derive(Debug, PartialEq, Eq)]
+struct ArrayRange {
+    first: i32,
+    length: i32,
+    /// Why the requested range was adjusted, if it was
+    note: Option<String>,
+}
+
+impl ArrayRange {
+    /// Fit a requested (non-negative) range to the array and the element cap
+    fn clamp(first: i64, length: i64, array_length: i32) -> Self {
+        let array_length = i64::from(array_length.max(0));
+
+        if first > array_length || (first == array_length && length > 0) {
+            return Self {
+                first: array_length as i32,
+                length: 0,
+                note: Some(format!("first index {} is past the end of the array", first)),
+            };
+        }
+
+        let available = array_length - first;
+        let clamped = length.min(available).min(MAX_ARRAY_ELEMENTS);
+
+        let note = if clamped == length {
+            None
+        } else if clamped == available {
+            Some(format!("requested {} elements, only {} available from index {}", length, available, first))
+        } else {
+            Some(format!("showing at most {} elements", MAX_ARRAY_ELEMENTS))
+        };
+
+        Self { first: first as i32, length: clamped as i32, note }
+    }
+}
+
+/// Collection interfaces recognised by debug.inspect_collection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CollectionKind {
+    List,
+    Set,
+    Collection,
+    Map,
+}
+
+impl CollectionKind {
+    /// Pick the most specific kind from a type's implemented interfaces
+    fn detect(interface_signatures: &[String]) -> Option<Self> {
+        let implements = |sig: &str| interface_signatures.iter().any(|s| s == sig);
+
+        if implements("Ljava/util/Map;") {
+            Some(Self::Map)
+        } else if implements("Ljava/util/List;") {
+            Some(Self::List)
+        } else if implements("Ljava/util/Set;") {
+            Some(Self::Set)
+        } else if implements("Ljava/util/Collection;") {
+            Some(Self::Collection)
+        } else {
+            None
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::List => "List",
+            Self::Set => "Set",
+            Self::Collection => "Collection",
+            Self::Map => "Map",
+        }
+    }
+}
+
+/// Render a collection through size() and toArray() (of entrySet() for maps)
+///
+/// Every object an invocation returns is pinned and added to `pinned`.
+async fn render_collection(
+    connection: &mut JdwpConnection,
+    thread_id: ThreadId,
+    object_id: ObjectId,
+    class_name: &str,
+    kind: CollectionKind,
+    max_elements: i64,
+    pinned: &mut Vec<ObjectId>,
+) -> Result<String, String> {
+    let size = match invoke_no_arg(connection, thread_id, object_id, "size", "()I", pinned).await?.data {
+        ValueData::Int(size) => size,
+        _ => return Err("size() did not return an int".to_string()),
+    };
+
+    // Collections and maps are both rendered through toArray(); for maps the
+    // elements are the Map.Entry objects of entrySet()
+    let array_source = match kind {
+        CollectionKind::Map => match invoke_no_arg(connection, thread_id, object_id, "entrySet", "()Ljava/util/Set;", pinned).await?.data {
+            ValueData::Object(entry_set) if entry_set != 0 => entry_set,
+            _ => return Err("entrySet() returned null".to_string()),
+        },
+        _ => object_id,
+    };
+
+    let array_id = match invoke_no_arg(connection, thread_id, array_source, "toArray", "()[Ljava/lang/Object;", pinned).await?.data {
+        ValueData::Object(array_id) if array_id != 0 => array_id,
+        _ => return Err("toArray() returned null".to_string()),
+    };
+
+    let length = connection.get_array_length(array_id).await
+        .map_err(|e| format!("Failed to get array length: {}", e))?;
+    let shown = (length as i64).min(max_elements) as i32;
+
+    let elements = connection.get_array_values(array_id, 0, shown).await
+        .map_err(|e| format!("Failed to get elements: {}", e))?;
+
+    let mut output = format!("📚 {} ({}), size = {}\n\n", class_name, kind.label(), size);
+
+    for (idx, element) in elements.iter().enumerate() {
+        let line = match kind {
+            CollectionKind::Map => {
+                let entry = match element.data {
+                    ValueData::Object(entry) if entry != 0 => entry,
+                    _ => {
+                        output.push_str(&format!("  [{}] = null\n", idx));
+                        continue;
+                    }
+                };
+                let key = invoke_no_arg(connection, thread_id, entry, "getKey", "()Ljava/lang/Object;", pinned).await?;
+                let value = invoke_no_arg(connection, thread_id, entry, "getValue", "()Ljava/lang/Object;", pinned).await?;
+                format!(
+                    "  {} => {}\n",
+                    format_value(connection, &key).await,
+                    format_value(connection, &value).await
+                )
+            }
+            _ => format!("  [{}] = {}\n", idx, format_value(connection, element).await),
+        };
+        output.push_str(&line);
+    }
+
+    if (size as i64) > elements.len() as i64 {
+        output.push_str(&format!("  … {} more\n", size as i64 - elements.len() as i64));
+    }
+
+    Ok(output)
+}
+
+/// Invoke a no-argument method on an object, looking it up by name and signature
+///
+/// Only the given thread is resumed while the method runs. A returned object
+/// is pinned and added to `pinned`; re-enable its collection once done.
+async fn invoke_no_arg(
+    connection: &mut JdwpConnection,
+    thread_id: ThreadId,
+    object_id: u64,
+    name: &str,
+    signature: &str,
+    pinned: &mut Vec<ObjectId>,
+) -> Result<Value, String> {
+    let ref_type = connection.get_object_reference_type(object_id).await
+        .map_err(|e| format!("Failed to get object type: {}", e))?;
+
+    let (class_id, method) = connection.find_method(ref_type, name, signature).await
+        .map_err(|e| format!("Failed to look up {}(): {}", name, e))?
+        .ok_or_else(|| format!("Method {}{} not found", name, signature))?;
+
+    let value = connection.invoke_object_method(
+        object_id,
+        thread_id,
+        class_id,
+        method.method_id,
+        Vec::new(),
+        invoke_options::INVOKE_SINGLE_THREADED,
+    ).await.map_err(|e| match e {
+        JdwpError::InvocationException(exception) => {
+            format!("{}() threw exception @{:x}", name, exception)
+        }
+        e => format!("Failed to invoke {}(): {}", name, e),
+    })?;
+
+    if let ValueData::Object(result_id) = value.data {
+        if result_id != 0 {
+            pin_object(connection, result_id, pinned).await;
+        }
+    }
+    Ok(value)
+}
+
+/// Wait for the first event set `matcher` accepts, or `None` on timeout
+///
+/// Event sets the matcher rejects are skipped; the session listener still
+/// records them.
+async fn wait_for_event<T>(
+    events: &mut broadcast::Receiver<EventSet>,
+    timeout: Duration,
+    mut matcher: impl FnMut(&EventSet) -> Option<T>,
+) -> Result<Option<T>, String> {
+    let deadline = tokio::time::Instant::now() + timeout;
+
+    loop {
+        let event_set = match tokio::time::timeout_at(deadline, events.recv()).await {
+            Ok(Ok(event_set)) => event_set,
+            Ok(Err(broadcast::error::RecvError::Lagged(skipped))) => {
+                warn!("Event wait lagged, skipped {} event sets", skipped);
+                continue;
+            }
+            Ok(Err(broadcast::error::RecvError::Closed)) => {
+                return Err("Event stream closed (the VM may have exited)".to_string());
+            }
+            Err(_) => return Ok(None),
+        };
+
+        if let Some(found) = matcher(&event_set) {
+            return Ok(Some(found));
+        }
+    }
+}
+
+/// Classes debug.step_into steps through unless `step_filters` is given,
+/// so it lands on the next line of the program's own code
+const DEFAULT_STEP_INTO_FILTERS: &[&str] = &[
+    "java.*",
+    "javax.*",
+    "jdk.*",
+    "sun.*",
+    "com.sun.*",
+    "kotlin.*",
+    "org.springframework.*",
+];
+
+/// Read a step's `step_filters` argument (class patterns to step through)
+///
+/// Without it, step-into skips JDK and common framework classes and other
+/// steps are unfiltered. An empty list turns filtering off.
+fn step_filters_arg(args: &serde_json::Value, depth: i32) -> Result<Vec<String>, String> {
+    let Some(filters) = args.get("step_filters") else {
+        return Ok(if depth == step_depths::INTO {
+            DEFAULT_STEP_INTO_FILTERS.iter().map(|p| p.to_string()).collect()
+        } else {
+            Vec::new()
+        });
+    };
+
+    filters.as_array()
+        .ok_or_else(|| "'step_filters' must be an array of class patterns".to_string())?
+        .iter()
+        .map(|p| p.as_str()
+            .map(str::to_string)
+            .ok_or_else(|| format!("Invalid step filter {}: expected a class pattern string", p)))
+        .collect()
+}
+
+/// Wait for the step event generated by `request_id`
+///
+/// Other events in the stream (thread starts, breakpoints, unrelated steps)
+/// are skipped.
+async fn wait_for_step(
+    events: &mut broadcast::Receiver<EventSet>,
+    request_id: i32,
+) -> Result<(ThreadId, Location), String> {
+    let step = wait_for_event(events, STEP_TIMEOUT, |event_set| {
+        event_set.step_event(request_id).map(|(thread, location)| (thread, location.clone()))
+    }).await?;
+
+    step.ok_or_else(|| format!(
+        "Step did not complete within {}s (the thread may be blocked)",
+        STEP_TIMEOUT.as_secs()
+    ))
+}
+
+/// Resolve a class:line to a code location
+async fn resolve_line_breakpoint(
+    session: &mut DebugSession,
+    class_pattern: &str,
+    line: i32,
+    method_hint: Option<&str>,
+) -> Result<BreakpointTarget, String> {
+    let class = find_class(&mut session.connection, class_pattern).await?;
+
+    // Get methods
+    let methods = session.methods(class.type_id).await
+        .map_err(|e| format!("Failed to get methods: {}", e))?;
+
+    // Find the right method (use hint if provided, otherwise find first method containing the line)
+    let mut target_method = None;
+
+    for method in &methods {
+        if let Some(hint) = method_hint {
+            if method.name == hint {
+                target_method = Some(method);
+                break;
+            }
+        }
+
+        // Check if this method contains the line
+        if let Ok(line_table) = session.line_table(class.type_id, method.method_id).await {
+            if line_table.lines.iter().any(|e| e.line_number == line) {
+                target_method = Some(method);
+                break;
+            }
+        }
+    }
+
+    let Some(method) = target_method else {
+        let nearby = nearby_lines(session, class.type_id, &methods, line).await;
+        return Err(with_nearby_lines(
+            format!("No method found containing line {} in class {}", line, class_pattern),
+            &nearby,
+        ));
+    };
+
+    // Get line table and find bytecode index for the line
+    let line_table = session.line_table(class.type_id, method.method_id).await
+        .map_err(|e| format!("Failed to get line table: {}", e))?;
+
+    let Some(line_entry) = line_table.lines.iter().find(|e| e.line_number == line) else {
+        let nearby = nearby_lines(session, class.type_id, std::slice::from_ref(method), line).await;
+        return Err(with_nearby_lines(format!("Line {} not found in method {}", line, method.name), &nearby));
+    };
+
+    Ok(BreakpointTarget {
+        class_id: class.type_id,
+        method: method.clone(),
+        index: line_entry.line_code_index,
+        line: Some(line_entry.line_number as u32),
+    })
+}
+
+/// Lines nearest a breakpoint line that has no code, for correcting it
+const NEARBY_LINES: usize = 5;
+
+/// The lines with code in `methods` closest to `line`, in source order,
+/// e.g. "41 (process)"
+async fn nearby_lines(session: &mut DebugSession, class_id: ReferenceTypeId, methods: &[MethodInfo], line: i32) -> Vec<String> {
+    let mut lines: Vec<(i32, &str)> = Vec::new();
+    for method in methods {
+        if let Ok(line_table) = session.line_table(class_id, method.method_id).await {
+            lines.extend(line_table.lines.iter().map(|e| (e.line_number, method.name.as_str())));
+        }
+    }
+    lines.sort();
+    lines.dedup_by_key(|(line, _)| *line);
+
+    // Closest first, earlier lines winning ties, then back in source order
+    lines.sort_by_key(|(candidate, _)| (candidate - line).abs());
+    lines.truncate(NEARBY_LINES);
+    lines.sort();
+
+    lines.iter().map(|(line, method)| format!("{} ({})", line, method)).collect()
+}
+
+/// Append the nearby lines to an error about a line without code
+fn with_nearby_lines(message: String, nearby: &[String]) -> String {
+    if nearby.is_empty() {
+        return message;
+    }
+    format!("{}; nearest lines with code: {}", message, nearby.join(", "))
+}
+
+/// A breakpoint location resolved to code, before any request is set
+struct BreakpointTarget {
+    class_id: ReferenceTypeId,
+    method: MethodInfo,
+    index: u64,
+    /// Source line, or None for a breakpoint on method entry
+    line: Option<u32>,
+}
+
+/// Describe where a breakpoint location resolves, without setting it
+///
+/// Lets a caller check a guessed line before committing to it. Unlike
+/// `place_breakpoint`, nothing is deferred for a class that isn't loaded.
+async fn validate_breakpoint(session: &mut DebugSession, location: &LocationSpec) -> Result<String, String> {
+    let (class, method) = (location.class.as_str(), location.method.as_deref());
+
+    if lookup_class(&mut session.connection, class).await?.is_none() {
+        let mut output = format!(
+            "🔍 {} isn't loaded yet, so the location can't be checked\n   Without validate_only the breakpoint would be deferred until it loads",
+            class
+        );
+        let suggestions = suggest_loaded_classes(&mut session.connection, class).await;
+        if !suggestions.is_empty() {
+            output.push_str(&format!(
+                "\n   ⚠️  Loaded classes with that name: {} (check the package)",
+                suggestions.join(", ")
+            ));
+        }
+        return Ok(output);
+    }
+
+    let target = resolve_breakpoint(session, class, location.line, method).await?;
+
+    let mut output = match target.line {
+        Some(line) => format!("🔍 {}:{} is a valid breakpoint location (not set)", class, line),
+        None => format!("🔍 {}.{}{} entry is a valid breakpoint location (not set)", class, target.method.name, target.method.signature),
+    };
+    output.push_str(&format!("\n   Method: {}\n   Code index: {}", target.method.declaration(), target.index));
+
+    if let Some(line) = target.line {
+        let methods = session.methods(target.class_id).await
+            .map_err(|e| format!("Failed to get methods: {}", e))?;
+        let nearby = nearby_lines(session, target.class_id, &methods, line as i32).await;
+        output.push_str(&format!("\n   Nearest lines with code: {}", nearby.join(", ")));
+    }
+
+    Ok(output)
+}
+
+/// Where `place_breakpoint` put a breakpoint
+enum Placement {
+    Set(BreakpointInfo),
+    /// The class isn't loaded yet
+    Deferred(DeferredBreakpoint),
+}
+
+/// Set a breakpoint, or defer it until its class loads
+async fn place_breakpoint(
+    session: &mut DebugSession,
+    class_pattern: &str,
+    line: Option<u32>,
+    method: Option<&str>,
+    options: BreakpointOptions,
+) -> Result<Placement, String> {
+    if lookup_class(&mut session.connection, class_pattern).await?.is_none() {
+        let deferred = defer_breakpoint(session, class_pattern, line, method, options).await?;
+
+        // The class may have loaded before the ClassPrepare request was in place
+        if lookup_class(&mut session.connection, class_pattern).await?.is_none() {
+            return Ok(Placement::Deferred(deferred));
+        }
+        session.deferred_breakpoints.remove(&deferred.id);
+        if let Err(e) = session.connection.clear_event_request(event_kinds::CLASS_PREPARE, deferred.request_id).await {
+            warn!("Failed to clear ClassPrepare request {}: {}", deferred.request_id, e);
+        }
+    }
+
+    set_breakpoint_on(session, class_pattern, line, method, options).await.map(Placement::Set)
+}
+
+/// Watch for a class to load, and set the breakpoint on it then
+///
+/// The ClassPrepare request suspends the loading thread, so the breakpoint
+/// is in place before any of the class's code runs.
+async fn defer_breakpoint(
+    session: &mut DebugSession,
+    class_pattern: &str,
+    line: Option<u32>,
+    method: Option<&str>,
+    options: BreakpointOptions,
+) -> Result<DeferredBreakpoint, String> {
+    let class_name = class_name_from_signature(class_pattern);
+    let request_id = session.connection
+        .set_class_prepare_request(&class_name, jdwp_client::SuspendPolicy::EventThread)
+        .await
+        .map_err(|e| format!("Failed to watch for class {} to load: {}", class_name, e))?;
+
+    let deferred = DeferredBreakpoint {
+        id: format!("bp_{}", request_id),
+        request_id,
+        class_pattern: class_pattern.to_string(),
+        line,
+        method: method.map(str::to_string),
+        hit_limit: options.hit_limit,
+        thread: options.thread,
+        suspend_policy: options.suspend_policy,
+        failure: None,
+    };
+    session.deferred_breakpoints.insert(deferred.id.clone(), deferred.clone());
+
+    Ok(deferred)
+}
+
+/// Set the deferred breakpoints whose class `event_set` reports prepared
+///
+/// Returns true if the event set held nothing else; the thread the
+/// ClassPrepare suspended has then been resumed.
+async fn install_deferred_breakpoints(session: &mut DebugSession, event_set: &EventSet) -> bool {
+    let mut only_deferred = !event_set.events.is_empty();
+
+    for event in &event_set.events {
+        let deferred = match event.details {
+            EventKind::ClassPrepare { .. } => session.deferred_breakpoints.values()
+                .find(|deferred| deferred.request_id == event.request_id && deferred.failure.is_none())
+                .cloned(),
+            _ => None,
+        };
+        let Some(mut deferred) = deferred else {
+            only_deferred = false;
+            continue;
+        };
+
+        // Other class loaders may load the class too; the first one wins
+        if let Err(e) = session.connection.clear_event_request(event_kinds::CLASS_PREPARE, deferred.request_id).await {
+            warn!("Failed to clear ClassPrepare request {}: {}", deferred.request_id, e);
+        }
+
+        let options = BreakpointOptions {
+            hit_limit: deferred.hit_limit,
+            thread: deferred.thread,
+            suspend_policy: deferred.suspend_policy,
+        };
+        let result = set_breakpoint_on(
+            session, &deferred.class_pattern, deferred.line, deferred.method.as_deref(), options,
+        ).await;
+
+        match result {
+            Ok(mut bp) => {
+                // Keep the ID the breakpoint was given when it was deferred
+                session.breakpoints.remove(&bp.id);
+                session.deferred_breakpoints.remove(&deferred.id);
+                info!("Deferred breakpoint {} set at {}", deferred.id, bp.location());
+                bp.id = deferred.id;
+                session.breakpoints.insert(bp.id.clone(), bp);
+            }
+            Err(e) => {
+                warn!("Failed to set deferred breakpoint {} at {}: {}", deferred.id, deferred.location(), e);
+                deferred.failure = Some(e);
+                session.deferred_breakpoints.insert(deferred.id.clone(), deferred);
+            }
+        }
+    }
+
+    if !only_deferred {
+        return false;
+    }
+
+    let resumed = match (event_set.suspend_policy, event_set.events[0].details.thread()) {
+        (0, _) => Ok(()),
+        (1, Some(thread)) => session.connection.resume_thread(thread).await,
+        _ => session.connection.resume_all().await,
+    };
+    if let Err(e) = resumed {
+        warn!("Failed to resume after setting deferred breakpoints: {}", e);
+    }
+    true
+}
+
+/// Set a tracked breakpoint again from its class, line and method
+///
+/// The new request is tracked under a new breakpoint ID.
+async fn request_breakpoint(session: &mut DebugSession, bp: &BreakpointInfo) -> Result<BreakpointInfo, String> {
+    let options = BreakpointOptions { hit_limit: bp.hit_limit, thread: bp.thread, suspend_policy: bp.suspend_policy };
+    set_breakpoint_on(session, &bp.class_pattern, bp.line, bp.method.as_deref(), options).await
+}
+
+/// Set a breakpoint on a line, or on method entry when there's no line
+async fn set_breakpoint_on(
+    session: &mut DebugSession,
+    class_pattern: &str,
+    line: Option<u32>,
+    method: Option<&str>,
+    options: BreakpointOptions,
+) -> Result<BreakpointInfo, String> {
+    let target = resolve_breakpoint(session, class_pattern, line, method).await?;
+    register_breakpoint(session, class_pattern, target.class_id, &target.method, target.index, target.line, options).await
+}
+
+/// Resolve a breakpoint location in a loaded class without setting it
+async fn resolve_breakpoint(
+    session: &mut DebugSession,
+    class_pattern: &str,
+    line: Option<u32>,
+    method: Option<&str>,
+) -> Result<BreakpointTarget, String> {
+    match (line, method) {
+        (None, Some(method)) => resolve_method_entry(session, class_pattern, method).await,
+        (line, method) => {
+            let line = line.unwrap_or_default() as i32;
+            resolve_line_breakpoint(session, class_pattern, line, method).await
+        }
+    }
+}
+
+/// Resolve a method's first instruction
+///
+/// `method_spec` is a method name, or a name followed by its JNI signature
+/// (e.g. "process(Ljava/lang/String;)V") to pick one overload. No line table
+/// is needed, so this works for classes compiled without debug info.
+async fn resolve_method_entry(
+    session: &mut DebugSession,
+    class_pattern: &str,
+    method_spec: &str,
+) -> Result<BreakpointTarget, String> {
+    let class = find_class(&mut session.connection, class_pattern).await?;
+
+    let methods = session.methods(class.type_id).await
+        .map_err(|e| format!("Failed to get methods: {}", e))?;
+
+    let method = find_method_by_spec(&methods, method_spec)
+        .map_err(|e| format!("{} in class {}", e, class_pattern))?;
+
+    if method.mod_bits & (ACC_ABSTRACT | ACC_NATIVE) != 0 {
+        return Err(format!("{} is abstract or native and has no bytecode to break in", method.name));
+    }
+
+    // Methods start at code index 0; the line table confirms it when present
+    let index = match session.line_table(class.type_id, method.method_id).await {
+        Ok(line_table) => line_table.start,
+        Err(_) => 0,
+    };
+
+    Ok(BreakpointTarget { class_id: class.type_id, method: method.clone(), index, line: None })
+}
+
+/// Pick the method named by a "name" or "name(signature)" spec
+///
+/// A bare name must not be overloaded; the error lists the signatures to
+/// choose from.
+fn find_method_by_spec<'a>(methods: &'a [MethodInfo], spec: &str) -> Result<&'a MethodInfo, String> {
+    let (name, signature) = match spec.find('(') {
+        Some(paren) => (&spec[..paren], Some(&spec[paren..])),
+        None => (spec, None),
+    };
+
+    let candidates: Vec<&MethodInfo> = methods.iter()
+        .filter(|m| m.name == name && signature.is_none_or(|sig| m.signature == sig))
+        .collect();
+
+    match candidates.as_slice() {
+        [] => Err(format!("No method {}", spec)),
+        [method] => Ok(method),
+        overloads => Err(format!(
+            "{} is overloaded; pass one of {}",
+            name,
+            overloads.iter().map(|m| format!("\"{}{}\"", m.name, m.signature)).collect::<Vec<_>>().join(", ")
+        )),
+    }
+}
+
+/// Set a breakpoint at a resolved code index and track it in the session
+///
+/// `line` is None for a breakpoint on method entry, which records the
+/// method's signature so it re-resolves to the same overload.
+async fn register_breakpoint(
+    session: &mut DebugSession,
+    class_pattern: &str,
+    class_id: ReferenceTypeId,
+    method: &MethodInfo,
+    index: u64,
+    line: Option<u32>,
+    options: BreakpointOptions,
+) -> Result<BreakpointInfo, String> {
+    let request_id = session.connection.set_breakpoint(
+        class_id,
+        method.method_id,
+        index,
+        options.thread,
+        options.hit_limit.map(|limit| limit as i32),
+        options.suspend_policy,
+    ).await.map_err(|e| format!("Failed to set breakpoint: {}", e))?;
+
+    let method_name = match line {
+        Some(_) => method.name.clone(),
+        None => format!("{}{}", method.name, method.signature),
+    };
+
+    // Track the breakpoint in session
+    let bp = BreakpointInfo {
+        id: format!("bp_{}", request_id),
+        request_id,
+        class_pattern: class_pattern.to_string(),
+        line,
+        method: Some(method_name),
+        enabled: true,
+        hit_count: 0,
+        hit_limit: options.hit_limit,
+        thread: options.thread,
+        suspend_policy: options.suspend_policy,
+    };
+    session.breakpoints.insert(bp.id.clone(), bp.clone());
+
+    Ok(bp)
+}
+
+/// When a breakpoint stops, beyond its location
+#[derive(Debug, Clone, Copy)]
+struct BreakpointOptions {
+    /// Stop on this hit only; the VM discards the request afterwards
+    hit_limit: Option<u32>,
+    /// Ignore hits on other threads
+    thread: Option<ThreadId>,
+    /// Which threads a hit suspends
+    suspend_policy: SuspendPolicy,
+}
+
+impl Default for BreakpointOptions {
+    /// Suspend just the thread that hit, as debug.set_breakpoint does
+    fn default() -> Self {
+        BreakpointOptions { hit_limit: None, thread: None, suspend_policy: SuspendPolicy::EventThread }
+    }
+}
+
+/// Read a breakpoint's location from `class_pattern`, which may carry
+/// "#method" and ":line" itself, and the `method` and `line` arguments
+fn location_args(args: &serde_json::Value) -> Result<LocationSpec, String> {
+    let class_pattern = args.get("class_pattern")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| "Missing 'class_pattern' parameter".to_string())?;
+    let mut location = parse_location(class_pattern)?;
+
+    if let Some(value) = args.get("line").filter(|v| !v.is_null()) {
+        let line = value.as_u64()
+            .filter(|line| (1..=i32::MAX as u64).contains(line))
+            .ok_or_else(|| format!("Invalid 'line' {}: must be a positive integer", value))? as u32;
+        match location.line {
+            Some(given) if given != line => {
+                return Err(format!("'class_pattern' gives line {} but 'line' is {}", given, line));
+            }
+            _ => location.line = Some(line),
+        }
+    }
+
+    if let Some(method) = args.get("method").and_then(|v| v.as_str()) {
+        match location.method.as_deref() {
+            Some(given) if given != method => {
+                return Err(format!("'class_pattern' gives method {} but 'method' is {}", given, method));
+            }
+            _ => location.method = Some(method.to_string()),
+        }
+    }
+
+    Ok(location)
+}
+
+/// How `thread` is suspended: only itself if it's stopped at an event
+/// that suspended just its thread, otherwise along with every other thread
+fn stop_suspend_policy(session: &DebugSession, thread: ThreadId) -> SuspendPolicy {
+    match &session.last_event {
+        Some(event_set)
+            if event_set.suspend_policy == SuspendPolicy::EventThread as u8
+                && event_set.events.iter().any(|event| event.details.thread() == Some(thread)) =>
+        {
+            SuspendPolicy::EventThread
+        }
+        _ => SuspendPolicy::All,
+    }
+}
+
+/// Read a breakpoint's optional `suspend_policy` argument; by default only
+/// the thread that hits it suspends, so the rest of the app keeps serving
+fn suspend_policy_arg(args: &serde_json::Value) -> Result<SuspendPolicy, String> {
+    match args.get("suspend_policy").and_then(|v| v.as_str()) {
+        None | Some("thread") => Ok(SuspendPolicy::EventThread),
+        Some("all") => Ok(SuspendPolicy::All),
+        Some("none") => Ok(SuspendPolicy::None),
+        Some(other) => Err(format!("Invalid suspend_policy '{}' (expected 'none', 'thread' or 'all')", other)),
+    }
+}
+
+/// How a suspend policy is spelled in tool arguments
+fn suspend_policy_name(policy: SuspendPolicy) -> &'static str {
+    match policy {
+        SuspendPolicy::None => "none",
+        SuspendPolicy::EventThread => "thread",
+        SuspendPolicy::All => "all",
+    }
+}
+
+/// Read a breakpoint's optional `hit_limit` argument
+fn hit_limit_arg(args: &serde_json::Value) -> Result<Option<u32>, String> {
+    match args.get("hit_limit") {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(value) => match value.as_u64() {
+            Some(limit) if (1..=i32::MAX as u64).contains(&limit) => Ok(Some(limit as u32)),
+            _ => Err(format!("Invalid 'hit_limit' {}: must be a positive integer", value)),
+        },
+    }
+}
+
+/// Count hits on tracked breakpoints, marking hit-limited ones expired
+/// since the VM discards their requests once they fire
+fn record_breakpoint_hits(session: &mut DebugSession, event_set: &EventSet) {
+    for event in &event_set.events {
+        if !matches!(event.details, EventKind::Breakpoint { .. }) {
+            continue;
+        }
+        if let Some(bp) = session.breakpoints.values_mut().find(|bp| bp.request_id == event.request_id) {
+            match bp.hit_limit {
+                // The VM skipped the earlier hits without reporting them
+                Some(limit) => {
+                    bp.hit_count = limit;
+                    bp.enabled = false;
+                }
+                None => bp.hit_count += 1,
+            }
+        }
+    }
+}
+
+/// Watch a field (declared or inherited) for reads or writes and track it
+async fn set_watchpoint_at(
+    session: &mut DebugSession,
+    class_pattern: &str,
+    field: &str,
+    event_kind: u8,
+) -> Result<WatchpointInfo, String> {
+    let class = find_class(&mut session.connection, class_pattern).await?;
+
+    let fields = session.connection.get_all_fields(class.type_id).await
+        .map_err(|e| format!("Failed to get fields: {}", e))?;
+
+    let declared = fields.iter()
+        .find(|f| f.field.name == field)
+        .ok_or_else(|| format!("Field '{}' not found in {}", field, class_pattern))?;
+
+    let request_id = session.connection.set_field_watchpoint(
+        event_kind,
+        declared.declaring_type,
+        declared.field.field_id,
+        jdwp_client::SuspendPolicy::All,
+    ).await.map_err(|e| format!("Failed to set watchpoint: {}", describe_error(&e)))?;
+
+    let wp = WatchpointInfo {
+        id: format!("wp_{}", request_id),
+        request_id,
+        event_kind,
+        class_pattern: class_pattern.to_string(),
+        field: field.to_string(),
+    };
+    session.watchpoints.insert(wp.id.clone(), wp.clone());
+
+    Ok(wp)
+}
+
+/// Break when an exception (optionally of one class) is thrown and track it
+async fn set_exception_breakpoint_at(
+    session: &mut DebugSession,
+    exception_class: Option<&str>,
+    caught: bool,
+    uncaught: bool,
+) -> Result<ExceptionBreakpointInfo, String> {
+    let ref_type = match exception_class {
+        Some(class_pattern) => Some(find_class(&mut session.connection, class_pattern).await?.type_id),
+        None => None,
+    };
+
+    let request_id = session.connection.set_exception_request(
+        ref_type,
+        caught,
+        uncaught,
+        jdwp_client::SuspendPolicy::All,
+    ).await.map_err(|e| format!("Failed to set exception breakpoint: {}", e))?;
+
+    let eb = ExceptionBreakpointInfo {
+        id: format!("ex_{}", request_id),
+        request_id,
+        exception_class: exception_class.map(str::to_string),
+        caught,
+        uncaught,
+    };
+    session.exception_breakpoints.insert(eb.id.clone(), eb.clone());
+
+    Ok(eb)
+}
+
+/// A source line for display; native and synthetic methods have no line
+/// table, and code before the first entry has no line either
+fn line_label(line: Option<i32>) -> String {
+    line.map_or_else(|| "<unknown>".to_string(), |line| line.to_string())
+}
+
+/// "HelloController.java:64", or just the file (or class) when the line is unknown
+fn source_position(source: &str, line: Option<i32>) -> String {
+    match line {
+        Some(line) => format!("{}:{}", source, line),
+        None => source.to_string(),
+    }
+}
+
+/// "java.io.IOException (caught and uncaught)"
+fn exception_label(eb: &ExceptionBreakpointInfo) -> String {
+    let which = match (eb.caught, eb.uncaught) {
+        (true, true) => "caught and uncaught",
+        (true, false) => "caught only",
+        _ => "uncaught only",
+    };
+    format!("{} ({})", eb.exception_class.as_deref().unwrap_or("any exception"), which)
+}
+
+fn watch_kind_label(event_kind: u8) -> &'static str {
+    if event_kind == event_kinds::FIELD_ACCESS {
+        "access"
+    } else {
+        "modification"
+    }
+}
+
+/// Describe the event that stopped the program (see `EventSet::stop_event`)
+async fn describe_stop(connection: &mut JdwpConnection, session: &DebugSession, event: &Event) -> String {
+    match &event.details {
+        EventKind::Breakpoint { thread, location } => {
+            let bp_id = session.breakpoints.values()
+                .find(|bp| bp.request_id == event.request_id)
+                .map(|bp| bp.id.clone())
+                .unwrap_or_else(|| format!("request {}", event.request_id));
+
+            let name = thread_name(connection, *thread).await;
+            let mut resolver = LocationResolver::default();
+            format!(
+                "🎯 Breakpoint {} hit on thread {} (0x{:x})\n   Location: {}",
+                bp_id, name, thread, resolver.describe(connection, location).await
+            )
+        }
+        EventKind::Exception { thread, location, exception, catch_location } => {
+            let mut resolver = LocationResolver::default();
+            let caught = match catch_location {
+                Some(catch) => format!("caught at {}", resolver.describe(connection, catch).await),
+                None => "uncaught".to_string(),
+            };
+            let eb_id = session.exception_breakpoints.values()
+                .find(|eb| eb.request_id == event.request_id)
+                .map(|eb| format!(" (breakpoint {})", eb.id))
+                .unwrap_or_default();
+
+            format!(
+                "💥 {} thrown on thread 0x{:x}{}\n   Exception object: 0x{:x}\n   Location: {}\n   {}",
+                describe_exception(connection, *exception).await, thread, eb_id, exception,
+                resolver.describe(connection, location).await, caught
+            )
+        }
+        EventKind::FieldAccess { thread, location, ref_type, field_id, object } => {
+            let access = FieldEvent {
+                thread: *thread,
+                location,
+                ref_type: *ref_type,
+                field_id: *field_id,
+                object: *object,
+                new_value: None,
+            };
+            describe_field_event(connection, session, event.request_id, &access).await
+        }
+        EventKind::FieldModification { thread, location, ref_type, field_id, object, new_value } => {
+            let modification = FieldEvent {
+                thread: *thread,
+                location,
+                ref_type: *ref_type,
+                field_id: *field_id,
+                object: *object,
+                new_value: Some(new_value),
+            };
+            describe_field_event(connection, session, event.request_id, &modification).await
+        }
+        _ => "🛑 The target VM exited".to_string(),
+    }
+}
+
+/// An exception's class and message, e.g.
+/// `java.lang.NullPointerException: "x is null"`, or just the class if it
+/// has no message
+async fn describe_exception(connection: &mut JdwpConnection, exception: ObjectId) -> String {
+    let class_name = exception_class_name(connection, exception).await;
+    match exception_message(connection, exception).await {
+        Some(message) => format!("{}: {:?}", class_name, message),
+        None => class_name,
+    }
+}
+
+async fn exception_class_name(connection: &mut JdwpConnection, exception: ObjectId) -> String {
+    match connection.get_object_reference_type(exception).await {
+        Ok(type_id) => connection.get_signature(type_id).await
+            .map(|sig| class_name_from_signature(&sig))
+            .unwrap_or_else(|_| "?".to_string()),
+        Err(_) => "?".to_string(),
+    }
+}
+
+/// Read Throwable.detailMessage without invoking getMessage(), so nothing
+/// runs in the target; None if there is no message
+async fn exception_message(connection: &mut JdwpConnection, exception: ObjectId) -> Option<String> {
+    let type_id = connection.get_object_reference_type(exception).await.ok()?;
+    let field = connection.get_all_fields(type_id).await.ok()?
+        .into_iter()
+        .find(|f| f.field.name == "detailMessage")?;
+    let value = connection.get_object_values(exception, vec![field.field.field_id]).await.ok()?
+        .into_iter()
+        .next()?;
+
+    match value.data {
+        ValueData::Object(0) => None,
+        ValueData::Object(message_id) => connection.get_string_value(message_id).await.ok(),
+        _ => None,
+    }
+}
+
+/// A field access or modification event, borrowed from its `EventKind`
+struct FieldEvent<'a> {
+    thread: ThreadId,
+    location: &'a Location,
+    ref_type: ReferenceTypeId,
+    field_id: FieldId,
+    /// 0 for a static field
+    object: ObjectId,
+    /// Only set for modifications
+    new_value: Option<&'a Value>,
+}
+
+/// Describe a watchpoint hit: the field, the object it belongs to, and the
+/// value(s) involved
+async fn describe_field_event(
+    connection: &mut JdwpConnection,
+    session: &DebugSession,
+    request_id: i32,
+    hit: &FieldEvent<'_>,
+) -> String {
+    let wp_id = session.watchpoints.values()
+        .find(|wp| wp.request_id == request_id)
+        .map(|wp| wp.id.clone())
+        .unwrap_or_else(|| format!("request {}", request_id));
+
+    let declaring_class = match connection.get_signature(hit.ref_type).await {
+        Ok(sig) => class_name_from_signature(&sig),
+        Err(_) => format!("0x{:x}", hit.ref_type),
+    };
+    let field_name = connection.get_fields(hit.ref_type).await.ok()
+        .and_then(|fields| fields.into_iter().find(|f| f.field_id == hit.field_id))
+        .map(|f| f.name)
+        .unwrap_or_else(|| format!("0x{:x}", hit.field_id));
+
+    // The owner may be an instance of a subclass of the declaring class
+    let owner = if hit.object == 0 {
+        format!("{} (static)", declaring_class)
+    } else {
+        let class_name = match connection.get_object_reference_type(hit.object).await {
+            Ok(type_id) => connection.get_signature(type_id).await
+                .map(|sig| class_name_from_signature(&sig))
+                .ok(),
+            Err(_) => None,
+        };
+        format!("{}@0x{:x}", class_name.as_deref().unwrap_or("?"), hit.object)
+    };
+
+    // The thread is suspended before a write happens, so this is the old value
+    let current = if hit.object == 0 {
+        connection.get_reference_type_values(hit.ref_type, vec![hit.field_id]).await
+    } else {
+        connection.get_object_values(hit.object, vec![hit.field_id]).await
+    };
+    let current = match current.ok().and_then(|values| values.into_iter().next()) {
+        Some(value) => format_value(connection, &value).await,
+        None => "<unavailable>".to_string(),
+    };
+
+    let location = LocationResolver::default().describe(connection, hit.location).await;
+
+    match hit.new_value {
+        Some(new_value) => format!(
+            "👁️ Watchpoint {} hit: {}.{} modified on thread 0x{:x}\n   Object: {}\n   Value: {} → {}\n   Location: {}",
+            wp_id, declaring_class, field_name, hit.thread, owner,
+            current, format_value(connection, new_value).await, location
+        ),
+        None => format!(
+            "👁️ Watchpoint {} hit: {}.{} read on thread 0x{:x}\n   Object: {}\n   Value: {}\n   Location: {}",
+            wp_id, declaring_class, field_name, hit.thread, owner, current, location
+        ),
+    }
+}
+
+/// Trim a tool result to at most `max_bytes` of text
+///
+/// Text blocks are cut at the budget and embedded JSON that doesn't fit is
+/// dropped, since half a JSON document is no use to anyone.
+fn cap_response(content: Vec<ContentBlock>, max_bytes: usize) -> Vec<ContentBlock> {
+    let mut remaining = max_bytes;
+    let mut capped = Vec::with_capacity(content.len());
+    let mut dropped = 0;
+
+    for block in content {
+        match block {
+            ContentBlock::Text { text } if remaining > 0 => {
+                let text = truncate_text(text, remaining);
+                remaining = remaining.saturating_sub(text.len());
+                capped.push(ContentBlock::Text { text });
+            }
+            ContentBlock::Resource { resource } if resource.text.len() <= remaining => {
+                remaining -= resource.text.len();
+                capped.push(ContentBlock::Resource { resource });
+            }
+            _ => dropped += 1,
+        }
+    }
+
+    if dropped > 0 {
+        capped.push(ContentBlock::text(format!(
+            "[{} more content block(s) omitted: result exceeds {} bytes]",
+            dropped, max_bytes
+        )));
+    }
+
+    capped
+}
+
+/// Cut `text` to `max_bytes`, ending it with a marker and a hint to narrow the query
+fn truncate_text(mut text: String, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text;
+    }
+
+    let total = text.len();
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    text.push_str(&format!(
+        "\n\n… [truncated {} of {} bytes; narrow the query, e.g. fewer frames (max_frames), \
+        a lower max_variable_depth, or a smaller range]",
+        total - end, total
+    ));
+    text
+}
+
+/// A thread's state, e.g. "running" or "waiting, suspended (count 2; resume 2 times)"
+async fn thread_status(connection: &mut JdwpConnection, thread_id: ThreadId) -> String {
+    let (status, suspend) = match connection.get_thread_status(thread_id).await {
+        Ok(status) => status,
+        Err(e) => return format!("unavailable ({})", describe_error(&e)),
+    };
+    if suspend != SuspendStatus::Suspended {
+        return thread_state_label(status, 0);
+    }
+
+    let count = connection.get_suspend_count(thread_id).await.unwrap_or(1);
+    thread_state_label(status, count.max(1))
+}
+
+/// A thread's state and suspend count, e.g. "running" or "waiting, suspended"
+fn thread_state_label(status: ThreadStatus, suspend_count: i32) -> String {
+    match suspend_count {
+        count if count <= 0 => status.label().to_string(),
+        1 => format!("{}, suspended", status.label()),
+        count => format!("{}, suspended (count {}; resume {} times)", status.label(), count, count),
+    }
+}
+
+/// Parse an optional hex `thread_id` argument; present but malformed is an error
+fn optional_thread_id(args: &serde_json::Value) -> Result<Option<ThreadId>, String> {
+    match args.get("thread_id").and_then(|v| v.as_str()) {
+        None => Ok(None),
+        Some(s) => u64::from_str_radix(s.trim_start_matches("0x"), 16)
+            .map(Some)
+            .map_err(|_| format!("Invalid 'thread_id' parameter: {}", s)),
+    }
+}
+
+/// A thread's name, or "<exited>" if it died since it was listed
+async fn thread_name(connection: &mut JdwpConnection, thread_id: ThreadId) -> String {
+    match connection.get_thread_name(thread_id).await {
+        Ok(name) => name,
+        Err(e) if e.error_code() == Some(error_codes::INVALID_THREAD) => "<exited>".to_string(),
+        Err(_) => "?".to_string(),
+    }
+}
+
+/// Class name of an object, or "?" if it can't be looked up
+async fn object_class_name(connection: &mut JdwpConnection, object_id: ObjectId) -> String {
+    let Ok(ref_type) = connection.get_object_reference_type(object_id).await else {
+        return "?".to_string();
+    };
+    match connection.get_signature(ref_type).await {
+        Ok(sig) => class_name_from_signature(&sig),
+        Err(_) => "?".to_string(),
+    }
+}
+
+/// Cycles in a waits-for graph of thread -> (monitor, owning thread)
+///
+/// Each thread waits on at most one monitor, so every thread leads to at
+/// most one cycle. Cycles start at their lowest thread ID and follow the
+/// waits-for order; they're sorted by that ID.
+fn find_deadlocks(waits_for: &HashMap<ThreadId, (ObjectId, ThreadId)>) -> Vec<Vec<ThreadId>> {
+    let mut starts: Vec<ThreadId> = waits_for.keys().copied().collect();
+    starts.sort_unstable();
+
+    let mut visited = HashSet::new();
+    let mut deadlocks = Vec::new();
+    for start in starts {
+        let mut path = Vec::new();
+        let mut current = Some(start);
+        while let Some(thread_id) = current.filter(|t| visited.insert(*t)) {
+            path.push(thread_id);
+            current = waits_for.get(&thread_id).map(|(_, owner)| *owner);
+        }
+
+        // Walking into this path again (not an earlier one) closes a new cycle
+        if let Some(pos) = current.and_then(|t| path.iter().position(|p| *p == t)) {
+            let mut cycle = path.split_off(pos);
+            let lowest = cycle.iter().enumerate().min_by_key(|(_, t)| **t).map(|(i, _)| i).unwrap_or(0);
+            cycle.rotate_left(lowest);
+            deadlocks.push(cycle);
+        }
+    }
+
+    deadlocks.sort_by_key(|cycle| cycle[0]);
+    deadlocks
+}
+
+/// Describe a JDWP error, explaining the ones the user can act on
+///
+/// Frame IDs are only valid while their thread stays suspended, so handlers
+/// fetch frames fresh for every inspection; a stale ID means the thread was
+/// resumed or stepped in the meantime.
+fn describe_error(e: &JdwpError) -> String {
+    match e.error_code() {
+        Some(error_codes::INVALID_FRAMEID) => {
+            "frames are stale; the thread moved—re-inspect the stack".to_string()
+        }
+        Some(error_codes::THREAD_NOT_SUSPENDED) => {
+            "thread is running; wait for a breakpoint or use debug.pause".to_string()
+        }
+        Some(error_codes::NO_MORE_FRAMES) => {
+            "no caller to return to; the thread is in its outermost frame".to_string()
+        }
+        Some(error_codes::OPAQUE_FRAME) => {
+            "the frame is native or otherwise opaque to the debugger".to_string()
+        }
+        Some(error_codes::NOT_IMPLEMENTED) => {
+            "this JVM does not support the operation (see debug.capabilities)".to_string()
+        }
+        Some(error_codes::NATIVE_METHOD) => {
+            "the method is native; only Java frames can be changed".to_string()
+        }
+        Some(error_codes::TYPE_MISMATCH) => {
+            "the value doesn't match the method's return type".to_string()
+        }
+        _ => e.to_string(),
+    }
+}
+
+/// Find a loaded class by dotted name or JNI signature, suggesting
+/// similarly named classes if it isn't found
+async fn find_class(connection: &mut JdwpConnection, class_pattern: &str) -> Result<ClassInfo, String> {
+    if let Some(class) = lookup_class(connection, class_pattern).await? {
+        return Ok(class);
+    }
+
+    // Usually a wrong package; offer loaded classes with the same simple name
+    let suggestions = suggest_loaded_classes(connection, class_pattern).await;
+
+    Err(match suggestions.as_slice() {
+        [] => format!(
+            "Class not found: {} (it may not be loaded yet; debug.classpath shows where the JVM looks for classes)",
+            class_pattern
+        ),
+        [only] => format!("Class not found: {}. Did you mean {}?", class_pattern, only),
+        many => format!("Class not found: {}. Did you mean one of: {}?", class_pattern, many.join(", ")),
+    })
+}
+
+/// A loaded class in any form `parse_location` accepts, or None if it isn't loaded
+async fn lookup_class(connection: &mut JdwpConnection, class_pattern: &str) -> Result<Option<ClassInfo>, String> {
+    let location = parse_location(class_pattern)?;
+    if location.method.is_some() || location.line.is_some() {
+        return Err(format!("Expected a class name, not a method or line: {}", class_pattern));
+    }
+
+    let classes = connection.classes_by_signature(&location.to_signature()).await
+        .map_err(|e| format!("Failed to find class: {}", e))?;
+
+    Ok(classes.into_iter().next())
+}
+
+/// Loaded classes with the same simple name as `class_pattern`
+async fn suggest_loaded_classes(connection: &mut JdwpConnection, class_pattern: &str) -> Vec<String> {
+    match connection.get_all_classes().await {
+        Ok(all_classes) => suggest_classes(class_pattern, &all_classes),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Maximum number of class name suggestions to offer
+const MAX_CLASS_SUGGESTIONS: usize = 5;
+
+/// Suggest loaded classes whose simple name matches the requested class
+///
+/// Exact simple-name matches (case-insensitive) are preferred; if there are
+/// none, classes whose simple name contains the requested name are offered.
+fn suggest_classes(requested: &str, classes: &[ClassInfo]) -> Vec<String> {
+    let requested = class_name_from_signature(requested).replace('/', ".");
+    let wanted = simple_class_name(&requested).to_lowercase();
+    if wanted.is_empty() {
+        return Vec::new();
+    }
+
+    let names: Vec<String> = classes
+        .iter()
+        .filter(|c| c.signature.starts_with('L'))
+        .map(|c| class_name_from_signature(&c.signature))
+        .collect();
+
+    let mut matches: Vec<String> = names
+        .iter()
+        .filter(|name| simple_class_name(name).to_lowercase() == wanted)
+        .cloned()
+        .collect();
+
+    if matches.is_empty() {
+        matches = names
+            .into_iter()
+            .filter(|name| simple_class_name(name).to_lowercase().contains(&wanted))
+            .collect();
+    }
+
+    matches.sort();
+    matches.truncate(MAX_CLASS_SUGGESTIONS);
+    matches
+}
+
+/// Convert a JSON value to a JDWP value for a variable of the given signature
+///
+/// Numbers must fit the primitive type, chars are one-character strings and
+/// references are null or a hex object ID. Strings that need creating in the
+/// VM are handled by the caller.
+fn value_from_json(signature: &str, json: &serde_json::Value) -> Result<Value, String> {
+    let tag = tag_for_signature(signature);
+    let type_name = type_name(signature);
+    let mismatch = || format!("Expected a {} value, got {}", type_name, json);
+
+    let integer = || json.as_i64().ok_or_else(mismatch);
+
+    let data = match tag {
+        b'B' => ValueData::Byte(i8::try_from(integer()?).map_err(|_| mismatch())?),
+        b'S' => ValueData::Short(i16::try_from(integer()?).map_err(|_| mismatch())?),
+        b'I' => ValueData::Int(i32::try_from(integer()?).map_err(|_| mismatch())?),
+        b'J' => ValueData::Long(integer()?),
+        b'F' => ValueData::Float(json.as_f64().ok_or_else(mismatch)? as f32),
+        b'D' => ValueData::Double(json.as_f64().ok_or_else(mismatch)?),
+        b'Z' => ValueData::Boolean(json.as_bool().ok_or_else(mismatch)?),
+        b'C' => {
+            let mut chars = json.as_str().ok_or_else(mismatch)?.encode_utf16();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => ValueData::Char(c),
+                _ => return Err(mismatch()),
+            }
+        }
+        b'V' => return Err("Can't assign to a void variable".to_string()),
+        _ => match json {
+            serde_json::Value::Null => ValueData::Object(0),
+            serde_json::Value::String(s) => u64::from_str_radix(s.trim_start_matches("0x"), 16)
+                .map(ValueData::Object)
+                .map_err(|_| format!("Expected null or a hex object ID for {}, got {}", type_name, json))?,
+            _ => return Err(format!("Expected null or a hex object ID for {}, got {}", type_name, json)),
+        },
+    };
+
+    Ok(Value { tag, data })
+}
+
+/// Simple name of a dotted class name, e.g. "com.example.Foo$Bar" -> "Foo$Bar"
+fn simple_class_name(class_name: &str) -> &str {
+    class_name.rsplit('.').next().unwrap_or(class_name)
+}
+
+/// Whether a dotted class name matches a debug.find_classes pattern
+///
+/// Patterns with `*` or `?` are case-insensitive globs over the whole name
+/// (e.g. "com.example.*Controller"); anything else is a case-insensitive
+/// substring.
+fn class_name_matches(name: &str, pattern: &str) -> bool {
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let pattern = pattern.to_lowercase();
+    if !pattern.contains(['*', '?']) {
+        return name.iter().collect::<String>().contains(&pattern);
+    }
+
+    // Iterative glob match, backtracking to the most recent '*'
+    let pattern: Vec<char> = pattern.chars().collect();
+    let (mut n, mut p) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            n += 1;
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Upper bound on debug.get_stack's max_variable_depth
+const MAX_VARIABLE_DEPTH: u32 = 3;
+
+/// Append an object's instance fields, including those inherited from its
+/// superclasses, as indented lines, expanding object-valued fields until
+/// `depth` runs out
+///
+/// Fields holding a known JDK type are shown by content instead, see
+/// [`render_known_type`].
+///
+/// Objects already in `visited` are not expanded again, so cyclic graphs
+/// terminate; they are marked "already shown" instead.
+async fn expand_object(
+    connection: &mut JdwpConnection,
+    object_id: ObjectId,
+    depth: u32,
+    indent: usize,
+    visited: &mut HashSet<ObjectId>,
+    output: &mut String,
+) {
+    if depth == 0 || !visited.insert(object_id) {
+        return;
+    }
+    let pad = " ".repeat(indent);
+
+    let Ok(ref_type) = connection.get_object_reference_type(object_id).await else {
+        return;
+    };
+    let fields: Vec<_> = match connection.get_all_fields(ref_type).await {
+        Ok(fields) => fields.into_iter()
+            .map(|f| f.field)
+            .filter(|f| f.mod_bits & ACC_STATIC == 0)
+            .collect(),
+        Err(_) => return,
+    };
+    if fields.is_empty() {
+        return;
+    }
+
+    let field_ids = fields.iter().map(|f| f.field_id).collect();
+    let values = match connection.get_object_values(object_id, field_ids).await {
+        Ok(values) => values,
+        Err(e) => {
+            output.push_str(&format!("{}(fields unavailable: {})\n", pad, describe_error(&e)));
+            return;
+        }
+    };
+
+    for (field, value) in fields.iter().zip(&values) {
+        if let (b'L', ValueData::Object(child)) = (value.tag, &value.data) {
+            if *child != 0 {
+                if let Some(rendered) = render_known_type(connection, *child).await {
+                    output.push_str(&format!("{}{} = {}\n", pad, field.name, rendered));
+                    continue;
+                }
+            }
+        }
+
+        let formatted_value = format_value(connection, value).await;
+        output.push_str(&format!("{}{} = {}", pad, field.name, formatted_value));
+
+        match (value.tag, &value.data) {
+            (b'L', ValueData::Object(child)) if *child != 0 && depth > 1 => {
+                if visited.contains(child) {
+                    // Only the roots are pinned, so an object seen earlier may be gone
+                    match connection.is_collected(*child).await {
+                        Ok(true) => output.push_str(" (collected)\n"),
+                        _ => output.push_str(" (already shown)\n"),
+                    }
+                } else {
+                    output.push('\n');
+                    Box::pin(expand_object(connection, *child, depth - 1, indent + 2, visited, output)).await;
+                }
+            }
+            _ => output.push('\n'),
+        }
+    }
+}
+
+/// Bytes shown per row of a bytecode dump
+const BYTECODE_ROW_BYTES: usize = 16;
+
+/// Hex dump of a method's bytecode, split at the line table's entries so
+/// each run of bytes is shown next to its source line
+fn format_bytecodes(bytecodes: &[u8], line_table: Option<&LineTable>) -> String {
+    let mut starts: Vec<(usize, Option<i32>)> = line_table
+        .map(|table| table.lines.iter()
+            .filter(|e| (e.line_code_index as usize) < bytecodes.len())
+            .map(|e| (e.line_code_index as usize, Some(e.line_number)))
+            .collect())
+        .unwrap_or_default();
+    starts.sort_unstable();
+    starts.dedup_by_key(|(index, _)| *index);
+    if starts.first().is_none_or(|(index, _)| *index > 0) {
+        starts.insert(0, (0, None));
+    }
+
+    let mut output = String::new();
+    for (idx, (start, line)) in starts.iter().enumerate() {
+        let end = starts.get(idx + 1).map_or(bytecodes.len(), |(next, _)| *next);
+        let label = match line {
+            Some(line) => format!("line {}", line),
+            None if line_table.is_some() => "no line".to_string(),
+            None => String::new(),
+        };
+
+        for (row, chunk) in bytecodes[*start..end].chunks(BYTECODE_ROW_BYTES).enumerate() {
+            let offset = start + row * BYTECODE_ROW_BYTES;
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            let label = if row == 0 { label.as_str() } else { "" };
+            output.push_str(&format!("  {:<10} {:>5}: {}\n", label, offset, hex.join(" ")));
+        }
+    }
+
+    output
+}
+
+/// Keep an object from being garbage collected, recording it in `pinned`
+/// so the caller can re-enable collection when done
+async fn pin_object(connection: &mut JdwpConnection, object_id: ObjectId, pinned: &mut Vec<ObjectId>) {
+    match connection.disable_collection(object_id).await {
+        Ok(()) => pinned.push(object_id),
+        Err(e) => debug!("Failed to disable collection of 0x{:x}: {}", object_id, e),
+    }
+}
+
+/// Let objects pinned with `pin_object` be garbage collected again
+async fn unpin_objects(connection: &mut JdwpConnection, pinned: Vec<ObjectId>) {
+    for object_id in pinned {
+        if let Err(e) = connection.enable_collection(object_id).await {
+            debug!("Failed to re-enable collection of 0x{:x}: {}", object_id, e);
+        }
+    }
+}
+
+/// Elements shown inline when format_value renders an array
+const ARRAY_PREVIEW_ELEMENTS: i32 = 10;
+
+/// Format a value for display, resolving Strings to their contents and
+/// previewing the first elements of arrays
+async fn format_value(connection: &mut JdwpConnection, value: &Value) -> String {
+    match value.data {
+        ValueData::Object(array_id) if value.tag == b'[' && array_id != 0 => {
+            match format_array_preview(connection, array_id).await {
+                Some(preview) => preview,
+                None => value.format(),
+            }
+        }
+        _ => format_scalar(connection, value).await,
+    }
+}
+
+/// Format a value without looking inside arrays
+async fn format_scalar(connection: &mut JdwpConnection, value: &Value) -> String {
+    // Tag 115 = 's' (String object)
+    if value.tag != 115 {
+        return value.format();
+    }
+
+    match value.data {
+        ValueData::Object(0) => "(String) null".to_string(),
+        ValueData::Object(object_id) => match connection.get_string_value(object_id).await {
+            Ok(string_val) => format!("(String) \"{}\"", string_val),
+            Err(_) => value.format(), // Fall back to object ID
+        },
+        _ => value.format(),
+    }
+}
+
+/// Format a stack local, cutting String contents to `max_length` characters
+async fn format_local(connection: &mut JdwpConnection, value: &Value, max_length: usize) -> String {
+    match value.data {
+        ValueData::Object(string_id) if value.tag == b's' && string_id != 0 => {
+            match connection.get_string_value(string_id).await {
+                Ok(text) => format!("(String) \"{}\"", truncate_chars(text, max_length)),
+                Err(_) => value.format(), // Collected; show the object ID
+            }
+        }
+        _ => format_value(connection, value).await,
+    }
+}
+
+/// Keep the first `max_chars` characters, marking the cut with "…"
+fn truncate_chars(text: String, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
+
+/// Render an array as "(int[] @id) length N [1, 2, …]"
+///
+/// Nested arrays are shown by ID only; use debug.inspect_array for the rest.
+async fn format_array_preview(connection: &mut JdwpConnection, array_id: u64) -> Option<String> {
+    let type_id = connection.get_object_reference_type(array_id).await.ok()?;
+    let array_type = type_name(&connection.get_signature(type_id).await.ok()?);
+    let length = connection.get_array_length(array_id).await.ok()?;
+
+    let elements = match length.min(ARRAY_PREVIEW_ELEMENTS) {
+        0 => Vec::new(),
+        shown => connection.get_array_values(array_id, 0, shown).await.ok()?,
+    };
+
+    let mut items = Vec::with_capacity(elements.len() + 1);
+    for element in &elements {
+        let text = format_scalar(connection, element).await;
+        // The array type already says what the elements are, so drop "(int) "
+        items.push(text.split_once(") ").map(|(_, v)| v.to_string()).unwrap_or(text));
+    }
+    let remaining = length - elements.len() as i32;
+    if remaining > 0 {
+        items.push(format!("… {} more", remaining));
+    }
+
+    Some(format!("({} @{:x}) length {} [{}]", array_type, array_id, length, items.join(", ")))
+}
+
+/// Convert a JNI class signature to a dotted class name
+/// e.g., "Lcom/example/MyClass;" -> "com.example.MyClass"
+fn class_name_from_signature(signature: &str) -> String {
+    signature
+        .strip_prefix('L')
+        .and_then(|s| s.strip_suffix(';'))
+        .map(|s| s.replace('/', "."))
+        .unwrap_or_else(|| signature.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::{Buf, BufMut};
+    use jdwp_client::commands::error_codes::NOT_IMPLEMENTED;
+    use jdwp_client::commands::{
+        array_reference_commands, class_type_commands, command_sets, event_commands, method_commands,
+        object_reference_commands, reference_type_commands, stack_frame_commands, string_reference_commands,
+        thread_commands, thread_group_commands, vm_commands,
+    };
+    use jdwp_client::fake_vm::{self, classes, first_id, frames, line_table, methods, put_string, version};
+    use jdwp_client::protocol::JDWP_HANDSHAKE;
+    use std::sync::atomic::{AtomicBool, AtomicI32};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_resume_guard_resumes_when_dropped() {
+        let resumes = Arc::new(AtomicUsize::new(0));
+        let counter = resumes.clone();
+        let connection = fake_vm::connect_with(move |command_set, command, _| {
+            if (command_set, command) == (command_sets::VIRTUAL_MACHINE, vm_commands::RESUME) {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(Vec::new())
+            } else {
+                Err(NOT_IMPLEMENTED)
+            }
+        })
+        .await;
+
+        // Explicit resume happens once, and the drop afterwards does nothing
+        ResumeGuard::new(connection.clone()).resume().await.unwrap();
+        assert_eq!(resumes.load(Ordering::SeqCst), 1);
+
+        // As if gathering returned early with an error
+        drop(ResumeGuard::new(connection));
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while resumes.load(Ordering::SeqCst) < 2 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("dropped guard should resume the VM");
+    }
+
+    #[tokio::test]
+    async fn test_event_hold_releases_when_dropped() {
+        use std::sync::Mutex;
+
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        let recorded = commands.clone();
+        let connection = fake_vm::connect_with(move |command_set, command, _| {
+            match (command_set, command) {
+                (command_sets::VIRTUAL_MACHINE, vm_commands::HOLD_EVENTS | vm_commands::RELEASE_EVENTS) => {
+                    recorded.lock().unwrap().push(command);
+                    Ok(Vec::new())
+                }
+                _ => Err(NOT_IMPLEMENTED),
+            }
+        })
+        .await;
+
+        // Explicit release happens once, and the drop afterwards does nothing
+        EventHold::hold(&connection).await.unwrap().release().await.unwrap();
+        assert_eq!(*commands.lock().unwrap(), [vm_commands::HOLD_EVENTS, vm_commands::RELEASE_EVENTS]);
+
+        // As if the operation returned early with an error
+        drop(EventHold::hold(&connection).await.unwrap());
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while commands.lock().unwrap().len() < 4 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("dropped guard should release events");
+        assert_eq!(commands.lock().unwrap()[3], vm_commands::RELEASE_EVENTS);
+    }
+
+    #[tokio::test]
+    async fn test_field_modification_reports_object_and_values() {
+        // Account.balance (declared at 0xa0) on a SavingsAccount instance 0x300
+        let mut connection = fake_vm::connect_with(|command_set, command, data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::REFERENCE_TYPE, reference_type_commands::SIGNATURE) => {
+                    match first_id(data) {
+                        0xa0 => put_string(&mut reply, "Lcom/bank/Account;"),
+                        _ => put_string(&mut reply, "Lcom/bank/SavingsAccount;"),
+                    }
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::FIELDS) => {
+                    reply.put_i32(1);
+                    reply.put_u64(0xf1);
+                    put_string(&mut reply, "balance");
+                    put_string(&mut reply, "I");
+                    reply.put_i32(0x2);
+                }
+                (command_sets::OBJECT_REFERENCE, object_reference_commands::REFERENCE_TYPE) => {
+                    reply.put_u8(1);
+                    reply.put_u64(0xa1);
+                }
+                (command_sets::OBJECT_REFERENCE, object_reference_commands::GET_VALUES) => {
+                    reply.put_i32(1);
+                    reply.put_u8(b'I');
+                    reply.put_i32(100);
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let manager = SessionManager::new();
+        manager.create_session(connection.clone(), "127.0.0.1", 0).await;
+        let session_guard = manager.get_current_session().await.unwrap();
+        let mut session = session_guard.lock().await;
+        session.watchpoints.insert("wp_7".to_string(), WatchpointInfo {
+            id: "wp_7".to_string(),
+            request_id: 7,
+            event_kind: event_kinds::FIELD_MODIFICATION,
+            class_pattern: "com.bank.Account".to_string(),
+            field: "balance".to_string(),
+        });
+
+        let event = Event {
+            kind: event_kinds::FIELD_MODIFICATION,
+            request_id: 7,
+            details: EventKind::FieldModification {
+                thread: 0x20,
+                location: Location { type_tag: 1, class_id: 0xa0, method_id: 0xb0, index: 4 },
+                ref_type: 0xa0,
+                field_id: 0xf1,
+                object: 0x300,
+                new_value: Value { tag: b'I', data: ValueData::Int(150) },
+            },
+        };
+
+        let description = describe_stop(&mut connection, &session, &event).await;
+        assert!(description.contains("wp_7"), "{}", description);
+        assert!(description.contains("com.bank.Account.balance modified"), "{}", description);
+        assert!(description.contains("com.bank.SavingsAccount@0x300"), "{}", description);
+        assert!(description.contains("(int) 100 → (int) 150"), "{}", description);
+    }
+
+    #[tokio::test]
+    async fn test_exception_stop_reports_class_and_message() {
+        // IllegalStateException 0xe0 (type 0xc1) with message string 0x50
+        let mut connection = fake_vm::connect_with(|command_set, command, data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::OBJECT_REFERENCE, object_reference_commands::REFERENCE_TYPE) => {
+                    reply.put_u8(1);
+                    reply.put_u64(0xc1);
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::SIGNATURE) if first_id(data) == 0xc1 => {
+                    put_string(&mut reply, "Ljava/lang/IllegalStateException;");
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::FIELDS) => {
+                    reply.put_i32(1);
+                    reply.put_u64(0xf2);
+                    put_string(&mut reply, "detailMessage");
+                    put_string(&mut reply, "Ljava/lang/String;");
+                    reply.put_i32(0x2);
+                }
+                (command_sets::CLASS_TYPE, class_type_commands::SUPERCLASS) => reply.put_u64(0),
+                (command_sets::REFERENCE_TYPE, reference_type_commands::MODIFIERS) => reply.put_i32(0x1),
+                (command_sets::OBJECT_REFERENCE, object_reference_commands::GET_VALUES) => {
+                    reply.put_i32(1);
+                    reply.put_u8(b's');
+                    reply.put_u64(0x50);
+                }
+                (command_sets::STRING_REFERENCE, string_reference_commands::VALUE) => {
+                    put_string(&mut reply, "bad state");
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let manager = SessionManager::new();
+        manager.create_session(connection.clone(), "127.0.0.1", 0).await;
+        let session_guard = manager.get_current_session().await.unwrap();
+        let mut session = session_guard.lock().await;
+        session.exception_breakpoints.insert("ex_3".to_string(), ExceptionBreakpointInfo {
+            id: "ex_3".to_string(),
+            request_id: 3,
+            exception_class: None,
+            caught: true,
+            uncaught: true,
+        });
+
+        let event = Event {
+            kind: event_kinds::EXCEPTION,
+            request_id: 3,
+            details: EventKind::Exception {
+                thread: 0x20,
+                location: Location { type_tag: 1, class_id: 0xa0, method_id: 0xb0, index: 4 },
+                exception: 0xe0,
+                catch_location: None,
+            },
+        };
+
+        let description = describe_stop(&mut connection, &session, &event).await;
+        assert!(
+            description.starts_with(
+                "💥 java.lang.IllegalStateException: \"bad state\" thrown on thread 0x20 (breakpoint ex_3)\n   Exception object: 0xe0\n"
+            ),
+            "{}",
+            description
+        );
+        assert!(description.ends_with("\n   uncaught"), "{}", description);
+    }
+
+    #[test]
+    fn test_truncate_text_marks_cut_and_respects_char_boundaries() {
+        assert_eq!(truncate_text("short".to_string(), 10), "short");
+
+        // 'é' is two bytes, so a cut at byte 2 backs up to byte 1
+        let truncated = truncate_text("aé and more".to_string(), 2);
+        assert!(truncated.starts_with("a\n\n… [truncated 11 of 12 bytes"), "{}", truncated);
+        assert!(truncated.contains("max_frames"));
+    }
+
+    #[test]
+    fn test_cap_response_drops_json_that_does_not_fit() {
+        let content = vec![
+            ContentBlock::text("x".repeat(40)),
+            ContentBlock::json("jdwp://threads/1/stack", &json!({ "frames": ["a", "b", "c"] })),
+        ];
+
+        let capped = cap_response(content, 50);
+        assert_eq!(capped.len(), 2);
+        assert!(matches!(&capped[0], ContentBlock::Text { text } if text.len() == 40));
+        assert!(matches!(&capped[1], ContentBlock::Text { text } if text.contains("1 more content block(s) omitted")));
+
+        let capped = cap_response(vec![ContentBlock::text("y".repeat(100))], 50);
+        assert!(matches!(&capped[0], ContentBlock::Text { text } if text.contains("truncated 50 of 100 bytes")));
+    }
+
+    #[test]
+    fn test_describe_error_explains_step_failures() {
+        let no_more_frames = JdwpError::JdwpErrorCode(error_codes::NO_MORE_FRAMES, "NO_MORE_FRAMES".to_string());
+        assert!(describe_error(&no_more_frames).contains("outermost frame"));
+
+        let opaque = JdwpError::JdwpErrorCode(error_codes::OPAQUE_FRAME, "OPAQUE_FRAME".to_string());
+        assert!(describe_error(&opaque).contains("native"));
+
+        let unsupported = JdwpError::JdwpErrorCode(error_codes::NOT_IMPLEMENTED, "NOT_IMPLEMENTED".to_string());
+        assert!(describe_error(&unsupported).starts_with("this JVM does not support"));
+
+        let other = JdwpError::JdwpErrorCode(error_codes::INVALID_THREAD, "INVALID_THREAD".to_string());
+        assert_eq!(describe_error(&other), other.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_thread_name_of_exited_thread() {
+        let mut connection = fake_vm::connect_with(|command_set, command, data| {
+            if (command_set, command) != (command_sets::THREAD_REFERENCE, thread_commands::NAME) {
+                return Err(NOT_IMPLEMENTED);
+            }
+            match first_id(data) {
+                0x1 => {
+                    let mut reply = Vec::new();
+                    put_string(&mut reply, "main");
+                    Ok(reply)
+                }
+                _ => Err(error_codes::INVALID_THREAD),
+            }
+        })
+        .await;
+
+        assert_eq!(thread_name(&mut connection, 0x1).await, "main");
+        assert_eq!(thread_name(&mut connection, 0x2).await, "<exited>");
+    }
+
+    #[tokio::test]
+    async fn test_thread_status_reports_suspend_count() {
+        // 0x1 is waiting and suspended twice, 0x2 is running, 0x3 has exited
+        let mut connection = fake_vm::connect_with(|command_set, command, data| {
+            if command_set != command_sets::THREAD_REFERENCE {
+                return Err(NOT_IMPLEMENTED);
+            }
+            let mut reply = Vec::new();
+            match (command, first_id(data)) {
+                (_, 0x3) => return Err(error_codes::INVALID_THREAD),
+                (thread_commands::STATUS, 0x1) => {
+                    reply.put_i32(4);
+                    reply.put_i32(1);
+                }
+                (thread_commands::STATUS, _) => {
+                    reply.put_i32(1);
+                    reply.put_i32(0);
+                }
+                (thread_commands::SUSPEND_COUNT, _) => reply.put_i32(2),
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        assert_eq!(thread_status(&mut connection, 0x1).await, "waiting, suspended (count 2; resume 2 times)");
+        assert_eq!(thread_status(&mut connection, 0x2).await, "running");
+        assert!(thread_status(&mut connection, 0x3).await.starts_with("unavailable"));
+    }
+
+    #[tokio::test]
+    async fn test_thread_tree_nests_threads_under_groups() {
+        // system (0x10) holds "Reference Handler" (0x1) and the main group
+        // (0x20), which holds "main" (0x2)
+        let connection = fake_vm::connect_with(|command_set, command, data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::VIRTUAL_MACHINE, vm_commands::TOP_LEVEL_THREAD_GROUPS) => {
+                    reply.put_i32(1);
+                    reply.put_u64(0x10);
+                }
+                (command_sets::THREAD_GROUP_REFERENCE, thread_group_commands::NAME) => {
+                    put_string(&mut reply, if first_id(data) == 0x10 { "system" } else { "main" });
+                }
+                (command_sets::THREAD_GROUP_REFERENCE, thread_group_commands::CHILDREN) => {
+                    let (threads, groups): (&[u64], &[u64]) = match first_id(data) {
+                        0x10 => (&[0x1], &[0x20]),
+                        _ => (&[0x2], &[]),
+                    };
+                    reply.put_i32(threads.len() as i32);
+                    threads.iter().for_each(|&id| reply.put_u64(id));
+                    reply.put_i32(groups.len() as i32);
+                    groups.iter().for_each(|&id| reply.put_u64(id));
+                }
+                (command_sets::THREAD_REFERENCE, thread_commands::NAME) => {
+                    put_string(&mut reply, if first_id(data) == 0x1 { "Reference Handler" } else { "main" });
+                }
+                (command_sets::THREAD_REFERENCE, thread_commands::STATUS) => {
+                    reply.put_i32(if first_id(data) == 0x1 { 4 } else { 1 });
+                    reply.put_i32(0);
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        let output = handler.handle_thread_tree(json!({})).await.unwrap();
+        assert_eq!(output.lines().collect::<Vec<_>>(), [
+            "🌳 Thread groups:",
+            "",
+            "  📁 system (0x10)",
+            "    🧵 Reference Handler (0x1): waiting",
+            "    📁 main (0x20)",
+            "      🧵 main (0x2): running",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_event_reports_the_next_breakpoint() {
+        // Breakpoint request 4 at com.example.Tree.walk, index 5 = line 42
+        let connection = fake_vm::connect_with(|command_set, command, _| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::THREAD_REFERENCE, thread_commands::NAME) => put_string(&mut reply, "main"),
+                (command_sets::REFERENCE_TYPE, reference_type_commands::SIGNATURE) => {
+                    put_string(&mut reply, "Lcom/example/Tree;");
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::METHODS) => reply = methods(&[(0x20, "walk", "(I)V")]),
+                (command_sets::METHOD, method_commands::LINE_TABLE) => reply = line_table(&[(0, 42)]),
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = Arc::new(RequestHandler::new());
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+        let session_guard = handler.session_manager.get_current_session().await.unwrap();
+        let events = session_guard.lock().await.events.clone();
+
+        let output = handler.handle_wait_for_event(json!({ "timeout_secs": 0 })).await.unwrap();
+        assert!(output.starts_with("⏳ No breakpoint, exception or watchpoint within 0s"), "{}", output);
+
+        let waiter = handler.clone();
+        let wait = tokio::spawn(async move { waiter.handle_wait_for_event(json!({ "timeout_secs": 5 })).await });
+        while events.receiver_count() == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        events.send(EventSet {
+            suspend_policy: 2,
+            events: vec![Event {
+                kind: event_kinds::BREAKPOINT,
+                request_id: 4,
+                details: EventKind::Breakpoint {
+                    thread: 0x1,
+                    location: Location { type_tag: 1, class_id: 0x10, method_id: 0x20, index: 5 },
+                },
+            }],
+        }).unwrap();
+
+        let output = wait.await.unwrap().unwrap();
+        assert_eq!(output.lines().collect::<Vec<_>>(), [
+            "🎯 Breakpoint request 4 hit on thread main (0x1)",
+            "   Location: com.example.Tree.walk line 42",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_exit_vm_sends_exit_code_and_ends_session() {
+        let exit_code = Arc::new(AtomicI32::new(-1));
+        let recorded = exit_code.clone();
+        let connection = fake_vm::connect_with(move |command_set, command, mut data| {
+            match (command_set, command) {
+                (command_sets::VIRTUAL_MACHINE, vm_commands::EXIT) => {
+                    recorded.store(data.get_i32(), Ordering::SeqCst);
+                    Ok(Vec::new())
+                }
+                _ => Err(NOT_IMPLEMENTED),
+            }
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        let session_id = handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        let error = handler.handle_exit_vm(json!({"exit_code": 3})).await.unwrap_err();
+        assert!(error.contains("pass confirm=true"), "{}", error);
+        assert_eq!(exit_code.load(Ordering::SeqCst), -1);
+
+        let output = handler.handle_exit_vm(json!({"exit_code": 3, "confirm": true})).await.unwrap();
+        assert_eq!(output, format!("🛑 Target JVM exited with code 3\n   Session {} terminated", session_id));
+        assert_eq!(exit_code.load(Ordering::SeqCst), 3);
+        assert!(handler.session_manager.get_session(&session_id).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_set_array_element_writes_untagged_value() {
+        use std::sync::Mutex;
+
+        // long[3]; records the SetValues data
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let recorded = written.clone();
+        let connection = fake_vm::connect_with(move |command_set, command, mut data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::OBJECT_REFERENCE, object_reference_commands::REFERENCE_TYPE) => {
+                    reply.put_u8(3);
+                    reply.put_u64(0x90);
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::SIGNATURE) => put_string(&mut reply, "[J"),
+                (command_sets::ARRAY_REFERENCE, array_reference_commands::LENGTH) => reply.put_i32(3),
+                (command_sets::ARRAY_REFERENCE, array_reference_commands::SET_VALUES) => {
+                    let _array_id = data.get_u64();
+                    if data.get_i32() >= 3 {
+                        return Err(error_codes::INVALID_INDEX);
+                    }
+                    recorded.lock().unwrap().extend_from_slice(data);
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        let output = handler.call_text_tool(
+            "debug.set_array_element",
+            json!({"array_id": "1f", "index": 2, "value": 42}),
+        ).await.unwrap();
+        assert_eq!(output, "✏️  Set (long[] @1f)[2] = (long) 42");
+        // One value, no tag before it
+        assert_eq!(*written.lock().unwrap(), [0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 42]);
+
+        let error = handler.handle_set_array_element(json!({"array_id": "1f", "index": 3, "value": 1})).await.unwrap_err();
+        assert_eq!(error, "Index 3 is out of bounds for length 3");
+
+        let error = handler.handle_set_array_element(json!({"array_id": "1f", "index": 0, "value": "x"})).await.unwrap_err();
+        assert!(error.starts_with("Expected a long value"), "{}", error);
+
+        let error = handler.handle_set_array_element(json!({"array_id": "1f", "index": -1, "value": 1})).await.unwrap_err();
+        assert!(error.starts_with("'index' must be between 0"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_format_value_previews_arrays() {
+        // int[12] holding 0, 10, 20, ...
+        let mut connection = fake_vm::connect_with(|command_set, command, mut data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::OBJECT_REFERENCE, object_reference_commands::REFERENCE_TYPE) => {
+                    reply.put_u8(3);
+                    reply.put_u64(0x90);
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::SIGNATURE) => put_string(&mut reply, "[I"),
+                (command_sets::ARRAY_REFERENCE, array_reference_commands::LENGTH) => reply.put_i32(12),
+                (command_sets::ARRAY_REFERENCE, array_reference_commands::GET_VALUES) => {
+                    let _array_id = data.get_u64();
+                    let first = data.get_i32();
+                    let length = data.get_i32();
+                    reply.put_u8(b'I');
+                    reply.put_i32(length);
+                    for i in first..first + length {
+                        reply.put_i32(i * 10);
+                    }
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let array = Value { tag: b'[', data: ValueData::Object(0x1f) };
+        assert_eq!(
+            format_value(&mut connection, &array).await,
+            "(int[] @1f) length 12 [0, 10, 20, 30, 40, 50, 60, 70, 80, 90, … 2 more]"
+        );
+
+        let null_array = Value { tag: b'[', data: ValueData::Object(0) };
+        assert_eq!(format_value(&mut connection, &null_array).await, "(array) null");
+    }
+
+    #[tokio::test]
+    async fn test_format_local_truncates_strings() {
+        // 0x5 is a live string; anything else has been collected
+        let mut connection = fake_vm::connect_with(|command_set, command, data| {
+            match (command_set, command) {
+                (command_sets::STRING_REFERENCE, string_reference_commands::VALUE) if first_id(data) == 0x5 => {
+                    let mut reply = Vec::new();
+                    put_string(&mut reply, "héllo world");
+                    Ok(reply)
+                }
+                (command_sets::STRING_REFERENCE, string_reference_commands::VALUE) => Err(error_codes::INVALID_OBJECT),
+                _ => Err(NOT_IMPLEMENTED),
+            }
+        })
+        .await;
+
+        let string = |id| Value { tag: b's', data: ValueData::Object(id) };
+        assert_eq!(format_local(&mut connection, &string(0x5), 200).await, "(String) \"héllo world\"");
+        assert_eq!(format_local(&mut connection, &string(0x5), 5).await, "(String) \"héllo…\"");
+        assert_eq!(format_local(&mut connection, &string(0x6), 200).await, string(0x6).format());
+        assert_eq!(format_local(&mut connection, &string(0), 200).await, "(String) null");
+    }
+
+    #[tokio::test]
+    async fn test_expand_object_stops_at_cycles() {
+        // Two nodes pointing at each other through `next`, with an `id`
+        // inherited from their base class
+        let mut connection = fake_vm::connect_with(|command_set, command, data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::OBJECT_REFERENCE, object_reference_commands::REFERENCE_TYPE) => {
+                    reply.put_u8(1);
+                    reply.put_u64(0x90);
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::FIELDS) if first_id(data) == 0x90 => {
+                    reply.put_i32(1);
+                    reply.put_u64(0xf1);
+                    put_string(&mut reply, "next");
+                    put_string(&mut reply, "LNode;");
+                    reply.put_i32(0x2);
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::FIELDS) => {
+                    reply.put_i32(1);
+                    reply.put_u64(0xf2);
+                    put_string(&mut reply, "id");
+                    put_string(&mut reply, "I");
+                    reply.put_i32(0x2);
+                }
+                (command_sets::CLASS_TYPE, class_type_commands::SUPERCLASS) => {
+                    reply.put_u64(if first_id(data) == 0x90 { 0x91 } else { 0 });
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::SIGNATURE) => put_string(&mut reply, "LNode;"),
+                (command_sets::REFERENCE_TYPE, reference_type_commands::MODIFIERS) => reply.put_i32(0x1),
+                (command_sets::OBJECT_REFERENCE, object_reference_commands::GET_VALUES) => {
+                    let object_id = first_id(data);
+                    reply.put_i32(2);
+                    reply.put_u8(b'L');
+                    reply.put_u64(if object_id == 0xa { 0xb } else { 0xa });
+                    reply.put_u8(b'I');
+                    reply.put_i32(object_id as i32);
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let mut output = String::new();
+        expand_object(&mut connection, 0xa, 3, 2, &mut HashSet::new(), &mut output).await;
+        assert_eq!(
+            output,
+            "  next = (object) @b\n    next = (object) @a (already shown)\n    id = (int) 11\n  id = (int) 10\n"
+        );
+
+        // Depth 1 lists fields without following them
+        let mut output = String::new();
+        expand_object(&mut connection, 0xa, 1, 2, &mut HashSet::new(), &mut output).await;
+        assert_eq!(output, "  next = (object) @b\n  id = (int) 10\n");
+    }
+
+    #[tokio::test]
+    async fn test_set_variable_assigns_a_new_string() {
+        use std::sync::Mutex;
+
+        // One frame with a String local `greeting` in slot 1
+        let set_values = Arc::new(Mutex::new(Vec::new()));
+        let recorded = set_values.clone();
+        let connection = fake_vm::connect_with(move |command_set, command, data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::THREAD_REFERENCE, thread_commands::FRAMES) => reply = frames(&[(0x100, 0x10, 0x20, 5)]),
+                (command_sets::METHOD, method_commands::VARIABLE_TABLE) => {
+                    reply.put_i32(0);
+                    reply.put_i32(1);
+                    reply.put_u64(0);
+                    put_string(&mut reply, "greeting");
+                    put_string(&mut reply, "Ljava/lang/String;");
+                    reply.put_u32(100);
+                    reply.put_u32(1);
+                }
+                (command_sets::VIRTUAL_MACHINE, vm_commands::CREATE_STRING) if data == b"\0\0\0\x05hello" => {
+                    reply.put_u64(0x77);
+                }
+                (command_sets::STACK_FRAME, stack_frame_commands::SET_VALUES) => {
+                    recorded.lock().unwrap().extend_from_slice(data);
+                }
+                (command_sets::STRING_REFERENCE, string_reference_commands::VALUE) => put_string(&mut reply, "hello"),
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        let result = handler.handle_set_variable(json!({
+            "thread_id": "0x1",
+            "name": "greeting",
+            "value": "hello",
+        })).await.unwrap();
+        assert_eq!(result, "✏️  Set greeting (String) = (String) \"hello\"");
+
+        let mut expected = Vec::new();
+        expected.put_u64(0x1); // thread
+        expected.put_u64(0x100); // frame
+        expected.put_i32(1);
+        expected.put_i32(1); // slot
+        expected.put_u8(b's');
+        expected.put_u64(0x77);
+        assert_eq!(*set_values.lock().unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_get_stack_reuses_cached_method_metadata() {
+        // Ten frames of the same recursive method, with no locals in scope
+        let connection = fake_vm::connect_with(|command_set, command, _| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::THREAD_REFERENCE, thread_commands::FRAME_COUNT) => {
+                    reply.put_i32(10);
+                }
+                (command_sets::VIRTUAL_MACHINE, vm_commands::HOLD_EVENTS | vm_commands::RELEASE_EVENTS) => {}
+                (command_sets::THREAD_REFERENCE, thread_commands::FRAMES) => {
+                    let stack: Vec<_> = (0..10).map(|frame| (0x100 + frame, 0x10, 0x20, 5)).collect();
+                    reply = frames(&stack);
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::METHODS) => reply = methods(&[(0x20, "walk", "(I)V")]),
+                (command_sets::REFERENCE_TYPE, reference_type_commands::SIGNATURE) => {
+                    put_string(&mut reply, "Lcom/example/Tree;");
+                }
+                // Compiled without a SourceFile attribute
+                (command_sets::REFERENCE_TYPE, reference_type_commands::SOURCE_FILE) => {
+                    return Err(error_codes::ABSENT_INFORMATION);
+                }
+                (command_sets::METHOD, method_commands::LINE_TABLE) => reply = line_table(&[(0, 42)]),
+                (command_sets::METHOD, method_commands::VARIABLE_TABLE) => {
+                    reply.put_i32(1);
+                    reply.put_i32(0);
+                }
+                (command_sets::STACK_FRAME, stack_frame_commands::THIS_OBJECT) => {
+                    reply.put_u8(b'L');
+                    reply.put_u64(0);
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection.clone(), "127.0.0.1", 0).await;
+
+        let mut commands_per_call = Vec::new();
+        for _ in 0..2 {
+            let before = connection.stats().commands_sent;
+            let content = handler.handle_get_stack(json!({ "thread_id": "0x1" })).await.unwrap();
+            commands_per_call.push(connection.stats().commands_sent - before);
+
+            // Without a source file, the class name stands in for it
+            let ContentBlock::Text { text } = &content[1] else { panic!("expected text") };
+            assert!(text.contains("\n  Source: com.example.Tree:42\n"), "{}", text);
+        }
+
+        // Uncached, every frame fetched the class signature, source file,
+        // method list, line table and variable table: 4 + 10 * 6 = 64
+        // commands per call
+        assert_eq!(commands_per_call, [19, 14]);
+    }
+
+    #[tokio::test]
+    async fn test_get_stack_json_format() {
+        // One frame in Worker.run() at line 42, with `count` = 7 in scope
+        let connection = fake_vm::connect_with(|command_set, command, _| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::THREAD_REFERENCE, thread_commands::FRAME_COUNT) => {
+                    reply.put_i32(1);
+                }
+                (command_sets::THREAD_REFERENCE, thread_commands::FRAMES) => reply = frames(&[(0x100, 0x10, 0x20, 5)]),
+                (command_sets::REFERENCE_TYPE, reference_type_commands::SIGNATURE) => {
+                    put_string(&mut reply, "Lcom/example/Worker;");
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::SOURCE_FILE) => {
+                    put_string(&mut reply, "Worker.java");
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::METHODS) => reply = methods(&[(0x20, "run", "()V")]),
+                (command_sets::METHOD, method_commands::LINE_TABLE) => reply = line_table(&[(0, 42)]),
+                (command_sets::METHOD, method_commands::VARIABLE_TABLE) => {
+                    reply.put_i32(1);
+                    reply.put_i32(1);
+                    reply.put_u64(0);
+                    put_string(&mut reply, "count");
+                    put_string(&mut reply, "I");
+                    reply.put_i32(10);
+                    reply.put_i32(1);
+                }
+                (command_sets::STACK_FRAME, stack_frame_commands::THIS_OBJECT) => {
+                    reply.put_u8(b'L');
+                    reply.put_u64(0);
+                }
+                (command_sets::STACK_FRAME, stack_frame_commands::GET_VALUES) => {
+                    reply.put_i32(1);
+                    reply.put_u8(b'I');
+                    reply.put_i32(7);
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        let content = handler.handle_get_stack(json!({"thread_id": "0x1", "format": "json"})).await.unwrap();
+        assert_eq!(content.len(), 1);
+        let ContentBlock::Text { text } = &content[0] else { panic!("expected text") };
+        let dump: serde_json::Value = serde_json::from_str(text).unwrap();
+        assert_eq!(dump, json!({
+            "thread_id": "0x1",
+            "frame_count": 1,
+            "frames": [{
+                "index": 0,
+                "frame_id": "0x100",
+                "class_id": "0x10",
+                "method_id": "0x20",
+                "code_index": 5,
+                "class": "com.example.Worker",
+                "source_file": "Worker.java",
+                "method": "void run()",
+                "line": 42,
+                "variables": [
+                    {"name": "this", "type": "Worker", "value": "<unavailable>"},
+                    {"name": "count", "type": "int", "value": "(int) 7"},
+                ],
+            }],
+        }));
+
+        let content = handler.handle_get_stack(json!({"thread_id": "0x1"})).await.unwrap();
+        let ContentBlock::Text { text } = &content[1] else { panic!("expected text") };
+        assert!(text.starts_with("Frame 0:\n  Location: class=10, method=20, index=5, line=42\n  Source: Worker.java:42\n"), "{}", text);
+
+        let error = handler.handle_get_stack(json!({"thread_id": "0x1", "format": "xml"})).await.unwrap_err();
+        assert_eq!(error, "Unknown format 'xml' (expected text or json)");
+    }
+
+    #[tokio::test]
+    async fn test_get_stack_pages_through_deep_stacks() {
+        let connection = fake_vm::connect_with(|command_set, command, mut data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::THREAD_REFERENCE, thread_commands::FRAME_COUNT) => {
+                    reply.put_i32(1432);
+                }
+                (command_sets::THREAD_REFERENCE, thread_commands::FRAMES) => {
+                    data.get_u64();
+                    let (start, length) = (data.get_i32(), data.get_i32());
+                    let page: Vec<_> = (start..start + length).map(|frame| (0x100 + frame as u64, 0x10, 0x20, 5)).collect();
+                    reply = frames(&page);
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        let content = handler.handle_get_stack(json!({"thread_id": "0x1", "include_variables": false})).await.unwrap();
+        assert_eq!(content.len(), 22);
+        let ContentBlock::Text { text } = &content[0] else { panic!("expected text") };
+        assert_eq!(text, "🔍 Stack for thread 1 (frames 0–19 of 1432):\n   (use start_frame=20 for more)");
+
+        let content = handler.handle_get_stack(json!({
+            "thread_id": "0x1", "start_frame": 1430, "include_variables": false,
+        })).await.unwrap();
+        let ContentBlock::Text { text } = &content[0] else { panic!("expected text") };
+        assert_eq!(text, "🔍 Stack for thread 1 (frames 1430–1431 of 1432):");
+        let ContentBlock::Text { text } = &content[1] else { panic!("expected text") };
+        assert!(text.starts_with("Frame 1430:\n"), "{}", text);
+
+        let error = handler.handle_get_stack(json!({"thread_id": "0x1", "start_frame": 1432})).await.unwrap_err();
+        assert_eq!(error, "'start_frame' 1432 is past the last frame (the stack has 1432 frames)");
+    }
+
+    #[tokio::test]
+    async fn test_get_stack_pins_expanded_objects() {
+        use std::sync::Mutex;
+
+        // One instance frame whose `this` is 0x50
+        let collection_commands = Arc::new(Mutex::new(Vec::new()));
+        let recorded = collection_commands.clone();
+        let connection = fake_vm::connect_with(move |command_set, command, data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::THREAD_REFERENCE, thread_commands::FRAME_COUNT) => reply.put_i32(1),
+                (command_sets::THREAD_REFERENCE, thread_commands::FRAMES) => reply = frames(&[(0x100, 0x10, 0x20, 5)]),
+                (command_sets::REFERENCE_TYPE, reference_type_commands::METHODS) => reply = methods(&[(0x20, "run", "()V")]),
+                (command_sets::METHOD, method_commands::VARIABLE_TABLE) => {
+                    reply.put_i32(1);
+                    reply.put_i32(0);
+                }
+                (command_sets::STACK_FRAME, stack_frame_commands::THIS_OBJECT) => {
+                    reply.put_u8(b'L');
+                    reply.put_u64(0x50);
+                }
+                (
+                    command_sets::OBJECT_REFERENCE,
+                    object_reference_commands::DISABLE_COLLECTION | object_reference_commands::ENABLE_COLLECTION,
+                ) => {
+                    recorded.lock().unwrap().push((command, first_id(data)));
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        handler.handle_get_stack(json!({"thread_id": "0x1"})).await.unwrap();
+        assert_eq!(
+            *collection_commands.lock().unwrap(),
+            [
+                (object_reference_commands::DISABLE_COLLECTION, 0x50),
+                (object_reference_commands::ENABLE_COLLECTION, 0x50),
+            ]
+        );
+
+        // Nothing is pinned when objects aren't expanded
+        collection_commands.lock().unwrap().clear();
+        handler.handle_get_stack(json!({"thread_id": "0x1", "max_variable_depth": 0})).await.unwrap();
+        assert!(collection_commands.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_stack_uses_generic_variable_types() {
+        // Frame 0 runs static method 0x20 with a List<String> local; frame 1
+        // runs 0x21, compiled without -g
+        let connection = fake_vm::connect_with(|command_set, command, mut data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::VIRTUAL_MACHINE, vm_commands::VERSION) => reply = version(),
+                (command_sets::THREAD_REFERENCE, thread_commands::FRAME_COUNT) => reply.put_i32(2),
+                (command_sets::THREAD_REFERENCE, thread_commands::FRAMES) => {
+                    reply = frames(&[(0x100, 0x10, 0x20, 5), (0x101, 0x10, 0x21, 5)]);
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::METHODS_WITH_GENERIC) => {
+                    reply.put_i32(2);
+                    for (method_id, name) in [(0x20, "collect"), (0x21, "legacy")] {
+                        reply.put_u64(method_id);
+                        put_string(&mut reply, name);
+                        put_string(&mut reply, "()V");
+                        put_string(&mut reply, "");
+                        reply.put_i32(0x9);
+                    }
+                }
+                (command_sets::METHOD, method_commands::VARIABLE_TABLE_WITH_GENERIC) => {
+                    data.advance(8);
+                    if data.get_u64() == 0x21 {
+                        return Err(error_codes::ABSENT_INFORMATION);
+                    }
+                    reply.put_i32(0);
+                    reply.put_i32(1);
+                    reply.put_u64(0);
+                    put_string(&mut reply, "names");
+                    put_string(&mut reply, "Ljava/util/List;");
+                    put_string(&mut reply, "Ljava/util/List<Ljava/lang/String;>;");
+                    reply.put_u32(20);
+                    reply.put_u32(0);
+                }
+                (command_sets::STACK_FRAME, stack_frame_commands::GET_VALUES) => {
+                    reply.put_i32(1);
+                    reply.put_u8(b'L');
+                    reply.put_u64(0);
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        let content = handler.handle_get_stack(json!({"thread_id": "0x1"})).await.unwrap();
+        let ContentBlock::Text { text } = &content[1] else { panic!("expected text") };
+        assert!(text.contains("  Variables (1):\n    names = "), "{}", text);
+        let ContentBlock::Text { text } = &content[2] else { panic!("expected text") };
+        assert!(text.ends_with("\n  <no local variable info; compile with -g>\n"), "{}", text);
+
+        let content = handler.handle_get_stack(json!({"thread_id": "0x1", "format": "json"})).await.unwrap();
+        let ContentBlock::Text { text } = &content[0] else { panic!("expected text") };
+        let stack: serde_json::Value = serde_json::from_str(text).unwrap();
+        assert_eq!(stack["frames"][0]["variables"][0]["type"], "List<String>");
+        assert_eq!(stack["frames"][1]["variables"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_get_stack_skips_variables_of_obsolete_methods() {
+        // Frame 0 runs method 0x20, replaced by a redefinition; frame 1 runs 0x21
+        let connection = fake_vm::connect_with(|command_set, command, mut data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::THREAD_REFERENCE, thread_commands::FRAME_COUNT) => reply.put_i32(2),
+                (command_sets::THREAD_REFERENCE, thread_commands::FRAMES) => {
+                    reply = frames(&[(0x100, 0x10, 0x20, 5), (0x101, 0x10, 0x21, 5)]);
+                }
+                (command_sets::METHOD, method_commands::IS_OBSOLETE) => {
+                    data.get_u64();
+                    reply.put_u8((data.get_u64() == 0x20) as u8);
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::METHODS) => reply = methods(&[(0x21, "run", "()V")]),
+                (command_sets::METHOD, method_commands::VARIABLE_TABLE) => {
+                    reply.put_i32(1);
+                    reply.put_i32(0);
+                }
+                (command_sets::STACK_FRAME, stack_frame_commands::THIS_OBJECT) => {
+                    reply.put_u8(b'L');
+                    reply.put_u64(0);
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        let session_id = handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+        let session_guard = handler.session_manager.get_session(&session_id).await.unwrap();
+        session_guard.lock().await.capabilities = Some(VmCapabilities { can_redefine_classes: true, ..Default::default() });
+
+        let content = handler.handle_get_stack(json!({"thread_id": "0x1"})).await.unwrap();
+        let ContentBlock::Text { text } = &content[1] else { panic!("expected text") };
+        assert_eq!(text, "Frame 0:\n  Location: class=10, method=20, index=5, line=<unknown>\n  Method: (obsolete method)\n");
+        let ContentBlock::Text { text } = &content[2] else { panic!("expected text") };
+        assert!(text.contains("  Method: void run()\n  this = "), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn test_list_members_shows_generic_field_types() {
+        let connection = fake_vm::connect_with(|command_set, command, _data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::VIRTUAL_MACHINE, vm_commands::VERSION) => reply = version(),
+                (command_sets::VIRTUAL_MACHINE, vm_commands::CLASSES_BY_SIGNATURE) => reply = classes(&[0x10]),
+                (command_sets::REFERENCE_TYPE, reference_type_commands::FIELDS_WITH_GENERIC) => {
+                    reply.put_i32(2);
+                    reply.put_u64(0x1);
+                    put_string(&mut reply, "names");
+                    put_string(&mut reply, "Ljava/util/List;");
+                    put_string(&mut reply, "Ljava/util/List<Ljava/lang/String;>;");
+                    reply.put_i32(0);
+                    reply.put_u64(0x2);
+                    put_string(&mut reply, "count");
+                    put_string(&mut reply, "I");
+                    put_string(&mut reply, "");
+                    reply.put_i32(0x8);
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::METHODS_WITH_GENERIC) => reply.put_i32(0),
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        let result = handler.handle_list_members(json!({"class_pattern": "com.example.Names"})).await.unwrap();
+        assert!(result.contains("\n  List<String> names\n"), "{}", result);
+        assert!(result.contains("\n  static int count\n"), "{}", result);
+    }
+
+    #[tokio::test]
+    async fn test_detect_deadlock_reports_lock_cycle() {
+        // Threads 1 and 2 each hold the lock the other is blocked on; 3 is idle
+        let connection = fake_vm::connect_with(|command_set, command, data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::VIRTUAL_MACHINE, vm_commands::SUSPEND | vm_commands::RESUME) => {}
+                (command_sets::VIRTUAL_MACHINE, vm_commands::ALL_THREADS) => {
+                    reply.put_i32(3);
+                    for thread_id in 1..=3 {
+                        reply.put_u64(thread_id);
+                    }
+                }
+                (command_sets::THREAD_REFERENCE, thread_commands::NAME) => {
+                    put_string(&mut reply, &format!("worker-{}", first_id(data)));
+                }
+                (command_sets::THREAD_REFERENCE, thread_commands::STATUS) => {
+                    reply.put_i32(if first_id(data) == 3 { 4 } else { 3 });
+                    reply.put_i32(1);
+                }
+                (command_sets::THREAD_REFERENCE, thread_commands::OWNED_MONITORS) => {
+                    let owned: &[u64] = match first_id(data) {
+                        1 => &[0xa],
+                        2 => &[0xb],
+                        _ => &[],
+                    };
+                    reply.put_i32(owned.len() as i32);
+                    for monitor in owned {
+                        reply.put_u8(b'L');
+                        reply.put_u64(*monitor);
+                    }
+                }
+                (command_sets::THREAD_REFERENCE, thread_commands::CURRENT_CONTENDED_MONITOR) => {
+                    reply.put_u8(b'L');
+                    reply.put_u64(match first_id(data) {
+                        1 => 0xb,
+                        2 => 0xa,
+                        _ => 0xc,
+                    });
+                }
+                (command_sets::OBJECT_REFERENCE, object_reference_commands::REFERENCE_TYPE) => {
+                    reply.put_u8(1);
+                    reply.put_u64(0x10);
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::SIGNATURE) => {
+                    put_string(&mut reply, "Lcom/example/Account;");
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        let output = handler.handle_detect_deadlock(json!({})).await.unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines, [
+            "💀 1 deadlock(s) among 3 threads",
+            "",
+            "Deadlock 1 (2 threads):",
+            "  \"worker-1\" (0x1) waits for com.example.Account @b, held by \"worker-2\" (0x2)",
+            "  \"worker-2\" (0x2) waits for com.example.Account @a, held by \"worker-1\" (0x1)",
+        ]);
+    }
+
+    #[test]
+    fn test_format_bytecodes_groups_by_line() {
+        let bytecodes: Vec<u8> = (0..22).collect();
+        let line_table = LineTable {
+            start: 0,
+            end: 21,
+            lines: vec![
+                LineTableEntry { line_code_index: 4, line_number: 18 },
+                LineTableEntry { line_code_index: 0, line_number: 17 },
+                LineTableEntry { line_code_index: 40, line_number: 19 },
+            ],
+        };
+        let dump = format_bytecodes(&bytecodes, Some(&line_table));
+        let rows: Vec<&str> = dump.lines().collect();
+        assert_eq!(rows, [
+            "  line 17        0: 00 01 02 03",
+            "  line 18        4: 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f 10 11 12 13",
+            "                20: 14 15",
+        ]);
+
+        assert_eq!(format_bytecodes(&[0x2a, 0xb1], None), "                 0: 2a b1\n");
+    }
+
+    #[test]
+    fn test_find_deadlocks() {
+        // 3 -> 1 -> 2 -> 1 is one cycle (3 only waits on it); 4 -> 5 is not
+        let waits_for = HashMap::from([
+            (1, (0xa, 2)),
+            (2, (0xb, 1)),
+            (3, (0xa, 1)),
+            (4, (0xc, 5)),
+        ]);
+        assert_eq!(find_deadlocks(&waits_for), vec![vec![1, 2]]);
+
+        let ring = HashMap::from([(7, (0xa, 9)), (9, (0xb, 8)), (8, (0xc, 7))]);
+        assert_eq!(find_deadlocks(&ring), vec![vec![7, 9, 8]]);
+
+        assert!(find_deadlocks(&HashMap::new()).is_empty());
+    }
+
+    #[test]
+    fn test_class_name_matches() {
+        assert!(class_name_matches("com.example.UserController", "controller"));
+        assert!(class_name_matches("com.example.UserController", ""));
+        assert!(!class_name_matches("com.example.UserService", "Controller"));
+
+        assert!(class_name_matches("com.example.UserController", "com.example.*Controller"));
+        assert!(class_name_matches("com.example.UserController", "*user*"));
+        assert!(class_name_matches("com.example.Map$Entry", "*.Map?Entry"));
+        assert!(!class_name_matches("com.example.UserController", "*Controller.Inner"));
+        assert!(!class_name_matches("org.example.UserController", "com.*"));
+    }
+
+    #[tokio::test]
+    async fn test_method_entry_breakpoint_without_line_table() {
+        use std::sync::Mutex;
+
+        // Class 0x10 compiled without debug info, with two overloads of process
+        let set_requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = set_requests.clone();
+        let connection = fake_vm::connect_with(move |command_set, command, data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::VIRTUAL_MACHINE, vm_commands::CLASSES_BY_SIGNATURE) => reply = classes(&[0x10]),
+                (command_sets::REFERENCE_TYPE, reference_type_commands::METHODS) => {
+                    reply = methods(&[(0x20, "process", "()V"), (0x21, "process", "(I)V")]);
+                }
+                (command_sets::EVENT_REQUEST, event_commands::SET) => {
+                    recorded.lock().unwrap().push(data.to_vec());
+                    reply.put_i32(5);
+                }
+                _ => return Err(error_codes::ABSENT_INFORMATION),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        let error = handler.handle_set_breakpoint(json!({
+            "class_pattern": "com.example.Worker",
+            "method": "process",
+        })).await.unwrap_err();
+        assert_eq!(
+            error,
+            "process is overloaded; pass one of \"process()V\", \"process(I)V\" in class com.example.Worker"
+        );
+
+        let result = handler.handle_set_breakpoint(json!({
+            "class_pattern": "com.example.Worker",
+            "method": "process(I)V",
+        })).await.unwrap();
+        assert!(result.starts_with("✅ Breakpoint set at com.example.Worker.process(I)V entry\n"), "{}", result);
+
+        let mut expected = Vec::new();
+        expected.put_u8(event_kinds::BREAKPOINT);
+        expected.put_u8(1); // suspend the event thread
+        expected.put_i32(1);
+        expected.put_u8(7); // LocationOnly
+        expected.put_u8(1);
+        expected.put_u64(0x10);
+        expected.put_u64(0x21);
+        expected.put_u64(0);
+        assert_eq!(*set_requests.lock().unwrap(), [expected]);
+
+        let listing = handler.handle_list_breakpoints(json!({})).await.unwrap();
+        assert!(listing.contains("[bp_5] com.example.Worker.process(I)V entry"), "{}", listing);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_breakpoint_keeps_its_id_when_enabled() {
+        use std::sync::Mutex;
+
+        let cleared = Arc::new(Mutex::new(Vec::new()));
+        let recorded = cleared.clone();
+        let next_request_id = AtomicI32::new(5);
+        let connection = fake_vm::connect_with(move |command_set, command, data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::VIRTUAL_MACHINE, vm_commands::CLASSES_BY_SIGNATURE) => reply = classes(&[0x10]),
+                (command_sets::REFERENCE_TYPE, reference_type_commands::METHODS) => reply = methods(&[(0x20, "run", "()V")]),
+                (command_sets::METHOD, method_commands::LINE_TABLE) => reply = line_table(&[(7, 42)]),
+                (command_sets::EVENT_REQUEST, event_commands::SET) => {
+                    reply.put_i32(next_request_id.fetch_add(1, Ordering::SeqCst));
+                }
+                (command_sets::EVENT_REQUEST, event_commands::CLEAR) => {
+                    recorded.lock().unwrap().push(data.to_vec());
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        handler.handle_set_breakpoint(json!({"class_pattern": "com.example.Worker", "line": 42})).await.unwrap();
+
+        let output = handler.handle_disable_breakpoint(json!({"breakpoint_id": "bp_5"})).await.unwrap();
+        assert!(output.starts_with("⏸️  Breakpoint disabled: bp_5 at com.example.Worker:42"), "{}", output);
+        let mut expected = vec![event_kinds::BREAKPOINT];
+        expected.put_i32(5);
+        assert_eq!(*cleared.lock().unwrap(), [expected]);
+
+        let listing = handler.handle_list_breakpoints(json!({})).await.unwrap();
+        assert!(listing.contains("✗ [bp_5] com.example.Worker:42"), "{}", listing);
+
+        // Re-enabling sets a new request but keeps the breakpoint ID
+        let output = handler.handle_enable_breakpoint(json!({"breakpoint_id": "bp_5"})).await.unwrap();
+        assert_eq!(output, "✅ Breakpoint enabled: bp_5 at com.example.Worker:42\n   JDWP Request ID: 6");
+
+        let listing = handler.handle_list_breakpoints(json!({})).await.unwrap();
+        assert!(listing.starts_with("📍 1 breakpoint(s):\n\n  ✓ [bp_5] com.example.Worker:42\n"), "{}", listing);
+
+        let output = handler.handle_enable_breakpoint(json!({"breakpoint_id": "bp_5"})).await.unwrap();
+        assert_eq!(output, "Breakpoint bp_5 at com.example.Worker:42 is already enabled");
+    }
+
+    #[tokio::test]
+    async fn test_hit_limited_breakpoint_expires_after_firing() {
+        use std::sync::Mutex;
+
+        // Class 0x10 with one method 0x20 whose line 42 starts at index 7
+        let set_requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = set_requests.clone();
+        let connection = fake_vm::connect_with(move |command_set, command, data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::VIRTUAL_MACHINE, vm_commands::CLASSES_BY_SIGNATURE) => reply = classes(&[0x10]),
+                (command_sets::REFERENCE_TYPE, reference_type_commands::METHODS) => reply = methods(&[(0x20, "run", "()V")]),
+                (command_sets::METHOD, method_commands::LINE_TABLE) => reply = line_table(&[(7, 42)]),
+                (command_sets::EVENT_REQUEST, event_commands::SET) => {
+                    recorded.lock().unwrap().push(data.to_vec());
+                    reply.put_i32(5);
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        assert_eq!(
+            handler.handle_set_breakpoint(json!({
+                "class_pattern": "com.example.Worker:42",
+                "line": 43,
+            })).await.unwrap_err(),
+            "'class_pattern' gives line 42 but 'line' is 43"
+        );
+
+        // The line can come with the class, in any class name form
+        let result = handler.handle_set_breakpoint(json!({
+            "class_pattern": "com/example/Worker:42",
+            "hit_limit": 3,
+            "suspend_policy": "all",
+        })).await.unwrap();
+        assert!(result.starts_with("✅ Breakpoint set at com.example.Worker:42\n"), "{}", result);
+        assert!(result.contains("Stops on hit 3 only, then expires"), "{}", result);
+
+        let mut expected = Vec::new();
+        expected.put_u8(event_kinds::BREAKPOINT);
+        expected.put_u8(2); // suspend all
+        expected.put_i32(2);
+        expected.put_u8(7); // LocationOnly
+        expected.put_u8(1);
+        expected.put_u64(0x10);
+        expected.put_u64(0x20);
+        expected.put_u64(7);
+        expected.put_u8(1); // Count
+        expected.put_i32(3);
+        assert_eq!(*set_requests.lock().unwrap(), [expected]);
+
+        assert!(handler.handle_set_breakpoint(json!({
+            "class_pattern": "com.example.Worker",
+            "line": 42,
+            "hit_limit": 0,
+        })).await.unwrap_err().contains("hit_limit"));
+
+        // The VM reports the third hit and drops the request
+        let hit = EventSet {
+            suspend_policy: 2,
+            events: vec![Event {
+                kind: event_kinds::BREAKPOINT,
+                request_id: 5,
+                details: EventKind::Breakpoint {
+                    thread: 0x1,
+                    location: Location { type_tag: 1, class_id: 0x10, method_id: 0x20, index: 7 },
+                },
+            }],
+        };
+        let session_guard = handler.session_manager.get_current_session().await.unwrap();
+        record_breakpoint_hits(&mut *session_guard.lock().await, &hit);
+
+        let listing = handler.handle_list_breakpoints(json!({})).await.unwrap();
+        assert!(listing.contains("✗ [bp_5]"), "{}", listing);
+        assert!(listing.contains("Hits: 3"), "{}", listing);
+        assert!(listing.contains("Hit limit: 3 (reached, expired)"), "{}", listing);
+
+        // Nothing is left to clear in the VM (the fake JVM rejects EventRequest.Clear)
+        handler.handle_clear_breakpoint(json!({"breakpoint_id": "bp_5"})).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_thread_filtered_breakpoint_sends_thread_only() {
+        use std::sync::Mutex;
+
+        // Class 0x10 with one method 0x20 whose line 42 starts at index 7
+        let set_requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = set_requests.clone();
+        let connection = fake_vm::connect_with(move |command_set, command, data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::VIRTUAL_MACHINE, vm_commands::CLASSES_BY_SIGNATURE) => reply = classes(&[0x10]),
+                (command_sets::REFERENCE_TYPE, reference_type_commands::METHODS) => reply = methods(&[(0x20, "run", "()V")]),
+                (command_sets::METHOD, method_commands::LINE_TABLE) => reply = line_table(&[(7, 42)]),
+                (command_sets::THREAD_REFERENCE, thread_commands::NAME) => put_string(&mut reply, "worker-1"),
+                (command_sets::EVENT_REQUEST, event_commands::SET) => {
+                    recorded.lock().unwrap().push(data.to_vec());
+                    reply.put_i32(5);
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        let result = handler.handle_set_breakpoint(json!({
+            "class_pattern": "com.example.Worker",
+            "line": 42,
+            "thread_id": "0x30",
+            "hit_limit": 2,
+        })).await.unwrap();
+        assert!(result.contains("\n   Only on thread worker-1 (0x30)\n"), "{}", result);
+        assert!(result.ends_with("\n   Suspends: thread (other threads keep running)"), "{}", result);
+
+        // The count comes last so only this thread's hits count towards it
+        let mut expected = Vec::new();
+        expected.put_u8(event_kinds::BREAKPOINT);
+        expected.put_u8(1); // suspend the event thread, the default
+        expected.put_i32(3);
+        expected.put_u8(7); // LocationOnly
+        expected.put_u8(1);
+        expected.put_u64(0x10);
+        expected.put_u64(0x20);
+        expected.put_u64(7);
+        expected.put_u8(3); // ThreadOnly
+        expected.put_u64(0x30);
+        expected.put_u8(1); // Count
+        expected.put_i32(2);
+        assert_eq!(*set_requests.lock().unwrap(), [expected]);
+
+        let listing = handler.handle_list_breakpoints(json!({})).await.unwrap();
+        assert!(listing.contains("     Thread: 0x30\n     Suspends: thread\n"), "{}", listing);
+
+        handler.handle_set_breakpoint(json!({
+            "class_pattern": "com.example.Worker:42",
+            "suspend_policy": "none",
+        })).await.unwrap();
+        assert_eq!(set_requests.lock().unwrap()[1][1], 0); // suspend nothing
+
+        assert!(handler.handle_set_breakpoint(json!({
+            "class_pattern": "com.example.Worker:42",
+            "suspend_policy": "vm",
+        })).await.unwrap_err().contains("suspend_policy"));
+
+        assert!(handler.handle_set_breakpoint(json!({
+            "class_pattern": "com.example.Worker",
+            "line": 42,
+            "thread_id": "main",
+        })).await.unwrap_err().contains("thread_id"));
+    }
+
+    #[tokio::test]
+    async fn test_validate_only_resolves_without_setting() {
+        // Class 0x10: run() has lines 40, 41 and 44; helper() has 50 and 51
+        let sets = Arc::new(AtomicUsize::new(0));
+        let recorded = sets.clone();
+        let connection = fake_vm::connect_with(move |command_set, command, mut data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::VIRTUAL_MACHINE, vm_commands::CLASSES_BY_SIGNATURE) => {
+                    reply = if data.windows(7).any(|w| w == b"Missing") { classes(&[]) } else { classes(&[0x10]) };
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::METHODS) => {
+                    reply = methods(&[(0x20, "run", "()V"), (0x21, "helper", "()V")]);
+                }
+                (command_sets::METHOD, method_commands::LINE_TABLE) => {
+                    let _class_id = data.get_u64();
+                    reply = match data.get_u64() {
+                        0x20 => line_table(&[(0, 40), (4, 41), (9, 44)]),
+                        _ => line_table(&[(0, 50), (3, 51)]),
+                    };
+                }
+                (command_sets::EVENT_REQUEST, event_commands::SET) => {
+                    recorded.fetch_add(1, Ordering::SeqCst);
+                    reply.put_i32(5);
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        let output = handler.handle_set_breakpoint(json!({
+            "class_pattern": "com.example.Worker:41",
+            "validate_only": true,
+        })).await.unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines[0], "🔍 com.example.Worker:41 is a valid breakpoint location (not set)");
+        assert!(lines[1].starts_with("   Method: ") && lines[1].contains("run"), "{}", output);
+        assert_eq!(lines[2..], [
+            "   Code index: 4",
+            "   Nearest lines with code: 40 (run), 41 (run), 44 (run), 50 (helper), 51 (helper)",
+        ]);
+
+        let output = handler.handle_set_breakpoint(json!({
+            "class_pattern": "com.example.Worker#helper",
+            "validate_only": true,
+        })).await.unwrap();
+        assert!(output.starts_with("🔍 com.example.Worker.helper()V entry is a valid breakpoint location"), "{}", output);
+        assert!(output.ends_with("\n   Code index: 0"), "{}", output);
+
+        // A line without code lists the lines to pick from instead
+        let error = handler.handle_set_breakpoint(json!({
+            "class_pattern": "com.example.Worker:43",
+            "validate_only": true,
+        })).await.unwrap_err();
+        assert_eq!(
+            error,
+            "No method found containing line 43 in class com.example.Worker; \
+             nearest lines with code: 40 (run), 41 (run), 44 (run), 50 (helper), 51 (helper)"
+        );
+
+        let output = handler.handle_set_breakpoint(json!({
+            "class_pattern": "com.example.Missing:10",
+            "validate_only": true,
+        })).await.unwrap();
+        assert!(output.starts_with("🔍 com.example.Missing isn't loaded yet"), "{}", output);
+
+        // Nothing was set or deferred
+        assert_eq!(sets.load(Ordering::SeqCst), 0);
+        assert!(handler.handle_list_breakpoints(json!({})).await.unwrap().starts_with("No breakpoints"));
+
+        // The same hint comes with a failed set
+        let error = handler.handle_set_breakpoint(json!({"class_pattern": "com.example.Worker:45"})).await.unwrap_err();
+        assert!(error.ends_with("; nearest lines with code: 40 (run), 41 (run), 44 (run), 50 (helper), 51 (helper)"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_breakpoint_on_unloaded_class_is_set_when_it_loads() {
+        use std::sync::Mutex;
+
+        // Class 0x10, once loaded, has one method 0x20 whose line 42 starts at index 7
+        let loaded = Arc::new(AtomicBool::new(false));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let (is_loaded, recorded) = (loaded.clone(), requests.clone());
+        let connection = fake_vm::connect_with(move |command_set, command, data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::VIRTUAL_MACHINE, vm_commands::CLASSES_BY_SIGNATURE) => {
+                    reply = if is_loaded.load(Ordering::SeqCst) { classes(&[0x10]) } else { classes(&[]) };
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::METHODS) => reply = methods(&[(0x20, "run", "()V")]),
+                (command_sets::METHOD, method_commands::LINE_TABLE) => reply = line_table(&[(7, 42)]),
+                (command_sets::EVENT_REQUEST, event_commands::SET) => {
+                    recorded.lock().unwrap().push((command, data.to_vec()));
+                    reply.put_i32(if data[0] == event_kinds::CLASS_PREPARE { 8 } else { 9 });
+                }
+                (command_sets::EVENT_REQUEST, event_commands::CLEAR)
+                | (command_sets::THREAD_REFERENCE, thread_commands::RESUME) => {
+                    recorded.lock().unwrap().push((command, data.to_vec()));
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        let output = handler.handle_set_breakpoint(json!({
+            "class_pattern": "com.example.Worker",
+            "line": 42,
+        })).await.unwrap();
+        assert_eq!(output.lines().collect::<Vec<_>>(), [
+            "⏳ Breakpoint deferred: com.example.Worker:42 isn't loaded yet",
+            "   Breakpoint ID: bp_8",
+            "   It will be set when the class loads",
+        ]);
+
+        // ClassPrepare, suspending the loading thread, matching the class name
+        let mut class_prepare = Vec::new();
+        class_prepare.put_u8(event_kinds::CLASS_PREPARE);
+        class_prepare.put_u8(1);
+        class_prepare.put_i32(1);
+        class_prepare.put_u8(5); // ClassMatch
+        put_string(&mut class_prepare, "com.example.Worker");
+        assert_eq!(requests.lock().unwrap().drain(..).collect::<Vec<_>>(), [(event_commands::SET, class_prepare)]);
+
+        let listing = handler.handle_list_breakpoints(json!({})).await.unwrap();
+        assert!(listing.contains("⏳ 1 deferred breakpoint(s)"), "{}", listing);
+        assert!(listing.contains("  [bp_8] com.example.Worker:42\n"), "{}", listing);
+
+        loaded.store(true, Ordering::SeqCst);
+        let prepared = EventSet {
+            suspend_policy: 1,
+            events: vec![Event {
+                kind: event_kinds::CLASS_PREPARE,
+                request_id: 8,
+                details: EventKind::ClassPrepare {
+                    thread: 0x1,
+                    ref_type: 0x10,
+                    signature: "Lcom/example/Worker;".to_string(),
+                    status: 7,
+                },
+            }],
+        };
+        let session_guard = handler.session_manager.get_current_session().await.unwrap();
+        assert!(install_deferred_breakpoints(&mut *session_guard.lock().await, &prepared).await);
+
+        // The ClassPrepare request is cleared, the breakpoint set, then the thread resumed
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 3, "{:?}", requests);
+        assert_eq!(requests[0], (event_commands::CLEAR, [&[event_kinds::CLASS_PREPARE][..], &[0, 0, 0, 8]].concat()));
+        assert_eq!((requests[1].0, requests[1].1[0]), (event_commands::SET, event_kinds::BREAKPOINT));
+        assert_eq!(requests[2], (thread_commands::RESUME, vec![0, 0, 0, 0, 0, 0, 0, 1]));
+
+        // Tracked under the ID it was given when deferred
+        let listing = handler.handle_list_breakpoints(json!({})).await.unwrap();
+        assert!(listing.starts_with("📍 1 breakpoint(s):\n\n  ✓ [bp_8] com.example.Worker:42\n"), "{}", listing);
+        assert!(!listing.contains("deferred"), "{}", listing);
+    }
+
+    #[test]
+    fn test_step_filters_arg() {
+        let defaults = step_filters_arg(&json!({}), step_depths::INTO).unwrap();
+        assert!(defaults.contains(&"java.*".to_string()));
+        assert!(step_filters_arg(&json!({}), step_depths::OVER).unwrap().is_empty());
+
+        let custom = step_filters_arg(&json!({"step_filters": ["com.vendor.*"]}), step_depths::INTO).unwrap();
+        assert_eq!(custom, ["com.vendor.*"]);
+        assert!(step_filters_arg(&json!({"step_filters": []}), step_depths::INTO).unwrap().is_empty());
+
+        assert!(step_filters_arg(&json!({"step_filters": "java.*"}), step_depths::INTO).is_err());
+        assert!(step_filters_arg(&json!({"step_filters": [1]}), step_depths::INTO).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_attach_honours_timeout() {
+        // A peer that accepts but never answers the handshake
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (_socket, _) = listener.accept().await.unwrap();
+            std::future::pending::<()>().await;
+        });
+
+        let handler = RequestHandler::new();
+        let started = std::time::Instant::now();
+        let error = handler
+            .handle_attach(json!({"host": "127.0.0.1", "port": port, "timeout_ms": 200}))
+            .await
+            .unwrap_err();
+        assert!(error.starts_with("Failed to connect:"), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(handler.session_manager.get_current_session().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_attach_listens_for_dialling_jvm() {
+        // Find a free port for the JVM to dial
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let bind = format!("127.0.0.1:{}", port);
+        tokio::spawn(fake_vm::dial(bind.clone(), |_, _, _| Err(NOT_IMPLEMENTED)));
+
+        let handler = RequestHandler::new();
+        let output = handler
+            .handle_attach(json!({"mode": "listen", "bind": bind, "timeout_ms": 5000}))
+            .await
+            .unwrap();
+        assert!(output.starts_with(&format!("Accepted JVM connection on {} (session: ", bind)), "{}", output);
+
+        let session_guard = handler.session_manager.get_current_session().await.unwrap();
+        let session = session_guard.lock().await;
+        assert!(session.listening);
+        assert_eq!((session.host.as_str(), session.port), ("127.0.0.1", port));
+    }
+
+    #[tokio::test]
+    async fn test_attach_rejects_unknown_mode() {
+        let handler = RequestHandler::new();
+        let error = handler.handle_attach(json!({"mode": "dial"})).await.unwrap_err();
+        assert_eq!(error, "Unknown mode 'dial' (expected attach or listen)");
+    }
+
+    #[tokio::test]
+    async fn test_attach_explains_unsupported_shmem_transport() {
+        let handler = RequestHandler::new();
+
+        let error = handler.handle_attach(json!({"transport": "shmem"})).await.unwrap_err();
+        assert!(error.starts_with("Missing 'name'"), "{}", error);
+
+        let error = handler.handle_attach(json!({"transport": "shmem", "name": "javadebug"})).await.unwrap_err();
+        assert!(error.contains("dt_shmem transport isn't supported yet") && error.contains("'javadebug'"), "{}", error);
+
+        let error = handler.handle_attach(json!({"transport": "pipe"})).await.unwrap_err();
+        assert_eq!(error, "Unknown transport 'pipe' (expected socket or shmem)");
+    }
+
+    #[tokio::test]
+    async fn test_session_ends_when_connection_closes() {
+        // A VM that hangs up right after the handshake
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut handshake = [0u8; 14];
+            socket.read_exact(&mut handshake).await.unwrap();
+            socket.write_all(JDWP_HANDSHAKE).await.unwrap();
+        });
+        let connection = JdwpConnection::connect("127.0.0.1", port).await.unwrap();
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", port).await;
+
+        let error = handler.run_tool("debug.list_threads", json!({})).await.unwrap_err();
+        assert!(error.starts_with("🛑 Debug session ended (connection to the VM closed)"), "{}", error);
+        assert!(handler.session_manager.get_current_session().await.is_none());
+
+        // Later calls report the missing session as usual
+        let error = handler.run_tool("debug.list_threads", json!({})).await.unwrap_err();
+        assert_eq!(error, "No active debug session");
+    }
+
+    #[tokio::test]
+    async fn test_pop_frame_reports_caller_location() {
+        let popped = AtomicBool::new(false);
+        let connection = fake_vm::connect_with(move |command_set, command, _| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::THREAD_REFERENCE, thread_commands::FRAMES) => {
+                    // The caller is on top once the frame is popped
+                    reply = match popped.load(Ordering::SeqCst) {
+                        false => frames(&[(0x100, 0x10, 0x20, 5)]),
+                        true => frames(&[(0x101, 0x10, 0x21, 3)]),
+                    };
+                }
+                (command_sets::STACK_FRAME, stack_frame_commands::POP_FRAMES) => {
+                    popped.store(true, Ordering::SeqCst);
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::SIGNATURE) => {
+                    put_string(&mut reply, "Lcom/example/Worker;");
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::METHODS) => reply = methods(&[(0x21, "process", "()V")]),
+                (command_sets::METHOD, method_commands::LINE_TABLE) => reply = line_table(&[(0, 17)]),
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        let output = handler.handle_pop_frame(json!({"thread_id": "0x1"})).await.unwrap();
+        assert_eq!(
+            output,
+            "⏏️  Popped 1 frame(s) on thread 0x1\n   Now at: com.example.Worker.process line 17\n   The call is re-run when the thread steps or resumes\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_force_return_encodes_value_for_return_type() {
+        let connection = fake_vm::connect_with(|command_set, command, data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::THREAD_REFERENCE, thread_commands::FRAMES) => reply = frames(&[(0x100, 0x10, 0x20, 5)]),
+                (command_sets::REFERENCE_TYPE, reference_type_commands::METHODS) => reply = methods(&[(0x20, "compute", "(J)I")]),
+                (command_sets::THREAD_REFERENCE, thread_commands::FORCE_EARLY_RETURN) => {
+                    // Thread 0x1, then the int 42 tagged 'I'
+                    let mut expected = vec![0, 0, 0, 0, 0, 0, 0, 1, b'I'];
+                    expected.put_i32(42);
+                    if data != expected.as_slice() {
+                        return Err(error_codes::TYPE_MISMATCH);
+                    }
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        let output = handler.handle_force_return(json!({"thread_id": "0x1", "value": 42})).await.unwrap();
+        assert_eq!(
+            output,
+            "↩️  compute on thread 0x1 returns (int) 42 without running the rest of its body\n   The return happens when the thread steps or resumes\n"
+        );
+
+        let error = handler.handle_force_return(json!({"thread_id": "0x1"})).await.unwrap_err();
+        assert_eq!(error, "Expected a int value, got null");
+    }
+
+    #[tokio::test]
+    async fn test_tools_address_sessions_by_id() {
+        let handler = RequestHandler::new();
+        let first = fake_vm::connect_with(|_, _, _| Err(NOT_IMPLEMENTED)).await;
+        let first = handler.session_manager.create_session(first, "127.0.0.1", 5005).await;
+        let second = fake_vm::connect_with(|_, _, _| Err(NOT_IMPLEMENTED)).await;
+        let second = handler.session_manager.create_session(second, "127.0.0.1", 5006).await;
+
+        let listing = handler.call_text_tool("debug.list_sessions", json!({})).await.unwrap();
+        assert!(listing.starts_with("🔌 2 debug session(s):"), "{}", listing);
+        assert!(listing.contains(&format!("    {} → 127.0.0.1:5005 (connected, 0 breakpoint(s))", first)), "{}", listing);
+        assert!(listing.contains(&format!("  * {} → 127.0.0.1:5006 (connected, 0 breakpoint(s))", second)), "{}", listing);
+
+        let error = handler.run_tool("debug.list_breakpoints", json!({"session_id": "session_missing"})).await.unwrap_err();
+        assert_eq!(error, "Unknown session: session_missing (see debug.list_sessions)");
+
+        // Disconnecting another session leaves the current one alone
+        let output = handler.call_text_tool("debug.disconnect", json!({"session_id": first})).await.unwrap();
+        assert_eq!(output, format!("✅ Disconnected from debug session: {}", first));
+        assert_eq!(handler.session_manager.get_current_session_id().await, Some(second.clone()));
+        assert!(handler.session_manager.get_session(&first).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_classpath_lists_entries() {
+        let connection = fake_vm::connect_with(|command_set, command, _| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::VIRTUAL_MACHINE, vm_commands::CLASS_PATHS) => {
+                    put_string(&mut reply, "/srv/app");
+                    reply.put_i32(2);
+                    put_string(&mut reply, "/srv/app/classes");
+                    put_string(&mut reply, "lib/dep.jar");
+                    reply.put_i32(0);
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        let output = handler.call_text_tool("debug.classpath", json!({})).await.unwrap();
+        assert_eq!(
+            output.lines().collect::<Vec<_>>(),
+            ["📂 Classpath (relative to /srv/app):", "  /srv/app/classes", "  lib/dep.jar"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_threads_records_thread_details() {
+        // main runs; worker sleeps, suspended twice; thread 3 exits mid-listing
+        let connection = fake_vm::connect_with(|command_set, command, data| {
+            let mut reply = Vec::new();
+            let thread_id = if command_set == command_sets::THREAD_REFERENCE { first_id(data) } else { 0 };
+            match (command_set, command, thread_id) {
+                (command_sets::VIRTUAL_MACHINE, vm_commands::ALL_THREADS, _) => {
+                    reply.put_i32(3);
+                    for thread_id in 1..=3 {
+                        reply.put_u64(thread_id);
+                    }
+                }
+                (command_sets::THREAD_REFERENCE, _, 3) => return Err(error_codes::INVALID_THREAD),
+                (command_sets::THREAD_REFERENCE, thread_commands::NAME, 1) => put_string(&mut reply, "main"),
+                (command_sets::THREAD_REFERENCE, thread_commands::NAME, _) => put_string(&mut reply, "worker"),
+                (command_sets::THREAD_REFERENCE, thread_commands::STATUS, 1) => {
+                    reply.put_i32(1);
+                    reply.put_i32(0);
+                }
+                (command_sets::THREAD_REFERENCE, thread_commands::STATUS, _) => {
+                    reply.put_i32(2);
+                    reply.put_i32(1);
+                }
+                (command_sets::THREAD_REFERENCE, thread_commands::SUSPEND_COUNT, 1) => reply.put_i32(0),
+                (command_sets::THREAD_REFERENCE, thread_commands::SUSPEND_COUNT, _) => reply.put_i32(2),
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        let output = handler.handle_list_threads(json!({})).await.unwrap();
+        assert_eq!(
+            output.lines().collect::<Vec<_>>(),
+            [
+                "🧵 2 thread(s):",
+                "",
+                "  main (0x1)",
+                "     Status: running",
+                "  worker (0x2)",
+                "     Status: sleeping, suspended (count 2; resume 2 times)",
+            ]
+        );
+
+        let session_guard = handler.session_manager.get_current_session().await.unwrap();
+        let session = session_guard.lock().await;
+        let worker = &session.threads[&0x2];
+        assert_eq!((worker.name.as_str(), worker.status, worker.suspend_count), ("worker", ThreadStatus::Sleeping, 2));
+        assert!(worker.is_suspended());
+        assert!(!session.threads[&0x1].is_suspended());
+        assert!(!session.threads.contains_key(&0x3));
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_reports_a_vm_that_stops_answering() {
+        // Answers Version while connecting, then fails every check
+        let versions = Arc::new(AtomicUsize::new(0));
+        let counted = versions.clone();
+        let connection = fake_vm::connect_with(move |command_set, command, _| match (command_set, command) {
+            (command_sets::VIRTUAL_MACHINE, vm_commands::VERSION) if counted.fetch_add(1, Ordering::SeqCst) == 0 => {
+                Ok(version())
+            }
+            (command_sets::VIRTUAL_MACHINE, vm_commands::VERSION) => Err(112), // VM_DEAD
+            _ => Err(NOT_IMPLEMENTED),
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.set_keepalive(Some(Duration::from_millis(20)));
+        let session_id = handler.session_manager.create_session(connection.clone(), "127.0.0.1", 0).await;
+        let (sender, mut notifications) = mpsc::channel(4);
+        {
+            let session_guard = handler.session_manager.get_session(&session_id).await.unwrap();
+            let mut session = session_guard.lock().await;
+            spawn_keepalive(&handler.session_manager, &session_id, &mut session, Some(sender));
+        }
+
+        let notification = tokio::time::timeout(Duration::from_secs(5), notifications.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let data = &notification["params"]["data"];
+        assert_eq!(notification["params"]["level"], "warning");
+        assert_eq!(data["event"], "disconnected");
+        assert_eq!(data["session_id"], session_id.as_str());
+        assert!(data["reason"].as_str().unwrap().starts_with("VM stopped answering"), "{}", data);
+
+        assert_eq!(versions.load(Ordering::SeqCst), 2);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!connection.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_stop_notification_describes_breakpoint_hit() {
+        let mut connection = fake_vm::connect_with(|command_set, command, _data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::REFERENCE_TYPE, reference_type_commands::SIGNATURE) => {
+                    put_string(&mut reply, "Lcom/example/Worker;");
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::METHODS) => reply = methods(&[(0x20, "run", "()V")]),
+                (command_sets::METHOD, method_commands::LINE_TABLE) => reply = line_table(&[(7, 42)]),
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let event_set = EventSet {
+            suspend_policy: 2,
+            events: vec![Event {
+                kind: event_kinds::BREAKPOINT,
+                request_id: 5,
+                details: EventKind::Breakpoint {
+                    thread: 0x1,
+                    location: Location { type_tag: 1, class_id: 0x10, method_id: 0x20, index: 9 },
+                },
+            }],
+        };
+        let event = event_set.stop_event().unwrap();
+        let notification = stop_notification(&mut connection, &mut LocationResolver::default(), &event_set, event).await;
+
+        assert_eq!(notification["method"], "notifications/message");
+        assert_eq!(notification["params"]["level"], "info");
+        assert_eq!(notification["params"]["data"], json!({
+            "event": "breakpoint",
+            "request_id": 5,
+            "suspend_policy": "all",
+            "thread_id": "0x1",
+            "class": "com.example.Worker",
+            "method": "run",
+            "line": 42,
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_stop_notification_includes_exception_message() {
+        // NullPointerException 0xe0 (type 0xc1) thrown in com.example.Worker 0x10
+        let mut connection = fake_vm::connect_with(|command_set, command, data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::OBJECT_REFERENCE, object_reference_commands::REFERENCE_TYPE) => {
+                    reply.put_u8(1);
+                    reply.put_u64(0xc1);
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::SIGNATURE) if first_id(data) == 0xc1 => {
+                    put_string(&mut reply, "Ljava/lang/NullPointerException;");
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::SIGNATURE) => {
+                    put_string(&mut reply, "Lcom/example/Worker;");
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::FIELDS) => {
+                    reply.put_i32(1);
+                    reply.put_u64(0xf2);
+                    put_string(&mut reply, "detailMessage");
+                    put_string(&mut reply, "Ljava/lang/String;");
+                    reply.put_i32(0x2);
+                }
+                (command_sets::CLASS_TYPE, class_type_commands::SUPERCLASS) => reply.put_u64(0),
+                (command_sets::REFERENCE_TYPE, reference_type_commands::MODIFIERS) => reply.put_i32(0x1),
+                (command_sets::OBJECT_REFERENCE, object_reference_commands::GET_VALUES) => {
+                    reply.put_i32(1);
+                    reply.put_u8(b's');
+                    reply.put_u64(0x50);
+                }
+                (command_sets::STRING_REFERENCE, string_reference_commands::VALUE) => {
+                    put_string(&mut reply, "x is null");
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let event_set = EventSet {
+            suspend_policy: 2,
+            events: vec![Event {
+                kind: event_kinds::EXCEPTION,
+                request_id: 3,
+                details: EventKind::Exception {
+                    thread: 0x1,
+                    location: Location { type_tag: 1, class_id: 0x10, method_id: 0x20, index: 9 },
+                    exception: 0xe0,
+                    catch_location: None,
+                },
+            }],
+        };
+        let event = event_set.stop_event().unwrap();
+        let notification = stop_notification(&mut connection, &mut LocationResolver::default(), &event_set, event).await;
+
+        let data = &notification["params"]["data"];
+        assert_eq!(data["event"], "exception");
+        assert_eq!(data["class"], "com.example.Worker");
+        assert_eq!(data["exception"], "java.lang.NullPointerException: \"x is null\"");
+        assert_eq!(data["exception_id"], "0xe0");
+    }
+
+    #[test]
+    fn test_optional_thread_id() {
+        assert_eq!(optional_thread_id(&json!({})), Ok(None));
+        assert_eq!(optional_thread_id(&json!({"thread_id": "0x1f"})), Ok(Some(0x1f)));
+        assert_eq!(optional_thread_id(&json!({"thread_id": "1f"})), Ok(Some(0x1f)));
+        assert!(optional_thread_id(&json!({"thread_id": "main"})).is_err());
+    }
+
+    #[test]
+    fn test_value_from_json_primitives() {
+        assert_eq!(value_from_json("I", &json!(42)).unwrap().data, ValueData::Int(42));
+        assert_eq!(value_from_json("J", &json!(-1)).unwrap().data, ValueData::Long(-1));
+        assert_eq!(value_from_json("Z", &json!(true)).unwrap().data, ValueData::Boolean(true));
+        assert_eq!(value_from_json("C", &json!("x")).unwrap().data, ValueData::Char('x' as u16));
+        assert_eq!(value_from_json("D", &json!(1.5)).unwrap().data, ValueData::Double(1.5));
+
+        assert!(value_from_json("B", &json!(300)).is_err());
+        assert!(value_from_json("I", &json!("42")).is_err());
+        assert!(value_from_json("C", &json!("xy")).is_err());
+    }
+
+    #[test]
+    fn test_value_from_json_references() {
+        let value = value_from_json("Ljava/util/List;", &json!(null)).unwrap();
+        assert_eq!((value.tag, value.data), (b'L', ValueData::Object(0)));
+
+        let value = value_from_json("[I", &json!("0x1f")).unwrap();
+        assert_eq!((value.tag, value.data), (b'[', ValueData::Object(0x1f)));
+
+        assert!(value_from_json("Ljava/lang/Object;", &json!(5)).is_err());
+    }
+
+    #[test]
+    fn test_array_range_within_bounds() {
+        assert_eq!(
+            ArrayRange::clamp(2, 3, 10),
+            ArrayRange { first: 2, length: 3, note: None }
+        );
+        assert_eq!(ArrayRange::clamp(0, 0, 0).note, None);
+    }
+
+    #[test]
+    fn test_array_range_clamps_oversized_requests() {
+        let range = ArrayRange::clamp(8, 50, 10);
+        assert_eq!((range.first, range.length), (8, 2));
+        assert!(range.note.is_some());
+
+        let range = ArrayRange::clamp(15, 5, 10);
+        assert_eq!((range.first, range.length), (10, 0));
+        assert!(range.note.unwrap().contains("past the end"));
+
+        let range = ArrayRange::clamp(0, 10_000, 100_000);
+        assert_eq!(range.length as i64, MAX_ARRAY_ELEMENTS);
+        assert!(range.note.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_step_after_thread_only_stop_leaves_other_threads_alone() {
+        use std::sync::Mutex;
+
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let (resumed_tx, mut resumed_rx) = tokio::sync::mpsc::unbounded_channel();
+        let connection = fake_vm::connect_with(move |command_set, command, data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::EVENT_REQUEST, event_commands::SET) => {
+                    recorded.lock().unwrap().push(data[..2].to_vec());
+                    reply.put_i32(9);
+                }
+                (command_sets::EVENT_REQUEST, event_commands::CLEAR) => {}
+                (command_sets::THREAD_REFERENCE, thread_commands::RESUME) => resumed_tx.send("thread").unwrap(),
+                (command_sets::VIRTUAL_MACHINE, vm_commands::RESUME) => resumed_tx.send("all").unwrap(),
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+        let session_guard = handler.session_manager.get_current_session().await.unwrap();
+        let location = Location { type_tag: 1, class_id: 0x10, method_id: 0x20, index: 7 };
+        let events = {
+            let mut session = session_guard.lock().await;
+            session.last_event = Some(EventSet {
+                suspend_policy: 1,
+                events: vec![Event {
+                    kind: event_kinds::BREAKPOINT,
+                    request_id: 5,
+                    details: EventKind::Breakpoint { thread: 0x1, location: location.clone() },
+                }],
+            });
+            session.events.clone()
+        };
+
+        // The step completes once the thread is resumed
+        let vm = async {
+            let resumed = resumed_rx.recv().await.unwrap();
+            events.send(EventSet {
+                suspend_policy: 1,
+                events: vec![Event {
+                    kind: event_kinds::SINGLE_STEP,
+                    request_id: 9,
+                    details: EventKind::Step { thread: 0x1, location },
+                }],
+            }).unwrap();
+            resumed
+        };
+        let (result, resumed) = tokio::join!(handler.handle_step_over(json!({"thread_id": "0x1"})), vm);
+
+        assert!(result.unwrap().starts_with("⏭️  Stepped thread 0x1\n"));
+        assert_eq!(resumed, "thread");
+        assert_eq!(*requests.lock().unwrap(), [vec![event_kinds::SINGLE_STEP, 1]]);
+    }
+
+    #[tokio::test]
+    async fn test_inspect_collection_pins_the_array_it_reads() {
+        use std::sync::Mutex;
+
+        // ArrayList 0x30 (type 0x90) whose toArray() gives array 0x40,
+        // collected before its length can be read
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let recorded = log.clone();
+        let connection = fake_vm::connect_with(move |command_set, command, mut data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::OBJECT_REFERENCE, object_reference_commands::REFERENCE_TYPE) => {
+                    reply.put_u8(1);
+                    reply.put_u64(0x90);
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::SIGNATURE) => {
+                    put_string(&mut reply, if first_id(data) == 0x90 { "Ljava/util/ArrayList;" } else { "Ljava/util/List;" });
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::INTERFACES) => {
+                    if first_id(data) == 0x90 {
+                        reply.put_i32(1);
+                        reply.put_u64(0x92);
+                    } else {
+                        reply.put_i32(0);
+                    }
+                }
+                (command_sets::CLASS_TYPE, class_type_commands::SUPERCLASS) => reply.put_u64(0),
+                (command_sets::REFERENCE_TYPE, reference_type_commands::METHODS) => {
+                    reply = methods(&[(0x1, "size", "()I"), (0x2, "toArray", "()[Ljava/lang/Object;")]);
+                }
+                (command_sets::OBJECT_REFERENCE, object_reference_commands::INVOKE_METHOD) => {
+                    data.advance(24); // object, thread, class
+                    if data.get_u64() == 0x1 {
+                        reply.put_u8(b'I');
+                        reply.put_i32(3);
+                    } else {
+                        reply.put_u8(b'[');
+                        reply.put_u64(0x40);
+                    }
+                    reply.put_u8(b'L');
+                    reply.put_u64(0);
+                }
+                (command_sets::OBJECT_REFERENCE, object_reference_commands::DISABLE_COLLECTION) => {
+                    recorded.lock().unwrap().push(("disable", first_id(data)));
+                }
+                (command_sets::OBJECT_REFERENCE, object_reference_commands::ENABLE_COLLECTION) => {
+                    recorded.lock().unwrap().push(("enable", first_id(data)));
+                }
+                (command_sets::ARRAY_REFERENCE, array_reference_commands::LENGTH) => {
+                    recorded.lock().unwrap().push(("length", first_id(data)));
+                    return Err(error_codes::INVALID_OBJECT);
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        let error = handler.handle_inspect_collection(json!({
+            "thread_id": "0x1",
+            "object_id": "0x30",
+        })).await.unwrap_err();
+        assert!(error.starts_with("Failed to get array length"), "{}", error);
+
+        // Pinned before it was read, and released despite the failure
+        assert_eq!(*log.lock().unwrap(), [("disable", 0x40), ("length", 0x40), ("enable", 0x40)]);
+    }
+
+    #[tokio::test]
+    async fn test_get_this_in_static_and_instance_frames() {
+        // Frame 0 (0x100) is in a static method; frame 1 (0x101) runs on
+        // com.example.Worker 0x77
+        let connection = fake_vm::connect_with(|command_set, command, mut data| {
+            let mut reply = Vec::new();
+            match (command_set, command) {
+                (command_sets::THREAD_REFERENCE, thread_commands::FRAMES) => {
+                    data.advance(8); // thread
+                    let start = data.get_i32();
+                    reply = frames(&[(0x100 + start as u64, 0x10, 0x20, 0)]);
+                }
+                (command_sets::STACK_FRAME, stack_frame_commands::THIS_OBJECT) => {
+                    data.advance(8); // thread
+                    reply.put_u8(b'L');
+                    reply.put_u64(if data.get_u64() == 0x101 { 0x77 } else { 0 });
+                }
+                (command_sets::OBJECT_REFERENCE, object_reference_commands::REFERENCE_TYPE) => {
+                    reply.put_u8(1);
+                    reply.put_u64(0x90);
+                }
+                (command_sets::REFERENCE_TYPE, reference_type_commands::SIGNATURE) => {
+                    put_string(&mut reply, "Lcom/example/Worker;");
+                }
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(reply)
+        })
+        .await;
+
+        let handler = RequestHandler::new();
+        handler.session_manager.create_session(connection, "127.0.0.1", 0).await;
+
+        let result = handler.handle_get_this(json!({"thread_id": "0x1"})).await.unwrap();
+        assert_eq!(result, "Frame 0 is a static or native method, so 'this' is null");
+
+        let result = handler.handle_get_this(json!({"thread_id": "0x1", "frame_index": 1})).await.unwrap();
+        assert_eq!(
+            result,
+            "👤 this = com.example.Worker @77\n   Object ID: 0x77\n   Use debug.inspect_object to see its fields"
+        );
+    }
 }
@@ -0,0 +1,377 @@
+// Semantic rendering of common JDK types
+//
+// A field dump of an ArrayList or HashMap shows its internals (elementData,
+// modCount, table, ...) rather than what it holds. Objects whose class is in
+// KNOWN_TYPES are instead rendered from their backing fields, e.g.
+// `ArrayList[3] = ["a", "b", "c"]` or `Integer(42)`; anything else is left
+// to the generic field-by-field expansion. Only fields are read and no
+// methods are invoked, so this works in any suspended frame.
+
+use jdwp_client::reftype::DeclaredField;
+use jdwp_client::types::{ObjectId, ReferenceTypeId, Value, ValueData, ACC_STATIC};
+use jdwp_client::JdwpConnection;
+use std::collections::HashMap;
+
+/// Where a known type keeps its contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    /// The primitive is in `value`
+    Boxed,
+    /// The first `size` slots of the `elementData` array
+    ArrayList,
+    /// `size` entries in the `table` buckets, each a chain of nodes with
+    /// `key`, `value` and `next`
+    HashMap,
+    /// `value`, or null when empty
+    Optional,
+}
+
+/// Classes rendered by their contents: JNI signature, display name, layout
+///
+/// Matches are exact, since a subclass may keep its contents elsewhere.
+const KNOWN_TYPES: &[(&str, &str, Layout)] = &[
+    ("Ljava/lang/Boolean;", "Boolean", Layout::Boxed),
+    ("Ljava/lang/Byte;", "Byte", Layout::Boxed),
+    ("Ljava/lang/Character;", "Character", Layout::Boxed),
+    ("Ljava/lang/Short;", "Short", Layout::Boxed),
+    ("Ljava/lang/Integer;", "Integer", Layout::Boxed),
+    ("Ljava/lang/Long;", "Long", Layout::Boxed),
+    ("Ljava/lang/Float;", "Float", Layout::Boxed),
+    ("Ljava/lang/Double;", "Double", Layout::Boxed),
+    ("Ljava/util/ArrayList;", "ArrayList", Layout::ArrayList),
+    ("Ljava/util/HashMap;", "HashMap", Layout::HashMap),
+    ("Ljava/util/Optional;", "Optional", Layout::Optional),
+];
+
+/// Elements or entries shown before "… N more"
+const MAX_ELEMENTS: i32 = 10;
+
+/// Nesting rendered, e.g. 2 shows a list of Integers but not a list of lists
+const MAX_DEPTH: u32 = 2;
+
+/// HashMap buckets fetched per ArrayReference.GetValues
+const BUCKET_CHUNK: i32 = 64;
+
+fn known_type(signature: &str) -> Option<(&'static str, Layout)> {
+    KNOWN_TYPES.iter()
+        .find(|(known, _, _)| *known == signature)
+        .map(|&(_, name, layout)| (name, layout))
+}
+
+/// Render an object by its contents if its class is a known type
+///
+/// Returns None for other classes, or if the backing fields can't be read
+/// (e.g. a JDK whose internals differ), so the caller can fall back to
+/// listing fields.
+pub async fn render_known_type(connection: &mut JdwpConnection, object_id: ObjectId) -> Option<String> {
+    let mut renderer = Renderer { connection, fields: HashMap::new() };
+    renderer.render(object_id, MAX_DEPTH).await
+}
+
+struct Renderer<'a> {
+    connection: &'a mut JdwpConnection,
+    /// Instance fields by type, so a map's nodes share one lookup
+    fields: HashMap<ReferenceTypeId, Vec<DeclaredField>>,
+}
+
+impl Renderer<'_> {
+    async fn render(&mut self, object_id: ObjectId, depth: u32) -> Option<String> {
+        let type_id = self.connection.get_object_reference_type(object_id).await.ok()?;
+        let signature = self.connection.get_signature(type_id).await.ok()?;
+        let (name, layout) = known_type(&signature)?;
+
+        match layout {
+            Layout::Boxed => {
+                let [value] = self.read_fields(object_id, type_id, ["value"]).await?;
+                Some(format!("{}({})", name, bare(&value)))
+            }
+            Layout::Optional => {
+                let [value] = self.read_fields(object_id, type_id, ["value"]).await?;
+                match value.data {
+                    ValueData::Object(0) => Some(format!("{}.empty", name)),
+                    _ => Some(format!("{}[{}]", name, self.element(&value, depth).await)),
+                }
+            }
+            Layout::ArrayList => {
+                let [size, elements] = self.read_fields(object_id, type_id, ["size", "elementData"]).await?;
+                let (ValueData::Int(size), ValueData::Object(array_id)) = (size.data, elements.data) else {
+                    return None;
+                };
+
+                let values = match size.min(MAX_ELEMENTS) {
+                    shown if shown > 0 && array_id != 0 => {
+                        self.connection.get_array_values(array_id, 0, shown).await.ok()?
+                    }
+                    _ => Vec::new(),
+                };
+                let mut items = Vec::with_capacity(values.len() + 1);
+                for value in &values {
+                    items.push(self.element(value, depth).await);
+                }
+                push_remaining(&mut items, size);
+                Some(format!("{}[{}] = [{}]", name, size, items.join(", ")))
+            }
+            Layout::HashMap => {
+                let [size, table] = self.read_fields(object_id, type_id, ["size", "table"]).await?;
+                let (ValueData::Int(size), ValueData::Object(table_id)) = (size.data, table.data) else {
+                    return None;
+                };
+
+                let mut items = Vec::new();
+                if table_id != 0 {
+                    self.map_entries(table_id, size.min(MAX_ELEMENTS) as usize, depth, &mut items).await?;
+                }
+                push_remaining(&mut items, size);
+                Some(format!("{}[{}] = {{{}}}", name, size, items.join(", ")))
+            }
+        }
+    }
+
+    /// Collect up to `limit` "key=value" entries from a HashMap's table,
+    /// in iteration order
+    async fn map_entries(&mut self, table_id: ObjectId, limit: usize, depth: u32, items: &mut Vec<String>) -> Option<()> {
+        let length = self.connection.get_array_length(table_id).await.ok()?;
+
+        // Tables are mostly empty buckets, so read them a chunk at a time
+        let mut first = 0;
+        while first < length && items.len() < limit {
+            let chunk = (length - first).min(BUCKET_CHUNK);
+            let buckets = self.connection.get_array_values(table_id, first, chunk).await.ok()?;
+            first += chunk;
+
+            for bucket in buckets {
+                let ValueData::Object(mut node) = bucket.data else {
+                    return None;
+                };
+                while node != 0 && items.len() < limit {
+                    let node_type = self.connection.get_object_reference_type(node).await.ok()?;
+                    let [key, value, next] = self.read_fields(node, node_type, ["key", "value", "next"]).await?;
+                    let key = self.element(&key, depth).await;
+                    let value = self.element(&value, depth).await;
+                    items.push(format!("{}={}", key, value));
+
+                    let ValueData::Object(next) = next.data else {
+                        return None;
+                    };
+                    node = next;
+                }
+            }
+        }
+        Some(())
+    }
+
+    /// Read instance fields (declared or inherited) by name
+    async fn read_fields<const N: usize>(
+        &mut self,
+        object_id: ObjectId,
+        type_id: ReferenceTypeId,
+        names: [&str; N],
+    ) -> Option<[Value; N]> {
+        if !self.fields.contains_key(&type_id) {
+            let fields = self.connection.get_all_fields(type_id).await.ok()?;
+            self.fields.insert(type_id, fields);
+        }
+        let fields = &self.fields[&type_id];
+
+        let field_ids = names.iter()
+            .map(|name| {
+                fields.iter()
+                    .find(|f| f.field.name == *name && f.field.mod_bits & ACC_STATIC == 0)
+                    .map(|f| f.field.field_id)
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let values = self.connection.get_object_values(object_id, field_ids).await.ok()?;
+        values.try_into().ok()
+    }
+
+    /// Format an element, rendering known types nested within `depth`
+    async fn element(&mut self, value: &Value, depth: u32) -> String {
+        match value.data {
+            ValueData::Object(0) => "null".to_string(),
+            ValueData::Object(string_id) if value.tag == b's' => {
+                match self.connection.get_string_value(string_id).await {
+                    Ok(text) => format!("\"{}\"", text),
+                    Err(_) => value.format(),
+                }
+            }
+            ValueData::Object(object_id) if value.tag == b'L' && depth > 1 => {
+                match Box::pin(self.render(object_id, depth - 1)).await {
+                    Some(text) => text,
+                    None => value.format(),
+                }
+            }
+            ValueData::Object(_) => value.format(),
+            _ => bare(value),
+        }
+    }
+}
+
+/// A primitive without its "(int) " prefix; the container says what it is
+fn bare(value: &Value) -> String {
+    let text = value.format();
+    text.split_once(") ").map(|(_, v)| v.to_string()).unwrap_or(text)
+}
+
+/// Note the elements of a `size`-element container that weren't shown
+fn push_remaining(items: &mut Vec<String>, size: i32) {
+    let remaining = size - items.len() as i32;
+    if remaining > 0 {
+        items.push(format!("… {} more", remaining));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::{Buf, BufMut};
+    use jdwp_client::commands::error_codes::NOT_IMPLEMENTED;
+    use jdwp_client::commands::{
+        array_reference_commands, class_type_commands, command_sets, object_reference_commands,
+        reference_type_commands, string_reference_commands,
+    };
+    use jdwp_client::fake_vm::{self, first_id, put_string};
+
+    #[test]
+    fn test_known_type_matches_exact_signatures() {
+        assert_eq!(known_type("Ljava/lang/Integer;"), Some(("Integer", Layout::Boxed)));
+        assert_eq!(known_type("Ljava/util/HashMap;"), Some(("HashMap", Layout::HashMap)));
+        // Subclasses and lookalikes keep their contents elsewhere
+        assert_eq!(known_type("Ljava/util/LinkedHashMap;"), None);
+        assert_eq!(known_type("Lcom/example/ArrayList;"), None);
+    }
+
+    /// Fields as (field id, name), by type
+    fn type_fields(type_id: u64) -> &'static [(u64, &'static str)] {
+        match type_id {
+            0x90 => &[(0xf0, "modCount"), (0xf1, "size"), (0xf2, "elementData")],
+            0x91 => &[(0xf3, "value")],
+            0x92 => &[(0xf4, "size"), (0xf5, "table")],
+            0x93 => &[(0xf6, "key"), (0xf7, "value"), (0xf8, "next")],
+            0x94 => &[(0xf9, "value")],
+            _ => &[],
+        }
+    }
+
+    /// A small heap: an ArrayList of ["a", 42, null], a HashMap {k=42, j=null}
+    /// in one bucket, an empty Optional and an unknown class
+    fn answer(command_set: u8, command: u8, mut data: &[u8]) -> Result<Vec<u8>, u16> {
+        let mut reply = Vec::new();
+        let put_object = |reply: &mut Vec<u8>, tag: u8, id: u64| {
+            reply.put_u8(tag);
+            reply.put_u64(id);
+        };
+        match (command_set, command) {
+            (command_sets::OBJECT_REFERENCE, object_reference_commands::REFERENCE_TYPE) => {
+                let type_id = match first_id(data) {
+                    0x10 => 0x90,
+                    0x30 => 0x91,
+                    0x40 => 0x92,
+                    0x50 | 0x51 => 0x93,
+                    0x60 => 0x94,
+                    _ => 0x95,
+                };
+                reply.put_u8(1);
+                reply.put_u64(type_id);
+            }
+            (command_sets::REFERENCE_TYPE, reference_type_commands::SIGNATURE) => {
+                let signature = match first_id(data) {
+                    0x90 => "Ljava/util/ArrayList;",
+                    0x91 => "Ljava/lang/Integer;",
+                    0x92 => "Ljava/util/HashMap;",
+                    0x93 => "Ljava/util/HashMap$Node;",
+                    0x94 => "Ljava/util/Optional;",
+                    _ => "Lcom/example/Foo;",
+                };
+                put_string(&mut reply, signature);
+            }
+            (command_sets::REFERENCE_TYPE, reference_type_commands::FIELDS) => {
+                let fields = type_fields(first_id(data));
+                reply.put_i32(fields.len() as i32);
+                for &(field_id, name) in fields {
+                    reply.put_u64(field_id);
+                    put_string(&mut reply, name);
+                    put_string(&mut reply, "Ljava/lang/Object;");
+                    reply.put_i32(0x2);
+                }
+            }
+            (command_sets::CLASS_TYPE, class_type_commands::SUPERCLASS) => reply.put_u64(0),
+            (command_sets::REFERENCE_TYPE, reference_type_commands::MODIFIERS) => reply.put_i32(0x1),
+            (command_sets::OBJECT_REFERENCE, object_reference_commands::GET_VALUES) => {
+                let object_id = data.get_u64();
+                let count = data.get_i32();
+                reply.put_i32(count);
+                for _ in 0..count {
+                    match (object_id, data.get_u64()) {
+                        (0x10, 0xf1) => {
+                            reply.put_u8(b'I');
+                            reply.put_i32(3);
+                        }
+                        (0x10, 0xf2) => put_object(&mut reply, b'[', 0x11),
+                        (0x30, 0xf3) => {
+                            reply.put_u8(b'I');
+                            reply.put_i32(42);
+                        }
+                        (0x40, 0xf4) => {
+                            reply.put_u8(b'I');
+                            reply.put_i32(2);
+                        }
+                        (0x40, 0xf5) => put_object(&mut reply, b'[', 0x41),
+                        (0x50, 0xf6) => put_object(&mut reply, b's', 0x21),
+                        (0x50, 0xf7) => put_object(&mut reply, b'L', 0x30),
+                        (0x50, 0xf8) => put_object(&mut reply, b'L', 0x51),
+                        (0x51, 0xf6) => put_object(&mut reply, b's', 0x22),
+                        (0x51, _) => put_object(&mut reply, b'L', 0),
+                        (0x60, 0xf9) => put_object(&mut reply, b'L', 0),
+                        _ => return Err(NOT_IMPLEMENTED),
+                    }
+                }
+            }
+            (command_sets::ARRAY_REFERENCE, array_reference_commands::LENGTH) => reply.put_i32(4),
+            (command_sets::ARRAY_REFERENCE, array_reference_commands::GET_VALUES) => {
+                let array_id = data.get_u64();
+                let first = data.get_i32() as usize;
+                let length = data.get_i32() as usize;
+                let elements: &[(u8, u64)] = match array_id {
+                    // Spare capacity past size
+                    0x11 => &[(b's', 0x20), (b'L', 0x30), (b'L', 0), (b'L', 0)],
+                    _ => &[(b'L', 0), (b'L', 0x50), (b'L', 0), (b'L', 0)],
+                };
+                reply.put_u8(b'L');
+                reply.put_i32(length as i32);
+                for &(tag, id) in &elements[first..first + length] {
+                    put_object(&mut reply, tag, id);
+                }
+            }
+            (command_sets::STRING_REFERENCE, string_reference_commands::VALUE) => {
+                let text = match first_id(data) {
+                    0x20 => "a",
+                    0x21 => "k",
+                    _ => "j",
+                };
+                put_string(&mut reply, text);
+            }
+            _ => return Err(NOT_IMPLEMENTED),
+        }
+        Ok(reply)
+    }
+
+    #[tokio::test]
+    async fn test_render_known_types_from_backing_fields() {
+        let mut connection = fake_vm::connect_with(answer).await;
+
+        assert_eq!(render_known_type(&mut connection, 0x30).await.as_deref(), Some("Integer(42)"));
+        assert_eq!(
+            render_known_type(&mut connection, 0x10).await.as_deref(),
+            Some("ArrayList[3] = [\"a\", Integer(42), null]")
+        );
+        assert_eq!(
+            render_known_type(&mut connection, 0x40).await.as_deref(),
+            Some("HashMap[2] = {\"k\"=Integer(42), \"j\"=null}")
+        );
+        assert_eq!(render_known_type(&mut connection, 0x60).await.as_deref(), Some("Optional.empty"));
+
+        // Anything else is left to the generic expansion
+        assert_eq!(render_known_type(&mut connection, 0x70).await, None);
+    }
+}
@@ -0,0 +1,106 @@
+// Location strings
+//
+// Tools that take a class accept it in any of the forms people paste:
+// "com.example.Foo", "com/example/Foo" or "Lcom/example/Foo;", optionally
+// followed by "#method" and ":line", e.g. "com.example.Foo#process:64".
+
+/// A parsed location: a class, and maybe a method and line within it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocationSpec {
+    /// Dotted class name, e.g. "com.example.Foo$Inner"
+    pub class: String,
+    /// Method name, possibly with its JNI signature, e.g. "process(I)V"
+    pub method: Option<String>,
+    pub line: Option<u32>,
+}
+
+impl LocationSpec {
+    /// JNI signature of the class, e.g. "Lcom/example/Foo;"
+    pub fn to_signature(&self) -> String {
+        format!("L{};", self.class.replace('.', "/"))
+    }
+}
+
+/// Parse "class[#method][:line]"
+pub fn parse_location(input: &str) -> Result<LocationSpec, String> {
+    let input = input.trim();
+
+    let (rest, line) = match input.rsplit_once(':') {
+        Some((rest, line)) => {
+            let line = line.trim().parse::<u32>()
+                .ok()
+                .filter(|&line| line > 0)
+                .ok_or_else(|| format!("Invalid line '{}' in location '{}'", line, input))?;
+            (rest, Some(line))
+        }
+        None => (input, None),
+    };
+
+    let (class, method) = match rest.split_once('#') {
+        Some((_, "")) => return Err(format!("Missing method name after '#' in location '{}'", input)),
+        Some((class, method)) => (class, Some(method.to_string())),
+        None => (rest, None),
+    };
+
+    let class = class_name(class.trim());
+    if class.is_empty() {
+        return Err(format!("Missing class name in location '{}'", input));
+    }
+
+    Ok(LocationSpec { class, method, line })
+}
+
+/// Dotted class name from a dotted, slashed or signature form
+fn class_name(class: &str) -> String {
+    let class = match class.strip_prefix('L').and_then(|c| c.strip_suffix(';')) {
+        Some(inner) => inner,
+        None => class,
+    };
+    class.replace('/', ".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(class: &str, method: Option<&str>, line: Option<u32>) -> LocationSpec {
+        LocationSpec { class: class.to_string(), method: method.map(str::to_string), line }
+    }
+
+    #[test]
+    fn test_parse_location_class_forms() {
+        let expected = spec("com.example.Foo", None, Some(64));
+        assert_eq!(parse_location("com.example.Foo:64").unwrap(), expected);
+        assert_eq!(parse_location("com/example/Foo:64").unwrap(), expected);
+        assert_eq!(parse_location("Lcom/example/Foo;:64").unwrap(), expected);
+        assert_eq!(parse_location(" com.example.Foo : 64 ").unwrap(), expected);
+
+        assert_eq!(parse_location("Lcom/example/Foo$Inner;").unwrap(), spec("com.example.Foo$Inner", None, None));
+        // A class that merely starts with L isn't mistaken for a signature
+        assert_eq!(parse_location("Logger").unwrap(), spec("Logger", None, None));
+        assert_eq!(parse_location("Lcom/example/Foo;").unwrap().to_signature(), "Lcom/example/Foo;");
+        assert_eq!(parse_location("com.example.Foo").unwrap().to_signature(), "Lcom/example/Foo;");
+    }
+
+    #[test]
+    fn test_parse_location_method_hint() {
+        assert_eq!(parse_location("com.example.Foo#bar").unwrap(), spec("com.example.Foo", Some("bar"), None));
+        assert_eq!(
+            parse_location("com.example.Foo#bar:64").unwrap(),
+            spec("com.example.Foo", Some("bar"), Some(64))
+        );
+        assert_eq!(
+            parse_location("com/example/Foo#process(Ljava/lang/String;)V").unwrap(),
+            spec("com.example.Foo", Some("process(Ljava/lang/String;)V"), None)
+        );
+    }
+
+    #[test]
+    fn test_parse_location_rejects_malformed_input() {
+        assert!(parse_location("com.example.Foo:abc").unwrap_err().contains("Invalid line 'abc'"));
+        assert!(parse_location("com.example.Foo:0").unwrap_err().contains("Invalid line"));
+        assert!(parse_location("com.example.Foo#").unwrap_err().contains("Missing method name"));
+        assert!(parse_location(":64").unwrap_err().contains("Missing class name"));
+        assert!(parse_location("").unwrap_err().contains("Missing class name"));
+    }
+}
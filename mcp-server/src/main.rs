@@ -4,10 +4,18 @@
 
 use anyhow::Result;
 use serde_json::Value;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Stdout};
+use tokio::sync::{mpsc, Notify};
+use tokio::task::JoinSet;
 use tracing::{debug, error, info};
 
+mod eval;
 mod handlers;
+mod known_types;
+mod location;
+mod method_cache;
+mod model;
 mod protocol;
 mod session;
 mod tools;
@@ -15,6 +23,9 @@ mod tools;
 use handlers::RequestHandler;
 use protocol::*;
 
+/// Responses and notifications queued for stdout
+const OUTGOING_CAPACITY: usize = 256;
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Tracing to stderr only - stdout is reserved for JSON-RPC protocol
@@ -28,95 +39,58 @@ async fn main() -> Result<()> {
 
     info!("Starting JDWP MCP Server...");
 
-    let handler = RequestHandler::new();
+    let handler = Arc::new(RequestHandler::new());
 
     // Stdio transport - no network, no files
     let stdin = tokio::io::stdin();
-    let stdout = tokio::io::stdout();
     let mut reader = BufReader::new(stdin);
-    let mut stdout = stdout;
+
+    // Requests run concurrently so a slow tool call doesn't block the others.
+    // Responses and event notifications are queued for a single writer, so
+    // each goes out as a whole line; every response carries its request's
+    // id, so out-of-order replies still correlate.
+    let (outgoing, outgoing_rx) = mpsc::channel(OUTGOING_CAPACITY);
+    handler.set_notification_sender(outgoing.clone());
+    let write_failed = Arc::new(Notify::new());
+    let shutdown = Arc::new(Notify::new());
+    let writer = tokio::spawn(write_messages(outgoing_rx, write_failed.clone(), shutdown.clone()));
+    let mut in_flight = JoinSet::new();
 
     info!("JDWP MCP server ready, waiting for requests...");
 
-    // Single-threaded message loop
     loop {
         let mut line = String::new();
-        match reader.read_line(&mut line).await {
+        let read = tokio::select! {
+            read = reader.read_line(&mut line) => read,
+            _ = write_failed.notified() => break,
+        };
+
+        // Reap finished requests
+        while in_flight.try_join_next().is_some() {}
+
+        match read {
             Ok(0) => {
                 info!("Client disconnected");
+                // Let in-flight requests finish so piped input still gets every response
+                while in_flight.join_next().await.is_some() {}
                 break;
             }
             Ok(_) => {
-                let line = line.trim();
+                let line = line.trim().to_string();
                 if line.is_empty() {
                     continue;
                 }
 
                 debug!("Received: {}", line);
 
-                // Parse as generic Value first
-                match serde_json::from_str::<Value>(line) {
-                    Ok(value) => {
-                        // Discriminate by id field
-                        if value.get("id").is_some() {
-                            // It's a request
-                            match serde_json::from_value::<JsonRpcRequest>(value) {
-                                Ok(request) => {
-                                    let response = handler.handle_request(request).await;
-                                    let response_str = serde_json::to_string(&response)?;
-                                    debug!("Sending: {}", response_str);
-                                    stdout.write_all(response_str.as_bytes()).await?;
-                                    stdout.write_all(b"\n").await?;
-                                    stdout.flush().await?;
-                                }
-                                Err(e) => {
-                                    error!("Invalid request: {}", e);
-                                    let error_response = JsonRpcResponse {
-                                        jsonrpc: "2.0".to_string(),
-                                        id: serde_json::Value::Null,
-                                        result: None,
-                                        error: Some(JsonRpcError {
-                                            code: INVALID_REQUEST,
-                                            message: "Invalid request".to_string(),
-                                            data: None,
-                                        }),
-                                    };
-                                    let response_str = serde_json::to_string(&error_response)?;
-                                    stdout.write_all(response_str.as_bytes()).await?;
-                                    stdout.write_all(b"\n").await?;
-                                    stdout.flush().await?;
-                                }
-                            }
-                        } else {
-                            // It's a notification
-                            match serde_json::from_value::<JsonRpcNotification>(value) {
-                                Ok(notification) => {
-                                    handler.handle_notification(notification).await;
-                                }
-                                Err(e) => {
-                                    error!("Invalid notification: {}", e);
-                                }
-                            }
-                        }
+                let handler = handler.clone();
+                let outgoing = outgoing.clone();
+                in_flight.spawn(async move {
+                    if let Some(response) = handle_line(&handler, &line).await {
+                        // Only fails once the writer has stopped
+                        let _ = outgoing.send(response).await;
                     }
-                    Err(e) => {
-                        error!("Parse error: {}", e);
-                        let error_response = JsonRpcResponse {
-                            jsonrpc: "2.0".to_string(),
-                            id: serde_json::Value::Null,
-                            result: None,
-                            error: Some(JsonRpcError {
-                                code: PARSE_ERROR,
-                                message: "Parse error".to_string(),
-                                data: None,
-                            }),
-                        };
-                        let response_str = serde_json::to_string(&error_response)?;
-                        stdout.write_all(response_str.as_bytes()).await?;
-                        stdout.write_all(b"\n").await?;
-                        stdout.flush().await?;
-                    }
-                }
+                });
             }
             Err(e) => {
                 error!("Read error: {}", e);
@@ -125,6 +99,204 @@ async fn main() -> Result<()> {
         }
     }
 
+    // Flush what's queued, then stop the writer
+    shutdown.notify_one();
+    let _ = writer.await;
+
     info!("JDWP MCP server shutting down");
     Ok(())
 }
+
+/// Write queued messages to stdout until `shutdown`, then flush the queue
+///
+/// A write failure means the client is gone or the pipe is unusable; either
+/// way `write_failed` is signalled so the server stops instead of exiting
+/// with an error.
+async fn write_messages(mut outgoing: mpsc::Receiver<Value>, write_failed: Arc<Notify>, shutdown: Arc<Notify>) {
+    let mut stdout = tokio::io::stdout();
+
+    loop {
+        let message = tokio::select! {
+            biased;
+            message = outgoing.recv() => message,
+            _ = shutdown.notified() => {
+                // Take no more, but still write what's already queued
+                outgoing.close();
+                outgoing.recv().await
+            }
+        };
+        let Some(message) = message else {
+            return;
+        };
+
+        if let Err(e) = write_response(&mut stdout, &message).await {
+            if e.kind() == std::io::ErrorKind::BrokenPipe {
+                info!("Client closed stdout");
+            } else {
+                error!("Write error: {}", e);
+            }
+            write_failed.notify_one();
+            return;
+        }
+    }
+}
+
+/// Handle one line of input, returning the response to send (if any)
+///
+/// A line is either a single message or a JSON-RPC batch array. Batches are
+/// processed in order and answered with an array holding one response per
+/// request; a batch of only notifications gets no response at all.
+async fn handle_line(handler: &RequestHandler, line: &str) -> Option<Value> {
+    // Parse as generic Value first
+    let value = match serde_json::from_str::<Value>(line) {
+        Ok(value) => value,
+        Err(e) => {
+            error!("Parse error: {}", e);
+            return Some(to_value(error_response(PARSE_ERROR, "Parse error")));
+        }
+    };
+
+    match value {
+        Value::Array(messages) => {
+            if messages.is_empty() {
+                error!("Empty batch");
+                return Some(to_value(error_response(INVALID_REQUEST, "Invalid request")));
+            }
+
+            let mut responses = Vec::new();
+            for message in messages {
+                if let Some(response) = handle_message(handler, message).await {
+                    responses.push(to_value(response));
+                }
+            }
+
+            if responses.is_empty() {
+                None
+            } else {
+                Some(Value::Array(responses))
+            }
+        }
+        value => handle_message(handler, value).await.map(to_value),
+    }
+}
+
+/// Handle a single request or notification
+async fn handle_message(handler: &RequestHandler, value: Value) -> Option<JsonRpcResponse> {
+    if !value.is_object() {
+        error!("Invalid message: {}", value);
+        return Some(error_response(INVALID_REQUEST, "Invalid request"));
+    }
+
+    // Discriminate by id field
+    if value.get("id").is_some() {
+        // It's a request
+        match serde_json::from_value::<JsonRpcRequest>(value) {
+            Ok(request) => Some(handler.handle_request(request).await),
+            Err(e) => {
+                error!("Invalid request: {}", e);
+                Some(error_response(INVALID_REQUEST, "Invalid request"))
+            }
+        }
+    } else {
+        // It's a notification
+        match serde_json::from_value::<JsonRpcNotification>(value) {
+            Ok(notification) => handler.handle_notification(notification).await,
+            Err(e) => error!("Invalid notification: {}", e),
+        }
+        None
+    }
+}
+
+fn to_value(response: JsonRpcResponse) -> Value {
+    serde_json::to_value(response).unwrap()
+}
+
+/// Build an error response that can't be correlated to a request id
+fn error_response(code: i32, message: &str) -> JsonRpcResponse {
+    JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        id: Value::Null,
+        result: None,
+        error: Some(JsonRpcError {
+            code,
+            message: message.to_string(),
+            data: None,
+        }),
+    }
+}
+
+/// Write a response as a single newline-terminated JSON line
+async fn write_response(stdout: &mut Stdout, response: &Value) -> std::io::Result<()> {
+    let response_str = serde_json::to_string(response)?;
+    debug!("Sending: {}", response_str);
+    stdout.write_all(response_str.as_bytes()).await?;
+    stdout.write_all(b"\n").await?;
+    stdout.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_mixed_batch_returns_responses_in_order() {
+        let handler = RequestHandler::new();
+        let batch = json!([
+            {"jsonrpc": "2.0", "id": 1, "method": "tools/list"},
+            {"jsonrpc": "2.0", "method": "notifications/initialized"},
+            {"jsonrpc": "2.0", "id": "b", "method": "no/such/method"},
+            42
+        ]);
+
+        let response = handle_line(&handler, &batch.to_string()).await.unwrap();
+        let responses = response.as_array().unwrap();
+
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[0]["id"], json!(1));
+        assert!(responses[0]["result"]["tools"].is_array());
+        assert_eq!(responses[1]["id"], json!("b"));
+        assert_eq!(responses[1]["error"]["code"], json!(METHOD_NOT_FOUND));
+        assert_eq!(responses[2]["id"], Value::Null);
+        assert_eq!(responses[2]["error"]["code"], json!(INVALID_REQUEST));
+    }
+
+    #[tokio::test]
+    async fn test_empty_batch_is_invalid_request() {
+        let handler = RequestHandler::new();
+        let response = handle_line(&handler, "[]").await.unwrap();
+
+        assert!(response.is_object());
+        assert_eq!(response["error"]["code"], json!(INVALID_REQUEST));
+    }
+
+    #[tokio::test]
+    async fn test_notification_only_batch_has_no_response() {
+        let handler = RequestHandler::new();
+        let batch = json!([
+            {"jsonrpc": "2.0", "method": "notifications/initialized"},
+            {"jsonrpc": "2.0", "method": "notifications/cancelled"}
+        ]);
+
+        assert!(handle_line(&handler, &batch.to_string()).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_notifications_are_ignored() {
+        let handler = RequestHandler::new();
+
+        for notification in [
+            json!({"jsonrpc": "2.0", "method": "$/setTrace", "params": {"value": "off"}}),
+            json!({"jsonrpc": "2.0", "method": "vendor/somethingHappened"}),
+            json!({"jsonrpc": "2.0", "method": "notifications/progress", "params": {"progressToken": 1, "progress": 5}}),
+            json!({"jsonrpc": "2.0", "method": "notifications/progress", "params": "not an object"}),
+        ] {
+            assert!(handle_line(&handler, &notification.to_string()).await.is_none());
+        }
+
+        // Requests stay strict
+        let request = json!({"jsonrpc": "2.0", "id": 7, "method": "$/setTrace"});
+        let response = handle_line(&handler, &request.to_string()).await.unwrap();
+        assert_eq!(response["error"]["code"], json!(METHOD_NOT_FOUND));
+    }
+}
@@ -0,0 +1,101 @@
+// Per-session cache of method metadata
+//
+// Class signatures, source files, method lists, line tables and variable
+// tables don't change while a class stays loaded, so each is fetched once
+// and reused across tool calls. Nothing is invalidated: a class redefined
+// in place (HotSwap) would leave stale entries. IDs belong to a
+// connection, so the cache is cleared on reconnect.
+
+use jdwp_client::commands::error_codes;
+use jdwp_client::method::LineTable;
+use jdwp_client::reftype::MethodInfo;
+use jdwp_client::types::{MethodId, ReferenceTypeId, Variable};
+use jdwp_client::{JdwpConnection, JdwpResult};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+/// Lazily filled method metadata; failed lookups are not cached, but a
+/// missing source file is
+#[derive(Debug, Default)]
+pub struct MethodCache {
+    signatures: HashMap<ReferenceTypeId, String>,
+    source_files: HashMap<ReferenceTypeId, Option<String>>,
+    methods: HashMap<ReferenceTypeId, Vec<MethodInfo>>,
+    line_tables: HashMap<(ReferenceTypeId, MethodId), LineTable>,
+    variable_tables: HashMap<(ReferenceTypeId, MethodId), Vec<Variable>>,
+}
+
+impl MethodCache {
+    /// JNI signature of a class, e.g. "Lcom/example/Foo;"
+    pub async fn signature(
+        &mut self,
+        connection: &mut JdwpConnection,
+        class_id: ReferenceTypeId,
+    ) -> JdwpResult<String> {
+        if let Entry::Vacant(entry) = self.signatures.entry(class_id) {
+            entry.insert(connection.get_signature(class_id).await?);
+        }
+        Ok(self.signatures[&class_id].clone())
+    }
+
+    /// Source file name of a class, or None if it was compiled without one
+    pub async fn source_file(
+        &mut self,
+        connection: &mut JdwpConnection,
+        class_id: ReferenceTypeId,
+    ) -> JdwpResult<Option<String>> {
+        if let Entry::Vacant(entry) = self.source_files.entry(class_id) {
+            let source_file = match connection.get_source_file(class_id).await {
+                Ok(file) => Some(file),
+                // A missing attribute is an answer, so remember it
+                Err(e) if e.error_code() == Some(error_codes::ABSENT_INFORMATION) => None,
+                Err(e) => return Err(e),
+            };
+            entry.insert(source_file);
+        }
+        Ok(self.source_files[&class_id].clone())
+    }
+
+    /// Methods declared by a class, with generic signatures where the VM provides them
+    pub async fn methods(
+        &mut self,
+        connection: &mut JdwpConnection,
+        class_id: ReferenceTypeId,
+    ) -> JdwpResult<Vec<MethodInfo>> {
+        if let Entry::Vacant(entry) = self.methods.entry(class_id) {
+            entry.insert(connection.get_methods_with_generic(class_id).await?);
+        }
+        Ok(self.methods[&class_id].clone())
+    }
+
+    pub async fn line_table(
+        &mut self,
+        connection: &mut JdwpConnection,
+        class_id: ReferenceTypeId,
+        method_id: MethodId,
+    ) -> JdwpResult<LineTable> {
+        let key = (class_id, method_id);
+        if let Entry::Vacant(entry) = self.line_tables.entry(key) {
+            entry.insert(connection.get_line_table(class_id, method_id).await?);
+        }
+        Ok(self.line_tables[&key].clone())
+    }
+
+    /// Local variables of a method, with generic signatures where the VM provides them
+    pub async fn variable_table(
+        &mut self,
+        connection: &mut JdwpConnection,
+        class_id: ReferenceTypeId,
+        method_id: MethodId,
+    ) -> JdwpResult<Vec<Variable>> {
+        let key = (class_id, method_id);
+        if let Entry::Vacant(entry) = self.variable_tables.entry(key) {
+            entry.insert(connection.get_variable_table_with_generic(class_id, method_id).await?);
+        }
+        Ok(self.variable_tables[&key].clone())
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
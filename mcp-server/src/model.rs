@@ -0,0 +1,44 @@
+// Structured tool results
+//
+// Serializable views of debugger state, returned as JSON by tools called
+// with `"format": "json"` and embedded as resources alongside the text.
+// IDs are hex strings, as the tools accept them.
+
+use serde::Serialize;
+
+/// One page of a thread's stack, as returned by debug.get_stack
+#[derive(Debug, Serialize)]
+pub struct StackDump {
+    pub thread_id: String,
+    /// Frames on the whole stack, not just this page
+    pub frame_count: i32,
+    pub frames: Vec<FrameDump>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FrameDump {
+    /// Position on the stack (0 = top)
+    pub index: usize,
+    pub frame_id: String,
+    pub class_id: String,
+    pub method_id: String,
+    pub code_index: u64,
+    /// Fully qualified class name, if the class could be looked up
+    pub class: Option<String>,
+    /// Source file name, e.g. "HelloController.java", if the class has one
+    pub source_file: Option<String>,
+    /// Method declaration, or "(obsolete method)" after a class redefinition
+    pub method: Option<String>,
+    /// Source line, if the method has line numbers
+    pub line: Option<i32>,
+    pub variables: Vec<VarDump>,
+}
+
+/// A local variable (or `this`) in scope at the frame's location
+#[derive(Debug, Serialize)]
+pub struct VarDump {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub value: String,
+}
@@ -49,6 +49,32 @@ pub struct InitializeParams {
     pub capabilities: ClientCapabilities,
     #[serde(rename = "clientInfo")]
     pub client_info: ClientInfo,
+    #[serde(rename = "initializationOptions", default)]
+    pub initialization_options: Option<ServerOptions>,
+}
+
+/// Server-specific options a client may pass in `initialize`
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerOptions {
+    /// Seconds a debug session may sit idle before it is disposed (0 disables)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_ttl_secs: Option<u64>,
+    /// Reconnect and re-register breakpoints when the JVM connection drops
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_reconnect: Option<bool>,
+    /// Truncate tool results larger than this many bytes (0 disables)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_response_bytes: Option<usize>,
+    /// Seconds a JDWP command may wait for its reply
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_timeout_secs: Option<u64>,
+    /// Largest JDWP packet accepted from the VM, in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_packet_bytes: Option<usize>,
+    /// Seconds between liveness checks on idle sessions (0 disables)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keepalive_secs: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -60,6 +86,9 @@ pub struct ClientCapabilities {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ToolsCapability {}
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoggingCapability {}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ClientInfo {
     pub name: String,
@@ -80,6 +109,8 @@ pub struct InitializeResult {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ServerCapabilities {
     pub tools: ToolsCapability,
+    /// Breakpoint hits and other stops are pushed as notifications/message
+    pub logging: LoggingCapability,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -120,6 +151,34 @@ pub struct CallToolResult {
 pub enum ContentBlock {
     #[serde(rename = "text")]
     Text { text: String },
+    /// Embedded resource; used to attach machine-readable JSON to a result
+    #[serde(rename = "resource")]
+    Resource { resource: ResourceContents },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResourceContents {
+    pub uri: String,
+    #[serde(rename = "mimeType")]
+    pub mime_type: String,
+    pub text: String,
+}
+
+impl ContentBlock {
+    pub fn text(text: impl Into<String>) -> Self {
+        ContentBlock::Text { text: text.into() }
+    }
+
+    /// A JSON document embedded as a resource under `uri`
+    pub fn json(uri: impl Into<String>, value: &impl Serialize) -> Self {
+        ContentBlock::Resource {
+            resource: ResourceContents {
+                uri: uri.into(),
+                mime_type: "application/json".to_string(),
+                text: serde_json::to_string_pretty(value).unwrap(),
+            },
+        }
+    }
 }
 
 // Standard JSON-RPC error codes
@@ -128,3 +187,23 @@ pub const INVALID_REQUEST: i32 = -32600;
 pub const METHOD_NOT_FOUND: i32 = -32601;
 pub const INVALID_PARAMS: i32 = -32602;
 pub const INTERNAL_ERROR: i32 = -32603;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_content_block_wire_format() {
+        let text = serde_json::to_value(ContentBlock::text("hi")).unwrap();
+        assert_eq!(text, json!({"type": "text", "text": "hi"}));
+
+        let resource = serde_json::to_value(ContentBlock::json("jdwp://x", &json!({"a": 1}))).unwrap();
+        assert_eq!(resource["type"], "resource");
+        assert_eq!(resource["resource"]["uri"], "jdwp://x");
+        assert_eq!(resource["resource"]["mimeType"], "application/json");
+
+        let embedded: Value = serde_json::from_str(resource["resource"]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(embedded, json!({"a": 1}));
+    }
+}
@@ -2,21 +2,91 @@
 //
 // Manages JDWP connection state, breakpoints, and thread tracking
 
-use jdwp_client::{JdwpConnection, EventSet};
+use crate::method_cache::MethodCache;
+use jdwp_client::method::LineTable;
+use jdwp_client::reftype::MethodInfo;
+use jdwp_client::thread::ThreadDetail;
+use jdwp_client::types::{MethodId, ReferenceTypeId, ThreadId, Variable};
+use jdwp_client::vm::VmCapabilities;
+use jdwp_client::{JdwpConnection, JdwpConnectionBuilder, JdwpResult, EventSet, SuspendPolicy};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, Mutex};
 use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Capacity of the per-session event broadcast channel
+const EVENT_CHANNEL_CAPACITY: usize = 64;
+
+/// Default idle time before an abandoned session is disposed
+pub const DEFAULT_SESSION_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// How often the reaper looks for idle sessions
+const REAPER_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Upper bound on the Dispose round trip when tearing a session down
+const DISPOSE_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub type SessionId = String;
 
 #[derive(Debug)]
 pub struct DebugSession {
     pub connection: JdwpConnection,
+    /// Address the session attached to, used when reconnecting
+    pub host: String,
+    pub port: u16,
+    /// The JVM dialled in to a debug.attach in listen mode, so dropped
+    /// connections can't be re-established from this side
+    pub listening: bool,
     pub breakpoints: HashMap<String, BreakpointInfo>,
-    pub threads: HashMap<String, ThreadInfo>,
+    /// Breakpoints waiting for their class to load, by breakpoint ID
+    pub deferred_breakpoints: HashMap<String, DeferredBreakpoint>,
+    pub watchpoints: HashMap<String, WatchpointInfo>,
+    pub exception_breakpoints: HashMap<String, ExceptionBreakpointInfo>,
+    /// Threads as of the last debug.list_threads
+    pub threads: HashMap<ThreadId, ThreadDetail>,
     pub last_event: Option<EventSet>,
+    /// Every event set received from the JVM is published here, so handlers
+    /// can wait for specific events while the listener task keeps running
+    pub events: broadcast::Sender<EventSet>,
     pub event_listener_task: Option<JoinHandle<()>>,
+    /// Checks an idle connection is still alive, when keepalive is enabled
+    pub keepalive_task: Option<JoinHandle<()>>,
+    /// Updated on every tool call; idle sessions are reaped after the TTL
+    pub last_activity: Instant,
+    /// Optional JVM features, fetched on attach (None if the query failed)
+    pub capabilities: Option<VmCapabilities>,
+    /// Class metadata, method lists, line tables and variable tables seen so far
+    pub method_cache: MethodCache,
+}
+
+impl DebugSession {
+    /// A class's JNI signature, cached for the session
+    pub async fn class_signature(&mut self, class_id: ReferenceTypeId) -> JdwpResult<String> {
+        self.method_cache.signature(&mut self.connection, class_id).await
+    }
+
+    /// A class's source file name (None if it has none), cached for the session
+    pub async fn source_file(&mut self, class_id: ReferenceTypeId) -> JdwpResult<Option<String>> {
+        self.method_cache.source_file(&mut self.connection, class_id).await
+    }
+
+    /// Methods declared by a class, cached for the session
+    pub async fn methods(&mut self, class_id: ReferenceTypeId) -> JdwpResult<Vec<MethodInfo>> {
+        self.method_cache.methods(&mut self.connection, class_id).await
+    }
+
+    /// A method's line table, cached for the session
+    pub async fn line_table(&mut self, class_id: ReferenceTypeId, method_id: MethodId) -> JdwpResult<LineTable> {
+        self.method_cache.line_table(&mut self.connection, class_id, method_id).await
+    }
+
+    /// A method's local variables, cached for the session
+    pub async fn variable_table(&mut self, class_id: ReferenceTypeId, method_id: MethodId) -> JdwpResult<Vec<Variable>> {
+        self.method_cache.variable_table(&mut self.connection, class_id, method_id).await
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -24,24 +94,104 @@ pub struct BreakpointInfo {
     pub id: String,
     pub request_id: i32,
     pub class_pattern: String,
-    pub line: u32,
+    /// Source line, or None for a breakpoint on method entry
+    pub line: Option<u32>,
+    /// Method name; "name(signature)" for a breakpoint on method entry
     pub method: Option<String>,
+    /// False once disabled or expired; there is no VM request then
     pub enabled: bool,
     pub hit_count: u32,
+    /// Stop only on this hit; the VM discards the request afterwards
+    pub hit_limit: Option<u32>,
+    /// Stop only on this thread
+    pub thread: Option<ThreadId>,
+    /// Which threads a hit suspends
+    pub suspend_policy: SuspendPolicy,
 }
 
+impl BreakpointInfo {
+    /// Where the breakpoint is, e.g. "com.example.Foo:42" or
+    /// "com.example.Foo.run()V entry"
+    pub fn location(&self) -> String {
+        format_location(&self.class_pattern, self.line, self.method.as_deref())
+    }
+
+    /// Whether a hit-limited breakpoint fired and the VM discarded it
+    pub fn expired(&self) -> bool {
+        !self.enabled && self.hit_limit.is_some_and(|limit| self.hit_count >= limit)
+    }
+}
+
+/// A breakpoint on a class that isn't loaded yet
+///
+/// A ClassPrepare request watches for the class; when it's prepared the
+/// breakpoint is set and tracked in `breakpoints` under the same ID.
 #[derive(Debug, Clone)]
-pub struct ThreadInfo {
+pub struct DeferredBreakpoint {
     pub id: String,
-    pub name: String,
-    pub status: String,
-    pub suspended: bool,
+    /// The ClassPrepare request watching for the class
+    pub request_id: i32,
+    pub class_pattern: String,
+    /// Source line, or None for a breakpoint on method entry
+    pub line: Option<u32>,
+    pub method: Option<String>,
+    pub hit_limit: Option<u32>,
+    pub thread: Option<ThreadId>,
+    pub suspend_policy: SuspendPolicy,
+    /// Why the breakpoint couldn't be set once the class loaded; nothing
+    /// is watching for the class then
+    pub failure: Option<String>,
+}
+
+impl DeferredBreakpoint {
+    /// Where the breakpoint will be, as `BreakpointInfo::location` puts it
+    pub fn location(&self) -> String {
+        format_location(&self.class_pattern, self.line, self.method.as_deref())
+    }
+}
+
+/// A breakpoint's class and line, or its method entry
+fn format_location(class_pattern: &str, line: Option<u32>, method: Option<&str>) -> String {
+    match line {
+        Some(line) => format!("{}:{}", class_pattern, line),
+        None => format!("{}.{} entry", class_pattern, method.unwrap_or("?")),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WatchpointInfo {
+    pub id: String,
+    pub request_id: i32,
+    /// `event_kinds::FIELD_ACCESS` or `event_kinds::FIELD_MODIFICATION`
+    pub event_kind: u8,
+    pub class_pattern: String,
+    pub field: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExceptionBreakpointInfo {
+    pub id: String,
+    pub request_id: i32,
+    /// None when breaking on every exception
+    pub exception_class: Option<String>,
+    pub caught: bool,
+    pub uncaught: bool,
 }
 
 #[derive(Clone)]
 pub struct SessionManager {
     sessions: Arc<Mutex<HashMap<SessionId, Arc<Mutex<DebugSession>>>>>,
     current_session: Arc<Mutex<Option<SessionId>>>,
+    /// Idle TTL in seconds (0 disables reaping)
+    session_ttl_secs: Arc<AtomicU64>,
+    reaper_started: Arc<AtomicBool>,
+    auto_reconnect: Arc<AtomicBool>,
+    /// Reply timeout for new connections in seconds (0 keeps the client default)
+    reply_timeout_secs: Arc<AtomicU64>,
+    /// Largest packet new connections accept (0 keeps the client default)
+    max_packet_bytes: Arc<AtomicUsize>,
+    /// Interval between liveness checks in milliseconds (0 disables them)
+    keepalive_ms: Arc<AtomicU64>,
 }
 
 impl SessionManager {
@@ -49,17 +199,94 @@ impl SessionManager {
         Self {
             sessions: Arc::new(Mutex::new(HashMap::new())),
             current_session: Arc::new(Mutex::new(None)),
+            session_ttl_secs: Arc::new(AtomicU64::new(DEFAULT_SESSION_TTL.as_secs())),
+            reaper_started: Arc::new(AtomicBool::new(false)),
+            auto_reconnect: Arc::new(AtomicBool::new(false)),
+            reply_timeout_secs: Arc::new(AtomicU64::new(0)),
+            max_packet_bytes: Arc::new(AtomicUsize::new(0)),
+            keepalive_ms: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Set how long commands on new connections wait for a reply
+    pub fn set_reply_timeout(&self, timeout: Duration) {
+        self.reply_timeout_secs.store(timeout.as_secs(), Ordering::SeqCst);
+    }
+
+    /// Set the largest packet new connections accept from the VM
+    pub fn set_max_packet_size(&self, bytes: usize) {
+        self.max_packet_bytes.store(bytes, Ordering::SeqCst);
+    }
+
+    /// Builder for a connection to `host:port` with the configured limits
+    pub fn connection_builder(&self, host: &str, port: u16) -> JdwpConnectionBuilder {
+        let mut builder = JdwpConnection::builder().host(host).port(port);
+        let reply_timeout_secs = self.reply_timeout_secs.load(Ordering::SeqCst);
+        if reply_timeout_secs > 0 {
+            builder = builder.reply_timeout(Duration::from_secs(reply_timeout_secs));
+        }
+        let max_packet_bytes = self.max_packet_bytes.load(Ordering::SeqCst);
+        if max_packet_bytes > 0 {
+            builder = builder.max_packet_size(max_packet_bytes);
+        }
+        builder
+    }
+
+    /// Set how often idle connections are checked for liveness (None disables)
+    pub fn set_keepalive(&self, interval: Option<Duration>) {
+        let ms = interval.map(|d| d.as_millis() as u64).unwrap_or(0);
+        self.keepalive_ms.store(ms, Ordering::SeqCst);
+    }
+
+    pub fn keepalive(&self) -> Option<Duration> {
+        match self.keepalive_ms.load(Ordering::SeqCst) {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        }
+    }
+
+    /// Whether tool calls should reconnect a session whose connection dropped
+    pub fn set_auto_reconnect(&self, enabled: bool) {
+        self.auto_reconnect.store(enabled, Ordering::SeqCst);
+    }
+
+    pub fn auto_reconnect(&self) -> bool {
+        self.auto_reconnect.load(Ordering::SeqCst)
+    }
+
+    /// Set how long a session may be idle before it is disposed (None disables)
+    pub fn set_session_ttl(&self, ttl: Option<Duration>) {
+        let secs = ttl.map(|d| d.as_secs()).unwrap_or(0);
+        self.session_ttl_secs.store(secs, Ordering::SeqCst);
+    }
+
+    fn session_ttl(&self) -> Option<Duration> {
+        match self.session_ttl_secs.load(Ordering::SeqCst) {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
         }
     }
 
-    pub async fn create_session(&self, connection: JdwpConnection) -> SessionId {
+    pub async fn create_session(&self, connection: JdwpConnection, host: &str, port: u16) -> SessionId {
         let session_id = format!("session_{}", uuid::v4());
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
         let session = DebugSession {
             connection,
+            host: host.to_string(),
+            port,
+            listening: false,
             breakpoints: HashMap::new(),
+            deferred_breakpoints: HashMap::new(),
+            watchpoints: HashMap::new(),
+            exception_breakpoints: HashMap::new(),
             threads: HashMap::new(),
             last_event: None,
+            events,
             event_listener_task: None,
+            keepalive_task: None,
+            last_activity: Instant::now(),
+            capabilities: None,
+            method_cache: MethodCache::default(),
         };
 
         let mut sessions = self.sessions.lock().await;
@@ -69,9 +296,74 @@ impl SessionManager {
         let mut current = self.current_session.lock().await;
         *current = Some(session_id.clone());
 
+        self.ensure_reaper();
+
         session_id
     }
 
+    /// Record activity on a session so it isn't reaped
+    pub async fn touch_session(&self, session_id: &str) {
+        if let Some(session_guard) = self.get_session(session_id).await {
+            session_guard.lock().await.last_activity = Instant::now();
+        }
+    }
+
+    /// Start the idle-session reaper the first time a session is created
+    fn ensure_reaper(&self) {
+        if self.reaper_started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(REAPER_INTERVAL);
+            loop {
+                interval.tick().await;
+                manager.reap_idle_sessions().await;
+            }
+        });
+    }
+
+    /// Dispose and remove sessions that have been idle longer than the TTL
+    async fn reap_idle_sessions(&self) {
+        let Some(ttl) = self.session_ttl() else {
+            return;
+        };
+
+        let idle: Vec<SessionId> = {
+            let sessions = self.sessions.lock().await;
+            sessions
+                .iter()
+                // A locked session is in use by a tool call, so it isn't idle
+                .filter(|(_, session)| {
+                    session
+                        .try_lock()
+                        .map(|s| s.last_activity.elapsed() > ttl)
+                        .unwrap_or(false)
+                })
+                .map(|(id, _)| id.clone())
+                .collect()
+        };
+
+        for session_id in idle {
+            info!("Session {} idle for more than {:?}, disposing", session_id, ttl);
+            self.remove_session(&session_id).await;
+        }
+    }
+
+    pub async fn get_session(&self, session_id: &str) -> Option<Arc<Mutex<DebugSession>>> {
+        self.sessions.lock().await.get(session_id).cloned()
+    }
+
+    /// Every session, ordered by ID
+    pub async fn list_sessions(&self) -> Vec<(SessionId, Arc<Mutex<DebugSession>>)> {
+        let sessions = self.sessions.lock().await;
+        let mut list: Vec<_> = sessions.iter().map(|(id, session)| (id.clone(), session.clone())).collect();
+        list.sort_by(|a, b| a.0.cmp(&b.0));
+        list
+    }
+
+    #[cfg(test)]
     pub async fn get_current_session(&self) -> Option<Arc<Mutex<DebugSession>>> {
         let current = self.current_session.lock().await;
         if let Some(session_id) = current.as_ref() {
@@ -88,18 +380,39 @@ impl SessionManager {
     }
 
     pub async fn remove_session(&self, session_id: &str) {
-        let mut sessions = self.sessions.lock().await;
+        self.close_session(session_id, true).await;
+    }
 
-        // Abort the event listener task if it exists
-        if let Some(session_arc) = sessions.get(session_id) {
+    /// Drop a session whose VM has already gone away (no Dispose is sent)
+    pub async fn terminate_session(&self, session_id: &str) {
+        self.close_session(session_id, false).await;
+    }
+
+    async fn close_session(&self, session_id: &str, dispose: bool) {
+        // Take the session out of the map first so lookups aren't blocked
+        // while we talk to the VM
+        let removed = self.sessions.lock().await.remove(session_id);
+
+        // Release the VM, then abort the event listener task if it exists
+        if let Some(session_arc) = removed {
             let mut session = session_arc.lock().await;
+
+            if dispose {
+                match tokio::time::timeout(DISPOSE_TIMEOUT, session.connection.dispose()).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => warn!("Failed to dispose session {}: {}", session_id, e),
+                    Err(_) => warn!("Timed out disposing session {}", session_id),
+                }
+            }
+
             if let Some(task) = session.event_listener_task.take() {
                 task.abort();
             }
+            if let Some(task) = session.keepalive_task.take() {
+                task.abort();
+            }
         }
 
-        sessions.remove(session_id);
-
         // Clear current if it was this session
         let mut current = self.current_session.lock().await;
         if current.as_ref() == Some(&session_id.to_string()) {
@@ -124,3 +437,72 @@ mod uuid {
         format!("{:x}{:x}", timestamp, counter)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jdwp_client::commands::error_codes::NOT_IMPLEMENTED;
+    use jdwp_client::commands::{command_sets, vm_commands};
+    use jdwp_client::fake_vm;
+
+    /// A manager with one session on a fake JVM that records Dispose, last
+    /// used five seconds ago
+    async fn idle_session(ttl: Option<Duration>) -> (SessionManager, SessionId, Arc<AtomicBool>) {
+        let disposed = Arc::new(AtomicBool::new(false));
+        let recorded = disposed.clone();
+        let connection = fake_vm::connect_with(move |command_set, command, _| {
+            match (command_set, command) {
+                (command_sets::VIRTUAL_MACHINE, vm_commands::DISPOSE) => recorded.store(true, Ordering::SeqCst),
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+            Ok(Vec::new())
+        })
+        .await;
+
+        let manager = SessionManager::new();
+        manager.set_session_ttl(ttl);
+        // Reap by hand rather than on the reaper's schedule
+        manager.reaper_started.store(true, Ordering::SeqCst);
+        let session_id = manager.create_session(connection, "127.0.0.1", 0).await;
+
+        let session_guard = manager.get_session(&session_id).await.unwrap();
+        session_guard.lock().await.last_activity = Instant::now() - Duration::from_secs(5);
+
+        (manager, session_id, disposed)
+    }
+
+    #[tokio::test]
+    async fn test_reap_disposes_and_removes_idle_session() {
+        let (manager, session_id, disposed) = idle_session(Some(Duration::from_secs(1))).await;
+
+        manager.reap_idle_sessions().await;
+
+        assert!(disposed.load(Ordering::SeqCst));
+        assert!(manager.get_session(&session_id).await.is_none());
+        assert!(manager.get_current_session_id().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reap_skips_session_in_use() {
+        let (manager, session_id, disposed) = idle_session(Some(Duration::from_secs(1))).await;
+
+        let session_guard = manager.get_session(&session_id).await.unwrap();
+        let session = session_guard.lock().await;
+        manager.reap_idle_sessions().await;
+        drop(session);
+
+        assert!(!disposed.load(Ordering::SeqCst));
+        assert!(manager.get_session(&session_id).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_zero_ttl_disables_reaping() {
+        // As set by sessionTtlSecs: 0
+        let (manager, session_id, disposed) = idle_session(Some(Duration::ZERO)).await;
+
+        manager.reap_idle_sessions().await;
+
+        assert!(!disposed.load(Ordering::SeqCst));
+        assert!(manager.get_session(&session_id).await.is_some());
+    }
+}
@@ -3,16 +3,80 @@
 // MCP tools for JDWP debugging operations
 
 use crate::protocol::Tool;
+use jdwp_client::vm::VmCapabilities;
 use serde_json::json;
 
+/// Whether the connected JVM supports the operation behind a tool
+///
+/// Tools without a capability requirement are always supported.
+pub fn is_supported(tool_name: &str, capabilities: &VmCapabilities) -> bool {
+    missing_capability(tool_name, capabilities).is_none()
+}
+
+/// What the JVM lacks for a tool, e.g. "popping frames (canPopFrames)",
+/// or None if the tool is supported
+pub fn missing_capability(tool_name: &str, capabilities: &VmCapabilities) -> Option<&'static str> {
+    match tool_name {
+        "debug.pop_frame" if !capabilities.can_pop_frames => Some("popping frames (canPopFrames)"),
+        "debug.force_return" if !capabilities.can_force_early_return => {
+            Some("forcing early returns (canForceEarlyReturn)")
+        }
+        "debug.set_watchpoint"
+            if !capabilities.can_watch_field_access && !capabilities.can_watch_field_modification =>
+        {
+            Some("field watchpoints (canWatchFieldAccess, canWatchFieldModification)")
+        }
+        "debug.bytecodes" if !capabilities.can_get_bytecodes => Some("reading bytecode (canGetBytecodes)"),
+        "debug.detect_deadlock"
+            if !capabilities.can_get_owned_monitor_info || !capabilities.can_get_current_contended_monitor =>
+        {
+            Some("monitor queries (canGetOwnedMonitorInfo, canGetCurrentContendedMonitor)")
+        }
+        _ => None,
+    }
+}
+
+/// Tools that don't act on a particular session
+const SESSIONLESS_TOOLS: &[&str] = &["debug.attach", "debug.list_sessions"];
+
 pub fn get_tools() -> Vec<Tool> {
+    let mut tools = tool_definitions();
+
+    // Every session tool can address a session other than the current one
+    for tool in tools.iter_mut().filter(|t| !SESSIONLESS_TOOLS.contains(&t.name.as_str())) {
+        tool.input_schema["properties"]["session_id"] = json!({
+            "type": "string",
+            "description": "Session to act on (from debug.attach or debug.list_sessions); defaults to the current session"
+        });
+    }
+
+    tools
+}
+
+fn tool_definitions() -> Vec<Tool> {
     vec![
         Tool {
             name: "debug.attach".to_string(),
-            description: "Connect to a JVM via JDWP protocol".to_string(),
+            description: "Connect to a JVM via JDWP protocol, or listen for a JVM started with server=n to connect".to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
+                    "transport": {
+                        "type": "string",
+                        "enum": ["socket", "shmem"],
+                        "description": "JDWP transport the JVM was started with: socket (dt_socket) or shmem (dt_shmem, Windows only; not supported yet)",
+                        "default": "socket"
+                    },
+                    "name": {
+                        "type": "string",
+                        "description": "Shared memory name for the shmem transport (the JVM's address=...)"
+                    },
+                    "mode": {
+                        "type": "string",
+                        "enum": ["attach", "listen"],
+                        "description": "attach connects to a JVM started with server=y; listen waits for a JVM started with server=n to connect to 'bind'",
+                        "default": "attach"
+                    },
                     "host": {
                         "type": "string",
                         "description": "JVM host (e.g., 'localhost')",
@@ -23,32 +87,114 @@ pub fn get_tools() -> Vec<Tool> {
                         "description": "JDWP port (e.g., 5005)",
                         "default": 5005
                     },
+                    "bind": {
+                        "type": "string",
+                        "description": "Address to listen on in listen mode (e.g., '0.0.0.0:5005')",
+                        "default": "127.0.0.1:5005"
+                    },
                     "timeout_ms": {
                         "type": "integer",
-                        "description": "Connection timeout in milliseconds",
+                        "description": "Connection timeout in milliseconds; in listen mode, how long to wait for the JVM (default 60000)",
                         "default": 5000
                     }
-                },
-                "required": ["host", "port"]
+                }
             }),
         },
         Tool {
             name: "debug.set_breakpoint".to_string(),
-            description: "Set a breakpoint at a specific location".to_string(),
+            description: "Set a breakpoint at a source line, or on entry to a method when no line is given. If the class isn't loaded yet, the breakpoint is deferred and set when it loads".to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
                     "class_pattern": {
                         "type": "string",
-                        "description": "Class name pattern (e.g., 'com.example.MyClass')"
+                        "description": "Class name, optionally with '#method' and ':line' (e.g., 'com.example.MyClass:64' or 'com.example.MyClass#process'); slashed and 'Lcom/example/MyClass;' forms work too"
                     },
                     "line": {
                         "type": "integer",
-                        "description": "Line number"
+                        "description": "Line number (omit to break on entry to 'method')"
                     },
                     "method": {
                         "type": "string",
-                        "description": "Method name (optional, helps resolve ambiguity)"
+                        "description": "Method name; helps resolve ambiguity with a line, or is the breakpoint target without one (add the JNI signature to pick an overload, e.g. 'process(I)V')"
+                    },
+                    "hit_limit": {
+                        "type": "integer",
+                        "description": "Stop only on the Nth hit, after which the breakpoint expires (1 = one-shot breakpoint)",
+                        "minimum": 1
+                    },
+                    "thread_id": {
+                        "type": "string",
+                        "description": "Thread ID (hex) to stop on; hits on other threads are ignored"
+                    },
+                    "validate_only": {
+                        "type": "boolean",
+                        "description": "Only check the location: report the method, code index and nearest lines with code without setting the breakpoint",
+                        "default": false
+                    },
+                    "suspend_policy": {
+                        "type": "string",
+                        "enum": ["none", "thread", "all"],
+                        "description": "Threads a hit suspends: just the hitting thread (default, other requests keep being served), every thread, or none (hits are only reported)"
+                    }
+                },
+                "required": ["class_pattern"]
+            }),
+        },
+        Tool {
+            name: "debug.set_breakpoints".to_string(),
+            description: "Set several breakpoints in one call, reporting success or failure for each".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "breakpoints": {
+                        "type": "array",
+                        "description": "Breakpoints to set",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "class_pattern": {
+                                    "type": "string",
+                                    "description": "Class name, optionally with '#method' and ':line' (e.g., 'com.example.MyClass:64' or 'com.example.MyClass#process'); slashed and 'Lcom/example/MyClass;' forms work too"
+                                },
+                                "line": {
+                                    "type": "integer",
+                                    "description": "Line number (omit to break on entry to 'method')"
+                                },
+                                "method": {
+                                    "type": "string",
+                                    "description": "Method name; the breakpoint target when no line is given (add the JNI signature to pick an overload)"
+                                },
+                                "hit_limit": {
+                                    "type": "integer",
+                                    "description": "Stop only on the Nth hit, after which the breakpoint expires (1 = one-shot breakpoint)",
+                                    "minimum": 1
+                                }
+                            },
+                            "required": ["class_pattern"]
+                        }
+                    }
+                },
+                "required": ["breakpoints"]
+            }),
+        },
+        Tool {
+            name: "debug.break_next_line".to_string(),
+            description: "Set a breakpoint at the first line at or after the given line that has code, and report the line chosen".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "class_pattern": {
+                        "type": "string",
+                        "description": "Class name, optionally with '#method' and ':line' (e.g., 'com.example.MyClass:64')"
+                    },
+                    "line": {
+                        "type": "integer",
+                        "description": "Line to start searching from"
+                    },
+                    "method": {
+                        "type": "string",
+                        "description": "Only consider this method (optional)"
                     }
                 },
                 "required": ["class_pattern", "line"]
@@ -56,7 +202,7 @@ pub fn get_tools() -> Vec<Tool> {
         },
         Tool {
             name: "debug.list_breakpoints".to_string(),
-            description: "List all active breakpoints".to_string(),
+            description: "List all active breakpoints and watchpoints".to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {}
@@ -76,6 +222,109 @@ pub fn get_tools() -> Vec<Tool> {
                 "required": ["breakpoint_id"]
             }),
         },
+        Tool {
+            name: "debug.enable_breakpoint".to_string(),
+            description: "Turn a disabled or expired breakpoint back on".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "breakpoint_id": {
+                        "type": "string",
+                        "description": "Breakpoint ID from list_breakpoints"
+                    }
+                },
+                "required": ["breakpoint_id"]
+            }),
+        },
+        Tool {
+            name: "debug.disable_breakpoint".to_string(),
+            description: "Stop a breakpoint from firing without deleting it".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "breakpoint_id": {
+                        "type": "string",
+                        "description": "Breakpoint ID from list_breakpoints"
+                    }
+                },
+                "required": ["breakpoint_id"]
+            }),
+        },
+        Tool {
+            name: "debug.break_on_exception".to_string(),
+            description: "Suspend when an exception is thrown; clear it with clear_breakpoint".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "exception_class": {
+                        "type": "string",
+                        "description": "Exception class, including subclasses (e.g., 'java.io.IOException'); omit for all exceptions"
+                    },
+                    "caught": {
+                        "type": "boolean",
+                        "description": "Break on exceptions that will be caught",
+                        "default": true
+                    },
+                    "uncaught": {
+                        "type": "boolean",
+                        "description": "Break on exceptions nothing catches",
+                        "default": true
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "debug.set_watchpoint".to_string(),
+            description: "Suspend when a field is read or written (if supported by the JVM)".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "class_pattern": {
+                        "type": "string",
+                        "description": "Class name (e.g., 'com.example.MyClass')"
+                    },
+                    "field": {
+                        "type": "string",
+                        "description": "Field name, declared in the class or a superclass"
+                    },
+                    "kind": {
+                        "type": "string",
+                        "enum": ["access", "modification", "both"],
+                        "description": "Watch reads, writes, or both",
+                        "default": "modification"
+                    }
+                },
+                "required": ["class_pattern", "field"]
+            }),
+        },
+        Tool {
+            name: "debug.clear_watchpoint".to_string(),
+            description: "Clear a specific watchpoint".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "watchpoint_id": {
+                        "type": "string",
+                        "description": "Watchpoint ID from set_watchpoint or list_breakpoints"
+                    }
+                },
+                "required": ["watchpoint_id"]
+            }),
+        },
+        Tool {
+            name: "debug.wait_for_watchpoint".to_string(),
+            description: "Resume execution and wait for a watched field to be read or written, returning the field, its object and the old/new values".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "timeout_secs": {
+                        "type": "integer",
+                        "description": "How long to wait for a watchpoint",
+                        "default": 30
+                    }
+                }
+            }),
+        },
         Tool {
             name: "debug.continue".to_string(),
             description: "Resume execution (all threads or specific thread)".to_string(),
@@ -89,6 +338,34 @@ pub fn get_tools() -> Vec<Tool> {
                 }
             }),
         },
+        Tool {
+            name: "debug.run".to_string(),
+            description: "Resume execution and wait for the next breakpoint or exception, returning where it stopped".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "timeout_secs": {
+                        "type": "integer",
+                        "description": "How long to wait for a breakpoint or exception",
+                        "default": 30
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "debug.wait_for_event".to_string(),
+            description: "Wait, without resuming, for the next breakpoint, exception or watchpoint and return where it stopped. Stops that happened before the call are shown by debug.get_last_event".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "timeout_secs": {
+                        "type": "integer",
+                        "description": "How long to wait for the program to stop",
+                        "default": 30
+                    }
+                }
+            }),
+        },
         Tool {
             name: "debug.step_over".to_string(),
             description: "Step over current line".to_string(),
@@ -98,6 +375,17 @@ pub fn get_tools() -> Vec<Tool> {
                     "thread_id": {
                         "type": "string",
                         "description": "Thread ID to step"
+                    },
+                    "step_size": {
+                        "type": "string",
+                        "enum": ["line", "min"],
+                        "description": "Step granularity: 'line' stops at the next source line, 'min' at the next bytecode instruction (useful within a complex line, but needs many more steps)",
+                        "default": "line"
+                    },
+                    "step_filters": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Class patterns to step through without stopping (e.g., 'java.*'). Defaults to none"
                     }
                 },
                 "required": ["thread_id"]
@@ -112,6 +400,17 @@ pub fn get_tools() -> Vec<Tool> {
                     "thread_id": {
                         "type": "string",
                         "description": "Thread ID to step"
+                    },
+                    "step_size": {
+                        "type": "string",
+                        "enum": ["line", "min"],
+                        "description": "Step granularity: 'line' stops at the next source line, 'min' at the next bytecode instruction (useful within a complex line, but needs many more steps)",
+                        "default": "line"
+                    },
+                    "step_filters": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Class patterns to step through without stopping (e.g., 'java.*', 'com.vendor.*'). Defaults to JDK, Kotlin and Spring classes; pass [] to stop anywhere"
                     }
                 },
                 "required": ["thread_id"]
@@ -126,6 +425,17 @@ pub fn get_tools() -> Vec<Tool> {
                     "thread_id": {
                         "type": "string",
                         "description": "Thread ID to step"
+                    },
+                    "step_size": {
+                        "type": "string",
+                        "enum": ["line", "min"],
+                        "description": "Step granularity: 'line' stops at the next source line, 'min' at the next bytecode instruction (useful within a complex line, but needs many more steps)",
+                        "default": "line"
+                    },
+                    "step_filters": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Class patterns to step through without stopping (e.g., 'java.*'). Defaults to none"
                     }
                 },
                 "required": ["thread_id"]
@@ -146,6 +456,11 @@ pub fn get_tools() -> Vec<Tool> {
                         "description": "Maximum number of frames to return",
                         "default": 20
                     },
+                    "start_frame": {
+                        "type": "integer",
+                        "description": "Index of the first frame to return (0 = top), for paging through deep stacks",
+                        "default": 0
+                    },
                     "include_variables": {
                         "type": "boolean",
                         "description": "Include local variables in frames",
@@ -153,8 +468,19 @@ pub fn get_tools() -> Vec<Tool> {
                     },
                     "max_variable_depth": {
                         "type": "integer",
-                        "description": "How deep to traverse object graphs (1-3)",
+                        "description": "How many levels of object fields to expand under each local (0-3, 0 disables)",
                         "default": 2
+                    },
+                    "max_result_length": {
+                        "type": "integer",
+                        "description": "Maximum characters shown for String locals",
+                        "default": 200
+                    },
+                    "format": {
+                        "type": "string",
+                        "enum": ["text", "json"],
+                        "description": "text for a readable dump; json for frames and locals as one JSON document (without expanded object fields)",
+                        "default": "text"
                     }
                 },
                 "required": ["thread_id"]
@@ -162,7 +488,7 @@ pub fn get_tools() -> Vec<Tool> {
         },
         Tool {
             name: "debug.evaluate".to_string(),
-            description: "Evaluate expression in frame context".to_string(),
+            description: "Evaluate a variable, field chain or method call (e.g. user.address.city, this.getName()) in a suspended frame".to_string(),
             input_schema: json!({
                 "type": "object",
                 "properties": {
@@ -177,7 +503,7 @@ pub fn get_tools() -> Vec<Tool> {
                     },
                     "expression": {
                         "type": "string",
-                        "description": "Java expression to evaluate"
+                        "description": "Local variable, `this`, or chain of field reads and calls with at most one argument (up to 8 steps, e.g. map.get(\"key\").size()); operators are not supported. Calls run code in the target"
                     },
                     "max_result_length": {
                         "type": "integer",
@@ -196,6 +522,14 @@ pub fn get_tools() -> Vec<Tool> {
                 "properties": {}
             }),
         },
+        Tool {
+            name: "debug.thread_tree".to_string(),
+            description: "Show thread groups as a tree, with each group's threads and their status".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
         Tool {
             name: "debug.pause".to_string(),
             description: "Pause execution (all threads or specific thread)".to_string(),
@@ -209,6 +543,36 @@ pub fn get_tools() -> Vec<Tool> {
                 }
             }),
         },
+        Tool {
+            name: "debug.snapshot".to_string(),
+            description: "Briefly suspend the JVM, capture every thread's stack, and resume; useful for diagnosing hangs without breakpoints".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "max_frames": {
+                        "type": "integer",
+                        "description": "Maximum frames to show per thread",
+                        "default": 10
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "debug.detect_deadlock".to_string(),
+            description: "Briefly suspend the JVM and report threads that are deadlocked waiting for each other's locks".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
+        Tool {
+            name: "debug.list_sessions".to_string(),
+            description: "List debug sessions, marking the current one".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
         Tool {
             name: "debug.disconnect".to_string(),
             description: "Disconnect from JVM debug session".to_string(),
@@ -225,5 +589,306 @@ pub fn get_tools() -> Vec<Tool> {
                 "properties": {}
             }),
         },
+        Tool {
+            name: "debug.inspect_object".to_string(),
+            description: "Show the field values of an object".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "object_id": {
+                        "type": "string",
+                        "description": "Object ID (hex, as shown in get_stack)"
+                    },
+                    "include_inherited": {
+                        "type": "boolean",
+                        "description": "Include fields declared in superclasses",
+                        "default": false
+                    }
+                },
+                "required": ["object_id"]
+            }),
+        },
+        Tool {
+            name: "debug.get_this".to_string(),
+            description: "Get the 'this' object of a stack frame".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "thread_id": {
+                        "type": "string",
+                        "description": "Thread ID"
+                    },
+                    "frame_index": {
+                        "type": "integer",
+                        "description": "Stack frame index (0 = current frame)",
+                        "default": 0
+                    }
+                },
+                "required": ["thread_id"]
+            }),
+        },
+        Tool {
+            name: "debug.set_variable".to_string(),
+            description: "Change a local variable in a suspended thread's stack frame".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "thread_id": {
+                        "type": "string",
+                        "description": "Suspended thread ID (hex)"
+                    },
+                    "frame_index": {
+                        "type": "integer",
+                        "description": "Frame to modify (0 = top of stack)",
+                        "default": 0
+                    },
+                    "name": {
+                        "type": "string",
+                        "description": "Variable name"
+                    },
+                    "value": {
+                        "description": "New value: a number, boolean, one-character string for char, text for String, or null / hex object ID for other references"
+                    }
+                },
+                "required": ["thread_id", "name", "value"]
+            }),
+        },
+        Tool {
+            name: "debug.inspect_array".to_string(),
+            description: "Show a range of array elements; ranges past the end of the array are clamped".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "array_id": {
+                        "type": "string",
+                        "description": "Array object ID (hex)"
+                    },
+                    "first": {
+                        "type": "integer",
+                        "description": "Index of the first element to show",
+                        "default": 0
+                    },
+                    "length": {
+                        "type": "integer",
+                        "description": "Number of elements to show",
+                        "default": 20
+                    }
+                },
+                "required": ["array_id"]
+            }),
+        },
+        Tool {
+            name: "debug.set_array_element".to_string(),
+            description: "Change one element of an array".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "array_id": {
+                        "type": "string",
+                        "description": "Array object ID (hex)"
+                    },
+                    "index": {
+                        "type": "integer",
+                        "description": "Index of the element to change"
+                    },
+                    "value": {
+                        "description": "New value: a number, boolean, one-character string for char, text for String, or null / hex object ID for other references"
+                    }
+                },
+                "required": ["array_id", "index", "value"]
+            }),
+        },
+        Tool {
+            name: "debug.inspect_collection".to_string(),
+            description: "Show the elements of a java.util List, Set, Collection or Map (thread must be suspended at an event)".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "thread_id": {
+                        "type": "string",
+                        "description": "Suspended thread used to call size()/toArray()"
+                    },
+                    "object_id": {
+                        "type": "string",
+                        "description": "Collection object ID (hex)"
+                    },
+                    "max_elements": {
+                        "type": "integer",
+                        "description": "Maximum number of elements to show",
+                        "default": 20
+                    }
+                },
+                "required": ["thread_id", "object_id"]
+            }),
+        },
+        Tool {
+            name: "debug.stats".to_string(),
+            description: "Show command, reply, error and event counts and average reply latency for the current session".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
+        Tool {
+            name: "debug.find_classes".to_string(),
+            description: "List loaded classes, optionally filtered by name, and show each one's kind and initialization status".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "Case-insensitive substring of the class name (e.g., 'Controller'), or a glob using * and ? (e.g., 'com.example.*Controller'). Omit to list all loaded classes"
+                    },
+                    "max_results": {
+                        "type": "integer",
+                        "description": "Maximum number of classes to show",
+                        "default": 50
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "debug.describe_class".to_string(),
+            description: "Show a class's kind, type ID, status (verified, prepared, initialized, error) and static field values".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "class_pattern": {
+                        "type": "string",
+                        "description": "Fully qualified class name (e.g., 'com.example.MyClass')"
+                    }
+                },
+                "required": ["class_pattern"]
+            }),
+        },
+        Tool {
+            name: "debug.bytecodes".to_string(),
+            description: "Hex dump of a method's bytecode, grouped by source line using the line table".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "class_pattern": {
+                        "type": "string",
+                        "description": "Fully qualified class name (e.g., 'com.example.MyClass')"
+                    },
+                    "method": {
+                        "type": "string",
+                        "description": "Method name, with its JNI signature if overloaded (e.g., 'process(I)V')"
+                    }
+                },
+                "required": ["class_pattern", "method"]
+            }),
+        },
+        Tool {
+            name: "debug.exit_vm".to_string(),
+            description: "Terminate the target JVM with an exit code and end the session (destructive, requires confirm=true)".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "exit_code": {
+                        "type": "integer",
+                        "description": "Exit code for the JVM process",
+                        "default": 0
+                    },
+                    "confirm": {
+                        "type": "boolean",
+                        "description": "Must be true; guards against accidentally killing the debuggee"
+                    }
+                },
+                "required": ["confirm"]
+            }),
+        },
+        Tool {
+            name: "debug.list_members".to_string(),
+            description: "List a loaded class's fields and methods, with generic types where available".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "class_pattern": {
+                        "type": "string",
+                        "description": "Fully qualified class name (e.g., 'com.example.MyClass')"
+                    }
+                },
+                "required": ["class_pattern"]
+            }),
+        },
+        Tool {
+            name: "debug.capabilities".to_string(),
+            description: "Show which optional debugging features the connected JVM supports".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
+        Tool {
+            name: "debug.classpath".to_string(),
+            description: "Show the JVM's classpath, e.g. to find out why a class isn't found".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
+        Tool {
+            name: "debug.pop_frame".to_string(),
+            description: "Pop a stack frame (and all frames above it) so the call can be re-entered; the thread must be suspended".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "thread_id": {
+                        "type": "string",
+                        "description": "Suspended thread ID"
+                    },
+                    "frame_index": {
+                        "type": "integer",
+                        "description": "Frame to pop (0 = current frame); frames above it are popped too",
+                        "default": 0
+                    }
+                },
+                "required": ["thread_id"]
+            }),
+        },
+        Tool {
+            name: "debug.force_return".to_string(),
+            description: "Make the current method of a suspended thread return immediately with the given value, skipping the rest of it".to_string(),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "thread_id": {
+                        "type": "string",
+                        "description": "Suspended thread ID"
+                    },
+                    "value": {
+                        "description": "Return value matching the method's return type: number, boolean, one-character string for char, string for String, hex object ID or null for objects; omit for void methods"
+                    }
+                },
+                "required": ["thread_id"]
+            }),
+        },
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_capability_names_the_feature() {
+        let mut capabilities = VmCapabilities::default();
+        assert_eq!(missing_capability("debug.pop_frame", &capabilities), Some("popping frames (canPopFrames)"));
+        assert!(!is_supported("debug.set_watchpoint", &capabilities));
+        assert!(is_supported("debug.list_threads", &capabilities));
+
+        capabilities.can_pop_frames = true;
+        capabilities.can_watch_field_modification = true;
+        assert_eq!(missing_capability("debug.pop_frame", &capabilities), None);
+        assert!(is_supported("debug.set_watchpoint", &capabilities));
+    }
+
+    #[test]
+    fn test_session_tools_accept_session_id() {
+        for tool in get_tools() {
+            let has_session_id = tool.input_schema["properties"].get("session_id").is_some();
+            assert_eq!(has_session_id, !SESSIONLESS_TOOLS.contains(&tool.name.as_str()), "{}", tool.name);
+        }
+    }
+}